@@ -3,6 +3,7 @@ mod readme_application;
 
 use blitz_html::HtmlDocument;
 use blitz_net::Provider;
+use blitz_traits::encoding::decode_html;
 use blitz_traits::navigation::NavigationProvider;
 use markdown::{BLITZ_MD_STYLES, GITHUB_MD_STYLES, markdown_to_html};
 use notify::{Error as NotifyError, Event as NotifyEvent, RecursiveMode, Watcher as _};
@@ -155,7 +156,11 @@ async fn fetch_url(url: Url) -> (String, String, bool, Option<PathBuf>) {
     let final_url = response.url().to_string();
 
     // Get the file content
-    let file_content = response.text().await.unwrap();
+    let content_type = content_type
+        .and_then(|ct| ct.to_str().ok())
+        .map(String::from);
+    let bytes = response.bytes().await.unwrap();
+    let file_content = decode_html(&bytes, content_type.as_deref());
 
     (final_url, file_content, is_md, None)
 }
@@ -192,7 +197,7 @@ fn fetch_file_path(raw_path: &str) -> (String, String, bool, Option<PathBuf>) {
         .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
 
     // Read file
-    let file_content = std::fs::read_to_string(&path).unwrap();
+    let file_content = decode_html(&std::fs::read(&path).unwrap(), None);
 
     (base_url, file_content, is_md, Some(path))
 }
@@ -4,6 +4,7 @@ use blitz_dom::net::Resource;
 use blitz_html::HtmlDocument;
 use blitz_net::{MpscCallback, Provider};
 use blitz_renderer_vello::render_to_buffer;
+use blitz_traits::encoding::decode_html;
 use blitz_traits::navigation::DummyNavigationProvider;
 use blitz_traits::net::SharedProvider;
 use blitz_traits::{ColorScheme, Viewport};
@@ -36,7 +37,7 @@ async fn main() {
     let html = match url.scheme() {
         "file" => {
             let file_content = std::fs::read(url.path()).unwrap();
-            String::from_utf8(file_content).unwrap()
+            decode_html(&file_content, None)
         }
         _ => {
             let client = reqwest::Client::new();
@@ -46,7 +47,13 @@ async fn main() {
                 .send()
                 .await
                 .unwrap();
-            response.text().await.unwrap()
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from);
+            let bytes = response.bytes().await.unwrap();
+            decode_html(&bytes, content_type.as_deref())
         }
     };
 
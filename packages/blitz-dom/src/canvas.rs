@@ -0,0 +1,898 @@
+//! `<canvas>` elements and a Rust API modelled on the Canvas 2D rendering context.
+//!
+//! Each canvas owns a `CanvasSurface` (stored in its `NodeSpecificData`), which records drawing
+//! operations into a display list of paths, glyph runs and images in the canvas's coordinate
+//! space (its `width` and `height` attributes). The renderer replays the display list as a
+//! sub-scene scaled to the canvas's content box.
+//!
+//! Drawing is done through a `CanvasContext2d`, which can be obtained from the document by node id
+//! and freely cloned and sent to other threads. Drawing marks the canvas as dirty, which wakes the
+//! task polling the document so that it is redrawn.
+//!
+//! See <https://html.spec.whatwg.org/multipage/canvas.html>
+
+use std::borrow::Cow;
+use std::f64::consts::TAU;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Waker};
+
+use markup5ever::local_name;
+use parley::layout::{Alignment, AlignmentOptions, PositionedLayoutItem};
+use parley::style::{FontStack, FontStyle, FontWeight, StyleProperty};
+use parley::{FontContext, LayoutContext};
+use peniko::kurbo::{self, Affine, BezPath, Cap, Join, Point, Rect, Shape, Stroke};
+use peniko::{Brush, Color, Fill, Font};
+
+use crate::BaseDocument;
+use crate::node::{ElementNodeData, NodeSpecificData, RasterImageData, TextBrush};
+
+/// The size of canvases without `width` and `height` attributes
+const DEFAULT_CANVAS_SIZE: (u32, u32) = (300, 150);
+
+/// The horizontal alignment of text drawn with `fill_text` relative to its position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CanvasTextAlign {
+    #[default]
+    Start,
+    End,
+    Left,
+    Right,
+    Center,
+}
+
+/// The vertical alignment of text drawn with `fill_text` relative to its position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CanvasTextBaseline {
+    Top,
+    Hanging,
+    Middle,
+    #[default]
+    Alphabetic,
+    Ideographic,
+    Bottom,
+}
+
+/// A glyph positioned in a glyph run of a canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasGlyph {
+    pub id: u32,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A drawing operation recorded by a canvas. Paths are in the canvas's coordinate space, and the
+/// transforms of strokes, glyph runs and images map into it.
+#[derive(Debug, Clone)]
+pub enum CanvasCommand {
+    Fill {
+        path: BezPath,
+        fill: Fill,
+        brush: Brush,
+    },
+    Stroke {
+        path: BezPath,
+        stroke: Stroke,
+        brush: Brush,
+        transform: Affine,
+    },
+    Glyphs {
+        font: Font,
+        font_size: f32,
+        normalized_coords: Vec<i16>,
+        glyphs: Vec<CanvasGlyph>,
+        brush: Brush,
+        transform: Affine,
+    },
+    Image {
+        image: RasterImageData,
+        alpha: f32,
+        transform: Affine,
+    },
+    /// Clear the pixels within a path to transparent black
+    Clear {
+        path: BezPath,
+    },
+    /// Clip subsequent commands to a path (until the matching `PopClip`)
+    PushClip {
+        path: BezPath,
+    },
+    PopClip,
+}
+
+/// The font used to draw text (a subset of the CSS `font` shorthand)
+#[derive(Debug, Clone, PartialEq)]
+struct CanvasFont {
+    family: String,
+    size: f32,
+    weight: f32,
+    italic: bool,
+}
+
+impl CanvasFont {
+    /// Parse a CSS `font` value such as "bold 16px sans-serif". Returns `None` if it has no size
+    /// or family.
+    fn parse(font: &str) -> Option<Self> {
+        let mut parsed = Self {
+            family: String::new(),
+            size: 0.0,
+            weight: 400.0,
+            italic: false,
+        };
+        let mut tokens = font.split_ascii_whitespace();
+        for token in tokens.by_ref() {
+            match token {
+                "normal" => {}
+                "italic" | "oblique" => parsed.italic = true,
+                "bold" => parsed.weight = 700.0,
+                "lighter" => parsed.weight = 300.0,
+                "bolder" => parsed.weight = 800.0,
+                _ => {
+                    if let Ok(weight) = token.parse::<f32>() {
+                        parsed.weight = weight;
+                        continue;
+                    }
+                    // The size may be followed by a line height (which is ignored)
+                    let size = token.split('/').next().unwrap_or(token);
+                    parsed.size = match size {
+                        _ if size.ends_with("px") => size.trim_end_matches("px").parse().ok()?,
+                        _ if size.ends_with("pt") => {
+                            size.trim_end_matches("pt").parse::<f32>().ok()? * 4.0 / 3.0
+                        }
+                        _ if size.ends_with("em") => {
+                            size.trim_end_matches("em").parse::<f32>().ok()? * 16.0
+                        }
+                        _ => return None,
+                    };
+                    break;
+                }
+            }
+        }
+        parsed.family = tokens.collect::<Vec<_>>().join(" ");
+        (!parsed.family.is_empty()).then_some(parsed)
+    }
+}
+
+/// The drawing state which is saved and restored by `save` and `restore`
+#[derive(Debug, Clone)]
+struct CanvasState {
+    transform: Affine,
+    fill_style: Brush,
+    stroke_style: Brush,
+    line_width: f64,
+    line_cap: Cap,
+    line_join: Join,
+    miter_limit: f64,
+    global_alpha: f32,
+    font: CanvasFont,
+    text_align: CanvasTextAlign,
+    text_baseline: CanvasTextBaseline,
+    /// The number of clips applied since the state was saved
+    clips: usize,
+}
+
+impl Default for CanvasState {
+    fn default() -> Self {
+        Self {
+            transform: Affine::IDENTITY,
+            fill_style: Brush::Solid(Color::BLACK),
+            stroke_style: Brush::Solid(Color::BLACK),
+            line_width: 1.0,
+            line_cap: Cap::Butt,
+            line_join: Join::Miter,
+            miter_limit: 10.0,
+            global_alpha: 1.0,
+            font: CanvasFont {
+                family: String::from("sans-serif"),
+                size: 10.0,
+                weight: 400.0,
+                italic: false,
+            },
+            text_align: CanvasTextAlign::Start,
+            text_baseline: CanvasTextBaseline::Alphabetic,
+            clips: 0,
+        }
+    }
+}
+
+struct CanvasData {
+    font_ctx: FontContext,
+    /// The size of the canvas's coordinate space
+    size: (u32, u32),
+    display_list: Vec<CanvasCommand>,
+    state: CanvasState,
+    saved_states: Vec<CanvasState>,
+    /// The current path (in the canvas's coordinate space)
+    path: BezPath,
+    /// Whether the canvas has been drawn to since the document was last polled
+    dirty: bool,
+    /// The waker of the task polling the document
+    waker: Option<Waker>,
+}
+
+impl CanvasData {
+    fn push(&mut self, command: CanvasCommand) {
+        self.display_list.push(command);
+        self.mark_dirty();
+    }
+
+    fn mark_dirty(&mut self) {
+        if !self.dirty {
+            self.dirty = true;
+            if let Some(waker) = &self.waker {
+                waker.wake_by_ref();
+            }
+        }
+    }
+
+    /// Apply the current transform to a shape
+    fn transformed(&self, shape: impl Shape) -> BezPath {
+        self.state.transform * shape.to_path(0.1)
+    }
+
+    fn fill_brush(&self) -> Brush {
+        self.state
+            .fill_style
+            .clone()
+            .multiply_alpha(self.state.global_alpha)
+    }
+
+    fn stroke(&self) -> Stroke {
+        Stroke::new(self.state.line_width)
+            .with_caps(self.state.line_cap)
+            .with_join(self.state.line_join)
+            .with_miter_limit(self.state.miter_limit)
+    }
+
+    /// Stroke a path (in the canvas's coordinate space) in the current transform, so that the
+    /// line width is transformed with it
+    fn stroke_path(&mut self, path: &BezPath) {
+        let transform = self.state.transform;
+        if transform.determinant() == 0.0 {
+            return;
+        }
+        let command = CanvasCommand::Stroke {
+            path: transform.inverse() * path.clone(),
+            stroke: self.stroke(),
+            brush: self
+                .state
+                .stroke_style
+                .clone()
+                .multiply_alpha(self.state.global_alpha),
+            transform,
+        };
+        self.push(command);
+    }
+}
+
+/// The drawing surface of a `<canvas>` element
+#[derive(Clone)]
+pub struct CanvasSurface(Arc<Mutex<CanvasData>>);
+
+impl CanvasSurface {
+    pub fn new(font_ctx: FontContext, size: (u32, u32)) -> Self {
+        Self(Arc::new(Mutex::new(CanvasData {
+            font_ctx,
+            size,
+            display_list: Vec::new(),
+            state: CanvasState::default(),
+            saved_states: Vec::new(),
+            path: BezPath::new(),
+            dirty: false,
+            waker: None,
+        })))
+    }
+
+    fn data(&self) -> MutexGuard<'_, CanvasData> {
+        self.0.lock().unwrap()
+    }
+
+    /// A 2D drawing context for the surface
+    pub fn context_2d(&self) -> CanvasContext2d {
+        CanvasContext2d(self.clone())
+    }
+
+    /// Call `f` with the display list of the surface (for rendering it)
+    pub fn with_display_list<R>(&self, f: impl FnOnce(&[CanvasCommand]) -> R) -> R {
+        f(&self.data().display_list)
+    }
+
+    /// Reset the surface to its initial (transparent) state with a new size. This happens when the
+    /// canvas is resized.
+    pub fn reset(&self, size: (u32, u32)) {
+        let mut data = self.data();
+        data.size = size;
+        data.display_list.clear();
+        data.state = CanvasState::default();
+        data.saved_states.clear();
+        data.path = BezPath::new();
+        data.mark_dirty();
+    }
+}
+
+impl std::fmt::Debug for CanvasSurface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CanvasSurface")
+            .field("commands", &self.data().display_list.len())
+            .finish()
+    }
+}
+
+/// A 2D drawing context for a canvas, modelled on `CanvasRenderingContext2D`
+///
+/// See <https://html.spec.whatwg.org/multipage/canvas.html#canvasrenderingcontext2d>
+#[derive(Clone, Debug)]
+pub struct CanvasContext2d(CanvasSurface);
+
+impl CanvasContext2d {
+    fn data(&self) -> MutexGuard<'_, CanvasData> {
+        self.0.data()
+    }
+
+    // State
+
+    /// Push the current drawing state onto the state stack
+    pub fn save(&self) {
+        let mut data = self.data();
+        let mut state = data.state.clone();
+        state.clips = 0;
+        let saved = std::mem::replace(&mut data.state, state);
+        data.saved_states.push(saved);
+    }
+
+    /// Pop the drawing state from the top of the state stack (removing the clips applied since it
+    /// was saved)
+    pub fn restore(&self) {
+        let mut data = self.data();
+        let Some(state) = data.saved_states.pop() else {
+            return;
+        };
+        for _ in 0..data.state.clips {
+            data.push(CanvasCommand::PopClip);
+        }
+        data.state = state;
+    }
+
+    pub fn set_fill_style(&self, brush: impl Into<Brush>) {
+        self.data().state.fill_style = brush.into();
+    }
+
+    pub fn set_stroke_style(&self, brush: impl Into<Brush>) {
+        self.data().state.stroke_style = brush.into();
+    }
+
+    pub fn set_line_width(&self, width: f64) {
+        if width.is_finite() && width > 0.0 {
+            self.data().state.line_width = width;
+        }
+    }
+
+    pub fn set_line_cap(&self, cap: Cap) {
+        self.data().state.line_cap = cap;
+    }
+
+    pub fn set_line_join(&self, join: Join) {
+        self.data().state.line_join = join;
+    }
+
+    pub fn set_miter_limit(&self, limit: f64) {
+        if limit.is_finite() && limit > 0.0 {
+            self.data().state.miter_limit = limit;
+        }
+    }
+
+    pub fn set_global_alpha(&self, alpha: f32) {
+        if (0.0..=1.0).contains(&alpha) {
+            self.data().state.global_alpha = alpha;
+        }
+    }
+
+    /// Set the font used to draw text, as a CSS `font` value (e.g. "bold 16px sans-serif").
+    /// Invalid values are ignored.
+    pub fn set_font(&self, font: &str) {
+        if let Some(font) = CanvasFont::parse(font) {
+            self.data().state.font = font;
+        }
+    }
+
+    pub fn set_text_align(&self, align: CanvasTextAlign) {
+        self.data().state.text_align = align;
+    }
+
+    pub fn set_text_baseline(&self, baseline: CanvasTextBaseline) {
+        self.data().state.text_baseline = baseline;
+    }
+
+    // Transforms
+
+    pub fn translate(&self, x: f64, y: f64) {
+        let mut data = self.data();
+        data.state.transform = data.state.transform.pre_translate((x, y).into());
+    }
+
+    /// Rotate by `angle` radians (clockwise)
+    pub fn rotate(&self, angle: f64) {
+        let mut data = self.data();
+        data.state.transform = data.state.transform.pre_rotate(angle);
+    }
+
+    pub fn scale(&self, x: f64, y: f64) {
+        let mut data = self.data();
+        data.state.transform = data.state.transform.pre_scale_non_uniform(x, y);
+    }
+
+    /// Multiply the current transform by the matrix `[a c e; b d f; 0 0 1]`
+    pub fn transform(&self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        let mut data = self.data();
+        data.state.transform *= Affine::new([a, b, c, d, e, f]);
+    }
+
+    /// Replace the current transform with the matrix `[a c e; b d f; 0 0 1]`
+    pub fn set_transform(&self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        self.data().state.transform = Affine::new([a, b, c, d, e, f]);
+    }
+
+    pub fn reset_transform(&self) {
+        self.data().state.transform = Affine::IDENTITY;
+    }
+
+    // Paths
+
+    pub fn begin_path(&self) {
+        self.data().path = BezPath::new();
+    }
+
+    pub fn close_path(&self) {
+        self.data().path.close_path();
+    }
+
+    pub fn move_to(&self, x: f64, y: f64) {
+        let mut data = self.data();
+        let point = data.state.transform * Point::new(x, y);
+        data.path.move_to(point);
+    }
+
+    pub fn line_to(&self, x: f64, y: f64) {
+        let mut data = self.data();
+        let point = data.state.transform * Point::new(x, y);
+        if data.path.elements().is_empty() {
+            data.path.move_to(point);
+        } else {
+            data.path.line_to(point);
+        }
+    }
+
+    pub fn quadratic_curve_to(&self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        let mut data = self.data();
+        let transform = data.state.transform;
+        let control = transform * Point::new(cpx, cpy);
+        if data.path.elements().is_empty() {
+            data.path.move_to(control);
+        }
+        data.path.quad_to(control, transform * Point::new(x, y));
+    }
+
+    pub fn bezier_curve_to(&self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        let mut data = self.data();
+        let transform = data.state.transform;
+        let control1 = transform * Point::new(cp1x, cp1y);
+        if data.path.elements().is_empty() {
+            data.path.move_to(control1);
+        }
+        data.path.curve_to(
+            control1,
+            transform * Point::new(cp2x, cp2y),
+            transform * Point::new(x, y),
+        );
+    }
+
+    /// Add an arc of a circle centered at `(x, y)` from `start_angle` to `end_angle` (in radians,
+    /// clockwise unless `counterclockwise` is set) to the path, connected to the previous point
+    /// by a straight line
+    pub fn arc(
+        &self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+        counterclockwise: bool,
+    ) {
+        if !radius.is_finite() || radius < 0.0 {
+            return;
+        }
+        let sweep = if !counterclockwise {
+            match end_angle - start_angle {
+                sweep if sweep >= TAU => TAU,
+                sweep => sweep.rem_euclid(TAU),
+            }
+        } else {
+            match start_angle - end_angle {
+                sweep if sweep >= TAU => -TAU,
+                sweep => -sweep.rem_euclid(TAU),
+            }
+        };
+        let arc = kurbo::Arc::new((x, y), (radius, radius), start_angle, sweep, 0.0);
+
+        let mut data = self.data();
+        let transform = data.state.transform;
+        let start = transform * (Point::new(x, y) + kurbo::Vec2::from_angle(start_angle) * radius);
+        if data.path.elements().is_empty() {
+            data.path.move_to(start);
+        } else {
+            data.path.line_to(start);
+        }
+        for element in arc.append_iter(0.1) {
+            data.path.push(transform * element);
+        }
+    }
+
+    /// Add a closed rectangle to the path
+    pub fn rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        let mut data = self.data();
+        let rect = data.transformed(Rect::new(x, y, x + width, y + height));
+        data.path.extend(rect);
+    }
+
+    /// Fill the current path with the fill style, using the non-zero winding rule
+    pub fn fill(&self) {
+        self.fill_with_rule(Fill::NonZero);
+    }
+
+    /// Fill the current path with the fill style, using the given winding rule
+    pub fn fill_with_rule(&self, fill: Fill) {
+        let mut data = self.data();
+        let command = CanvasCommand::Fill {
+            path: data.path.clone(),
+            fill,
+            brush: data.fill_brush(),
+        };
+        data.push(command);
+    }
+
+    /// Stroke the current path with the stroke style
+    pub fn stroke(&self) {
+        let mut data = self.data();
+        let path = data.path.clone();
+        data.stroke_path(&path);
+    }
+
+    /// Clip subsequent drawing to the current path (until the state is restored)
+    pub fn clip(&self) {
+        let mut data = self.data();
+        let command = CanvasCommand::PushClip {
+            path: data.path.clone(),
+        };
+        data.push(command);
+        data.state.clips += 1;
+    }
+
+    // Rectangles
+
+    pub fn fill_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        let mut data = self.data();
+        let command = CanvasCommand::Fill {
+            path: data.transformed(Rect::new(x, y, x + width, y + height)),
+            fill: Fill::NonZero,
+            brush: data.fill_brush(),
+        };
+        data.push(command);
+    }
+
+    pub fn stroke_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        let mut data = self.data();
+        let path = data.transformed(Rect::new(x, y, x + width, y + height));
+        data.stroke_path(&path);
+    }
+
+    /// Clear a rectangle to transparent black. Commands drawn entirely within the rectangle are
+    /// discarded, so that clearing and redrawing the canvas every frame doesn't grow its display
+    /// list.
+    pub fn clear_rect(&self, x: f64, y: f64, width: f64, height: f64) {
+        let mut data = self.data();
+        let rect = Rect::new(x, y, x + width, y + height);
+        let path = data.transformed(rect);
+
+        // The cleared area is only known to be rectangular if the transform doesn't rotate or skew
+        // it, and if it isn't clipped
+        let [_, b, c, _, _, _] = data.state.transform.as_coeffs();
+        let is_clipped = data.state.clips > 0 || data.saved_states.iter().any(|s| s.clips > 0);
+        if b == 0.0 && c == 0.0 && !is_clipped {
+            let cleared = data.state.transform.transform_rect_bbox(rect);
+            let (canvas_width, canvas_height) = data.size;
+            let canvas_rect = Rect::new(0.0, 0.0, canvas_width as f64, canvas_height as f64);
+            if cleared.union(canvas_rect) == cleared {
+                data.display_list.clear();
+                data.mark_dirty();
+                return;
+            }
+            data.display_list.retain(|command| {
+                command_bounds(command).is_none_or(|bounds| cleared.union(bounds) != cleared)
+            });
+            remove_empty_clips(&mut data.display_list);
+        }
+        data.push(CanvasCommand::Clear { path });
+    }
+
+    // Text
+
+    /// Lay out a line of text in the current font
+    fn layout_text(data: &mut CanvasData, text: &str) -> parley::Layout<TextBrush> {
+        let font = data.state.font.clone();
+        let mut layout_ctx: LayoutContext<TextBrush> = LayoutContext::new();
+        let mut builder = layout_ctx.ranged_builder(&mut data.font_ctx, text, 1.0);
+        builder.push_default(StyleProperty::FontStack(FontStack::Source(Cow::Borrowed(
+            &font.family,
+        ))));
+        builder.push_default(StyleProperty::FontSize(font.size));
+        builder.push_default(StyleProperty::FontWeight(FontWeight::new(font.weight)));
+        if font.italic {
+            builder.push_default(StyleProperty::FontStyle(FontStyle::Italic));
+        }
+        let mut layout = builder.build(text);
+        layout.break_all_lines(None);
+        layout.align(None, Alignment::Start, AlignmentOptions::default());
+        layout
+    }
+
+    /// The width of a line of text in the current font
+    pub fn measure_text(&self, text: &str) -> f64 {
+        let mut data = self.data();
+        Self::layout_text(&mut data, text).full_width() as f64
+    }
+
+    /// Draw a line of text at `(x, y)` (positioned according to the text alignment and baseline)
+    /// with the fill style
+    pub fn fill_text(&self, text: &str, x: f64, y: f64) {
+        let mut data = self.data();
+        let layout = Self::layout_text(&mut data, text);
+        let Some(line) = layout.lines().next() else {
+            return;
+        };
+
+        let metrics = line.metrics();
+        let offset_x = match data.state.text_align {
+            CanvasTextAlign::Start | CanvasTextAlign::Left => 0.0,
+            CanvasTextAlign::End | CanvasTextAlign::Right => -layout.full_width(),
+            CanvasTextAlign::Center => -layout.full_width() / 2.0,
+        };
+        let offset_y = match data.state.text_baseline {
+            CanvasTextBaseline::Top | CanvasTextBaseline::Hanging => metrics.ascent,
+            CanvasTextBaseline::Middle => (metrics.ascent - metrics.descent) / 2.0,
+            CanvasTextBaseline::Alphabetic => 0.0,
+            CanvasTextBaseline::Ideographic | CanvasTextBaseline::Bottom => -metrics.descent,
+        };
+        let transform = data.state.transform.pre_translate(kurbo::Vec2::new(
+            x + offset_x as f64,
+            y + offset_y as f64 - metrics.baseline as f64,
+        ));
+        let brush = data.fill_brush();
+
+        for item in line.items() {
+            let PositionedLayoutItem::GlyphRun(glyph_run) = item else {
+                continue;
+            };
+            let run = glyph_run.run();
+            let mut glyph_x = glyph_run.offset();
+            let glyph_y = glyph_run.baseline();
+            let glyphs = glyph_run
+                .glyphs()
+                .map(|glyph| {
+                    let positioned = CanvasGlyph {
+                        id: glyph.id as u32,
+                        x: glyph_x + glyph.x,
+                        y: glyph_y - glyph.y,
+                    };
+                    glyph_x += glyph.advance;
+                    positioned
+                })
+                .collect();
+            data.push(CanvasCommand::Glyphs {
+                font: run.font().clone(),
+                font_size: run.font_size(),
+                normalized_coords: run.normalized_coords().to_vec(),
+                glyphs,
+                brush: brush.clone(),
+                transform,
+            });
+        }
+    }
+
+    // Images
+
+    /// Draw an image scaled into the rectangle at `(x, y)` with the given width and height
+    pub fn draw_image(&self, image: &RasterImageData, x: f64, y: f64, width: f64, height: f64) {
+        if image.width == 0 || image.height == 0 {
+            return;
+        }
+        let mut data = self.data();
+        let transform = data
+            .state
+            .transform
+            .pre_translate(kurbo::Vec2::new(x, y))
+            .pre_scale_non_uniform(width / image.width as f64, height / image.height as f64);
+        let command = CanvasCommand::Image {
+            image: image.clone(),
+            alpha: data.state.global_alpha,
+            transform,
+        };
+        data.push(command);
+    }
+}
+
+/// A rectangle (in the canvas's coordinate space) containing everything a command draws, or
+/// `None` for commands which don't draw (clips)
+fn command_bounds(command: &CanvasCommand) -> Option<Rect> {
+    match command {
+        CanvasCommand::Fill { path, .. } | CanvasCommand::Clear { path } => {
+            Some(path.bounding_box())
+        }
+        CanvasCommand::Stroke {
+            path,
+            stroke,
+            transform,
+            ..
+        } => {
+            // Miter joins extend at most `miter_limit` half-widths from the path
+            let extent = stroke.width * stroke.miter_limit.max(1.0);
+            Some(transform.transform_rect_bbox(path.bounding_box().inflate(extent, extent)))
+        }
+        CanvasCommand::Glyphs {
+            font_size,
+            glyphs,
+            transform,
+            ..
+        } => {
+            // Glyphs are assumed to be within an em of their origin (and two of it horizontally)
+            let size = *font_size as f64;
+            let origins = glyphs.iter().map(|glyph| {
+                let origin = Point::new(glyph.x as f64, glyph.y as f64);
+                Rect::from_points(origin, origin)
+            });
+            let bounds = origins.reduce(|a, b| a.union(b))?;
+            let bounds = Rect::new(
+                bounds.x0 - size,
+                bounds.y0 - size,
+                bounds.x1 + 2.0 * size,
+                bounds.y1 + size,
+            );
+            Some(transform.transform_rect_bbox(bounds))
+        }
+        CanvasCommand::Image {
+            image, transform, ..
+        } => {
+            let rect = Rect::new(0.0, 0.0, image.width as f64, image.height as f64);
+            Some(transform.transform_rect_bbox(rect))
+        }
+        CanvasCommand::PushClip { .. } | CanvasCommand::PopClip => None,
+    }
+}
+
+/// Remove clips which no longer apply to any commands
+fn remove_empty_clips(display_list: &mut Vec<CanvasCommand>) {
+    let mut kept: Vec<CanvasCommand> = Vec::with_capacity(display_list.len());
+    for command in display_list.drain(..) {
+        if matches!(command, CanvasCommand::PopClip)
+            && matches!(kept.last(), Some(CanvasCommand::PushClip { .. }))
+        {
+            kept.pop();
+        } else {
+            kept.push(command);
+        }
+    }
+    *display_list = kept;
+}
+
+impl ElementNodeData {
+    /// The size of a `<canvas>` element's coordinate space, from its `width` and `height`
+    /// attributes
+    pub fn canvas_size(&self) -> (u32, u32) {
+        let width = self.attr(local_name!("width")).and_then(|w| w.parse().ok());
+        let height = self
+            .attr(local_name!("height"))
+            .and_then(|h| h.parse().ok());
+        (
+            width.unwrap_or(DEFAULT_CANVAS_SIZE.0),
+            height.unwrap_or(DEFAULT_CANVAS_SIZE.1),
+        )
+    }
+
+    /// The drawing surface of a `<canvas>` element (if it has been drawn to)
+    pub fn canvas_surface(&self) -> Option<&CanvasSurface> {
+        match &self.node_specific_data {
+            NodeSpecificData::Canvas(surface) => Some(surface),
+            _ => None,
+        }
+    }
+}
+
+impl BaseDocument {
+    /// A 2D drawing context for a `<canvas>` element (or `None` if the node isn't a canvas)
+    pub fn canvas_context(&mut self, node_id: usize) -> Option<CanvasContext2d> {
+        let element = self.nodes.get_mut(node_id)?.element_data_mut()?;
+        if element.name.local != local_name!("canvas") {
+            return None;
+        }
+        if element.canvas_surface().is_none() {
+            let surface = CanvasSurface::new(self.font_ctx.clone(), element.canvas_size());
+            element.node_specific_data = NodeSpecificData::Canvas(surface);
+        }
+        element.canvas_surface().map(CanvasSurface::context_2d)
+    }
+
+    /// Clear a canvas after its `width` or `height` attribute has changed
+    pub fn reset_canvas(&mut self, node_id: usize) {
+        let Some(node) = self.nodes.get_mut(node_id) else {
+            return;
+        };
+        if let Some(element) = node.element_data() {
+            let size = element.canvas_size();
+            element
+                .canvas_surface()
+                .inspect(|surface| surface.reset(size));
+        }
+        node.cache.clear();
+    }
+
+    /// Register the waker of the task polling the document with its canvases, so that drawing to
+    /// them wakes it. Returns whether any canvas has been drawn to since the last poll (and so a
+    /// redraw is needed).
+    pub fn poll_canvases(&mut self, cx: &Context) -> bool {
+        let mut changed = false;
+        for (_, node) in self.nodes.iter() {
+            let Some(surface) = node.element_data().and_then(|el| el.canvas_surface()) else {
+                continue;
+            };
+            let mut data = surface.data();
+            data.waker = Some(cx.waker().clone());
+            changed |= std::mem::take(&mut data.dirty);
+        }
+        for sub_document in self.sub_documents.values_mut() {
+            changed |= sub_document.doc.poll_canvases(cx);
+        }
+        changed
+    }
+}
+
+#[test]
+fn clearing_a_canvas_bounds_its_display_list() {
+    let surface = CanvasSurface::new(FontContext::default(), (200, 100));
+    let ctx = surface.context_2d();
+    let len = || surface.with_display_list(|commands| commands.len());
+
+    // Clearing the whole canvas every frame discards the previous frame
+    for frame in 0..100 {
+        ctx.clear_rect(0.0, 0.0, 200.0, 100.0);
+        ctx.fill_rect(frame as f64, 10.0, 20.0, 20.0);
+        ctx.stroke_rect(10.0, 10.0, 50.0, 50.0);
+    }
+    assert_eq!(len(), 2);
+
+    // As does clearing it through a scaling transform
+    ctx.save();
+    ctx.scale(2.0, 2.0);
+    ctx.clear_rect(0.0, 0.0, 100.0, 50.0);
+    ctx.restore();
+    assert_eq!(len(), 0);
+
+    // Clearing part of the canvas discards commands drawn entirely within it
+    ctx.fill_rect(150.0, 0.0, 50.0, 100.0);
+    for frame in 0..100 {
+        ctx.clear_rect(0.0, 0.0, 100.0, 100.0);
+        ctx.save();
+        ctx.begin_path();
+        ctx.rect(0.0, 0.0, 50.0, 50.0);
+        ctx.clip();
+        ctx.fill_rect(frame as f64 % 50.0, 10.0, 20.0, 20.0);
+        ctx.restore();
+    }
+    assert_eq!(len(), 5);
+
+    // Commands are kept when the clear is clipped
+    ctx.save();
+    ctx.begin_path();
+    ctx.rect(0.0, 0.0, 10.0, 10.0);
+    ctx.clip();
+    ctx.clear_rect(0.0, 0.0, 200.0, 100.0);
+    ctx.restore();
+    assert_eq!(len(), 8);
+}
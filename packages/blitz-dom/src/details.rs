@@ -0,0 +1,51 @@
+//! `<details>` and `<summary>` elements.
+//!
+//! A `<details>` element without an `open` attribute only renders its summary (the first `<summary>`
+//! child). Activating the summary (by clicking it, or pressing Enter or Space while it is focussed)
+//! toggles the `open` attribute of its `<details>` element.
+//!
+//! See <https://html.spec.whatwg.org/multipage/interactive-elements.html#the-details-element>
+
+use markup5ever::local_name;
+
+use crate::BaseDocument;
+
+impl BaseDocument {
+    /// The summary of a `<details>` element (its first `<summary>` child)
+    pub fn details_summary(&self, details_id: usize) -> Option<usize> {
+        let node = self.nodes.get(details_id)?;
+        if !node.data.is_element_with_tag_name(&local_name!("details")) {
+            return None;
+        }
+        node.children.iter().copied().find(|&child_id| {
+            self.nodes[child_id]
+                .data
+                .is_element_with_tag_name(&local_name!("summary"))
+        })
+    }
+
+    /// The `<details>` element that a `<summary>` element is the summary for (if any)
+    pub fn summary_details(&self, summary_id: usize) -> Option<usize> {
+        let details_id = self.nodes.get(summary_id)?.parent?;
+        (self.details_summary(details_id) == Some(summary_id)).then_some(details_id)
+    }
+
+    /// Whether a `<details>` element is open
+    pub fn is_details_open(&self, details_id: usize) -> bool {
+        self.nodes
+            .get(details_id)
+            .and_then(|node| node.attr(local_name!("open")))
+            .is_some()
+    }
+
+    /// Open or close a `<details>` element by setting or removing its `open` attribute
+    pub fn set_details_open(&mut self, details_id: usize, open: bool) {
+        self.set_boolean_attribute(details_id, local_name!("open"), open);
+    }
+
+    /// Toggle the `open` state of a `<details>` element
+    pub fn toggle_details(&mut self, details_id: usize) {
+        let open = self.is_details_open(details_id);
+        self.set_details_open(details_id, !open);
+    }
+}
@@ -0,0 +1,109 @@
+//! `<dialog>` elements.
+//!
+//! Dialogs are shown non-modally with `show_dialog` (which is equivalent to setting the `open`
+//! attribute), or modally with `show_modal_dialog`, which adds the dialog to the top layer, makes
+//! the rest of the document inert and traps focus within the dialog until it is closed (either with
+//! `close_dialog` or by pressing Escape).
+//!
+//! See <https://html.spec.whatwg.org/multipage/interactive-elements.html#the-dialog-element>
+
+use markup5ever::local_name;
+use style::invalidation::element::restyle_hints::RestyleHint;
+use style_dom::ElementState;
+
+use crate::BaseDocument;
+
+impl BaseDocument {
+    fn is_dialog(&self, node_id: usize) -> bool {
+        self.nodes
+            .get(node_id)
+            .is_some_and(|node| node.data.is_element_with_tag_name(&local_name!("dialog")))
+    }
+
+    /// Whether a `<dialog>` element is open (either modally or non-modally)
+    pub fn is_dialog_open(&self, dialog_id: usize) -> bool {
+        self.is_dialog(dialog_id) && self.nodes[dialog_id].attr(local_name!("open")).is_some()
+    }
+
+    /// Whether a `<dialog>` element is open modally
+    pub fn is_dialog_modal(&self, dialog_id: usize) -> bool {
+        self.is_dialog(dialog_id)
+            && self.nodes[dialog_id]
+                .element_state
+                .contains(ElementState::MODAL)
+    }
+
+    /// Show a `<dialog>` element non-modally (the equivalent of `dialog.show()`)
+    pub fn show_dialog(&mut self, dialog_id: usize) {
+        if !self.is_dialog(dialog_id) || self.is_dialog_open(dialog_id) {
+            return;
+        }
+
+        self.set_boolean_attribute(dialog_id, local_name!("open"), true);
+        self.run_dialog_focusing_steps(dialog_id);
+    }
+
+    /// Show a `<dialog>` element modally (the equivalent of `dialog.showModal()`)
+    pub fn show_modal_dialog(&mut self, dialog_id: usize) {
+        if !self.is_dialog(dialog_id)
+            || self.is_dialog_open(dialog_id)
+            || !self.is_connected(dialog_id)
+        {
+            return;
+        }
+
+        // Auto popovers are closed when a modal dialog is shown
+        self.hide_auto_popovers_except(None);
+
+        self.set_boolean_attribute(dialog_id, local_name!("open"), true);
+        self.snapshot_node_and(dialog_id, |node| {
+            node.element_state.insert(ElementState::MODAL);
+            node.set_restyle_hint(RestyleHint::restyle_subtree());
+        });
+        self.add_to_top_layer(dialog_id);
+        self.run_dialog_focusing_steps(dialog_id);
+    }
+
+    /// Close a `<dialog>` element (the equivalent of `dialog.close()`). If the dialog was modal, then
+    /// focus is returned to the node which was focussed when it was shown.
+    pub fn close_dialog(&mut self, dialog_id: usize) {
+        if !self.is_dialog_open(dialog_id) {
+            return;
+        }
+
+        self.set_boolean_attribute(dialog_id, local_name!("open"), false);
+        if self.is_dialog_modal(dialog_id) {
+            self.snapshot_node_and(dialog_id, |node| {
+                node.element_state.remove(ElementState::MODAL);
+                node.set_restyle_hint(RestyleHint::restyle_subtree());
+            });
+        }
+        self.remove_from_top_layer(dialog_id);
+    }
+
+    /// Focus the first descendant of a dialog with an `autofocus` attribute, or else its first
+    /// focussable descendant, or else the dialog itself.
+    fn run_dialog_focusing_steps(&mut self, dialog_id: usize) {
+        let mut first_focussable = None;
+        let mut autofocus = None;
+        let mut stack: Vec<usize> = self.nodes[dialog_id]
+            .children
+            .iter()
+            .rev()
+            .copied()
+            .collect();
+        while let Some(node_id) = stack.pop() {
+            let node = &self.nodes[node_id];
+            if node.is_focussable() {
+                if node.attr(local_name!("autofocus")).is_some() {
+                    autofocus = Some(node_id);
+                    break;
+                }
+                first_focussable.get_or_insert(node_id);
+            }
+            stack.extend(node.children.iter().rev().copied());
+        }
+
+        self.set_focus_to(autofocus.or(first_focussable).unwrap_or(dialog_id));
+    }
+}
@@ -1,75 +1,61 @@
 use crate::events::handle_event;
+use crate::font_metrics::BlitzFontMetricsProvider;
+use crate::form::DirtyFormState;
+use crate::iframe::{DummyIframeProvider, IframeProvider, SubDocument};
+use crate::image_animation::{FrameWaker, ImageAnimationClock};
 use crate::layout::construct::collect_layout_children;
-use crate::node::{ImageData, NodeSpecificData, RasterImageData, Status, TextBrush};
+use crate::media::{MediaElement, MediaEvent};
+use crate::node::{
+    AnimatedImageData, Attribute, ImageData, NodeSpecificData, RasterImageData, Status, TextBrush,
+};
+use crate::scroll::ScrollAnimation;
+use crate::scrollbar::{ScrollbarDrag, ScrollbarMode};
 use crate::stylo_to_cursor_icon::stylo_to_cursor_icon;
+use crate::top_layer::TopLayerElement;
 use crate::util::{ImageType, resolve_url};
 use crate::{ElementNodeData, Node, NodeData, TextNodeData};
-use app_units::Au;
+use blitz_traits::media::{DummyMediaProvider, SharedMediaProvider};
 use blitz_traits::navigation::{DummyNavigationProvider, NavigationProvider};
-use blitz_traits::net::{DummyNetProvider, SharedProvider};
+use blitz_traits::net::{DummyNetProvider, Request, SharedProvider};
 use blitz_traits::{ColorScheme, Document, Viewport};
 use blitz_traits::{DomEvent, HitResult};
 use cursor_icon::CursorIcon;
-use markup5ever::local_name;
+use markup5ever::{LocalName, QualName, local_name, namespace_url, ns};
 use parley::FontContext;
 use peniko::kurbo;
 use string_cache::Atom;
 use style::attr::{AttrIdentifier, AttrValue};
 use style::data::{ElementData, ElementStyles};
+use style::invalidation::element::restyle_hints::RestyleHint;
 use style::properties::ComputedValues;
 use style::properties::style_structs::Font;
 use style::values::GenericAtomIdent;
 use style::values::computed::Overflow;
+use style_dom::ElementState;
 // use quadtree_rs::Quadtree;
-use crate::net::{Resource, StylesheetLoader};
+use crate::net::{CssHandler, Resource, StylesheetLoader};
 use selectors::{Element, matching::QuirksMode};
 use slab::Slab;
+use std::cell::RefCell;
 use std::collections::{BTreeMap, Bound, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use style::media_queries::MediaType;
 use style::queries::values::PrefersColorScheme;
 use style::selector_parser::ServoElementSnapshot;
-use style::servo::media_queries::FontMetricsProvider;
 use style::servo_arc::Arc as ServoArc;
 use style::{
     dom::{TDocument, TNode},
     media_queries::{Device, MediaList},
     selector_parser::SnapshotMap,
     shared_lock::{SharedRwLock, StylesheetGuards},
+    stylesheet_set::AuthorStylesheetSet,
     stylesheets::{AllowImportRules, DocumentStyleSheet, Origin, Stylesheet, UrlExtraData},
     stylist::Stylist,
 };
 use taffy::AvailableSpace;
 use url::Url;
 
-// TODO: implement a proper font metrics provider
-#[derive(Debug, Clone)]
-struct DummyFontMetricsProvider;
-impl FontMetricsProvider for DummyFontMetricsProvider {
-    fn query_font_metrics(
-        &self,
-        _vertical: bool,
-        _font: &style::properties::style_structs::Font,
-        _base_size: style::values::computed::CSSPixelLength,
-        _in_media_query: bool,
-        _retrieve_math_scales: bool,
-    ) -> style::font_metrics::FontMetrics {
-        Default::default()
-    }
-
-    fn base_size_for_generic(
-        &self,
-        generic: style::values::computed::font::GenericFontFamily,
-    ) -> style::values::computed::Length {
-        let size = match generic {
-            style::values::computed::font::GenericFontFamily::Monospace => 13.0,
-            _ => 16.0,
-        };
-        style::values::computed::Length::from(Au::from_f32_px(size))
-    }
-}
-
 pub struct BaseDocument {
     id: usize,
 
@@ -105,15 +91,35 @@ pub struct BaseDocument {
     // Scroll within our viewport
     pub(crate) viewport_scroll: kurbo::Point,
 
+    /// Whether scrollbars reserve space in layout or are overlaid on top of content
+    pub(crate) scrollbar_mode: ScrollbarMode,
+    /// The scrollbar thumb which is currently being dragged (if any)
+    pub(crate) scrollbar_drag: Option<ScrollbarDrag>,
+    /// Smooth and inertial scrolls which are in progress
+    pub(crate) scroll_animations: Vec<ScrollAnimation>,
+
     /// Stylesheets added by the useragent
     /// where the key is the hashed CSS
     pub(crate) ua_stylesheets: HashMap<String, DocumentStyleSheet>,
 
     pub(crate) nodes_to_stylesheet: BTreeMap<usize, DocumentStyleSheet>,
 
+    /// Stylesheets within shadow trees, keyed by the id of the shadow root
+    pub(crate) shadow_stylesheets: HashMap<usize, AuthorStylesheetSet<DocumentStyleSheet>>,
+
+    /// The shadow hosts whose slot assignments need to be recomputed before the next restyle
+    pub(crate) dirty_slot_hosts: HashSet<usize>,
+
+    /// Whether counters, quotes and generated content need to be resolved again before the next
+    /// layout (because the tree's structure or styles have changed)
+    pub(crate) generated_content_dirty: bool,
+
     /// A Parley font context
     pub font_ctx: parley::FontContext,
 
+    /// Provides font metrics to Stylo (for font-relative units)
+    pub(crate) font_metrics_provider: BlitzFontMetricsProvider,
+
     /// A Parley layout context
     pub layout_ctx: parley::LayoutContext<TextBrush>,
 
@@ -132,9 +138,54 @@ pub struct BaseDocument {
     /// Navigation provider. Can be used to navigate to a new page (bubbles up the event
     /// on e.g. clicking a Link)
     pub navigation_provider: Arc<dyn NavigationProvider>,
+
+    /// Iframe provider. Used to create the nested documents of `<iframe>` elements
+    pub iframe_provider: Arc<dyn IframeProvider>,
+
+    /// Media provider. Used to open and decode the resources of `<video>` and `<audio>` elements
+    pub media_provider: SharedMediaProvider<RasterImageData>,
+
+    /// The nested documents of `<iframe>` elements, keyed by the iframe's node id
+    pub(crate) sub_documents: HashMap<usize, SubDocument>,
+
+    /// The generation of the pending load of each iframe's nested document (keyed by the iframe's
+    /// node id), so that responses for superseded loads can be dropped
+    pub(crate) pending_iframe_loads: HashMap<usize, u64>,
+
+    /// The playback state of `<video>` and `<audio>` elements, keyed by the element's node id
+    pub(crate) media_elements: HashMap<usize, MediaElement>,
+
+    /// Media events which have been fired but not yet dispatched by the embedder
+    pub(crate) media_events: Vec<(usize, MediaEvent)>,
+
+    /// Modal dialogs and open popovers, which are rendered above the rest of the document (in
+    /// the order that they were added)
+    pub(crate) top_layer: Vec<TopLayerElement>,
+
+    /// Absolutely positioned boxes in inline formatting contexts whose containing block is outside
+    /// of their inline root, keyed by node id (with the id of their inline root and their static
+    /// position). They are positioned once the rest of the document has been laid out.
+    pub(crate) deferred_absolute_boxes: HashMap<usize, (usize, taffy::Point<f32>)>,
+
+    /// Advances animated images
+    pub(crate) image_animation_clock: ImageAnimationClock,
+
+    /// The nodes whose images (or background images) are animated
+    pub(crate) animated_image_nodes: HashSet<usize>,
+
+    /// Wakes the task polling the document when the next frame of an animated image or playing
+    /// media element is due
+    pub(crate) frame_waker: FrameWaker,
+
+    /// The nodes whose form pseudo-class state needs to be recomputed before the next restyle
+    pub(crate) dirty_form_state: DirtyFormState,
+
+    /// The compiled regexes of the `pattern` attributes of inputs, keyed by pattern (or `None` for
+    /// invalid patterns)
+    pub(crate) compiled_patterns: RefCell<HashMap<String, Option<regex::Regex>>>,
 }
 
-fn make_device(viewport: &Viewport) -> Device {
+fn make_device(viewport: &Viewport, font_metrics_provider: BlitzFontMetricsProvider) -> Device {
     let width = viewport.window_size.0 as f32 / viewport.scale();
     let height = viewport.window_size.1 as f32 / viewport.scale();
     let viewport_size = euclid::Size2D::new(width, height);
@@ -145,7 +196,7 @@ fn make_device(viewport: &Viewport) -> Device {
         selectors::matching::QuirksMode::NoQuirks,
         viewport_size,
         device_pixel_ratio,
-        Box::new(DummyFontMetricsProvider),
+        Box::new(font_metrics_provider),
         ComputedValues::initial_values_with_font_override(Font::initial_values()),
         match viewport.color_scheme {
             ColorScheme::Light => PrefersColorScheme::Light,
@@ -174,7 +225,13 @@ impl BaseDocument {
         static ID_GENERATOR: AtomicUsize = AtomicUsize::new(1);
 
         let id = ID_GENERATOR.fetch_add(1, Ordering::SeqCst);
-        let device = make_device(&viewport);
+
+        font_ctx
+            .collection
+            .register_fonts(crate::BULLET_FONT.to_vec());
+        let font_metrics_provider = BlitzFontMetricsProvider::new(font_ctx.clone());
+
+        let device = make_device(&viewport, font_metrics_provider.clone());
         let stylist = Stylist::new(device, QuirksMode::NoQuirks);
         let snapshots = SnapshotMap::new();
         let nodes = Box::new(Slab::new());
@@ -188,10 +245,6 @@ impl BaseDocument {
         style_config::set_bool("layout.unimplemented", true);
         style_config::set_bool("layout.columns.enabled", true);
 
-        font_ctx
-            .collection
-            .register_fonts(crate::BULLET_FONT.to_vec());
-
         let mut doc = Self {
             id,
             guard,
@@ -201,11 +254,18 @@ impl BaseDocument {
             nodes_to_id,
             viewport,
             viewport_scroll: kurbo::Point::ZERO,
+            scrollbar_mode: ScrollbarMode::default(),
+            scrollbar_drag: None,
+            scroll_animations: Vec::new(),
             base_url: None,
             // quadtree: Quadtree::new(20),
             ua_stylesheets: HashMap::new(),
             nodes_to_stylesheet: BTreeMap::new(),
+            shadow_stylesheets: HashMap::new(),
+            dirty_slot_hosts: HashSet::new(),
+            generated_content_dirty: true,
             font_ctx,
+            font_metrics_provider,
             layout_ctx: parley::LayoutContext::new(),
 
             hover_node_id: None,
@@ -214,6 +274,19 @@ impl BaseDocument {
             changed: HashSet::new(),
             net_provider: Arc::new(DummyNetProvider::default()),
             navigation_provider: Arc::new(DummyNavigationProvider {}),
+            iframe_provider: Arc::new(DummyIframeProvider),
+            media_provider: Arc::new(DummyMediaProvider::default()),
+            sub_documents: HashMap::new(),
+            pending_iframe_loads: HashMap::new(),
+            media_elements: HashMap::new(),
+            media_events: Vec::new(),
+            top_layer: Vec::new(),
+            image_animation_clock: ImageAnimationClock::default(),
+            deferred_absolute_boxes: HashMap::new(),
+            animated_image_nodes: HashSet::new(),
+            frame_waker: FrameWaker::default(),
+            dirty_form_state: DirtyFormState::default(),
+            compiled_patterns: RefCell::new(HashMap::new()),
         };
 
         // Initialise document with root Document node
@@ -240,6 +313,11 @@ impl BaseDocument {
         self.net_provider = net_provider;
     }
 
+    /// Set the Document's iframe provider
+    pub fn set_iframe_provider(&mut self, iframe_provider: Arc<dyn IframeProvider>) {
+        self.iframe_provider = iframe_provider;
+    }
+
     /// Set the Document's navigation provider
     pub fn set_navigation_provider(&mut self, navigation_provider: Arc<dyn NavigationProvider>) {
         self.navigation_provider = navigation_provider;
@@ -337,6 +415,50 @@ impl BaseDocument {
                 }
             }
         }
+        self.mark_form_state_dirty(target_radio_id);
+    }
+
+    /// Add or remove a boolean attribute (such as `open` or `disabled`) and restyle the element
+    pub(crate) fn set_boolean_attribute(&mut self, node_id: usize, name: LocalName, present: bool) {
+        let has_attr = self.nodes[node_id].attr(name.clone()).is_some();
+        if has_attr == present {
+            return;
+        }
+
+        self.snapshot_node(node_id);
+        self.mark_form_state_dirty(node_id);
+        let node = &mut self.nodes[node_id];
+        let Some(element) = node.element_data_mut() else {
+            return;
+        };
+        if present {
+            element.attrs.push(Attribute {
+                name: QualName::new(None, ns!(), name),
+                value: String::new(),
+            });
+        } else {
+            element.attrs.retain(|attr| attr.name.local != name);
+        }
+        node.set_restyle_hint(RestyleHint::restyle_subtree());
+    }
+
+    /// Find the connected element with a given `id` attribute. Elements in shadow trees are not
+    /// found.
+    pub fn get_element_by_id(&self, id: &str) -> Option<usize> {
+        if let Some(&node_id) = self.nodes_to_id.get(id) {
+            return Some(node_id);
+        }
+
+        self.nodes
+            .iter()
+            .find(|(node_id, node)| {
+                node.element_data()
+                    .and_then(|el| el.id.as_ref())
+                    .is_some_and(|el_id| *el_id == *id)
+                    && self.is_connected(*node_id)
+                    && self.containing_shadow_root(*node_id).is_none()
+            })
+            .map(|(node_id, _)| node_id)
     }
 
     pub fn root_node(&self) -> &Node {
@@ -393,6 +515,16 @@ impl BaseDocument {
         }
         self.nodes[new_node_id].children = new_children;
 
+        // Cloning a template also clones its contents
+        let template_contents = self.nodes[new_node_id]
+            .element_data()
+            .and_then(|el| el.template_contents);
+        if let Some(fragment_id) = template_contents {
+            let new_fragment_id = self.deep_clone_node(fragment_id);
+            let element = self.nodes[new_node_id].element_data_mut().unwrap();
+            element.template_contents = Some(new_fragment_id);
+        }
+
         new_node_id
     }
 
@@ -430,11 +562,20 @@ impl BaseDocument {
         }
 
         self.nodes[parent_id].children = children;
+        for &inserted_node_id in inserted_node_ids {
+            self.mark_form_state_dirty(inserted_node_id);
+        }
+        self.mark_slots_dirty(parent_id);
+        self.generated_content_dirty = true;
     }
 
     pub fn append(&mut self, node_id: usize, appended_node_ids: &[usize]) {
-        let node = &self.nodes[node_id];
-        let parent_id = node.parent.unwrap();
+        let parent_id = self.nodes[node_id].parent.unwrap();
+        self.append_children(parent_id, appended_node_ids);
+    }
+
+    /// Append nodes to the children of `parent_id`
+    pub fn append_children(&mut self, parent_id: usize, appended_node_ids: &[usize]) {
         self.nodes[parent_id]
             .children
             .extend_from_slice(appended_node_ids);
@@ -442,17 +583,24 @@ impl BaseDocument {
         // Update parent values
         for &child_id in appended_node_ids {
             self.nodes[child_id].parent = Some(parent_id);
+            self.mark_form_state_dirty(child_id);
         }
+        self.mark_slots_dirty(parent_id);
+        self.generated_content_dirty = true;
     }
 
     /// Remove the node from it's parent but don't drop it
     pub fn remove_node(&mut self, node_id: usize) {
+        self.mark_removed_radio_groups_dirty(node_id);
         let node = &mut self.nodes[node_id];
 
         // Update child_idx values
         if let Some(parent_id) = node.parent.take() {
             let parent = &mut self.nodes[parent_id];
             parent.children.retain(|id| *id != node_id);
+            self.mark_form_state_ancestors_dirty(parent_id);
+            self.mark_slots_dirty(parent_id);
+            self.generated_content_dirty = true;
         }
     }
 
@@ -463,16 +611,36 @@ impl BaseDocument {
                 for &child in &node.children {
                     remove_node_ignoring_parent(doc, child);
                 }
+                if let Some(shadow_root_id) = node.shadow_root {
+                    doc.shadow_stylesheets.remove(&shadow_root_id);
+                    remove_node_ignoring_parent(doc, shadow_root_id);
+                }
+                doc.sub_documents.remove(&node_id);
+                doc.pending_iframe_loads.remove(&node_id);
+                doc.media_elements.remove(&node_id);
+                doc.top_layer.retain(|element| element.node_id != node_id);
+                if let Some(backdrop_id) = node.backdrop {
+                    remove_node_ignoring_parent(doc, backdrop_id);
+                }
+                if let Some(fragment_id) = node.element_data().and_then(|el| el.template_contents) {
+                    remove_node_ignoring_parent(doc, fragment_id);
+                }
             }
             node
         }
 
+        if self.nodes.contains(node_id) {
+            self.mark_removed_radio_groups_dirty(node_id);
+        }
         let node = remove_node_ignoring_parent(self, node_id);
 
         // Update child_idx values
         if let Some(parent_id) = node.as_ref().and_then(|node| node.parent) {
             let parent = &mut self.nodes[parent_id];
             parent.children.retain(|id| *id != node_id);
+            self.mark_form_state_ancestors_dirty(parent_id);
+            self.mark_slots_dirty(parent_id);
+            self.generated_content_dirty = true;
         }
 
         node
@@ -502,6 +670,45 @@ impl BaseDocument {
         self.add_stylesheet_for_node(sheet, target_id);
     }
 
+    /// Start loading the stylesheet at `href` for a node (a `<link>` element or an
+    /// `xml-stylesheet` processing instruction)
+    pub fn load_stylesheet(&mut self, target_id: usize, href: &str) {
+        let url = self.resolve_url(href);
+        self.net_provider.fetch(
+            self.id(),
+            Request::get(url.clone()),
+            Box::new(CssHandler {
+                node: target_id,
+                source_url: url,
+                guard: self.guard.clone(),
+                provider: self.net_provider.clone(),
+            }),
+        );
+    }
+
+    /// Start loading the resource referenced by a `<link rel="stylesheet">`, `<img>`, `<iframe>`,
+    /// `<video>` or `<audio>` element
+    pub fn load_element_resources(&mut self, node_id: usize) {
+        let Some(element) = self.nodes[node_id].element_data() else {
+            return;
+        };
+        match element.name.local {
+            local_name!("link") => {
+                let is_stylesheet = element.attr(local_name!("rel")).is_some_and(|rels| {
+                    rels.split_ascii_whitespace().any(|rel| rel == "stylesheet")
+                });
+                if let Some(href) = element.attr(local_name!("href")).filter(|_| is_stylesheet) {
+                    let href = href.to_string();
+                    self.load_stylesheet(node_id, &href);
+                }
+            }
+            local_name!("img") => self.load_image(node_id),
+            local_name!("iframe") => self.load_iframe(node_id),
+            local_name!("video") | local_name!("audio") => self.load_media(node_id),
+            _ => {}
+        }
+    }
+
     pub fn remove_user_agent_stylesheet(&mut self, contents: &str) {
         if let Some(sheet) = self.ua_stylesheets.remove(contents) {
             self.stylist.remove_stylesheet(sheet, &self.guard.read());
@@ -541,6 +748,11 @@ impl BaseDocument {
     }
 
     pub fn add_stylesheet_for_node(&mut self, stylesheet: DocumentStyleSheet, node_id: usize) {
+        // Stylesheets within a shadow tree only apply to that shadow tree
+        if let Some(shadow_root_id) = self.containing_shadow_root(node_id) {
+            return self.add_shadow_stylesheet_for_node(stylesheet, node_id, shadow_root_id);
+        }
+
         let old = self.nodes_to_stylesheet.insert(node_id, stylesheet.clone());
 
         if let Some(old) = old {
@@ -551,7 +763,7 @@ impl BaseDocument {
         let insertion_point = self
             .nodes_to_stylesheet
             .range((Bound::Excluded(node_id), Bound::Unbounded))
-            .next()
+            .find(|(id, _)| self.containing_shadow_root(**id).is_none())
             .map(|(_, sheet)| sheet);
 
         if let Some(insertion_point) = insertion_point {
@@ -566,6 +778,35 @@ impl BaseDocument {
         }
     }
 
+    fn add_shadow_stylesheet_for_node(
+        &mut self,
+        stylesheet: DocumentStyleSheet,
+        node_id: usize,
+        shadow_root_id: usize,
+    ) {
+        let guard = self.guard.read();
+        let old = self.nodes_to_stylesheet.insert(node_id, stylesheet.clone());
+        let insertion_point = self
+            .nodes_to_stylesheet
+            .range((Bound::Excluded(node_id), Bound::Unbounded))
+            .find(|(id, _)| self.containing_shadow_root(**id) == Some(shadow_root_id))
+            .map(|(_, sheet)| sheet.clone());
+
+        let sheets = self
+            .shadow_stylesheets
+            .entry(shadow_root_id)
+            .or_insert_with(AuthorStylesheetSet::new);
+        if let Some(old) = old {
+            sheets.remove_stylesheet(None, old, &guard);
+        }
+        match insertion_point {
+            Some(insertion_point) => {
+                sheets.insert_stylesheet_before(None, stylesheet, insertion_point, &guard)
+            }
+            None => sheets.append_stylesheet(None, stylesheet, &guard),
+        }
+    }
+
     pub fn load_resource(&mut self, resource: Resource) {
         match resource {
             Resource::Css(node_id, css) => {
@@ -596,6 +837,30 @@ impl BaseDocument {
                     }
                 }
             }
+            Resource::AnimatedImage(node_id, kind, frames) => {
+                self.animated_image_nodes.insert(node_id);
+                let node = self.get_node_mut(node_id).unwrap();
+                let image_data = ImageData::Animated(Box::new(AnimatedImageData::new(frames)));
+
+                match kind {
+                    ImageType::Image => {
+                        node.element_data_mut().unwrap().node_specific_data =
+                            NodeSpecificData::Image(Box::new(image_data));
+
+                        // Clear layout cache
+                        node.cache.clear();
+                    }
+                    ImageType::Background(idx) => {
+                        if let Some(Some(bg_image)) = node
+                            .element_data_mut()
+                            .and_then(|el| el.background_images.get_mut(idx))
+                        {
+                            bg_image.status = Status::Ok;
+                            bg_image.image = image_data;
+                        }
+                    }
+                }
+            }
             #[cfg(feature = "svg")]
             Resource::Svg(node_id, kind, tree) => {
                 let node = self.get_node_mut(node_id).unwrap();
@@ -619,8 +884,43 @@ impl BaseDocument {
                     }
                 }
             }
+            Resource::Html {
+                node_id,
+                generation,
+                url,
+                content_type,
+                html,
+            } => {
+                // Drop responses for loads which have been superseded (or whose iframe has been
+                // removed)
+                if self.pending_iframe_loads.get(&node_id) == Some(&generation) {
+                    self.pending_iframe_loads.remove(&node_id);
+                    let url = Some(url);
+                    self.create_sub_document(node_id, generation, url, content_type, &html);
+                }
+            }
+            Resource::SubDocument {
+                node_id,
+                generation,
+                resource,
+            } => {
+                // Drop responses to requests made by nested documents which have since been
+                // replaced (their node ids refer to nodes of the old document)
+                let sub_document = self.sub_documents.get_mut(&node_id);
+                if let Some(sub_document) = sub_document.filter(|sub| sub.generation == generation)
+                {
+                    sub_document.doc.load_resource(*resource);
+                }
+            }
             Resource::Font(bytes) => {
                 self.font_ctx.collection.register_fonts(bytes.to_vec());
+                self.font_metrics_provider
+                    .set_font_context(self.font_ctx.clone());
+
+                // Font-relative lengths and text layout depend on the fonts that are available
+                if let Some(root_id) = self.try_root_element().map(|root| root.id) {
+                    self.nodes[root_id].set_restyle_hint(RestyleHint::restyle_subtree());
+                }
             }
             Resource::None => {
                 // Do nothing
@@ -634,6 +934,8 @@ impl BaseDocument {
         node.has_snapshot = true;
         node.snapshot_handled
             .store(false, std::sync::atomic::Ordering::SeqCst);
+        // Attributes such as `<ol start>` and `<li value>` affect counters without affecting styles
+        self.generated_content_dirty = true;
 
         // TODO: handle invalidations other than hover
         if let Some(_existing_snapshot) = self.snapshots.get_mut(&opaque_node_id) {
@@ -703,9 +1005,18 @@ impl BaseDocument {
             return;
         }
 
+        // Assign slots and update shadow tree stylesheets
+        self.flush_shadow_dom();
+
+        // Update form-related pseudo-class state (:valid, :disabled, etc) before styling
+        self.flush_form_state();
+
         // we need to resolve stylist first since it will need to drive our layout bits
         self.resolve_stylist();
 
+        // Resolve counters, quotes and the content of pseudo-elements
+        self.resolve_generated_content();
+
         // Fix up tree for layout (insert anonymous blocks as necessary, etc)
         self.resolve_layout_children();
 
@@ -714,6 +1025,15 @@ impl BaseDocument {
 
         // Next we resolve layout with the data resolved by stlist
         self.resolve_layout();
+
+        // Lay out modal dialogs and open popovers (and their backdrops) against the viewport
+        self.resolve_top_layer();
+
+        // Nested documents are sized by the layout of their iframes
+        self.resolve_sub_documents();
+
+        // Resume animated images which have been scrolled into view
+        self.schedule_image_animation_wake();
     }
 
     // Takes (x, y) co-ordinates (relative to the )
@@ -726,6 +1046,11 @@ impl BaseDocument {
             return None;
         }
 
+        // Elements in the top layer are rendered above (and so are hit before) the rest of the document
+        if let Some(hit) = self.hit_top_layer(x, y) {
+            return Some(hit);
+        }
+
         self.root_element().hit(x, y)
     }
 
@@ -782,6 +1107,8 @@ impl BaseDocument {
         }
     }
 
+    /// Iterate over a node's children in the flat tree (see `Node::flat_tree_children`) and its
+    /// `::before` and `::after` pseudo-elements
     pub fn iter_children_and_pseudos_mut(
         &mut self,
         node_id: usize,
@@ -793,7 +1120,10 @@ impl BaseDocument {
         }
         self.nodes[node_id].before = before;
 
-        self.iter_children_mut(node_id, &mut cb);
+        let children = self.nodes[node_id].flat_tree_children().to_vec();
+        for child_id in children {
+            cb(child_id, self);
+        }
 
         let after = self.nodes[node_id].after.take();
         if let Some(after_node_id) = after {
@@ -866,8 +1196,10 @@ impl BaseDocument {
 
     pub fn focus_next_node(&mut self) -> Option<usize> {
         let focussed_node_id = self.get_focussed_node_id()?;
-        let id = self.next_node(&self.nodes[focussed_node_id], |node| node.is_focussable())?;
-        self.set_focus_to(id);
+        let id = self.next_node(&self.nodes[focussed_node_id], |node| {
+            node.is_focussable() && !self.is_inert(node.id)
+        })?;
+        self.set_focus(id, true);
         Some(id)
     }
 
@@ -875,24 +1207,46 @@ impl BaseDocument {
     pub fn clear_focus(&mut self) {
         if let Some(id) = self.focus_node_id {
             self.snapshot_node_and(id, |node| node.blur());
+            self.mark_form_state_ancestors_dirty(id);
             self.focus_node_id = None;
         }
     }
 
     pub fn set_focus_to(&mut self, focus_node_id: usize) -> bool {
+        self.set_focus(focus_node_id, false)
+    }
+
+    /// Focus a node. Nodes focussed from the keyboard (and text inputs, which are edited from the
+    /// keyboard) match `:focus-visible`.
+    pub(crate) fn set_focus(&mut self, focus_node_id: usize, from_keyboard: bool) -> bool {
         if Some(focus_node_id) == self.focus_node_id {
             return false;
         }
 
+        // Focus is trapped within a modal dialog
+        if self.is_inert(focus_node_id) {
+            return false;
+        }
+
         println!("Focussed node {}", focus_node_id);
 
         // Remove focus from the old node
         if let Some(id) = self.focus_node_id {
             self.snapshot_node_and(id, |node| node.blur());
+            self.mark_form_state_ancestors_dirty(id);
         }
 
         // Focus the new node
-        self.snapshot_node_and(focus_node_id, |node| node.focus());
+        let is_text_input = self.nodes[focus_node_id]
+            .element_data()
+            .is_some_and(|el| el.text_input_data().is_some());
+        self.snapshot_node_and(focus_node_id, |node| {
+            node.focus();
+            if from_keyboard || is_text_input {
+                node.element_state.insert(ElementState::FOCUSRING);
+            }
+        });
+        self.mark_form_state_ancestors_dirty(focus_node_id);
 
         self.focus_node_id = Some(focus_node_id);
 
@@ -970,7 +1324,15 @@ impl BaseDocument {
 
     pub fn set_viewport(&mut self, viewport: Viewport) {
         self.viewport = viewport;
-        self.set_stylist_device(make_device(&self.viewport));
+        // List markers are laid out at the viewport's scale
+        self.generated_content_dirty = true;
+        self.set_stylist_device(make_device(
+            &self.viewport,
+            self.font_metrics_provider.clone(),
+        ));
+
+        // The best source for responsive images depends on the viewport's size and scale
+        self.reselect_image_sources();
     }
 
     pub fn get_viewport(&self) -> Viewport {
@@ -988,6 +1350,11 @@ impl BaseDocument {
             self.stylist.set_device(device, &guards)
         };
         self.stylist.force_stylesheet_origins_dirty(origins);
+
+        // Media queries in shadow tree stylesheets need to be re-evaluated against the new device
+        for sheets in self.shadow_stylesheets.values_mut() {
+            sheets.force_dirty();
+        }
     }
 
     pub fn stylist_device(&mut self) -> &Device {
@@ -1033,7 +1400,25 @@ impl BaseDocument {
 
         // println!("\n\nRESOLVE LAYOUT\n===========\n");
 
+        self.resolve_root_intrinsic_sizes(root_element_id.into(), available_space);
         taffy::compute_root_layout(self, root_element_id, available_space);
+
+        // If the document overflows the viewport then reserve space for a classic vertical scrollbar
+        let gutter = self.scrollbar_mode.gutter();
+        let root_height = self.nodes[usize::from(root_element_id)]
+            .unrounded_layout
+            .size
+            .height;
+        if gutter > 0.0 && root_height > size.height.to_f32_px() {
+            let available_space = taffy::Size {
+                width: AvailableSpace::Definite((size.width.to_f32_px() - gutter).max(0.0)),
+                ..available_space
+            };
+            self.resolve_root_intrinsic_sizes(root_element_id.into(), available_space);
+            taffy::compute_root_layout(self, root_element_id, available_space);
+        }
+        self.resolve_deferred_absolute_boxes();
+
         taffy::round_layout(self, root_element_id);
 
         // println!("\n\n");
@@ -1048,6 +1433,11 @@ impl BaseDocument {
         // todo: cache this on the node itself
         let node = &self.nodes[self.get_hover_node_id()?];
 
+        // Use the cursor of the hovered node within an iframe
+        if let Some(sub_document) = self.sub_documents.get(&node.id) {
+            return sub_document.doc.get_cursor();
+        }
+
         let style = node.primary_styles()?;
         let keyword = stylo_to_cursor_icon(style.clone_cursor().keyword);
 
@@ -1083,6 +1473,16 @@ impl BaseDocument {
     /// Will bubble scrolling up to parent node once it can no longer scroll further
     /// If we're already at the root node, bubbles scrolling up to the viewport
     pub fn scroll_node_by(&mut self, node_id: usize, x: f64, y: f64) {
+        // Scrolling within an iframe is handled by the nested document
+        if let Some(sub_document) = self.sub_documents.get_mut(&node_id) {
+            let doc = &mut sub_document.doc;
+            match doc.get_hover_node_id() {
+                Some(hover_node_id) => doc.scroll_node_by(hover_node_id, x, y),
+                None => doc.scroll_viewport_by(x, y),
+            }
+            return;
+        }
+
         let Some(node) = self.nodes.get_mut(node_id) else {
             return;
         };
@@ -1199,7 +1599,12 @@ impl BaseDocument {
                 chain.push(node_id);
             }
 
-            next_node_id = node.parent;
+            // Events propagate through the flat tree: from a slotted node to its slot, and from a
+            // shadow tree to its host
+            next_node_id = match &node.data {
+                NodeData::ShadowRoot(data) => Some(data.host),
+                _ => node.assigned_slot.or(node.parent),
+            };
         }
 
         chain
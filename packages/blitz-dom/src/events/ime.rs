@@ -30,6 +30,7 @@ pub(crate) fn handle_ime_event(doc: &mut BaseDocument, event: BlitzImeEvent) {
                 }
             }
             println!("Sent ime event to {}", node_id);
+            doc.mark_form_state_dirty(node_id);
         }
     }
 }
@@ -4,9 +4,15 @@ use crate::{
 };
 use blitz_traits::BlitzKeyEvent;
 use keyboard_types::{Key, Modifiers};
+use markup5ever::local_name;
 use parley::{FontContext, LayoutContext};
 
 pub(crate) fn handle_keypress(doc: &mut BaseDocument, target: usize, event: BlitzKeyEvent) {
+    // Escape closes the topmost modal dialog or auto popover
+    if event.key == Key::Escape && event.state.is_pressed() && doc.close_top_layer_on_escape() {
+        return;
+    }
+
     if let Some(node_id) = doc.focus_node_id {
         if target != node_id {
             return;
@@ -21,7 +27,51 @@ pub(crate) fn handle_keypress(doc: &mut BaseDocument, target: usize, event: Blit
         if let Some(input_data) = text_input_data {
             println!("Sent text event to {}", node_id);
             apply_keypress_event(input_data, &mut doc.font_ctx, &mut doc.layout_ctx, event);
+            doc.mark_form_state_dirty(node_id);
+            return;
         }
+
+        // Enter and Space activate a focussed summary, toggling its details element
+        if let Some(details_id) = doc.summary_details(node_id) {
+            let activates = match &event.key {
+                Key::Enter => true,
+                Key::Character(c) => c == " ",
+                _ => false,
+            };
+            if activates {
+                if event.state.is_pressed() {
+                    doc.toggle_details(details_id);
+                }
+                return;
+            }
+        }
+
+        // Space activates a focussed button, link or form control rather than scrolling
+        let is_space = matches!(&event.key, Key::Character(c) if c == " ");
+        if is_space && is_activatable(doc, node_id) {
+            return;
+        }
+    }
+
+    // Keys which aren't consumed by a text input scroll the nearest scroll container. If nothing
+    // is focussed (so the keypress was sent to the root element), that is the container under the
+    // mouse.
+    let scroll_origin = match doc.focus_node_id {
+        Some(_) => target,
+        None => doc.hover_node_id.unwrap_or(target),
+    };
+    doc.scroll_for_keypress(scroll_origin, &event);
+}
+
+/// Whether a node is a button, link or form control (which the keyboard activates)
+fn is_activatable(doc: &BaseDocument, node_id: usize) -> bool {
+    let Some(el) = doc.nodes[node_id].element_data() else {
+        return false;
+    };
+    match el.name.local {
+        local_name!("button") | local_name!("input") | local_name!("select") => true,
+        local_name!("a") | local_name!("area") => el.attr(local_name!("href")).is_some(),
+        _ => false,
     }
 }
 
@@ -5,11 +5,16 @@ mod mouse;
 use blitz_traits::{DomEvent, DomEventData};
 pub(crate) use ime::handle_ime_event;
 pub(crate) use keyboard::handle_keypress;
-pub(crate) use mouse::{handle_click, handle_mousedown, handle_mousemove};
+pub(crate) use mouse::{handle_click, handle_mousedown, handle_mousemove, handle_mouseup};
 
 use crate::BaseDocument;
 
 pub(crate) fn handle_event(doc: &mut BaseDocument, event: &mut DomEvent) {
+    // Events targeting iframes are handled by the nested document
+    if doc.forward_event_to_sub_document(event) {
+        return;
+    }
+
     let target_node_id = event.target;
 
     match &event.data {
@@ -28,7 +33,9 @@ pub(crate) fn handle_event(doc: &mut BaseDocument, event: &mut DomEvent) {
         DomEventData::MouseDown(event) => {
             handle_mousedown(doc, target_node_id, event.x, event.y);
         }
-        DomEventData::MouseUp(_) => {}
+        DomEventData::MouseUp(event) => {
+            handle_mouseup(doc, target_node_id, event.x, event.y);
+        }
         DomEventData::Hover => {}
         DomEventData::Click(event) => {
             handle_click(doc, target_node_id, event.x, event.y);
@@ -18,6 +18,14 @@ pub(crate) fn handle_mousemove(
     y: f32,
     buttons: MouseEventButtons,
 ) -> bool {
+    if doc.is_dragging_scrollbar() {
+        if buttons == MouseEventButtons::None {
+            doc.end_scrollbar_drag();
+            return false;
+        }
+        return doc.drag_scrollbar(x, y);
+    }
+
     let Some(hit) = doc.hit(x, y) else {
         return false;
     };
@@ -61,6 +69,10 @@ pub(crate) fn handle_mousemove(
 }
 
 pub(crate) fn handle_mousedown(doc: &mut BaseDocument, target: usize, x: f32, y: f32) {
+    if doc.start_scrollbar_interaction(x, y) {
+        return;
+    }
+
     let Some(hit) = doc.hit(x, y) else {
         return;
     };
@@ -95,9 +107,21 @@ pub(crate) fn handle_mousedown(doc: &mut BaseDocument, target: usize, x: f32, y:
     }
 }
 
+pub(crate) fn handle_mouseup(doc: &mut BaseDocument, _target: usize, _x: f32, _y: f32) {
+    doc.end_scrollbar_drag();
+}
+
 pub(crate) fn handle_click(doc: &mut BaseDocument, _target: usize, x: f32, y: f32) {
+    // Clicks on scrollbars are handled on mousedown
+    if doc.hit_scrollbar(x, y).is_some() {
+        return;
+    }
+
     let mut maybe_hit = doc.hit(x, y);
 
+    // Clicking outside of open auto popovers closes them
+    doc.light_dismiss_popovers(maybe_hit.map(|hit| hit.node_id));
+
     while let Some(hit) = maybe_hit {
         let node = &mut doc.nodes[hit.node_id];
 
@@ -117,6 +141,7 @@ pub(crate) fn handle_click(doc: &mut BaseDocument, _target: usize, x: f32, y: f3
             && matches!(el.attr(local_name!("type")), Some("checkbox"))
         {
             BaseDocument::toggle_checkbox(el);
+            doc.mark_form_state_dirty(hit.node_id);
             doc.set_focus_to(hit.node_id);
             return;
         } else if el.name.local == local_name!("input")
@@ -128,6 +153,21 @@ pub(crate) fn handle_click(doc: &mut BaseDocument, _target: usize, x: f32, y: f3
             BaseDocument::set_focus_to(doc, hit.node_id);
             return;
         }
+        // Clicking the controls of a media element plays, pauses or seeks it
+        else if matches!(el.name.local, local_name!("video") | local_name!("audio")) {
+            if doc.click_media_controls(hit.node_id, hit.x, hit.y) {
+                return;
+            }
+        }
+        // Clicking the summary of a details element toggles it
+        else if el.name.local == local_name!("summary") {
+            let node_id = node.id;
+            if let Some(details_id) = doc.summary_details(node_id) {
+                doc.toggle_details(details_id);
+                doc.set_focus_to(node_id);
+                return;
+            }
+        }
         // Clicking labels triggers click, and possibly input event, of associated input
         else if el.name.local == local_name!("label") {
             let node_id = node.id;
@@ -140,6 +180,7 @@ pub(crate) fn handle_click(doc: &mut BaseDocument, _target: usize, x: f32, y: f3
                 if let Some(target_element) = target_node.element_data_mut() {
                     BaseDocument::toggle_checkbox(target_element);
                 }
+                doc.mark_form_state_dirty(target_node_id);
                 doc.set_focus_to(node_id);
                 return;
             }
@@ -159,6 +200,11 @@ pub(crate) fn handle_click(doc: &mut BaseDocument, _target: usize, x: f32, y: f3
             }
         }
 
+        // Buttons with a popovertarget attribute show, hide or toggle their popover
+        if doc.invoke_popover_target(hit.node_id) {
+            return;
+        }
+
         // No match. Recurse up to parent.
         maybe_hit = parent_hit(&doc.nodes[hit.node_id], x, y)
     }
@@ -0,0 +1,267 @@
+//! A Stylo `FontMetricsProvider` which reads metrics from the fonts in a Parley `FontContext`.
+//!
+//! This is used to resolve font-relative units (`ex`, `ch`, `cap`, `ic`) and `font-size-adjust`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use app_units::Au;
+use parley::fontique::{Attributes, QueryStatus};
+use parley::{FontContext, FontWidth};
+use skrifa::instance::{LocationRef, Size};
+use skrifa::{FontRef, MetadataProvider as _};
+use style::font_metrics::FontMetrics;
+use style::properties::style_structs::Font;
+use style::servo::media_queries::FontMetricsProvider;
+use style::values::computed::font::GenericFontFamily;
+use style::values::computed::{CSSPixelLength, Length};
+
+use crate::stylo_to_parley;
+
+/// Metrics for a single font, as a fraction of the font's em size
+#[derive(Debug, Clone, Copy)]
+struct UnitMetrics {
+    ascent: f32,
+    x_height: Option<f32>,
+    cap_height: Option<f32>,
+    zero_advance: Option<f32>,
+    ic_width: Option<f32>,
+}
+
+impl UnitMetrics {
+    fn from_font(font: &FontRef) -> Self {
+        let size = Size::unscaled();
+        let location = LocationRef::default();
+        let metrics = font.metrics(size, location);
+        let units_per_em = metrics.units_per_em.max(1) as f32;
+        let charmap = font.charmap();
+        let glyph_metrics = font.glyph_metrics(size, location);
+
+        let advance = |c: char| {
+            let glyph_id = charmap.map(c)?;
+            Some(glyph_metrics.advance_width(glyph_id)? / units_per_em)
+        };
+        // Older fonts don't specify an x-height or cap height in their OS/2 table, in which case
+        // we use the height of the "x" and "H" glyphs instead.
+        let glyph_height = |c: char| {
+            let glyph_id = charmap.map(c)?;
+            let bounds = glyph_metrics.bounds(glyph_id)?;
+            (bounds.y_max > 0.0).then_some(bounds.y_max)
+        };
+
+        Self {
+            ascent: metrics.ascent / units_per_em,
+            x_height: metrics
+                .x_height
+                .or_else(|| glyph_height('x'))
+                .map(|h| h / units_per_em),
+            cap_height: metrics
+                .cap_height
+                .or_else(|| glyph_height('H'))
+                .map(|h| h / units_per_em),
+            zero_advance: advance('0'),
+            // The advance of the CJK water ideograph is used as the ideographic advance
+            ic_width: advance('\u{6C34}'),
+        }
+    }
+}
+
+/// The state shared by the clones of a `BlitzFontMetricsProvider`
+struct SharedFontMetrics {
+    /// The font context that the contexts used for queries are cloned from
+    font_ctx: Mutex<FontContext>,
+    /// Incremented whenever `font_ctx` is replaced
+    version: AtomicU64,
+    /// Clones of `font_ctx` which aren't currently in use, with the version they were cloned at.
+    /// Each thread resolving styles takes its own context, so that queries don't block each other.
+    pool: Mutex<Vec<(u64, FontContext)>>,
+    /// Metrics keyed by font blob id and index within the blob
+    metrics: RwLock<HashMap<(u64, u32), UnitMetrics>>,
+}
+
+/// Resolves the primary font for a computed `font` style using Parley's font collection
+/// and returns its real metrics.
+///
+/// Cloning the provider shares the underlying font collection and cache.
+#[derive(Clone)]
+pub(crate) struct BlitzFontMetricsProvider {
+    shared: Arc<SharedFontMetrics>,
+}
+
+impl fmt::Debug for BlitzFontMetricsProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlitzFontMetricsProvider")
+            .finish_non_exhaustive()
+    }
+}
+
+impl BlitzFontMetricsProvider {
+    pub(crate) fn new(font_ctx: FontContext) -> Self {
+        Self {
+            shared: Arc::new(SharedFontMetrics {
+                font_ctx: Mutex::new(font_ctx),
+                version: AtomicU64::new(0),
+                pool: Mutex::new(Vec::new()),
+                metrics: RwLock::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Replace the font context that fonts are looked up in (e.g. with a clone of the document's
+    /// font context after fonts from `@font-face` rules have been registered in it)
+    pub(crate) fn set_font_context(&self, font_ctx: FontContext) {
+        *self.shared.font_ctx.lock().unwrap() = font_ctx;
+        self.shared.version.fetch_add(1, Ordering::AcqRel);
+        self.shared.pool.lock().unwrap().clear();
+    }
+
+    /// Run `f` with a font context which isn't in use by any other thread
+    fn with_font_ctx<R>(&self, f: impl FnOnce(&mut FontContext) -> R) -> R {
+        let version = self.shared.version.load(Ordering::Acquire);
+        let pooled = self.shared.pool.lock().unwrap().pop();
+        let mut font_ctx = match pooled {
+            Some((pooled_version, font_ctx)) if pooled_version == version => font_ctx,
+            _ => self.shared.font_ctx.lock().unwrap().clone(),
+        };
+
+        let result = f(&mut font_ctx);
+
+        if self.shared.version.load(Ordering::Acquire) == version {
+            self.shared.pool.lock().unwrap().push((version, font_ctx));
+        }
+        result
+    }
+
+    fn primary_font_metrics(&self, font: &Font) -> Option<UnitMetrics> {
+        let families: Vec<_> = font
+            .font_family
+            .families
+            .list
+            .iter()
+            .map(stylo_to_parley::font_family)
+            .collect();
+        let attributes = Attributes::new(
+            FontWidth::from_percentage(font.font_stretch.0.to_float()),
+            stylo_to_parley::font_style(font.font_style),
+            parley::FontWeight::new(font.font_weight.value()),
+        );
+        self.query_unit_metrics(&families, attributes)
+    }
+
+    /// The metrics of the first font in `families` that matches `attributes`
+    fn query_unit_metrics(
+        &self,
+        families: &[parley::FontFamily],
+        attributes: Attributes,
+    ) -> Option<UnitMetrics> {
+        self.with_font_ctx(|font_ctx| {
+            let mut query = font_ctx.collection.query(&mut font_ctx.source_cache);
+            query.set_families(families.iter().map(|family| match family {
+                parley::FontFamily::Named(name) => parley::fontique::QueryFamily::Named(name),
+                parley::FontFamily::Generic(generic) => {
+                    parley::fontique::QueryFamily::Generic(*generic)
+                }
+            }));
+            query.set_attributes(attributes);
+
+            let mut result = None;
+            query.matches_with(|query_font| {
+                let key = (query_font.blob.id(), query_font.index);
+                let cached = self.shared.metrics.read().unwrap().get(&key).copied();
+                result = cached.or_else(|| {
+                    let font_ref =
+                        FontRef::from_index(query_font.blob.as_ref(), query_font.index).ok()?;
+                    let unit_metrics = UnitMetrics::from_font(&font_ref);
+                    self.shared
+                        .metrics
+                        .write()
+                        .unwrap()
+                        .insert(key, unit_metrics);
+                    Some(unit_metrics)
+                });
+                match result {
+                    Some(_) => QueryStatus::Stop,
+                    None => QueryStatus::Continue,
+                }
+            });
+
+            result
+        })
+    }
+}
+
+impl FontMetricsProvider for BlitzFontMetricsProvider {
+    fn query_font_metrics(
+        &self,
+        _vertical: bool,
+        font: &Font,
+        base_size: CSSPixelLength,
+        _in_media_query: bool,
+        _retrieve_math_scales: bool,
+    ) -> FontMetrics {
+        let Some(metrics) = self.primary_font_metrics(font) else {
+            return Default::default();
+        };
+
+        // TODO: use vertical advances for the zero advance in vertical writing modes
+        let size = base_size.px();
+        let scale = |value: f32| Length::new(value * size);
+        FontMetrics {
+            x_height: metrics.x_height.map(scale),
+            zero_advance_measure: metrics.zero_advance.map(scale),
+            cap_height: metrics.cap_height.map(scale),
+            ic_width: metrics.ic_width.map(scale),
+            ascent: scale(metrics.ascent),
+            script_percent_scale_down: None,
+            script_script_percent_scale_down: None,
+        }
+    }
+
+    fn base_size_for_generic(&self, generic: GenericFontFamily) -> Length {
+        let size = match generic {
+            GenericFontFamily::Monospace => 13.0,
+            _ => 16.0,
+        };
+        Length::from(Au::from_f32_px(size))
+    }
+}
+
+#[test]
+fn reads_metrics_from_registered_fonts() {
+    let families = [parley::FontFamily::Named("Bullet".into())];
+    let provider = BlitzFontMetricsProvider::new(FontContext::default());
+    assert!(
+        provider
+            .query_unit_metrics(&families, Attributes::default())
+            .is_none()
+    );
+
+    // Fonts registered in the document's font context are found once it has been synced
+    let mut font_ctx = FontContext::default();
+    font_ctx
+        .collection
+        .register_fonts(crate::BULLET_FONT.to_vec());
+    provider.set_font_context(font_ctx);
+    let metrics = provider
+        .query_unit_metrics(&families, Attributes::default())
+        .unwrap();
+    assert!(metrics.ascent > 0.0);
+
+    // Queries from several threads at once each use their own font context
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let provider = provider.clone();
+            let families = families.clone();
+            std::thread::spawn(move || {
+                provider
+                    .query_unit_metrics(&families, Attributes::default())
+                    .map(|metrics| metrics.ascent)
+            })
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), Some(metrics.ascent));
+    }
+}
@@ -0,0 +1,768 @@
+//! Form control state: constraint validation and the form-related pseudo-classes
+//! (`:enabled`, `:disabled`, `:required`, `:optional`, `:read-only`, `:read-write`,
+//! `:placeholder-shown`, `:valid`, `:invalid`, `:in-range`, `:out-of-range`, `:indeterminate`,
+//! `:focus-within` and the `:-moz-meter-*` optimum pseudo-classes).
+//!
+//! The pseudo-classes are stored as `ElementState` flags on each node so that Stylo can invalidate
+//! styles when they change. They are recomputed before each restyle by `flush_form_state`, for the
+//! elements affected by changes since the last restyle (see `mark_form_state_dirty`).
+//!
+//! See <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#constraints>
+
+use std::collections::{HashMap, HashSet};
+
+use markup5ever::{LocalName, local_name};
+use regex::Regex;
+use style::invalidation::element::restyle_hints::RestyleHint;
+use style_dom::ElementState;
+
+use crate::layout::construct::build_placeholder_layout;
+use crate::node::{ElementNodeData, NodeSpecificData};
+use crate::{BaseDocument, Node};
+
+/// The element state flags managed by `flush_form_state`
+const FORM_STATES: ElementState = ElementState::DISABLED
+    .union(ElementState::ENABLED)
+    .union(ElementState::REQUIRED)
+    .union(ElementState::OPTIONAL_)
+    .union(ElementState::READONLY)
+    .union(ElementState::READWRITE)
+    .union(ElementState::PLACEHOLDER_SHOWN)
+    .union(ElementState::VALID)
+    .union(ElementState::INVALID)
+    .union(ElementState::INRANGE)
+    .union(ElementState::OUTOFRANGE)
+    .union(ElementState::INDETERMINATE)
+    .union(ElementState::FOCUS_WITHIN)
+    .union(ElementState::METER_OPTIMUM_STATES);
+
+/// The radio buttons in each named radio group (keyed by name)
+type RadioGroups = HashMap<String, Vec<usize>>;
+
+/// The nodes whose form state needs to be recomputed by the next `flush_form_state`
+pub(crate) struct DirtyFormState {
+    /// Whether every element needs to be recomputed (before the first flush)
+    all: bool,
+    /// Nodes which need to be recomputed along with their ancestors and descendants
+    subtrees: HashSet<usize>,
+    /// Nodes which need to be recomputed along with their ancestors
+    ancestors: HashSet<usize>,
+    /// The names of radio groups whose buttons need to be recomputed
+    radio_groups: HashSet<String>,
+}
+
+impl Default for DirtyFormState {
+    fn default() -> Self {
+        Self {
+            all: true,
+            subtrees: HashSet::new(),
+            ancestors: HashSet::new(),
+            radio_groups: HashSet::new(),
+        }
+    }
+}
+
+impl DirtyFormState {
+    fn is_empty(&self) -> bool {
+        !self.all
+            && self.subtrees.is_empty()
+            && self.ancestors.is_empty()
+            && self.radio_groups.is_empty()
+    }
+}
+
+/// The ways in which a form control's value can fail to satisfy its constraints.
+///
+/// See <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#validitystate>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidityState {
+    pub value_missing: bool,
+    pub type_mismatch: bool,
+    pub pattern_mismatch: bool,
+    pub too_long: bool,
+    pub too_short: bool,
+    pub range_underflow: bool,
+    pub range_overflow: bool,
+    pub step_mismatch: bool,
+    pub bad_input: bool,
+}
+
+impl ValidityState {
+    pub fn is_valid(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Whether a boolean attribute is set. `"false"` is treated as unset as some renderers set boolean
+/// attributes to `"true"` or `"false"` rather than adding and removing them.
+fn has_bool_attr(el: &ElementNodeData, name: LocalName) -> bool {
+    el.attr(name).is_some_and(|value| value != "false")
+}
+
+/// The (lowercased) type of an `<input>` element
+fn input_type(el: &ElementNodeData) -> String {
+    el.attr(local_name!("type"))
+        .map(|ty| ty.to_ascii_lowercase())
+        .unwrap_or_else(|| String::from("text"))
+}
+
+/// The name of the radio group of an `<input type=radio>` element (if it is one and has a name)
+fn radio_group_name(el: &ElementNodeData) -> Option<&str> {
+    let is_radio = el.name.local == local_name!("input") && input_type(el) == "radio";
+    el.attr(local_name!("name"))
+        .filter(|name| is_radio && !name.is_empty())
+}
+
+/// Whether an `<input>` type has a text value which is edited by the user
+fn is_text_input_type(ty: &str) -> bool {
+    matches!(
+        ty,
+        "text" | "search" | "url" | "tel" | "email" | "password" | "number"
+    )
+}
+
+fn is_form_associated(el: &ElementNodeData) -> bool {
+    matches!(
+        el.name.local,
+        local_name!("button")
+            | local_name!("input")
+            | local_name!("select")
+            | local_name!("textarea")
+            | local_name!("optgroup")
+            | local_name!("option")
+            | local_name!("fieldset")
+    )
+}
+
+/// Parse a floating-point number as per the HTML spec's rules for floating-point number values
+pub(crate) fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// A very permissive check for a valid email address
+///
+/// See <https://html.spec.whatwg.org/multipage/input.html#valid-e-mail-address>
+fn is_valid_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.is_empty()
+        && !value.chars().any(char::is_whitespace)
+        && !domain.contains('@')
+        && domain
+            .split('.')
+            .all(|label| !label.is_empty() && !label.starts_with('-') && !label.ends_with('-'))
+}
+
+impl BaseDocument {
+    /// Mark a node whose attributes, value or children have changed, so that the form state of it,
+    /// its ancestors and its descendants (and of the radio buttons in its group) is recomputed by
+    /// the next `flush_form_state`
+    pub fn mark_form_state_dirty(&mut self, node_id: usize) {
+        self.dirty_form_state.subtrees.insert(node_id);
+    }
+
+    /// Mark the ancestors of a node whose children have been removed (or which has gained or lost
+    /// focus), so that their form state is recomputed by the next `flush_form_state`
+    pub(crate) fn mark_form_state_ancestors_dirty(&mut self, node_id: usize) {
+        self.dirty_form_state.ancestors.insert(node_id);
+    }
+
+    /// Mark the radio groups of the radio buttons in a subtree which is being removed
+    pub(crate) fn mark_removed_radio_groups_dirty(&mut self, node_id: usize) {
+        let mut stack = vec![node_id];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            if let Some(name) = node.element_data().and_then(radio_group_name) {
+                self.dirty_form_state.radio_groups.insert(name.to_string());
+            }
+            stack.extend_from_slice(&node.children);
+        }
+    }
+
+    /// Lay out the placeholder of a text input or textarea again, after its `placeholder` attribute
+    /// has changed
+    pub fn update_placeholder(&mut self, node_id: usize) {
+        let placeholder = build_placeholder_layout(self, node_id);
+        let input_data = self.nodes[node_id]
+            .element_data_mut()
+            .and_then(|el| el.text_input_data_mut());
+        if let Some(input_data) = input_data {
+            input_data.placeholder = placeholder;
+        }
+    }
+
+    /// The current value of a form control (`<input>`, `<textarea>` or `<select>`)
+    pub fn form_control_value(&self, node_id: usize) -> Option<String> {
+        let node = &self.nodes[node_id];
+        let el = node.element_data()?;
+
+        if let NodeSpecificData::TextInput(input_data) = &el.node_specific_data {
+            return Some(input_data.editor.text().to_string());
+        }
+
+        match el.name.local {
+            local_name!("input") => Some(el.attr(local_name!("value")).unwrap_or("").to_string()),
+            local_name!("textarea") => Some(node.text_content()),
+            local_name!("select") => {
+                let options = self.descendants_with_tag(node_id, local_name!("option"));
+                let selected = options
+                    .iter()
+                    .find(|&&id| self.nodes[id].attr(local_name!("selected")).is_some())
+                    .or(options.first())?;
+                let option = &self.nodes[*selected];
+                Some(
+                    option
+                        .attr(local_name!("value"))
+                        .map(str::to_string)
+                        .unwrap_or_else(|| option.text_content().trim().to_string()),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    fn descendants_with_tag(&self, node_id: usize, tag: LocalName) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut stack: Vec<usize> = self.nodes[node_id].children.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            if node.data.is_element_with_tag_name(&tag) {
+                result.push(id);
+            }
+            stack.extend(node.children.iter().rev().copied());
+        }
+        result
+    }
+
+    /// Whether a checkbox or radio button is checked
+    fn is_checked(&self, node_id: usize) -> bool {
+        let el = self.nodes[node_id].element_data().unwrap();
+        el.checkbox_input_checked()
+            .unwrap_or_else(|| has_bool_attr(el, local_name!("checked")))
+    }
+
+    /// Index the radio buttons in the document by the name of their group
+    fn radio_groups(&self, names: &HashSet<String>) -> RadioGroups {
+        let mut groups = RadioGroups::new();
+        for (node_id, node) in self.nodes.iter() {
+            let name = node.element_data().and_then(radio_group_name);
+            if let Some(name) = name.filter(|name| names.contains(*name)) {
+                groups.entry(name.to_string()).or_default().push(node_id);
+            }
+        }
+        groups
+    }
+
+    /// The radio buttons in the same group as a radio button (including itself), looked up in an
+    /// index of the document's radio groups (if one has been built)
+    fn radio_group(&self, node_id: usize, groups: Option<&RadioGroups>) -> Vec<usize> {
+        let name = self.nodes[node_id]
+            .element_data()
+            .and_then(radio_group_name);
+        let Some(name) = name else {
+            return vec![node_id];
+        };
+        match groups {
+            Some(groups) => groups.get(name).cloned().unwrap_or_else(|| vec![node_id]),
+            None => self
+                .radio_groups(&HashSet::from([name.to_string()]))
+                .remove(name)
+                .unwrap_or_else(|| vec![node_id]),
+        }
+    }
+
+    /// Whether a form control is disabled, either by its own `disabled` attribute or by that of
+    /// an ancestor `<fieldset>` or `<optgroup>`.
+    pub fn is_disabled(&self, node_id: usize) -> bool {
+        let node = &self.nodes[node_id];
+        let Some(el) = node.element_data() else {
+            return false;
+        };
+        if !is_form_associated(el) {
+            return false;
+        }
+        if has_bool_attr(el, local_name!("disabled")) {
+            return true;
+        }
+
+        // Options are disabled by a disabled parent optgroup
+        if el.name.local == local_name!("option") {
+            return node.parent.is_some_and(|parent_id| {
+                let parent = &self.nodes[parent_id];
+                parent
+                    .data
+                    .is_element_with_tag_name(&local_name!("optgroup"))
+                    && parent
+                        .element_data()
+                        .is_some_and(|el| has_bool_attr(el, local_name!("disabled")))
+            });
+        }
+
+        // Controls are disabled by a disabled ancestor fieldset, unless they are within its first legend
+        let mut child_id = node_id;
+        let mut ancestor_id = node.parent;
+        while let Some(id) = ancestor_id {
+            let ancestor = &self.nodes[id];
+            let is_disabled_fieldset = ancestor.element_data().is_some_and(|el| {
+                el.name.local == local_name!("fieldset")
+                    && has_bool_attr(el, local_name!("disabled"))
+            });
+            if is_disabled_fieldset {
+                let first_legend = ancestor.children.iter().copied().find(|&id| {
+                    self.nodes[id]
+                        .data
+                        .is_element_with_tag_name(&local_name!("legend"))
+                });
+                if first_legend != Some(child_id) {
+                    return true;
+                }
+            }
+            child_id = id;
+            ancestor_id = ancestor.parent;
+        }
+
+        false
+    }
+
+    /// Whether an element is a candidate for constraint validation
+    fn is_validation_candidate(&self, node_id: usize) -> bool {
+        let Some(el) = self.nodes[node_id].element_data() else {
+            return false;
+        };
+        let is_candidate_type = match el.name.local {
+            local_name!("input") => {
+                !matches!(input_type(el).as_str(), "hidden" | "reset" | "button")
+                    && !has_bool_attr(el, local_name!("readonly"))
+            }
+            local_name!("textarea") => !has_bool_attr(el, local_name!("readonly")),
+            local_name!("select") => true,
+            local_name!("button") => !matches!(
+                el.attr(local_name!("type"))
+                    .map(|ty| ty.to_ascii_lowercase())
+                    .as_deref(),
+                Some("reset" | "button")
+            ),
+            _ => false,
+        };
+        is_candidate_type
+            && !self.is_disabled(node_id)
+            && !self.has_ancestor_with_tag(node_id, local_name!("datalist"))
+    }
+
+    fn has_ancestor_with_tag(&self, node_id: usize, tag: LocalName) -> bool {
+        let mut ancestor_id = self.nodes[node_id].parent;
+        while let Some(id) = ancestor_id {
+            if self.nodes[id].data.is_element_with_tag_name(&tag) {
+                return true;
+            }
+            ancestor_id = self.nodes[id].parent;
+        }
+        false
+    }
+
+    /// The validity of a form control, or `None` if the element isn't a candidate for constraint
+    /// validation.
+    ///
+    /// Note: the `minlength` and `maxlength` constraints are checked whether or not the value has
+    /// been edited by the user.
+    pub fn validity(&self, node_id: usize) -> Option<ValidityState> {
+        self.validity_in(node_id, None)
+    }
+
+    fn validity_in(&self, node_id: usize, groups: Option<&RadioGroups>) -> Option<ValidityState> {
+        if !self.is_validation_candidate(node_id) {
+            return None;
+        }
+
+        let el = self.nodes[node_id].element_data().unwrap();
+        let required = has_bool_attr(el, local_name!("required"));
+        let mut validity = ValidityState::default();
+
+        match el.name.local {
+            local_name!("input") => {
+                let ty = input_type(el);
+                let value = self.form_control_value(node_id).unwrap_or_default();
+                match ty.as_str() {
+                    "checkbox" => validity.value_missing = required && !self.is_checked(node_id),
+                    "radio" => {
+                        let group = self.radio_group(node_id, groups);
+                        let group_required = group.iter().any(|&id| {
+                            self.nodes[id]
+                                .element_data()
+                                .is_some_and(|el| has_bool_attr(el, local_name!("required")))
+                        });
+                        validity.value_missing =
+                            group_required && !group.iter().any(|&id| self.is_checked(id));
+                    }
+                    "range" | "color" | "submit" | "image" | "file" => {}
+                    _ => {
+                        validity.value_missing = required && value.is_empty();
+                        if ty == "number" {
+                            self.check_number(el, &value, &mut validity);
+                        } else {
+                            self.check_text(el, &ty, &value, &mut validity);
+                        }
+                    }
+                }
+            }
+            local_name!("textarea") => {
+                let value = self.form_control_value(node_id).unwrap_or_default();
+                validity.value_missing = required && value.is_empty();
+                self.check_length(el, &value, &mut validity);
+            }
+            local_name!("select") => {
+                let value = self.form_control_value(node_id).unwrap_or_default();
+                validity.value_missing = required && value.is_empty();
+            }
+            _ => {}
+        }
+
+        Some(validity)
+    }
+
+    /// Whether an element satisfies its constraints. Forms and fieldsets are valid if all of their
+    /// descendant form controls are valid.
+    pub fn check_validity(&self, node_id: usize) -> bool {
+        self.check_validity_in(node_id, None)
+    }
+
+    fn check_validity_in(&self, node_id: usize, groups: Option<&RadioGroups>) -> bool {
+        let node = &self.nodes[node_id];
+        if node.data.is_element_with_tag_name(&local_name!("form"))
+            || node.data.is_element_with_tag_name(&local_name!("fieldset"))
+        {
+            let mut stack: Vec<usize> = node.children.clone();
+            while let Some(id) = stack.pop() {
+                if self
+                    .validity_in(id, groups)
+                    .is_some_and(|validity| !validity.is_valid())
+                {
+                    return false;
+                }
+                stack.extend_from_slice(&self.nodes[id].children);
+            }
+            return true;
+        }
+        self.validity_in(node_id, groups)
+            .is_none_or(|validity| validity.is_valid())
+    }
+
+    fn check_length(&self, el: &ElementNodeData, value: &str, validity: &mut ValidityState) {
+        if value.is_empty() {
+            return;
+        }
+        // Lengths are measured in UTF-16 code units
+        let length = value.encode_utf16().count();
+        if let Some(max) = el.attr_parsed::<usize>(local_name!("maxlength")) {
+            validity.too_long = length > max;
+        }
+        if let Some(min) = el.attr_parsed::<usize>(local_name!("minlength")) {
+            validity.too_short = length < min;
+        }
+    }
+
+    fn check_text(
+        &self,
+        el: &ElementNodeData,
+        ty: &str,
+        value: &str,
+        validity: &mut ValidityState,
+    ) {
+        if value.is_empty() {
+            return;
+        }
+        self.check_length(el, value, validity);
+
+        // `<input type=email multiple>` accepts a comma-separated list of addresses
+        let values: Vec<&str> = if ty == "email" && has_bool_attr(el, local_name!("multiple")) {
+            value.split(',').map(str::trim).collect()
+        } else {
+            vec![value]
+        };
+
+        validity.type_mismatch = match ty {
+            "email" => !values.iter().all(|value| is_valid_email(value)),
+            "url" => url::Url::parse(value).is_err(),
+            _ => false,
+        };
+
+        // An invalid pattern is ignored. Compiled patterns are cached, as they are checked
+        // whenever the value changes.
+        if let Some(pattern) = el.attr(local_name!("pattern")) {
+            let mut patterns = self.compiled_patterns.borrow_mut();
+            let regex = patterns
+                .entry(pattern.to_string())
+                .or_insert_with(|| Regex::new(&format!("^(?:{pattern})$")).ok());
+            if let Some(regex) = regex {
+                validity.pattern_mismatch = !values.iter().all(|value| regex.is_match(value));
+            }
+        }
+    }
+
+    fn check_number(&self, el: &ElementNodeData, value: &str, validity: &mut ValidityState) {
+        if value.is_empty() {
+            return;
+        }
+        let Some(number) = parse_number(value) else {
+            validity.bad_input = true;
+            return;
+        };
+
+        let min = el.attr(local_name!("min")).and_then(parse_number);
+        let max = el.attr(local_name!("max")).and_then(parse_number);
+        validity.range_underflow = min.is_some_and(|min| number < min);
+        validity.range_overflow = max.is_some_and(|max| number > max);
+
+        // The default step for number inputs is 1
+        let step = match el.attr(local_name!("step")) {
+            Some(step) if step.eq_ignore_ascii_case("any") => None,
+            Some(step) => parse_number(step).filter(|step| *step > 0.0).or(Some(1.0)),
+            None => Some(1.0),
+        };
+        if let Some(step) = step {
+            let base = min
+                .or_else(|| el.attr(local_name!("value")).and_then(parse_number))
+                .unwrap_or(0.0);
+            let steps = (number - base) / step;
+            validity.step_mismatch = (steps - steps.round()).abs() > 1e-9;
+        }
+    }
+
+    /// Whether an element matches `:read-write`
+    fn is_read_write(&self, node_id: usize) -> bool {
+        let node = &self.nodes[node_id];
+        let Some(el) = node.element_data() else {
+            return false;
+        };
+        let is_editable_control = match el.name.local {
+            local_name!("input") => {
+                let ty = input_type(el);
+                (is_text_input_type(&ty)
+                    || matches!(
+                        ty.as_str(),
+                        "date" | "month" | "week" | "time" | "datetime-local"
+                    ))
+                    && !has_bool_attr(el, local_name!("readonly"))
+            }
+            local_name!("textarea") => !has_bool_attr(el, local_name!("readonly")),
+            _ => false,
+        };
+        if is_editable_control {
+            return !self.is_disabled(node_id);
+        }
+
+        // Editing hosts (and their descendants) are read-write
+        let mut current = Some(node);
+        while let Some(node) = current {
+            match node.attr(local_name!("contenteditable")) {
+                Some("" | "true" | "plaintext-only") => return true,
+                Some("false") => return false,
+                _ => current = node.parent.map(|id| &self.nodes[id]),
+            }
+        }
+        false
+    }
+
+    /// Compute the form-related element state flags for a node
+    fn form_state(
+        &self,
+        node_id: usize,
+        focus_path: &[usize],
+        groups: &RadioGroups,
+    ) -> ElementState {
+        let node = &self.nodes[node_id];
+        let el = node.element_data().unwrap();
+        let mut state = ElementState::empty();
+
+        if focus_path.contains(&node_id) {
+            state.insert(ElementState::FOCUS_WITHIN);
+        }
+
+        if is_form_associated(el) {
+            state.insert(match self.is_disabled(node_id) {
+                true => ElementState::DISABLED,
+                false => ElementState::ENABLED,
+            });
+        }
+
+        state.insert(match self.is_read_write(node_id) {
+            true => ElementState::READWRITE,
+            false => ElementState::READONLY,
+        });
+
+        let is_input = el.name.local == local_name!("input");
+        let ty = is_input.then(|| input_type(el));
+        let supports_required = match el.name.local {
+            local_name!("input") => !matches!(
+                ty.as_deref(),
+                Some("hidden" | "range" | "color" | "submit" | "image" | "reset" | "button")
+            ),
+            local_name!("select") | local_name!("textarea") => true,
+            _ => false,
+        };
+        if supports_required {
+            state.insert(match has_bool_attr(el, local_name!("required")) {
+                true => ElementState::REQUIRED,
+                false => ElementState::OPTIONAL_,
+            });
+        }
+
+        let shows_placeholder = (ty.as_deref().is_some_and(is_text_input_type)
+            || el.name.local == local_name!("textarea"))
+            && el
+                .attr(local_name!("placeholder"))
+                .is_some_and(|placeholder| !placeholder.is_empty())
+            && self
+                .form_control_value(node_id)
+                .is_some_and(|value| value.is_empty());
+        if shows_placeholder {
+            state.insert(ElementState::PLACEHOLDER_SHOWN);
+        }
+
+        let is_form_or_fieldset =
+            matches!(el.name.local, local_name!("form") | local_name!("fieldset"));
+        if let Some(validity) = self.validity_in(node_id, Some(groups)) {
+            state.insert(match validity.is_valid() {
+                true => ElementState::VALID,
+                false => ElementState::INVALID,
+            });
+
+            // Only number and range inputs with a minimum or maximum have range limitations
+            // TODO: support range limitations for date and time inputs
+            let has_range_limitations = matches!(ty.as_deref(), Some("number" | "range"))
+                && (el.attr(local_name!("min")).is_some() || el.attr(local_name!("max")).is_some());
+            if has_range_limitations {
+                state.insert(match validity.range_underflow || validity.range_overflow {
+                    true => ElementState::OUTOFRANGE,
+                    false => ElementState::INRANGE,
+                });
+            }
+        } else if is_form_or_fieldset {
+            state.insert(match self.check_validity_in(node_id, Some(groups)) {
+                true => ElementState::VALID,
+                false => ElementState::INVALID,
+            });
+        }
+
+        // Radio buttons are indeterminate if no button in their group is checked, and progress
+        // bars are indeterminate if they have no value
+        let is_indeterminate = match el.name.local {
+            local_name!("input") if ty.as_deref() == Some("radio") => !self
+                .radio_group(node_id, Some(groups))
+                .iter()
+                .any(|&id| self.is_checked(id)),
+            local_name!("progress") => el.attr(local_name!("value")).is_none(),
+            _ => false,
+        };
+        if is_indeterminate {
+            state.insert(ElementState::INDETERMINATE);
+        }
+
+        if let Some(meter) = el.meter_values() {
+            state.insert(meter.region().element_state());
+        }
+
+        state
+    }
+
+    /// The elements whose form state may have changed since the last flush: the nodes marked as
+    /// dirty, their ancestors (whose validity and `:focus-within` depend on them), their
+    /// descendants (which may be disabled or made read-write by them) and the radio buttons in
+    /// their groups (which are added to `dirty.radio_groups`).
+    fn form_state_affected_nodes(&self, dirty: &mut DirtyFormState) -> HashSet<usize> {
+        let affected: HashSet<usize> = match dirty.all {
+            true => self.nodes.iter().map(|(node_id, _)| node_id).collect(),
+            false => self.dirty_subtrees(dirty),
+        };
+
+        for &node_id in &affected {
+            if let Some(name) = self.nodes[node_id]
+                .element_data()
+                .and_then(radio_group_name)
+            {
+                dirty.radio_groups.insert(name.to_string());
+            }
+        }
+        affected
+    }
+
+    /// The nodes marked as dirty, their ancestors and their descendants
+    fn dirty_subtrees(&self, dirty: &DirtyFormState) -> HashSet<usize> {
+        let mut affected = HashSet::new();
+        for &node_id in &dirty.subtrees {
+            if !self.nodes.contains(node_id) {
+                continue;
+            }
+            affected.extend(self.node_chain(node_id));
+            let mut stack = vec![node_id];
+            while let Some(id) = stack.pop() {
+                let node = &self.nodes[id];
+                affected.insert(id);
+                stack.extend_from_slice(&node.children);
+                stack.extend(node.shadow_root);
+            }
+        }
+        for &node_id in &dirty.ancestors {
+            if self.nodes.contains(node_id) {
+                affected.extend(self.node_chain(node_id));
+            }
+        }
+        affected
+    }
+
+    /// Recompute the form-related pseudo-class state of the elements affected by changes since
+    /// the last flush, snapshotting elements whose state has changed so that they are restyled.
+    pub(crate) fn flush_form_state(&mut self) {
+        if self.dirty_form_state.is_empty() {
+            return;
+        }
+        let mut dirty = std::mem::replace(
+            &mut self.dirty_form_state,
+            DirtyFormState {
+                all: false,
+                ..DirtyFormState::default()
+            },
+        );
+
+        let mut affected = self.form_state_affected_nodes(&mut dirty);
+
+        // The radio buttons in affected groups (and so the forms containing them) are affected
+        let groups = match dirty.radio_groups.is_empty() {
+            true => RadioGroups::new(),
+            false => self.radio_groups(&dirty.radio_groups),
+        };
+        for &radio_id in groups.values().flatten() {
+            affected.extend(self.node_chain(radio_id));
+        }
+
+        // The focused element and its ancestors (including the hosts of any shadow trees it is in)
+        let focus_path = self
+            .focus_node_id
+            .map(|id| self.node_chain(id))
+            .unwrap_or_default();
+
+        let changes: Vec<(usize, ElementState)> = affected
+            .into_iter()
+            .map(|node_id| (node_id, &self.nodes[node_id]))
+            .filter(|(_, node)| node.is_element())
+            .filter_map(|(node_id, node)| {
+                let state = self.form_state(node_id, &focus_path, &groups);
+                (state != node.element_state.intersection(FORM_STATES)).then_some((node_id, state))
+            })
+            .collect();
+
+        for (node_id, state) in changes {
+            // Elements which have never been styled don't need to be invalidated
+            let is_styled = self.nodes[node_id].stylo_element_data.borrow().is_some();
+            if is_styled {
+                self.snapshot_node(node_id);
+            }
+            let node: &mut Node = &mut self.nodes[node_id];
+            node.element_state.remove(FORM_STATES);
+            node.element_state.insert(state);
+            node.set_restyle_hint(RestyleHint::restyle_subtree());
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! `<iframe>` elements and the nested documents they embed.
+//!
+//! Each iframe owns a nested `BaseDocument` (stored in `BaseDocument::sub_documents`, keyed by the
+//! iframe's node id) with its own stylist, viewport and net requests. The iframe itself is laid out
+//! as a replaced element, and the nested document's viewport is sized to the iframe's content box.
+//!
+//! As blitz-dom doesn't know how to parse HTML, nested documents are created by the embedder
+//! through an `IframeProvider`.
+//!
+//! See <https://html.spec.whatwg.org/multipage/iframe-embed-object.html#the-iframe-element>
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use blitz_traits::media::SharedMediaProvider;
+use blitz_traits::navigation::{DummyNavigationProvider, NavigationProvider};
+use blitz_traits::net::{Request, SharedProvider};
+use blitz_traits::{Document, DomEvent, DomEventData, Viewport};
+use markup5ever::local_name;
+use parley::FontContext;
+use url::Url;
+
+use crate::BaseDocument;
+use crate::net::{HtmlHandler, Resource, SubDocumentNetProvider};
+use crate::node::RasterImageData;
+
+/// The restrictions applied to an iframe by its `sandbox` attribute
+///
+/// See <https://html.spec.whatwg.org/multipage/iframe-embed-object.html#attr-iframe-sandbox>
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IframeSandbox {
+    /// The tokens of the `sandbox` attribute, or `None` if the iframe is not sandboxed
+    tokens: Option<Vec<String>>,
+}
+
+impl IframeSandbox {
+    pub fn parse(attr: Option<&str>) -> Self {
+        Self {
+            tokens: attr.map(|value| {
+                value
+                    .split_ascii_whitespace()
+                    .map(|token| token.to_ascii_lowercase())
+                    .collect()
+            }),
+        }
+    }
+
+    pub fn is_sandboxed(&self) -> bool {
+        self.tokens.is_some()
+    }
+
+    /// Whether the sandbox lifts the given restriction (e.g. "allow-top-navigation"). This is
+    /// always true for iframes that aren't sandboxed.
+    pub fn allows(&self, token: &str) -> bool {
+        match &self.tokens {
+            Some(tokens) => tokens.iter().any(|t| t == token),
+            None => true,
+        }
+    }
+}
+
+/// The generation of the next load of an iframe's nested document
+static LOAD_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// The nested document of an iframe
+pub(crate) struct SubDocument {
+    /// The generation of the load which created the document. Responses to the document's own
+    /// requests carry it, so that they are dropped once the document has been replaced.
+    pub(crate) generation: u64,
+    pub(crate) doc: Box<BaseDocument>,
+}
+
+/// Everything needed to create the nested document of an iframe
+pub struct SubDocumentConfig {
+    /// The url of the nested document (`None` for `srcdoc` documents)
+    pub url: Option<Url>,
+    /// The `Content-Type` of the nested document (`text/html` for `srcdoc` documents, `None` if
+    /// the response didn't have one)
+    pub content_type: Option<String>,
+    pub sandbox: IframeSandbox,
+    viewport: Viewport,
+    font_ctx: FontContext,
+    net_provider: SharedProvider<Resource>,
+    navigation_provider: Arc<dyn NavigationProvider>,
+    iframe_provider: Arc<dyn IframeProvider>,
+    media_provider: SharedMediaProvider<RasterImageData>,
+}
+
+impl SubDocumentConfig {
+    /// Create an empty document using this configuration. Embedders should add their user agent
+    /// stylesheets to it and parse the nested document's HTML into it.
+    pub fn create_document(&self) -> BaseDocument {
+        let mut doc = BaseDocument::with_font_ctx(self.viewport.clone(), self.font_ctx.clone());
+        if let Some(url) = &self.url {
+            doc.set_base_url(url.as_str());
+        }
+        doc.set_net_provider(self.net_provider.clone());
+        doc.set_navigation_provider(self.navigation_provider.clone());
+        doc.set_iframe_provider(self.iframe_provider.clone());
+        doc.set_media_provider(self.media_provider.clone());
+        doc
+    }
+
+    /// The net provider of the nested document
+    pub fn net_provider(&self) -> SharedProvider<Resource> {
+        self.net_provider.clone()
+    }
+}
+
+/// An embedder hook for creating the nested documents of `<iframe>` elements
+pub trait IframeProvider: Send + Sync + 'static {
+    /// Whether the iframe may load its content. `url` is `None` for `srcdoc` documents.
+    ///
+    /// The default implementation allows everything.
+    fn allow_load(&self, _url: Option<&Url>, _sandbox: &IframeSandbox) -> bool {
+        true
+    }
+
+    /// Create a nested document from its HTML source (either fetched from the iframe's `src` or
+    /// taken from its `srcdoc` attribute).
+    fn create_document(&self, html: &str, config: SubDocumentConfig) -> Option<BaseDocument>;
+}
+
+/// The default `IframeProvider`, which doesn't create any documents
+pub struct DummyIframeProvider;
+
+impl IframeProvider for DummyIframeProvider {
+    fn create_document(&self, _html: &str, _config: SubDocumentConfig) -> Option<BaseDocument> {
+        None
+    }
+}
+
+impl BaseDocument {
+    /// The nested document of an `<iframe>` element (if it has loaded)
+    pub fn sub_document(&self, iframe_id: usize) -> Option<&BaseDocument> {
+        self.sub_documents.get(&iframe_id).map(|sub| &*sub.doc)
+    }
+
+    pub fn sub_document_mut(&mut self, iframe_id: usize) -> Option<&mut BaseDocument> {
+        self.sub_documents
+            .get_mut(&iframe_id)
+            .map(|sub| &mut *sub.doc)
+    }
+
+    /// (Re)load the nested document of an `<iframe>` element from its `srcdoc` or `src`
+    /// attribute. This should be called when the iframe is connected to the document or either
+    /// attribute changes.
+    ///
+    /// Each load is given a new generation. Only the response for the iframe's latest load of a
+    /// `src` is used, and responses to requests made by replaced nested documents are dropped.
+    pub fn load_iframe(&mut self, iframe_id: usize) {
+        self.sub_documents.remove(&iframe_id);
+        self.pending_iframe_loads.remove(&iframe_id);
+
+        let Some(element) = self.nodes[iframe_id].element_data() else {
+            return;
+        };
+        let sandbox = IframeSandbox::parse(element.attr(local_name!("sandbox")));
+        let generation = LOAD_GENERATION.fetch_add(1, Ordering::Relaxed);
+
+        // srcdoc takes priority over src
+        if let Some(srcdoc) = element.attr(local_name!("srcdoc")) {
+            if self.iframe_provider.allow_load(None, &sandbox) {
+                let srcdoc = srcdoc.to_string();
+                let content_type = Some(String::from("text/html"));
+                self.create_sub_document(iframe_id, generation, None, content_type, &srcdoc);
+            }
+            return;
+        }
+
+        let Some(src) = element
+            .attr(local_name!("src"))
+            .filter(|src| !src.is_empty())
+        else {
+            return;
+        };
+        let url = self.resolve_url(src);
+        if !self.iframe_provider.allow_load(Some(&url), &sandbox) {
+            return;
+        }
+        self.pending_iframe_loads.insert(iframe_id, generation);
+        self.net_provider.fetch(
+            self.id(),
+            Request::get(url.clone()),
+            Box::new(HtmlHandler {
+                node: iframe_id,
+                generation,
+                url,
+            }),
+        );
+    }
+
+    /// Create the nested document of an iframe from its HTML source
+    pub(crate) fn create_sub_document(
+        &mut self,
+        iframe_id: usize,
+        generation: u64,
+        url: Option<Url>,
+        content_type: Option<String>,
+        html: &str,
+    ) {
+        let Some(element) = self
+            .nodes
+            .get(iframe_id)
+            .and_then(|node| node.element_data())
+        else {
+            return;
+        };
+        let sandbox = IframeSandbox::parse(element.attr(local_name!("sandbox")));
+
+        // Links within the nested document navigate the top-level page unless sandboxed
+        let navigation_provider = if sandbox.allows("allow-top-navigation") {
+            self.navigation_provider.clone()
+        } else {
+            Arc::new(DummyNavigationProvider)
+        };
+
+        let config = SubDocumentConfig {
+            url,
+            content_type,
+            sandbox,
+            viewport: self.sub_document_viewport(iframe_id),
+            font_ctx: self.font_ctx.clone(),
+            net_provider: Arc::new(SubDocumentNetProvider {
+                parent_doc_id: self.id(),
+                iframe_node_id: iframe_id,
+                generation,
+                provider: self.net_provider.clone(),
+            }),
+            navigation_provider,
+            iframe_provider: self.iframe_provider.clone(),
+            media_provider: self.media_provider.clone(),
+        };
+
+        if let Some(doc) = self.iframe_provider.create_document(html, config) {
+            let doc = Box::new(doc);
+            let sub_document = SubDocument { generation, doc };
+            self.sub_documents.insert(iframe_id, sub_document);
+        }
+    }
+
+    /// A viewport matching the content box of an iframe
+    fn sub_document_viewport(&self, iframe_id: usize) -> Viewport {
+        let layout = &self.nodes[iframe_id].final_layout;
+        let scale = self.viewport.scale();
+        let width = layout.size.width - layout.padding.left - layout.padding.right;
+        let width = width - layout.border.left - layout.border.right;
+        let height = layout.size.height - layout.padding.top - layout.padding.bottom;
+        let height = height - layout.border.top - layout.border.bottom;
+        Viewport::new(
+            (width.max(0.0) * scale) as u32,
+            (height.max(0.0) * scale) as u32,
+            scale,
+            self.viewport.color_scheme,
+        )
+    }
+
+    /// Resize the viewports of nested documents to match their iframes, and then resolve their
+    /// styles and layout. Must be called after the layout of this document has been resolved.
+    pub(crate) fn resolve_sub_documents(&mut self) {
+        let iframe_ids: Vec<usize> = self.sub_documents.keys().copied().collect();
+        for iframe_id in iframe_ids {
+            let viewport = self.sub_document_viewport(iframe_id);
+            let doc = &mut self.sub_documents.get_mut(&iframe_id).unwrap().doc;
+            let current = doc.get_viewport();
+            if current.window_size != viewport.window_size || current.scale() != viewport.scale() {
+                doc.set_viewport(viewport);
+            }
+            doc.resolve();
+        }
+    }
+
+    /// Forward an event targeting an iframe to its nested document. Mouse events are translated
+    /// into the nested document's coordinate space and retargeted to the node under the pointer,
+    /// whereas keyboard events are sent to the nested document's focussed node.
+    ///
+    /// Returns `false` if the event's target is not an iframe with a nested document.
+    pub(crate) fn forward_event_to_sub_document(&mut self, event: &mut DomEvent) -> bool {
+        let iframe_id = event.target;
+        if !self.sub_documents.contains_key(&iframe_id) {
+            return false;
+        }
+
+        let mut data = event.data.clone();
+        let nested_target = match &mut data {
+            DomEventData::MouseMove(mouse_event)
+            | DomEventData::MouseDown(mouse_event)
+            | DomEventData::MouseUp(mouse_event)
+            | DomEventData::Click(mouse_event) => {
+                let Some(hit) = self.hit(mouse_event.x, mouse_event.y) else {
+                    return true;
+                };
+                if hit.node_id != iframe_id {
+                    return true;
+                }
+
+                // Translate into the coordinate space of the nested document
+                let layout = &self.nodes[iframe_id].final_layout;
+                let doc = &mut self.sub_documents.get_mut(&iframe_id).unwrap().doc;
+                let scroll = doc.viewport_scroll();
+                mouse_event.x = hit.x - layout.border.left - layout.padding.left + scroll.x as f32;
+                mouse_event.y = hit.y - layout.border.top - layout.padding.top + scroll.y as f32;
+
+                if doc.set_hover_to(mouse_event.x, mouse_event.y) {
+                    event.request_redraw = true;
+                }
+                doc.get_hover_node_id()
+            }
+            DomEventData::KeyPress(_) | DomEventData::Ime(_) => {
+                self.sub_documents[&iframe_id].doc.get_focussed_node_id()
+            }
+            DomEventData::Hover => None,
+        };
+
+        // Clicking within an iframe focusses it, so that it receives keyboard events
+        if matches!(event.data, DomEventData::MouseDown(_)) {
+            self.set_focus_to(iframe_id);
+        }
+
+        if let Some(target) = nested_target {
+            let doc = &mut self.sub_documents.get_mut(&iframe_id).unwrap().doc;
+            let mut nested_event = DomEvent::new(target, data);
+            doc.handle_event(&mut nested_event);
+            event.request_redraw |= nested_event.request_redraw;
+        }
+
+        true
+    }
+}
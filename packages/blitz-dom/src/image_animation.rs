@@ -0,0 +1,299 @@
+//! Animated images (GIF, APNG and animated WebP).
+//!
+//! Animated images are decoded into a sequence of frames (see `AnimatedImageData`), which are
+//! advanced by the document's image animation clock when the document is polled. The document's
+//! `FrameWaker` wakes the task polling it when the next frame is due. Images which aren't
+//! rendered or are outside of the viewport are paused.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Waker};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use peniko::kurbo::{Rect, Vec2};
+use taffy::Display;
+
+use crate::BaseDocument;
+use crate::node::{AnimatedImageData, ElementNodeData, ImageData, NodeSpecificData};
+
+/// The clock which advances the animated images of a document
+#[derive(Default)]
+pub(crate) struct ImageAnimationClock {
+    /// When animated images were last advanced
+    last_tick: Option<Instant>,
+    /// Whether any animated images were visible at the last tick. Time isn't counted while none
+    /// are, so that images resume from where they were paused.
+    animating: bool,
+}
+
+/// Wakes the task polling a document when the next frame of an animated image or playing media
+/// element is due. Wakes are scheduled on a single timer thread, which is started the first time a
+/// wake is scheduled and stopped when the document is dropped.
+#[derive(Default)]
+pub(crate) struct FrameWaker {
+    timer: Option<(Arc<FrameTimer>, JoinHandle<()>)>,
+    /// The waker of the task polling the document
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct FrameTimer {
+    state: Mutex<FrameTimerState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct FrameTimerState {
+    waker: Option<Waker>,
+    /// When the waker is scheduled to be woken (if it is)
+    scheduled_wake: Option<Instant>,
+    stopped: bool,
+}
+
+impl FrameTimer {
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !state.stopped {
+            let Some(time) = state.scheduled_wake else {
+                state = self.changed.wait(state).unwrap();
+                continue;
+            };
+            let now = Instant::now();
+            if time > now {
+                state = self.changed.wait_timeout(state, time - now).unwrap().0;
+                continue;
+            }
+            state.scheduled_wake = None;
+            if let Some(waker) = &state.waker {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+impl FrameWaker {
+    pub(crate) fn set_waker(&mut self, waker: &Waker) {
+        self.waker = Some(waker.clone());
+    }
+
+    /// Wake the task polling the document at `time` (unless it is already scheduled to be woken
+    /// before then)
+    pub(crate) fn wake_at(&mut self, time: Instant) {
+        let Some(waker) = self.waker.clone() else {
+            return;
+        };
+        let (timer, _) = self.timer.get_or_insert_with(|| {
+            let timer = Arc::new(FrameTimer::default());
+            let thread_timer = timer.clone();
+            let thread = std::thread::spawn(move || thread_timer.run());
+            (timer, thread)
+        });
+
+        let mut state = timer.state.lock().unwrap();
+        state.waker = Some(waker);
+        if state
+            .scheduled_wake
+            .is_some_and(|scheduled| scheduled <= time)
+        {
+            return;
+        }
+        state.scheduled_wake = Some(time);
+        timer.changed.notify_one();
+    }
+}
+
+impl Drop for FrameWaker {
+    fn drop(&mut self) {
+        if let Some((timer, thread)) = self.timer.take() {
+            timer.state.lock().unwrap().stopped = true;
+            timer.changed.notify_one();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Whether an element's image or any of its background images is animated
+fn has_animated_image(el: &ElementNodeData) -> bool {
+    matches!(el.image_data(), Some(ImageData::Animated(_)))
+        || el
+            .background_images
+            .iter()
+            .flatten()
+            .any(|bg_image| matches!(bg_image.image, ImageData::Animated(_)))
+}
+
+impl AnimatedImageData {
+    /// Advance the animation by `elapsed`. Returns whether the current frame changed.
+    fn advance(&mut self, elapsed: Duration) -> bool {
+        let total: Duration = self.frames.iter().map(|frame| frame.delay).sum();
+        if total.is_zero() {
+            return false;
+        }
+
+        // Skip whole loops of the animation (after the document hasn't been polled for a while)
+        let mut elapsed = self.frame_elapsed + elapsed;
+        if elapsed > total {
+            elapsed = Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64);
+        }
+
+        let previous_frame = self.current_frame;
+        while elapsed >= self.current_frame().delay {
+            elapsed -= self.current_frame().delay;
+            self.current_frame = (self.current_frame + 1) % self.frames.len();
+        }
+        self.frame_elapsed = elapsed;
+        self.current_frame != previous_frame
+    }
+
+    /// How long until the next frame is due
+    fn remaining(&self) -> Duration {
+        self.current_frame()
+            .delay
+            .saturating_sub(self.frame_elapsed)
+    }
+}
+
+impl BaseDocument {
+    /// Call `f` with the animated images of elements (and their background images) which are
+    /// currently visible
+    fn for_each_visible_animated_image(&mut self, mut f: impl FnMut(&mut AnimatedImageData)) {
+        // Forget nodes which have been removed or whose animated images have been replaced
+        let nodes = &self.nodes;
+        self.animated_image_nodes.retain(|&node_id| {
+            nodes
+                .get(node_id)
+                .and_then(|node| node.element_data())
+                .is_some_and(has_animated_image)
+        });
+
+        let visible: Vec<usize> = self
+            .animated_image_nodes
+            .iter()
+            .copied()
+            .filter(|&node_id| self.is_in_viewport(node_id))
+            .collect();
+        for node_id in visible {
+            let Some(el) = self.nodes[node_id].element_data_mut() else {
+                continue;
+            };
+            let background_images = el.background_images.iter_mut().flatten();
+            let element_image = match &mut el.node_specific_data {
+                NodeSpecificData::Image(image) => Some(&mut **image),
+                _ => None,
+            };
+            let images = element_image
+                .into_iter()
+                .chain(background_images.map(|bg_image| &mut bg_image.image));
+            for image in images {
+                if let ImageData::Animated(animation) = image {
+                    f(animation);
+                }
+            }
+        }
+    }
+
+    /// Whether any part of a node's border box is rendered within the viewport
+    fn is_in_viewport(&self, node_id: usize) -> bool {
+        let node = &self.nodes[node_id];
+        let size = node.final_layout.size;
+        let mut rect = Rect::new(0.0, 0.0, size.width as f64, size.height as f64);
+        let mut child_id = node_id;
+        loop {
+            let child = &self.nodes[child_id];
+            if child.style.display == Display::None {
+                return false;
+            }
+            rect = rect
+                + Vec2::new(
+                    child.final_layout.location.x as f64,
+                    child.final_layout.location.y as f64,
+                );
+
+            let Some(parent_id) = child.layout_parent.get() else {
+                break;
+            };
+            let parent = &self.nodes[parent_id];
+            rect = rect - parent.scroll_offset.to_vec2();
+            child_id = parent_id;
+        }
+
+        // Nodes which aren't connected to the root of the layout tree (or the top layer) aren't rendered
+        if child_id != self.root_node().id && !self.is_in_top_layer(child_id) {
+            return false;
+        }
+
+        let scale = self.viewport.scale() as f64;
+        let (width, height) = self.viewport.window_size;
+        let viewport = Rect::from_origin_size(
+            self.viewport_scroll,
+            (width as f64 / scale, height as f64 / scale),
+        );
+        rect.overlaps(viewport)
+    }
+
+    /// Advance the visible animated images in the document to `now`. Images which aren't visible
+    /// are paused. Returns whether the frame of any image changed (and so a redraw is needed).
+    pub fn tick_image_animations(&mut self, now: Instant) -> bool {
+        let clock = &mut self.image_animation_clock;
+        let elapsed = clock
+            .last_tick
+            .filter(|_| clock.animating)
+            .map(|last_tick| now.saturating_duration_since(last_tick))
+            .unwrap_or_default();
+        clock.last_tick = Some(now);
+
+        let mut animating = false;
+        let mut changed = false;
+        self.for_each_visible_animated_image(|animation| {
+            animating = true;
+            changed |= animation.advance(elapsed);
+        });
+        self.image_animation_clock.animating = animating;
+        changed
+    }
+
+    /// When the next frame of a visible animated image is due (if there are any)
+    pub fn next_image_frame_time(&mut self) -> Option<Instant> {
+        let last_tick = self.image_animation_clock.last_tick?;
+        let mut next_frame_time: Option<Instant> = None;
+        self.for_each_visible_animated_image(|animation| {
+            let frame_time = last_tick + animation.remaining();
+            next_frame_time = Some(next_frame_time.map_or(frame_time, |time| time.min(frame_time)));
+        });
+        next_frame_time
+    }
+
+    /// Advance animated images (in this document and its nested documents) and schedule the task
+    /// polling the document to be woken when the next frame is due. Returns whether a redraw is
+    /// needed.
+    pub fn poll_image_animations(&mut self, cx: &Context) -> bool {
+        let mut changed = self.tick_image_animations(Instant::now());
+        for sub_document in self.sub_documents.values_mut() {
+            changed |= sub_document.doc.poll_image_animations(cx);
+        }
+
+        self.frame_waker.set_waker(cx.waker());
+        self.schedule_image_animation_wake();
+        changed
+    }
+
+    /// Schedule the waker to be woken when the next frame of a visible animated image is due. This
+    /// is also called after each resolve so that paused images resume when scrolled into view.
+    pub(crate) fn schedule_image_animation_wake(&mut self) {
+        if let Some(next_frame_time) = self.next_image_frame_time() {
+            self.frame_waker.wake_at(next_frame_time);
+        }
+    }
+}
+
+#[test]
+fn stops_the_frame_timer_when_dropped() {
+    let mut frame_waker = FrameWaker::default();
+    frame_waker.set_waker(Waker::noop());
+    frame_waker.wake_at(Instant::now() + Duration::from_secs(3600));
+    let timer = Arc::downgrade(&frame_waker.timer.as_ref().unwrap().0);
+
+    // The timer thread (which owns the other reference to the timer) has exited
+    drop(frame_waker);
+    assert!(timer.upgrade().is_none());
+}
@@ -9,11 +9,17 @@ use style::{
     properties::longhands::{
         list_style_position::computed_value::T as ListStylePosition,
         list_style_type::computed_value::T as ListStyleType,
+        text_wrap_mode::computed_value::T as TextWrapMode,
+        white_space_collapse::computed_value::T as StyloWhiteSpaceCollapse,
     },
+    selector_parser::PseudoElement,
     shared_lock::StylesheetGuards,
     values::{
-        computed::{Content, ContentItem, Display},
-        specified::box_::{DisplayInside, DisplayOutside},
+        computed::Display,
+        specified::{
+            box_::{DisplayInside, DisplayOutside},
+            text::TextOverflowSide,
+        },
     },
 };
 
@@ -26,6 +32,7 @@ use crate::{
     stylo_to_parley,
 };
 
+use super::counters::format_counter;
 use super::table::build_table_context;
 
 const DUMMY_NAME: QualName = QualName {
@@ -38,7 +45,7 @@ fn push_children_and_pseudos(layout_children: &mut Vec<usize>, node: &Node) {
     if let Some(before) = node.before {
         layout_children.push(before);
     }
-    layout_children.extend_from_slice(&node.children);
+    layout_children.extend_from_slice(node.flat_tree_children());
     if let Some(after) = node.after {
         layout_children.push(after);
     }
@@ -82,6 +89,21 @@ pub(crate) fn collect_layout_children(
             }
         }
 
+        // The contents of iframes, media elements, canvases, progress bars and meters are only
+        // used as a fallback by browsers that don't support them
+        if matches!(
+            tag_name,
+            "iframe" | "video" | "audio" | "canvas" | "progress" | "meter"
+        ) {
+            return;
+        }
+
+        // Only the summary of a closed details element is rendered
+        if matches!(tag_name, "details") && !doc.is_details_open(container_node_id) {
+            layout_children.extend(doc.details_summary(container_node_id));
+            return;
+        }
+
         #[cfg(feature = "svg")]
         if matches!(tag_name, "svg") {
             let mut outer_html = doc.get_node(container_node_id).unwrap().outer_html();
@@ -109,25 +131,12 @@ pub(crate) fn collect_layout_children(
             };
             return;
         }
-
-        //Only ol tags have start and reversed attributes
-        let (mut index, reversed) = if tag_name == "ol" {
-            (
-                el.attr_parsed(local_name!("start"))
-                    .map(|start: usize| start - 1)
-                    .unwrap_or(0),
-                el.attr_parsed(local_name!("reversed")).unwrap_or(false),
-            )
-        } else {
-            (1, false)
-        };
-        collect_list_item_children(doc, &mut index, reversed, container_node_id);
     }
 
     // Skip further construction if the node has no children or psuedo-children
     {
         let node = &doc.nodes[container_node_id];
-        if node.children.is_empty() && node.before.is_none() && node.after.is_none() {
+        if node.flat_tree_children().is_empty() && node.before.is_none() && node.after.is_none() {
             return;
         }
     }
@@ -142,30 +151,33 @@ pub(crate) fn collect_layout_children(
     match container_display.inside() {
         DisplayInside::None => {}
         DisplayInside::Contents => {
-            // Take children array from node to avoid borrow checker issues.
-            let children = std::mem::take(&mut doc.nodes[container_node_id].children);
+            // Copy children array from node to avoid borrow checker issues.
+            let children = doc.nodes[container_node_id].flat_tree_children().to_vec();
 
             for child_id in children.iter().copied() {
                 collect_layout_children(doc, child_id, layout_children, anonymous_block_id)
             }
-
-            // Put children array back
-            doc.nodes[container_node_id].children = children;
         }
         DisplayInside::Flow | DisplayInside::FlowRoot | DisplayInside::TableCell => {
             // TODO: make "all_inline" detection work in the presence of display:contents nodes
             let mut all_block = true;
             let mut all_inline = true;
             let mut has_contents = false;
+            let mut has_inline_content = false;
+            let mut has_out_of_flow = false;
             for child in doc.nodes[container_node_id]
-                .children
+                .flat_tree_children()
                 .iter()
                 .copied()
                 .map(|child_id| &doc.nodes[child_id])
             {
                 // Unwraps on Text and SVG nodes
                 let display = child.display_style().unwrap_or(Display::inline());
-                if matches!(display.inside(), DisplayInside::Contents) {
+                if child.is_absolutely_positioned() {
+                    // Absolutely positioned children are out-of-flow, so they do not affect whether
+                    // the container establishes an inline formatting context.
+                    has_out_of_flow = true;
+                } else if matches!(display.inside(), DisplayInside::Contents) {
                     has_contents = true;
                 } else {
                     match display.outside() {
@@ -175,6 +187,7 @@ pub(crate) fn collect_layout_children(
                         | DisplayOutside::InternalTable => all_inline = false,
                         DisplayOutside::Inline => {
                             all_block = false;
+                            has_inline_content |= !child.is_whitespace_text();
 
                             // We need the "complex" tree fixing when an inline contains a block
                             if child.is_or_contains_block() {
@@ -185,6 +198,12 @@ pub(crate) fn collect_layout_children(
                 }
             }
 
+            // Out-of-flow children are only placed in an inline formatting context if there is inline content
+            // for them to be positioned within.
+            if has_out_of_flow && !has_inline_content {
+                all_inline = false;
+            }
+
             // TODO: fix display:contents
             if all_inline {
                 let (inline_layout, ilayout_children) = build_inline_layout(doc, container_node_id);
@@ -227,7 +246,7 @@ pub(crate) fn collect_layout_children(
         }
         DisplayInside::Flex | DisplayInside::Grid => {
             let has_text_node_or_contents = doc.nodes[container_node_id]
-                .children
+                .flat_tree_children()
                 .iter()
                 .copied()
                 .map(|child_id| &doc.nodes[child_id])
@@ -282,7 +301,7 @@ pub(crate) fn collect_layout_children(
     }
 }
 
-fn flush_pseudo_elements(doc: &mut BaseDocument, node_id: usize) {
+pub(crate) fn flush_pseudo_elements(doc: &mut BaseDocument, node_id: usize) {
     let (before_style, after_style, before_node_id, after_node_id) = {
         let node = &doc.nodes[node_id];
 
@@ -321,20 +340,6 @@ fn flush_pseudo_elements(doc: &mut BaseDocument, node_id: usize) {
             )));
             doc.nodes[new_node_id].parent = Some(node_id);
 
-            let content = &pe_style.as_ref().get_counters().content;
-            if let Content::Items(item_data) = content {
-                let items = &item_data.items[0..item_data.alt_start];
-                match &items[0] {
-                    ContentItem::String(owned_str) => {
-                        let text_node_id = doc.create_text_node(owned_str);
-                        doc.nodes[new_node_id].children.push(text_node_id);
-                    }
-                    _ => {
-                        // TODO: other types of content
-                    }
-                }
-            }
-
             let mut element_data = ElementData::default();
             element_data.styles.primary = Some(pe_style.clone());
             element_data.set_restyled();
@@ -344,9 +349,8 @@ fn flush_pseudo_elements(doc: &mut BaseDocument, node_id: usize) {
         }
 
         // Else: Update psuedo element
+        // Note: the content of pseudo elements is generated by `resolve_generated_content`
         if let (Some(pe_node_id), Some(pe_style)) = (pe_node_id, pe_style) {
-            // TODO: Update content
-
             let mut node_styles = doc.nodes[pe_node_id].stylo_element_data.borrow_mut();
             let node_styles = &mut node_styles.as_mut().unwrap();
             let primary_styles = &mut node_styles.styles.primary;
@@ -359,37 +363,11 @@ fn flush_pseudo_elements(doc: &mut BaseDocument, node_id: usize) {
     }
 }
 
-fn collect_list_item_children(
-    doc: &mut BaseDocument,
-    index: &mut usize,
-    reversed: bool,
-    node_id: usize,
-) {
-    let mut children = doc.nodes[node_id].children.clone();
-    if reversed {
-        children.reverse();
-    }
-    for child in children.into_iter() {
-        if let Some(layout) = node_list_item_child(doc, child, *index) {
-            let node = &mut doc.nodes[child];
-            node.element_data_mut().unwrap().list_item_data = Some(Box::new(layout));
-            *index += 1;
-            collect_list_item_children(doc, index, reversed, child);
-        } else {
-            // Unset marker in case it was previously set
-            let node = &mut doc.nodes[child];
-            if let Some(element_data) = node.element_data_mut() {
-                element_data.list_item_data = None;
-            }
-        }
-    }
-}
-
-// Return a child node which is of display: list-item
-fn node_list_item_child(
+// Return the list item layout for a node which is of display: list-item
+pub(crate) fn node_list_item_child(
     doc: &mut BaseDocument,
     child_id: usize,
-    index: usize,
+    value: i32,
 ) -> Option<ListItemLayout> {
     let node = &doc.nodes[child_id];
 
@@ -416,9 +394,16 @@ fn node_list_item_child(
     };
 
     let styles = node.primary_styles().unwrap();
+    let style = node
+        .stylo_element_data
+        .borrow()
+        .as_ref()?
+        .styles
+        .primary()
+        .clone();
     let list_style_type = styles.clone_list_style_type();
     let list_style_position = styles.clone_list_style_position();
-    let marker = marker_for_style(list_style_type, index)?;
+    let marker = marker_for_style(list_style_type, value)?;
 
     let position = match list_style_position {
         ListStylePosition::Inside => ListItemLayoutPosition::Inside,
@@ -447,33 +432,29 @@ fn node_list_item_child(
         }
     };
 
-    Some(ListItemLayout { marker, position })
+    Some(ListItemLayout {
+        marker,
+        position,
+        value,
+        style,
+    })
 }
 
-// Determine the marker to render for a given list style type
-fn marker_for_style(list_style_type: ListStyleType, index: usize) -> Option<Marker> {
+// Determine the marker to render for a given list style type and `list-item` counter value
+fn marker_for_style(list_style_type: ListStyleType, value: i32) -> Option<Marker> {
     if list_style_type == ListStyleType::None {
         return None;
     }
 
     Some(match list_style_type {
-        ListStyleType::LowerAlpha => {
-            let mut marker = String::new();
-            build_alpha_marker(index, &mut marker);
-            Marker::String(format!("{}. ", marker))
-        }
-        ListStyleType::UpperAlpha => {
-            let mut marker = String::new();
-            build_alpha_marker(index, &mut marker);
-            Marker::String(format!("{}. ", marker.to_ascii_uppercase()))
+        ListStyleType::Disc
+        | ListStyleType::Circle
+        | ListStyleType::Square
+        | ListStyleType::DisclosureOpen
+        | ListStyleType::DisclosureClosed => {
+            Marker::Char(format_counter(value, list_style_type).chars().next()?)
         }
-        ListStyleType::Decimal => Marker::String(format!("{}. ", index + 1)),
-        ListStyleType::Disc => Marker::Char('•'),
-        ListStyleType::Circle => Marker::Char('◦'),
-        ListStyleType::Square => Marker::Char('▪'),
-        ListStyleType::DisclosureOpen => Marker::Char('▾'),
-        ListStyleType::DisclosureClosed => Marker::Char('▸'),
-        _ => Marker::Char('□'),
+        _ => Marker::String(format!("{}. ", format_counter(value, list_style_type))),
     })
 }
 
@@ -490,42 +471,26 @@ fn font_for_bullet_style(list_style_type: ListStyleType) -> Option<FontStack<'st
     }
 }
 
-const ALPHABET: [char; 26] = [
-    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
-    't', 'u', 'v', 'w', 'x', 'y', 'z',
-];
-
-// Construct alphanumeric marker from index, appending characters when index exceeds powers of 26
-fn build_alpha_marker(index: usize, str: &mut String) {
-    let rem = index % 26;
-    let sym = ALPHABET[rem];
-    str.insert(0, sym);
-    let rest = (index - rem) as i64 / 26 - 1;
-    if rest >= 0 {
-        build_alpha_marker(rest as usize, str);
-    }
-}
-
 #[test]
 fn test_marker_for_disc() {
-    let result = marker_for_style(ListStyleType::Disc, 0);
+    let result = marker_for_style(ListStyleType::Disc, 1);
     assert_eq!(result, Some(Marker::Char('•')));
 }
 
 #[test]
 fn test_marker_for_decimal() {
-    let result_1 = marker_for_style(ListStyleType::Decimal, 0);
-    let result_2 = marker_for_style(ListStyleType::Decimal, 1);
+    let result_1 = marker_for_style(ListStyleType::Decimal, 1);
+    let result_2 = marker_for_style(ListStyleType::Decimal, 2);
     assert_eq!(result_1, Some(Marker::String("1. ".to_string())));
     assert_eq!(result_2, Some(Marker::String("2. ".to_string())));
 }
 
 #[test]
 fn test_marker_for_lower_alpha() {
-    let result_1 = marker_for_style(ListStyleType::LowerAlpha, 0);
-    let result_2 = marker_for_style(ListStyleType::LowerAlpha, 1);
-    let result_extended_1 = marker_for_style(ListStyleType::LowerAlpha, 26);
-    let result_extended_2 = marker_for_style(ListStyleType::LowerAlpha, 27);
+    let result_1 = marker_for_style(ListStyleType::LowerAlpha, 1);
+    let result_2 = marker_for_style(ListStyleType::LowerAlpha, 2);
+    let result_extended_1 = marker_for_style(ListStyleType::LowerAlpha, 27);
+    let result_extended_2 = marker_for_style(ListStyleType::LowerAlpha, 28);
     assert_eq!(result_1, Some(Marker::String("a. ".to_string())));
     assert_eq!(result_2, Some(Marker::String("b. ".to_string())));
     assert_eq!(result_extended_1, Some(Marker::String("aa. ".to_string())));
@@ -534,10 +499,10 @@ fn test_marker_for_lower_alpha() {
 
 #[test]
 fn test_marker_for_upper_alpha() {
-    let result_1 = marker_for_style(ListStyleType::UpperAlpha, 0);
-    let result_2 = marker_for_style(ListStyleType::UpperAlpha, 1);
-    let result_extended_1 = marker_for_style(ListStyleType::UpperAlpha, 26);
-    let result_extended_2 = marker_for_style(ListStyleType::UpperAlpha, 27);
+    let result_1 = marker_for_style(ListStyleType::UpperAlpha, 1);
+    let result_2 = marker_for_style(ListStyleType::UpperAlpha, 2);
+    let result_extended_1 = marker_for_style(ListStyleType::UpperAlpha, 27);
+    let result_extended_2 = marker_for_style(ListStyleType::UpperAlpha, 28);
     assert_eq!(result_1, Some(Marker::String("A. ".to_string())));
     assert_eq!(result_2, Some(Marker::String("B. ".to_string())));
     assert_eq!(result_extended_1, Some(Marker::String("AA. ".to_string())));
@@ -553,112 +518,148 @@ fn collect_complex_layout_children(
     hide_whitespace: bool,
     needs_wrap: impl Fn(NodeKind, DisplayOutside) -> bool,
 ) {
-    fn block_is_only_whitespace(doc: &BaseDocument, node_id: usize) -> bool {
-        for child_id in doc.nodes[node_id].children.iter().copied() {
-            let child = &doc.nodes[child_id];
-            if child
-                .text_data()
-                .is_none_or(|text_data| !text_data.content.chars().all(|c| c.is_ascii_whitespace()))
-            {
-                return false;
-            }
+    doc.iter_children_and_pseudos_mut(container_node_id, |child_id, doc| {
+        collect_complex_layout_child(
+            doc,
+            container_node_id,
+            child_id,
+            layout_children,
+            anonymous_block_id,
+            hide_whitespace,
+            &needs_wrap,
+        )
+    });
+
+    // If anonymous block node only contains whitespace then delete it
+    if let Some(anon_id) = *anonymous_block_id {
+        if block_is_only_whitespace(doc, anon_id) {
+            layout_children.pop();
+            doc.nodes.remove(anon_id);
         }
+    }
+}
 
-        true
+fn collect_complex_layout_child(
+    doc: &mut BaseDocument,
+    container_node_id: usize,
+    child_id: usize,
+    layout_children: &mut Vec<usize>,
+    anonymous_block_id: &mut Option<usize>,
+    hide_whitespace: bool,
+    needs_wrap: &impl Fn(NodeKind, DisplayOutside) -> bool,
+) {
+    // Get node kind (text, element, comment, etc)
+    let child_node_kind = doc.nodes[child_id].data.kind();
+
+    // Get Display style. Default to inline because nodes without styles are probably text nodes
+    let contains_block = doc.nodes[child_id].is_or_contains_block();
+    let child_display = &doc.nodes[child_id]
+        .display_style()
+        .unwrap_or(Display::inline());
+    let display_inside = child_display.inside();
+    let display_outside = if contains_block {
+        DisplayOutside::Block
+    } else if doc.nodes[child_id].is_absolutely_positioned() {
+        // Absolutely positioned boxes that were originally inline-level are placed within the
+        // anonymous block containing the surrounding inline content
+        doc.nodes[child_id]
+            .primary_styles()
+            .map(|s| s.get_box().original_display.outside())
+            .unwrap_or(DisplayOutside::Block)
+    } else {
+        child_display.outside()
+    };
+
+    let is_whitespace_node = match &doc.nodes[child_id].data {
+        NodeData::Text(data) => data.content.chars().all(|c| c.is_ascii_whitespace()),
+        _ => false,
+    };
+
+    // Skip comment nodes. Note that we do *not* skip `Display::None` nodes as they may need to be hidden.
+    // Taffy knows how to deal with `Display::None` children.
+    //
+    // Also hide all-whitespace flexbox children as these should be ignored
+    if child_node_kind == NodeKind::Comment || (hide_whitespace && is_whitespace_node) {
+        // return;
+    }
+    // Recurse into `Display::Contents` nodes, treating their children as children of the container
+    else if display_inside == DisplayInside::Contents {
+        flush_pseudo_elements(doc, child_id);
+        doc.iter_children_and_pseudos_mut(child_id, |child_id, doc| {
+            collect_complex_layout_child(
+                doc,
+                container_node_id,
+                child_id,
+                layout_children,
+                anonymous_block_id,
+                hide_whitespace,
+                needs_wrap,
+            )
+        });
     }
+    // Push nodes that need wrapping into the current "anonymous block container".
+    // If there is not an open one then we create one.
+    else if needs_wrap(child_node_kind, display_outside) {
+        if anonymous_block_id.is_none() {
+            const NAME: QualName = QualName {
+                prefix: None,
+                ns: ns!(html),
+                local: local_name!("div"),
+            };
+            let node_id = doc.create_node(NodeData::AnonymousBlock(ElementNodeData::new(
+                NAME,
+                Vec::new(),
+            )));
 
-    doc.iter_children_and_pseudos_mut(container_node_id, |child_id, doc| {
-        // Get node kind (text, element, comment, etc)
-        let child_node_kind = doc.nodes[child_id].data.kind();
-
-        // Get Display style. Default to inline because nodes without styles are probably text nodes
-        let contains_block = doc.nodes[child_id].is_or_contains_block();
-        let child_display = &doc.nodes[child_id]
-            .display_style()
-            .unwrap_or(Display::inline());
-        let display_inside = child_display.inside();
-        let display_outside = if contains_block {
-            DisplayOutside::Block
-        } else {
-            child_display.outside()
-        };
-
-        let is_whitespace_node = match &doc.nodes[child_id].data {
-            NodeData::Text(data) => data.content.chars().all(|c| c.is_ascii_whitespace()),
-            _ => false,
-        };
-
-        // Skip comment nodes. Note that we do *not* skip `Display::None` nodes as they may need to be hidden.
-        // Taffy knows how to deal with `Display::None` children.
-        //
-        // Also hide all-whitespace flexbox children as these should be ignored
-        if child_node_kind == NodeKind::Comment || (hide_whitespace && is_whitespace_node) {
-            // return;
-        }
-        // Recurse into `Display::Contents` nodes
-        else if display_inside == DisplayInside::Contents {
-            collect_layout_children(doc, child_id, layout_children, anonymous_block_id)
-        }
-        // Push nodes that need wrapping into the current "anonymous block container".
-        // If there is not an open one then we create one.
-        else if needs_wrap(child_node_kind, display_outside) {
-            use style::selector_parser::PseudoElement;
-
-            if anonymous_block_id.is_none() {
-                const NAME: QualName = QualName {
-                    prefix: None,
-                    ns: ns!(html),
-                    local: local_name!("div"),
-                };
-                let node_id = doc.create_node(NodeData::AnonymousBlock(ElementNodeData::new(
-                    NAME,
-                    Vec::new(),
-                )));
-
-                // Set style data
-                let parent_style = doc.nodes[container_node_id].primary_styles().unwrap();
-                let read_guard = doc.guard.read();
-                let guards = StylesheetGuards::same(&read_guard);
-                let style = doc.stylist.style_for_anonymous::<&Node>(
-                    &guards,
-                    &PseudoElement::ServoAnonymousBox,
-                    &parent_style,
-                );
-                let mut element_data = ElementData::default();
-                element_data.styles.primary = Some(style);
-                element_data.set_restyled();
-                *doc.nodes[node_id].stylo_element_data.borrow_mut() = Some(element_data);
-
-                layout_children.push(node_id);
-                *anonymous_block_id = Some(node_id);
-            }
+            // Set style data
+            let parent_style = doc.nodes[container_node_id].primary_styles().unwrap();
+            let read_guard = doc.guard.read();
+            let guards = StylesheetGuards::same(&read_guard);
+            let style = doc.stylist.style_for_anonymous::<&Node>(
+                &guards,
+                &PseudoElement::ServoAnonymousBox,
+                &parent_style,
+            );
+            let mut element_data = ElementData::default();
+            element_data.styles.primary = Some(style);
+            element_data.set_restyled();
+            *doc.nodes[node_id].stylo_element_data.borrow_mut() = Some(element_data);
 
-            doc.nodes[anonymous_block_id.unwrap()]
-                .children
-                .push(child_id);
+            layout_children.push(node_id);
+            *anonymous_block_id = Some(node_id);
         }
-        // Else push the child directly (and close any open "anonymous block container")
-        else {
-            // If anonymous block node only contains whitespace then delete it
-            if let Some(anon_id) = *anonymous_block_id {
-                if block_is_only_whitespace(doc, anon_id) {
-                    layout_children.pop();
-                    doc.nodes.remove(anon_id);
-                }
-            }
 
-            *anonymous_block_id = None;
-            layout_children.push(child_id);
+        doc.nodes[anonymous_block_id.unwrap()]
+            .children
+            .push(child_id);
+    }
+    // Else push the child directly (and close any open "anonymous block container")
+    else {
+        // If anonymous block node only contains whitespace then delete it
+        if let Some(anon_id) = *anonymous_block_id {
+            if block_is_only_whitespace(doc, anon_id) {
+                layout_children.pop();
+                doc.nodes.remove(anon_id);
+            }
         }
-    });
 
-    // If anonymous block node only contains whitespace then delete it
-    if let Some(anon_id) = *anonymous_block_id {
-        if block_is_only_whitespace(doc, anon_id) {
-            layout_children.pop();
-            doc.nodes.remove(anon_id);
+        *anonymous_block_id = None;
+        layout_children.push(child_id);
+    }
+}
+
+fn block_is_only_whitespace(doc: &BaseDocument, node_id: usize) -> bool {
+    for child_id in doc.nodes[node_id].children.iter().copied() {
+        let child = &doc.nodes[child_id];
+        if child
+            .text_data()
+            .is_none_or(|text_data| !text_data.content.chars().all(|c| c.is_ascii_whitespace()))
+        {
+            return false;
         }
     }
+
+    true
 }
 
 fn create_text_editor(doc: &mut BaseDocument, input_element_id: usize, is_multiline: bool) {
@@ -674,21 +675,61 @@ fn create_text_editor(doc: &mut BaseDocument, input_element_id: usize, is_multil
         let mut text_input_data = TextInputData::new(is_multiline);
         let editor = &mut text_input_data.editor;
 
-        editor.set_text(element.attr(local_name!("value")).unwrap_or(" "));
+        // Inputs without a `value` attribute start out empty (rather than containing a space) so
+        // that they match `:placeholder-shown` and fail `required` validation
+        editor.set_text(element.attr(local_name!("value")).unwrap_or(""));
         editor.set_scale(doc.viewport.scale_f64() as f32);
         editor.set_width(None);
 
         let styles = editor.edit_styles();
         styles.insert(StyleProperty::FontSize(parley_style.font_size));
         styles.insert(StyleProperty::LineHeight(parley_style.line_height));
-        styles.insert(StyleProperty::Brush(parley_style.brush));
+        styles.insert(StyleProperty::Brush(parley_style.brush.clone()));
 
         editor.refresh_layout(&mut doc.font_ctx, &mut doc.layout_ctx);
 
         element.node_specific_data = NodeSpecificData::TextInput(text_input_data);
+        doc.update_placeholder(input_element_id);
     }
 }
 
+/// Lay out the `placeholder` attribute of a text input or textarea. Stylo's servo build doesn't
+/// support the `::placeholder` pseudo-element, so placeholders are styled by the UA stylesheet's
+/// rule for the `::-servo-legacy-input-text` anonymous box (which inherits from the input).
+pub(crate) fn build_placeholder_layout(
+    doc: &mut BaseDocument,
+    input_element_id: usize,
+) -> Option<Box<parley::Layout<TextBrush>>> {
+    let node = &doc.nodes[input_element_id];
+    let placeholder = node
+        .attr(local_name!("placeholder"))
+        .filter(|placeholder| !placeholder.is_empty())?;
+    let parent_style = node.primary_styles()?;
+    let read_guard = doc.guard.read();
+    let guards = StylesheetGuards::same(&read_guard);
+    let style = doc.stylist.style_for_anonymous::<&Node>(
+        &guards,
+        &PseudoElement::ServoLegacyInputText,
+        &parent_style,
+    );
+    drop(parent_style);
+    drop(read_guard);
+
+    let mut parley_style = stylo_to_parley::style(node.id, &style);
+    parley_style.brush.brush = parley_style
+        .brush
+        .brush
+        .multiply_alpha(style.get_effects().opacity);
+
+    let mut builder =
+        doc.layout_ctx
+            .tree_builder(&mut doc.font_ctx, doc.viewport.scale(), &parley_style);
+    builder.push_text(placeholder);
+    let (mut layout, _) = builder.build();
+    layout.break_all_lines(None);
+    Some(Box::new(layout))
+}
+
 fn create_checkbox_input(doc: &mut BaseDocument, input_element_id: usize) {
     let node = &mut doc.nodes[input_element_id];
 
@@ -728,6 +769,16 @@ pub(crate) fn build_inline_layout(
 
     let root_line_height = parley_style.line_height;
 
+    // The string which replaces content overflowing the end of a line
+    let overflow_string =
+        root_node_style
+            .as_ref()
+            .and_then(|s| match &s.get_text().text_overflow.second {
+                TextOverflowSide::Clip => None,
+                TextOverflowSide::Ellipsis => Some(String::from("\u{2026}")),
+                TextOverflowSide::String(str) => Some(str.to_string()),
+            });
+
     // Create a parley tree builder
     let mut builder =
         doc.layout_ctx
@@ -744,6 +795,7 @@ pub(crate) fn build_inline_layout(
     if let Some(ListItemLayout {
         marker,
         position: ListItemLayoutPosition::Inside,
+        ..
     }) = root_node
         .element_data()
         .and_then(|el| el.list_item_data.as_deref())
@@ -764,7 +816,7 @@ pub(crate) fn build_inline_layout(
             root_line_height,
         );
     }
-    for child_id in root_node.children.iter().copied() {
+    for child_id in root_node.flat_tree_children().iter().copied() {
         build_inline_layout_recursive(
             &mut builder,
             &doc.nodes,
@@ -787,17 +839,51 @@ pub(crate) fn build_inline_layout(
 
     let (layout, text) = builder.build();
 
+    // Lay out the text-overflow string using the inline context root's style
+    let ellipsis = overflow_string.map(|overflow_string| {
+        let mut builder =
+            doc.layout_ctx
+                .tree_builder(&mut doc.font_ctx, doc.viewport.scale(), &parley_style);
+        builder.push_text(&overflow_string);
+        let (mut ellipsis, _) = builder.build();
+        ellipsis.break_all_lines(None);
+        ellipsis
+    });
+
+    // Lay out the hyphen which is shown when a line breaks at a soft hyphen (U+00AD)
+    // TODO: automatic hyphenation (`hyphens: auto`) isn't supported as Stylo's servo build doesn't
+    // implement the `hyphens` property.
+    let hyphen = text.contains('\u{00AD}').then(|| {
+        let mut builder =
+            doc.layout_ctx
+                .tree_builder(&mut doc.font_ctx, doc.viewport.scale(), &parley_style);
+        builder.push_text("-");
+        let (mut hyphen, _) = builder.build();
+        hyphen.break_all_lines(None);
+        hyphen
+    });
+
     // Obtain layout children for the inline layout
     let layout_children: Vec<usize> = layout
         .inline_boxes()
         .iter()
+        .filter(|ibox| is_node_box(ibox.id))
         .map(|ibox| ibox.id as usize)
         .collect();
 
-    return (TextLayout { text, layout }, layout_children);
+    return (
+        TextLayout {
+            text,
+            layout,
+            ellipsis,
+            hyphen,
+        },
+        layout_children,
+    );
 
     fn flush_inline_pseudos_recursive(doc: &mut BaseDocument, node_id: usize) {
-        doc.iter_children_mut(node_id, |child_id, doc| {
+        let children = doc.nodes[node_id].flat_tree_children().to_vec();
+        for child_id in children {
             flush_pseudo_elements(doc, child_id);
             let display = doc.nodes[node_id]
                 .display_style()
@@ -810,7 +896,7 @@ pub(crate) fn build_inline_layout(
             if do_recurse {
                 flush_inline_pseudos_recursive(doc, child_id);
             }
-        });
+        }
     }
 
     fn build_inline_layout_recursive(
@@ -853,7 +939,7 @@ pub(crate) fn build_inline_layout(
                 match (display.outside(), display.inside()) {
                     (DisplayOutside::None, DisplayInside::None) => {}
                     (DisplayOutside::None, DisplayInside::Contents) => {
-                        for child_id in node.children.iter().copied() {
+                        for child_id in node.flat_tree_children().iter().copied() {
                             build_inline_layout_recursive(
                                 builder,
                                 nodes,
@@ -868,6 +954,10 @@ pub(crate) fn build_inline_layout(
                         let tag_name = &element_data.name.local;
 
                         if *tag_name == local_name!("img")
+                            || *tag_name == local_name!("iframe")
+                            || *tag_name == local_name!("video")
+                            || *tag_name == local_name!("audio")
+                            || *tag_name == local_name!("canvas")
                             || *tag_name == local_name!("svg")
                             || *tag_name == local_name!("input")
                             || *tag_name == local_name!("textarea")
@@ -917,7 +1007,7 @@ pub(crate) fn build_inline_layout(
                                 );
                             }
 
-                            for child_id in node.children.iter().copied() {
+                            for child_id in node.flat_tree_children().iter().copied() {
                                 build_inline_layout_recursive(
                                     builder,
                                     nodes,
@@ -956,10 +1046,70 @@ pub(crate) fn build_inline_layout(
             }
             NodeData::Text(data) => {
                 // dbg!(&data.content);
-                builder.push_text(&data.content);
+                let parent_style = node
+                    .parent
+                    .and_then(|parent_id| nodes[parent_id].primary_styles());
+                let text_styles = parent_style.as_ref().map(|s| s.get_inherited_text());
+                let white_space_collapse = text_styles.map(|s| s.white_space_collapse);
+                let text_wrap_mode = text_styles.map(|s| s.text_wrap_mode);
+
+                let text = data.content.as_str();
+
+                // TODO: support `word-break` and `overflow-wrap`. Parley only breaks lines at the
+                // break opportunities of its own text analysis, and has no setting for either.
+                let no_wrap = text_wrap_mode == Some(TextWrapMode::Nowrap);
+
+                // Parley can only disable wrapping for an entire layout, so the extent of
+                // `text-wrap-mode: nowrap` text is marked for line breaking to keep it on one line
+                if no_wrap {
+                    push_marker_box(builder, NO_WRAP_START_BOX_ID);
+                }
+
+                // `white-space-collapse: preserve-breaks` collapses spaces but preserves segment breaks
+                if white_space_collapse == Some(StyloWhiteSpaceCollapse::PreserveBreaks) {
+                    for (i, segment) in text.split('\n').enumerate() {
+                        if i > 0 {
+                            builder.push_style_modification_span(&[]);
+                            builder.set_white_space_mode(WhiteSpaceCollapse::Preserve);
+                            builder.push_text("\n");
+                            builder.pop_style_span();
+                            builder.set_white_space_mode(collapse_mode);
+                        }
+                        builder.push_text(segment);
+                    }
+                } else {
+                    builder.push_text(text);
+                }
+
+                if no_wrap {
+                    push_marker_box(builder, NO_WRAP_END_BOX_ID);
+                }
+            }
+            NodeData::Comment | NodeData::ProcessingInstruction { .. } => {}
+            NodeData::Document | NodeData::ShadowRoot(_) | NodeData::DocumentFragment => {
+                unreachable!()
             }
-            NodeData::Comment => {}
-            NodeData::Document => unreachable!(),
         }
     }
 }
+
+/// The id of the zero-width inline boxes which mark the start of `text-wrap-mode: nowrap` text
+/// (rather than being the box of a node)
+pub(crate) const NO_WRAP_START_BOX_ID: u64 = u64::MAX;
+/// The id of the zero-width inline boxes which mark the end of `text-wrap-mode: nowrap` text
+pub(crate) const NO_WRAP_END_BOX_ID: u64 = u64::MAX - 1;
+
+/// Whether an inline box is the box of a node (rather than a zero-width marker box)
+pub(crate) fn is_node_box(id: u64) -> bool {
+    id < NO_WRAP_END_BOX_ID
+}
+
+fn push_marker_box(builder: &mut TreeBuilder<TextBrush>, id: u64) {
+    builder.push_inline_box(InlineBox {
+        id,
+        // Overridden by push_inline_box method
+        index: 0,
+        width: 0.0,
+        height: 0.0,
+    });
+}
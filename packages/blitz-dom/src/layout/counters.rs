@@ -0,0 +1,564 @@
+//! CSS counters, quotes and generated content.
+//!
+//! Counters and quote depths depend on document order, so they are resolved in a single pass over
+//! the DOM before layout children are constructed. This pass creates `::before` and `::after`
+//! pseudo-elements, fills them with their generated content and computes list item markers from
+//! the `list-item` counter. The pass is skipped when neither the tree's structure nor any styles
+//! have changed since it last ran.
+//!
+//! See <https://drafts.csswg.org/css-lists-3/#auto-numbering> and
+//! <https://drafts.csswg.org/css-content-3/#content-property>
+
+use markup5ever::{QualName, local_name, namespace_url, ns};
+use style::properties::longhands::list_style_type::computed_value::T as ListStyleType;
+use style::selector_parser::PseudoElement;
+use style::servo::url::ComputedUrl;
+use style::servo_arc::Arc;
+use style::shared_lock::StylesheetGuards;
+use style::values::computed::{Content, ContentItem, Display};
+use style::values::generics::image::Image as StyloImage;
+use style::values::specified::list::Quotes;
+use style::{Atom, data::ElementData};
+
+use super::construct::{flush_pseudo_elements, node_list_item_child};
+use crate::net::ImageHandler;
+use crate::node::{Attribute, ElementNodeData, NodeData};
+use crate::util::ImageType;
+use crate::{BaseDocument, Node};
+use blitz_traits::net::Request;
+
+/// A single instance of a named counter
+#[derive(Debug)]
+struct Counter {
+    name: Atom,
+    value: i32,
+    /// Reversed counters are implicitly decremented (rather than incremented) by list items
+    reversed: bool,
+}
+
+#[derive(Debug, Default)]
+struct CounterState {
+    /// The counters which are in scope, innermost last
+    counters: Vec<Counter>,
+    /// The length of the counter stack when the current sibling list was entered
+    scope_start: usize,
+    /// The current nesting level of quotes
+    quote_depth: usize,
+}
+
+impl CounterState {
+    fn find_mut(&mut self, name: &Atom) -> Option<&mut Counter> {
+        self.counters.iter_mut().rev().find(|c| c.name == *name)
+    }
+
+    /// Instantiate a new counter on the current element. A counter of the same name created by a
+    /// preceding sibling is replaced.
+    fn reset(&mut self, name: Atom, value: i32, reversed: bool) {
+        let sibling_counter = self.counters[self.scope_start..]
+            .iter_mut()
+            .rev()
+            .find(|c| c.name == name);
+        match sibling_counter {
+            Some(counter) => {
+                counter.value = value;
+                counter.reversed = reversed;
+            }
+            None => self.counters.push(Counter {
+                name,
+                value,
+                reversed,
+            }),
+        }
+    }
+
+    /// Get the innermost counter with a given name, instantiating it if it doesn't exist
+    fn get_or_instantiate(&mut self, name: &Atom) -> &mut Counter {
+        if self.find_mut(name).is_none() {
+            self.reset(name.clone(), 0, false);
+        }
+        self.find_mut(name).unwrap()
+    }
+
+    fn increment(&mut self, name: &Atom, by: i32) {
+        let counter = self.get_or_instantiate(name);
+        counter.value = counter.value.saturating_add(by);
+    }
+
+    fn set(&mut self, name: &Atom, value: i32) {
+        self.get_or_instantiate(name).value = value;
+    }
+}
+
+/// The resolved content of a `::before` or `::after` pseudo-element
+#[derive(Debug, Clone, PartialEq)]
+enum GeneratedContent {
+    Text(String),
+    Image(url::Url),
+}
+
+/// The counter properties of an element (or pseudo-element), extracted from its style
+struct CounterProperties {
+    display: Display,
+    resets: Vec<(Atom, i32, bool)>,
+    increments: Vec<(Atom, i32)>,
+}
+
+impl CounterProperties {
+    fn from_node(node: &Node) -> Option<Self> {
+        let style = node.primary_styles()?;
+        let counters = style.get_counters();
+        Some(Self {
+            display: style.clone_display(),
+            resets: counters
+                .counter_reset
+                .iter()
+                .map(|pair| (pair.name.0.clone(), pair.value, pair.is_reversed))
+                .collect(),
+            increments: counters
+                .counter_increment
+                .iter()
+                .map(|pair| (pair.name.0.clone(), pair.value))
+                .collect(),
+        })
+    }
+}
+
+impl BaseDocument {
+    /// Resolve counters and quotes in document order, updating the generated content of
+    /// pseudo-elements and the markers of list items.
+    pub(crate) fn resolve_generated_content(&mut self) {
+        if !self.generated_content_dirty {
+            return;
+        }
+
+        let mut state = CounterState::default();
+        let root_id = self.root_node().id;
+        let children = self.nodes[root_id].children.clone();
+        for child_id in children {
+            resolve_generated_content_recursive(self, child_id, &mut state);
+        }
+
+        // Updating the content of pseudo-elements mutates the tree, but doesn't invalidate it
+        self.generated_content_dirty = false;
+    }
+}
+
+fn resolve_generated_content_recursive(
+    doc: &mut BaseDocument,
+    node_id: usize,
+    state: &mut CounterState,
+) {
+    if !doc.nodes[node_id].is_element() {
+        return;
+    }
+    // Elements which don't generate boxes (and their descendants) don't affect counters
+    let Some(properties) = CounterProperties::from_node(&doc.nodes[node_id]) else {
+        return;
+    };
+    if properties.display == Display::None {
+        return;
+    }
+
+    flush_pseudo_elements(doc, node_id);
+
+    apply_counter_properties(doc, node_id, properties, state);
+
+    // Resolve the list item marker from the `list-item` counter
+    let list_item_value = doc.nodes[node_id]
+        .display_style()
+        .is_some_and(|display| display.is_list_item())
+        .then(|| state.get_or_instantiate(&list_item_atom()).value);
+    if !has_current_list_item_data(&doc.nodes[node_id], list_item_value) {
+        let list_item_data = list_item_value
+            .and_then(|value| node_list_item_child(doc, node_id, value))
+            .map(Box::new);
+        if let Some(element_data) = doc.nodes[node_id].element_data_mut() {
+            element_data.list_item_data = list_item_data;
+        }
+    }
+
+    // Counters created by children are scoped to the children (and their descendants)
+    let parent_scope_start = state.scope_start;
+    state.scope_start = state.counters.len();
+
+    if let Some(before_id) = doc.nodes[node_id].before {
+        resolve_pseudo_element_content(doc, node_id, before_id, state);
+    }
+    let children = doc.nodes[node_id].flat_tree_children().to_vec();
+    for child_id in children {
+        resolve_generated_content_recursive(doc, child_id, state);
+    }
+    if let Some(after_id) = doc.nodes[node_id].after {
+        resolve_pseudo_element_content(doc, node_id, after_id, state);
+    }
+
+    state.counters.truncate(state.scope_start);
+    state.scope_start = parent_scope_start;
+}
+
+/// Whether a node's list item marker was created from its current style and `list-item` counter
+/// value (and so doesn't need to be laid out again)
+fn has_current_list_item_data(node: &Node, value: Option<i32>) -> bool {
+    let Some(list_item) = node
+        .element_data()
+        .and_then(|el| el.list_item_data.as_deref())
+    else {
+        return false;
+    };
+    let style_data = node.stylo_element_data.borrow();
+    let style = style_data
+        .as_ref()
+        .and_then(|data| data.styles.get_primary());
+    value == Some(list_item.value)
+        && style.is_some_and(|style| Arc::ptr_eq(style, &list_item.style))
+}
+
+fn list_item_atom() -> Atom {
+    Atom::from("list-item")
+}
+
+/// Apply `counter-reset`, `counter-increment` and the implicit `list-item` counter to the
+/// counters in scope.
+///
+/// Note: `counter-set` is only implemented by Stylo's gecko build, so isn't supported here.
+fn apply_counter_properties(
+    doc: &BaseDocument,
+    node_id: usize,
+    properties: CounterProperties,
+    state: &mut CounterState,
+) {
+    let node = &doc.nodes[node_id];
+    let list_item = list_item_atom();
+    let is_list_item = properties.display.is_list_item();
+
+    for (name, value, reversed) in properties.resets {
+        // The `start` and `reversed` attributes of `<ol>` elements modify the `list-item` counter
+        // created by the UA stylesheet
+        if name == list_item && node.data.is_element_with_tag_name(&local_name!("ol")) {
+            let reversed = reversed || node.attr(local_name!("reversed")).is_some();
+            let start = node
+                .attr(local_name!("start"))
+                .and_then(|start| start.trim().parse::<i32>().ok());
+            let value = match (start, reversed) {
+                (Some(start), false) => start.saturating_sub(1),
+                (Some(start), true) => start.saturating_add(1),
+                (None, false) => value,
+                (None, true) => count_list_items(doc, node_id).saturating_add(1),
+            };
+            state.reset(name, value, reversed);
+        } else {
+            state.reset(name, value, reversed);
+        }
+    }
+
+    // List items implicitly increment the `list-item` counter unless it is explicitly incremented
+    let increments_list_item = properties
+        .increments
+        .iter()
+        .any(|(name, _)| *name == list_item);
+    for (name, by) in properties.increments {
+        state.increment(&name, by);
+    }
+    if is_list_item && !increments_list_item {
+        let reversed = state.get_or_instantiate(&list_item).reversed;
+        state.increment(&list_item, if reversed { -1 } else { 1 });
+    }
+
+    // The `value` attribute of `<li>` elements sets the `list-item` counter
+    let li_value = node
+        .attr(local_name!("value"))
+        .filter(|_| node.data.is_element_with_tag_name(&local_name!("li")))
+        .and_then(|value| value.trim().parse::<i32>().ok());
+    if let Some(value) = li_value {
+        state.set(&list_item, value);
+    }
+}
+
+/// Count the list items which are children of a node
+fn count_list_items(doc: &BaseDocument, node_id: usize) -> i32 {
+    doc.nodes[node_id]
+        .children
+        .iter()
+        .filter(|&&child_id| {
+            doc.nodes[child_id]
+                .display_style()
+                .is_some_and(|display| display.is_list_item())
+        })
+        .count() as i32
+}
+
+/// Resolve the `content` property of a `::before` or `::after` pseudo-element and update its
+/// children to match.
+fn resolve_pseudo_element_content(
+    doc: &mut BaseDocument,
+    originating_node_id: usize,
+    pseudo_node_id: usize,
+    state: &mut CounterState,
+) {
+    let Some(properties) = CounterProperties::from_node(&doc.nodes[pseudo_node_id]) else {
+        return;
+    };
+    apply_counter_properties(doc, pseudo_node_id, properties, state);
+
+    let Some((content, quotes)) = doc.nodes[pseudo_node_id].primary_styles().map(|style| {
+        (
+            style.get_counters().content.clone(),
+            style.get_list().quotes.clone(),
+        )
+    }) else {
+        return;
+    };
+
+    let mut generated = Vec::new();
+    let push_text = |generated: &mut Vec<GeneratedContent>, text: &str| {
+        if let Some(GeneratedContent::Text(existing)) = generated.last_mut() {
+            existing.push_str(text);
+        } else if !text.is_empty() {
+            generated.push(GeneratedContent::Text(text.to_string()));
+        }
+    };
+
+    if let Content::Items(item_data) = &content {
+        // Items after `alt_start` are alternative text for accessibility
+        for item in &item_data.items[0..item_data.alt_start] {
+            match item {
+                ContentItem::String(str) => push_text(&mut generated, str),
+                ContentItem::Counter(name, style) => {
+                    let value = state.get_or_instantiate(&name.0).value;
+                    push_text(&mut generated, &format_counter(value, *style));
+                }
+                ContentItem::Counters(name, separator, style) => {
+                    if state.find_mut(&name.0).is_none() {
+                        state.get_or_instantiate(&name.0);
+                    }
+                    let values: Vec<String> = state
+                        .counters
+                        .iter()
+                        .filter(|c| c.name == name.0)
+                        .map(|c| format_counter(c.value, *style))
+                        .collect();
+                    push_text(&mut generated, &values.join(separator));
+                }
+                ContentItem::OpenQuote => {
+                    push_text(&mut generated, quote(&quotes, state.quote_depth, true));
+                    state.quote_depth += 1;
+                }
+                ContentItem::CloseQuote => {
+                    // Closing quotes at depth 0 are not rendered (but still "close" nothing)
+                    if state.quote_depth > 0 {
+                        state.quote_depth -= 1;
+                        push_text(&mut generated, quote(&quotes, state.quote_depth, false));
+                    }
+                }
+                ContentItem::NoOpenQuote => state.quote_depth += 1,
+                ContentItem::NoCloseQuote => {
+                    state.quote_depth = state.quote_depth.saturating_sub(1)
+                }
+                ContentItem::Attr(attr) => {
+                    let node = &doc.nodes[originating_node_id];
+                    let value = node.attrs().and_then(|attrs| {
+                        attrs
+                            .iter()
+                            .find(|a| *a.name.local == *attr.attribute)
+                            .map(|a| a.value.as_str())
+                    });
+                    push_text(&mut generated, value.unwrap_or(&attr.fallback));
+                }
+                ContentItem::Image(image) => {
+                    // TODO: support gradients and other non-url images
+                    if let StyloImage::Url(ComputedUrl::Valid(url)) = image {
+                        generated.push(GeneratedContent::Image((**url).clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    update_pseudo_element_children(doc, pseudo_node_id, generated);
+}
+
+/// Get the quote to use at a given nesting depth
+fn quote(quotes: &Quotes, depth: usize, open: bool) -> &str {
+    // TODO: choose quotes based on the content language when `quotes` is `auto`
+    const AUTO_QUOTES: [(&str, &str); 2] = [("\u{201C}", "\u{201D}"), ("\u{2018}", "\u{2019}")];
+
+    match quotes {
+        Quotes::QuoteList(list) => {
+            let Some(pair) = list.0.get(depth).or(list.0.last()) else {
+                return "";
+            };
+            if open { &pair.opening } else { &pair.closing }
+        }
+        Quotes::Auto => {
+            let (opening, closing) = AUTO_QUOTES[depth.min(AUTO_QUOTES.len() - 1)];
+            if open { opening } else { closing }
+        }
+    }
+}
+
+/// Replace the children of a pseudo-element with its generated content (if it has changed)
+fn update_pseudo_element_children(
+    doc: &mut BaseDocument,
+    pseudo_node_id: usize,
+    generated: Vec<GeneratedContent>,
+) {
+    let current: Vec<Option<GeneratedContent>> = doc.nodes[pseudo_node_id]
+        .children
+        .iter()
+        .map(|&child_id| match &doc.nodes[child_id].data {
+            NodeData::Text(data) => Some(GeneratedContent::Text(data.content.clone())),
+            NodeData::Element(el) => el
+                .attr(local_name!("src"))
+                .and_then(|src| url::Url::parse(src).ok())
+                .map(GeneratedContent::Image),
+            _ => None,
+        })
+        .collect();
+    if current.len() == generated.len()
+        && current
+            .iter()
+            .zip(generated.iter())
+            .all(|(current, generated)| current.as_ref() == Some(generated))
+    {
+        return;
+    }
+
+    for child_id in std::mem::take(&mut doc.nodes[pseudo_node_id].children) {
+        doc.remove_and_drop_node(child_id);
+    }
+
+    for item in generated {
+        let child_id = match item {
+            GeneratedContent::Text(text) => doc.create_text_node(&text),
+            GeneratedContent::Image(url) => create_image_node(doc, pseudo_node_id, url),
+        };
+        doc.nodes[child_id].parent = Some(pseudo_node_id);
+        doc.nodes[pseudo_node_id].children.push(child_id);
+    }
+}
+
+/// Create an anonymous image node for a `url()` in the `content` property and fetch its image
+fn create_image_node(doc: &mut BaseDocument, pseudo_node_id: usize, url: url::Url) -> usize {
+    const IMG: QualName = QualName {
+        prefix: None,
+        ns: ns!(html),
+        local: local_name!("img"),
+    };
+    let attrs = vec![Attribute {
+        name: QualName::new(None, ns!(), local_name!("src")),
+        value: url.to_string(),
+    }];
+    let node_id = doc.create_node(NodeData::Element(ElementNodeData::new(IMG, attrs)));
+
+    // Set style data
+    let parent_style = doc.nodes[pseudo_node_id].primary_styles().unwrap();
+    let read_guard = doc.guard.read();
+    let guards = StylesheetGuards::same(&read_guard);
+    let style = doc.stylist.style_for_anonymous::<&Node>(
+        &guards,
+        &PseudoElement::ServoAnonymousBox,
+        &parent_style,
+    );
+    drop(parent_style);
+    drop(read_guard);
+    let mut element_data = ElementData::default();
+    element_data.styles.primary = Some(style);
+    element_data.set_restyled();
+    *doc.nodes[node_id].stylo_element_data.borrow_mut() = Some(element_data);
+
+    doc.net_provider.fetch(
+        doc.id(),
+        Request::get(url),
+        Box::new(ImageHandler::new(node_id, ImageType::Image)),
+    );
+
+    node_id
+}
+
+/// Format a counter value using a counter style.
+///
+/// See <https://drafts.csswg.org/css-counter-styles-3/#predefined-counters>
+pub(crate) fn format_counter(value: i32, style: ListStyleType) -> String {
+    /// The zero digit of numeric counter styles which use contiguous decimal digits
+    fn zero_digit(style: ListStyleType) -> Option<char> {
+        Some(match style {
+            ListStyleType::ArabicIndic => '\u{0660}',
+            ListStyleType::Persian => '\u{06F0}',
+            ListStyleType::Devanagari => '\u{0966}',
+            ListStyleType::Bengali => '\u{09E6}',
+            ListStyleType::Gurmukhi => '\u{0A66}',
+            ListStyleType::Gujarati => '\u{0AE6}',
+            ListStyleType::Oriya => '\u{0B66}',
+            ListStyleType::Telugu => '\u{0C66}',
+            ListStyleType::Kannada => '\u{0CE6}',
+            ListStyleType::Malayalam => '\u{0D66}',
+            ListStyleType::Thai => '\u{0E50}',
+            ListStyleType::Lao => '\u{0ED0}',
+            ListStyleType::Tibetan => '\u{0F20}',
+            ListStyleType::Myanmar => '\u{1040}',
+            ListStyleType::Khmer | ListStyleType::Cambodian => '\u{17E0}',
+            ListStyleType::Mongolian => '\u{1810}',
+            _ => return None,
+        })
+    }
+
+    const CJK_DIGITS: [char; 10] = ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+    const LOWER_ALPHA: &[char] = &[
+        'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r',
+        's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+    ];
+    const UPPER_ALPHA: &[char] = &[
+        'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R',
+        'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
+    ];
+    const LOWER_GREEK: &[char] = &[
+        'α', 'β', 'γ', 'δ', 'ε', 'ζ', 'η', 'θ', 'ι', 'κ', 'λ', 'μ', 'ν', 'ξ', 'ο', 'π', 'ρ', 'σ',
+        'τ', 'υ', 'φ', 'χ', 'ψ', 'ω',
+    ];
+
+    let alphabetic = |symbols: &[char]| {
+        // Alphabetic counter styles can't represent values below 1, so fall back to decimal
+        if value < 1 {
+            return value.to_string();
+        }
+        let mut output = Vec::new();
+        let mut value = value as usize;
+        while value > 0 {
+            value -= 1;
+            output.push(symbols[value % symbols.len()]);
+            value /= symbols.len();
+        }
+        output.iter().rev().collect()
+    };
+    let numeric = |digit: &dyn Fn(u32) -> char| {
+        let digits: String = value
+            .unsigned_abs()
+            .to_string()
+            .chars()
+            .map(|c| digit(c.to_digit(10).unwrap()))
+            .collect();
+        if value < 0 {
+            format!("-{digits}")
+        } else {
+            digits
+        }
+    };
+
+    match style {
+        ListStyleType::None => String::new(),
+        ListStyleType::Disc => String::from("•"),
+        ListStyleType::Circle => String::from("◦"),
+        ListStyleType::Square => String::from("▪"),
+        ListStyleType::DisclosureOpen => String::from("▾"),
+        ListStyleType::DisclosureClosed => String::from("▸"),
+        ListStyleType::LowerAlpha => alphabetic(LOWER_ALPHA),
+        ListStyleType::UpperAlpha => alphabetic(UPPER_ALPHA),
+        ListStyleType::LowerGreek => alphabetic(LOWER_GREEK),
+        ListStyleType::CjkDecimal => numeric(&|d| CJK_DIGITS[d as usize]),
+        _ => match zero_digit(style) {
+            Some(zero) => numeric(&|d| char::from_u32(zero as u32 + d).unwrap()),
+            // TODO: implement the remaining (alphabetic and additive) counter styles
+            None => value.to_string(),
+        },
+    }
+}
@@ -1,11 +1,20 @@
+use std::ops::Range;
+
 use parley::AlignmentOptions;
+use parley::layout::PositionedLayoutItem;
 use taffy::{
     AvailableSpace, LayoutPartialTree as _, MaybeMath as _, MaybeResolve as _, NodeId, Position,
     ResolveOrZero as _, Size, compute_leaf_layout,
 };
 
+use style::computed_values::position::T as StyloPosition;
+use style::computed_values::text_wrap_mode::T as TextWrapMode;
+use style::values::specified::box_::DisplayOutside;
+
+use super::construct::{NO_WRAP_END_BOX_ID, NO_WRAP_START_BOX_ID, is_node_box};
 use super::resolve_calc_value;
 use crate::BaseDocument;
+use crate::node::{TextBrush, TextLayout};
 
 impl BaseDocument {
     pub(crate) fn compute_inline_layout(
@@ -24,7 +33,7 @@ impl BaseDocument {
             .unwrap();
 
         // TODO: eliminate clone
-        let style = self.nodes[node_id].style.clone();
+        let style = self.nodes[node_id].layout_style().clone();
 
         let output = compute_leaf_layout(
             inputs,
@@ -36,15 +45,20 @@ impl BaseDocument {
                     return Size::ZERO;
                 }
 
-                // Compute size of inline boxes
+                // Compute size of inline boxes. They are sized using their own styles even when
+                // the inline root is being measured for its content-based size.
                 let child_inputs = taffy::tree::LayoutInput {
                     known_dimensions: Size::NONE,
                     available_space,
                     parent_size: available_space.into_options(),
+                    sizing_mode: taffy::SizingMode::InherentSize,
                     ..inputs
                 };
                 for ibox in inline_layout.layout.inline_boxes_mut() {
-                    let style = &self.nodes[ibox.id as usize].style;
+                    if !is_node_box(ibox.id) {
+                        continue;
+                    }
+                    let style = self.nodes[ibox.id as usize].layout_style();
                     let margin = style
                         .margin
                         .resolve_or_zero(inputs.parent_size, resolve_calc_value);
@@ -60,7 +74,11 @@ impl BaseDocument {
                 }
 
                 // Perform inline layout
+                let no_wrap = self.nodes[node_id]
+                    .primary_styles()
+                    .is_some_and(|s| s.get_inherited_text().text_wrap_mode == TextWrapMode::Nowrap);
                 let max_advance = match available_space.width {
+                    _ if no_wrap => None,
                     AvailableSpace::Definite(px) => Some(px * scale),
                     AvailableSpace::MinContent => Some(0.0),
                     AvailableSpace::MaxContent => None,
@@ -86,7 +104,8 @@ impl BaseDocument {
                     })
                     .unwrap_or(parley::layout::Alignment::Start);
 
-                inline_layout.layout.break_all_lines(max_advance);
+                break_lines(&mut inline_layout, max_advance);
+                let content_width = content_width(&inline_layout);
 
                 let padding = style
                     .padding
@@ -104,7 +123,7 @@ impl BaseDocument {
                     .width
                     .map(|w| (w * scale) - pbw)
                     .unwrap_or_else(|| {
-                        let computed_width = inline_layout.layout.width();
+                        let computed_width = content_width;
                         let style_width = style
                             .size
                             .width
@@ -137,88 +156,131 @@ impl BaseDocument {
                 );
 
                 // Store sizes and positions of inline boxes
+                let mut absolute_boxes: Vec<(usize, taffy::Point<f32>)> = Vec::new();
                 for line in inline_layout.layout.lines() {
+                    let line_metrics = *line.metrics();
+                    let mut is_line_start = true;
                     for item in line.items() {
-                        if let parley::layout::PositionedLayoutItem::InlineBox(ibox) = item {
-                            let node = &mut self.nodes[ibox.id as usize];
-                            let padding = node
-                                .style
-                                .padding
-                                .resolve_or_zero(child_inputs.parent_size, resolve_calc_value);
-                            let border = node
-                                .style
-                                .border
-                                .resolve_or_zero(child_inputs.parent_size, resolve_calc_value);
-                            let margin = node
-                                .style
-                                .margin
-                                .resolve_or_zero(child_inputs.parent_size, resolve_calc_value);
-
-                            // Resolve inset
-                            let left = node
-                                .style
-                                .inset
-                                .left
-                                .maybe_resolve(child_inputs.parent_size.width, resolve_calc_value);
-                            let right = node
-                                .style
-                                .inset
-                                .right
-                                .maybe_resolve(child_inputs.parent_size.width, resolve_calc_value);
-                            let top = node
-                                .style
-                                .inset
-                                .top
-                                .maybe_resolve(child_inputs.parent_size.height, resolve_calc_value);
-                            let bottom = node
-                                .style
-                                .inset
-                                .bottom
-                                .maybe_resolve(child_inputs.parent_size.height, resolve_calc_value);
-
-                            if node.style.position == Position::Absolute {
-                                let output =
-                                    self.compute_child_layout(NodeId::from(ibox.id), child_inputs);
-
-                                let layout = &mut self.nodes[ibox.id as usize].unrounded_layout;
-                                layout.size = output.size;
-
-                                // TODO: Implement absolute positioning
-                                layout.location.x = left
-                                    .or_else(|| {
-                                        child_inputs
-                                            .parent_size
-                                            .width
-                                            .zip(right)
-                                            .map(|(w, r)| w - r)
-                                    })
-                                    .unwrap_or((ibox.x / scale) + margin.left + container_pb.left);
-                                layout.location.y = top
-                                    .or_else(|| {
-                                        child_inputs
-                                            .parent_size
-                                            .height
-                                            .zip(bottom)
-                                            .map(|(w, r)| w - r)
-                                    })
-                                    .unwrap_or((ibox.y / scale) + margin.top + container_pb.top);
-
-                                layout.padding = padding; //.map(|p| p / scale);
-                                layout.border = border; //.map(|p| p / scale);
-                            } else {
-                                let layout = &mut node.unrounded_layout;
-                                layout.size.width =
-                                    (ibox.width / scale) - margin.left - margin.right;
-                                layout.size.height =
-                                    (ibox.height / scale) - margin.top - margin.bottom;
-                                layout.location.x =
-                                    (ibox.x / scale) + margin.left + container_pb.left;
-                                layout.location.y =
-                                    (ibox.y / scale) + margin.top + container_pb.top;
-                                layout.padding = padding; //.map(|p| p / scale);
-                                layout.border = border; //.map(|p| p / scale);
+                        let ibox = match item {
+                            parley::layout::PositionedLayoutItem::GlyphRun(run) => {
+                                if run.advance() > 0.0 {
+                                    is_line_start = false;
+                                }
+                                continue;
                             }
+                            parley::layout::PositionedLayoutItem::InlineBox(ibox) => ibox,
+                        };
+                        if !is_node_box(ibox.id) {
+                            continue;
+                        }
+
+                        let node = &mut self.nodes[ibox.id as usize];
+
+                        if node.style.position == Position::Absolute {
+                            // The static position of an absolutely positioned box is where it would have been
+                            // placed if it were position: static. Boxes that were originally inline-level sit at
+                            // their position in the line, while boxes that were originally block-level start
+                            // below the current line (unless the line is empty up to this point).
+                            let is_inline_level = node
+                                .primary_styles()
+                                .map(|s| s.get_box().original_display.outside())
+                                .is_some_and(|outside| outside == DisplayOutside::Inline);
+                            let static_position = if is_inline_level {
+                                taffy::Point {
+                                    x: ibox.x,
+                                    y: line_metrics.min_coord,
+                                }
+                            } else if is_line_start {
+                                taffy::Point {
+                                    x: 0.0,
+                                    y: line_metrics.min_coord,
+                                }
+                            } else {
+                                taffy::Point {
+                                    x: 0.0,
+                                    y: line_metrics.max_coord,
+                                }
+                            };
+                            absolute_boxes.push((
+                                ibox.id as usize,
+                                taffy::Point {
+                                    x: (static_position.x / scale) + container_pb.left,
+                                    y: (static_position.y / scale) + container_pb.top,
+                                },
+                            ));
+                            continue;
+                        }
+
+                        is_line_start = false;
+
+                        let padding = node
+                            .style
+                            .padding
+                            .resolve_or_zero(child_inputs.parent_size, resolve_calc_value);
+                        let border = node
+                            .style
+                            .border
+                            .resolve_or_zero(child_inputs.parent_size, resolve_calc_value);
+                        let margin = node
+                            .style
+                            .margin
+                            .resolve_or_zero(child_inputs.parent_size, resolve_calc_value);
+
+                        let layout = &mut node.unrounded_layout;
+                        layout.size.width = (ibox.width / scale) - margin.left - margin.right;
+                        layout.size.height = (ibox.height / scale) - margin.top - margin.bottom;
+                        layout.location.x = (ibox.x / scale) + margin.left + container_pb.left;
+                        layout.location.y = (ibox.y / scale) + margin.top + container_pb.top;
+                        layout.padding = padding; //.map(|p| p / scale);
+                        layout.border = border; //.map(|p| p / scale);
+                    }
+                }
+
+                let content_size = taffy::Size {
+                    width: content_width.ceil() / scale,
+                    height: inline_layout.layout.height() / scale,
+                };
+
+                // Absolutely positioned boxes only need to be laid out once the size of the inline root is known
+                if inputs.run_mode == taffy::RunMode::PerformLayout {
+                    // The padding box of the inline root. Used as the containing block for absolutely positioned
+                    // boxes that do not have a positioned inline ancestor (provisionally if the inline root isn't
+                    // positioned either, see `resolve_deferred_absolute_boxes`).
+                    let border_box = taffy::Size {
+                        width: inputs.known_dimensions.width.unwrap_or(
+                            (alignment_width / scale) + container_pb.horizontal_components().sum(),
+                        ),
+                        height: inputs.known_dimensions.height.unwrap_or(
+                            content_size.height + container_pb.vertical_components().sum(),
+                        ),
+                    };
+                    let root_padding_box = taffy::Rect {
+                        left: border.left,
+                        top: border.top,
+                        right: border_box.width - border.right,
+                        bottom: border_box.height - border.bottom,
+                    };
+
+                    let is_root_positioned = self.nodes[node_id]
+                        .primary_styles()
+                        .is_some_and(|s| s.clone_position() != StyloPosition::Static);
+                    for (box_id, static_position) in absolute_boxes {
+                        let containing_block = self
+                            .inline_containing_block(node_id, box_id, &inline_layout.layout)
+                            .map(|rect| taffy::Rect {
+                                left: (rect.left / scale) + container_pb.left,
+                                right: (rect.right / scale) + container_pb.left,
+                                top: (rect.top / scale) + container_pb.top,
+                                bottom: (rect.bottom / scale) + container_pb.top,
+                            });
+                        if containing_block.is_none() && !is_root_positioned {
+                            self.deferred_absolute_boxes
+                                .insert(box_id, (node_id, static_position));
+                        } else {
+                            self.deferred_absolute_boxes.remove(&box_id);
                         }
+                        let containing_block = containing_block.unwrap_or(root_padding_box);
+                        self.layout_inline_absolute_box(box_id, containing_block, static_position);
                     }
                 }
 
@@ -228,10 +290,7 @@ impl BaseDocument {
                 // println!("known_dimensions: w: {:?} h: {:?}", inputs.known_dimensions.width, inputs.known_dimensions.height);
                 // println!("\n");
 
-                inputs.known_dimensions.unwrap_or(taffy::Size {
-                    width: inline_layout.layout.width().ceil() / scale,
-                    height: inline_layout.layout.height() / scale,
-                })
+                inputs.known_dimensions.unwrap_or(content_size)
             },
         );
 
@@ -244,4 +303,507 @@ impl BaseDocument {
 
         output
     }
+
+    /// Find the containing block of an absolutely positioned box within an inline formatting context:
+    /// the bounding box of the fragments of its nearest positioned inline ancestor.
+    ///
+    /// Returns `None` if there is no positioned inline ancestor between the box and the inline root, in which
+    /// case the inline root is the containing block. Coordinates are in the (scaled) space of the parley layout.
+    fn inline_containing_block(
+        &self,
+        inline_root_id: usize,
+        box_id: usize,
+        layout: &parley::Layout<TextBrush>,
+    ) -> Option<taffy::Rect<f32>> {
+        let is_positioned = |node_id: usize| {
+            self.nodes[node_id]
+                .primary_styles()
+                .is_some_and(|s| s.clone_position() != StyloPosition::Static)
+        };
+        let is_inclusive_descendant = |mut node_id: usize, ancestor_id: usize| loop {
+            if node_id == ancestor_id {
+                return true;
+            }
+            match self.nodes[node_id].parent {
+                Some(parent_id) if node_id != inline_root_id => node_id = parent_id,
+                _ => return false,
+            }
+        };
+
+        let mut ancestor_id = self.nodes[box_id].parent?;
+        while ancestor_id != inline_root_id && !is_positioned(ancestor_id) {
+            ancestor_id = self.nodes[ancestor_id].parent?;
+        }
+        if ancestor_id == inline_root_id {
+            return None;
+        }
+
+        // Union of the ancestor's text fragments and in-flow inline boxes across all lines
+        let mut bounds: Option<taffy::Rect<f32>> = None;
+        let mut add_fragment = |rect: taffy::Rect<f32>| {
+            bounds = Some(match bounds {
+                None => rect,
+                Some(b) => taffy::Rect {
+                    left: b.left.min(rect.left),
+                    right: b.right.max(rect.right),
+                    top: b.top.min(rect.top),
+                    bottom: b.bottom.max(rect.bottom),
+                },
+            });
+        };
+        for line in layout.lines() {
+            for item in line.items() {
+                match item {
+                    parley::layout::PositionedLayoutItem::GlyphRun(run) => {
+                        if !is_inclusive_descendant(run.style().brush.id, ancestor_id) {
+                            continue;
+                        }
+                        let metrics = run.run().metrics();
+                        add_fragment(taffy::Rect {
+                            left: run.offset(),
+                            right: run.offset() + run.advance(),
+                            top: run.baseline() - metrics.ascent,
+                            bottom: run.baseline() + metrics.descent,
+                        });
+                    }
+                    parley::layout::PositionedLayoutItem::InlineBox(ibox) => {
+                        if !is_node_box(ibox.id) {
+                            continue;
+                        }
+                        let id = ibox.id as usize;
+                        if id == box_id
+                            || self.nodes[id].style.position == Position::Absolute
+                            || !is_inclusive_descendant(id, ancestor_id)
+                        {
+                            continue;
+                        }
+                        add_fragment(taffy::Rect {
+                            left: ibox.x,
+                            right: ibox.x + ibox.width,
+                            top: ibox.y,
+                            bottom: ibox.y + ibox.height,
+                        });
+                    }
+                }
+            }
+        }
+
+        bounds
+    }
+
+    /// Position the absolutely positioned boxes in inline formatting contexts whose containing block is
+    /// outside of their inline root: the padding box of the inline root's nearest positioned ancestor (or
+    /// else the initial containing block). Must be called once the rest of the document has been laid out.
+    pub(crate) fn resolve_deferred_absolute_boxes(&mut self) {
+        // Forget boxes which have been removed or moved into another inline root
+        let nodes = &self.nodes;
+        self.deferred_absolute_boxes
+            .retain(|&box_id, &mut (inline_root_id, _)| {
+                nodes.get(box_id).is_some_and(|node| {
+                    node.style.position == Position::Absolute
+                        && node.layout_parent.get() == Some(inline_root_id)
+                })
+            });
+
+        let boxes: Vec<(usize, usize, taffy::Point<f32>)> = self
+            .deferred_absolute_boxes
+            .iter()
+            .map(|(&box_id, &(inline_root_id, static_position))| {
+                (box_id, inline_root_id, static_position)
+            })
+            .collect();
+        for (box_id, inline_root_id, static_position) in boxes {
+            // Find the nearest positioned ancestor, and the offset of the inline root from its border box
+            let mut offset = taffy::Point::ZERO;
+            let mut ancestor_id = inline_root_id;
+            let containing_block = loop {
+                let ancestor = &self.nodes[ancestor_id];
+                let layout = &ancestor.unrounded_layout;
+                let is_positioned = ancestor
+                    .primary_styles()
+                    .is_some_and(|s| s.clone_position() != StyloPosition::Static);
+                if is_positioned && ancestor_id != inline_root_id {
+                    break taffy::Rect {
+                        left: layout.border.left,
+                        top: layout.border.top,
+                        right: layout.size.width - layout.border.right,
+                        bottom: layout.size.height - layout.border.bottom,
+                    };
+                }
+                offset = offset + layout.location;
+                match ancestor.layout_parent.get() {
+                    Some(parent_id) => ancestor_id = parent_id,
+                    // The initial containing block has the size of the viewport
+                    None => {
+                        let (width, height) = self.viewport.window_size;
+                        let scale = self.viewport.scale();
+                        break taffy::Rect {
+                            left: 0.0,
+                            top: 0.0,
+                            right: width as f32 / scale,
+                            bottom: height as f32 / scale,
+                        };
+                    }
+                }
+            };
+
+            // Convert the containing block into the coordinate space of the inline root
+            let containing_block = taffy::Rect {
+                left: containing_block.left - offset.x,
+                right: containing_block.right - offset.x,
+                top: containing_block.top - offset.y,
+                bottom: containing_block.bottom - offset.y,
+            };
+            self.layout_inline_absolute_box(box_id, containing_block, static_position);
+        }
+    }
+
+    /// Size and position an absolutely positioned box relative to its containing block (given as a rect
+    /// relative to the border box of the inline root). Boxes with auto insets are placed at their static position.
+    fn layout_inline_absolute_box(
+        &mut self,
+        box_id: usize,
+        containing_block: taffy::Rect<f32>,
+        static_position: taffy::Point<f32>,
+    ) {
+        let cb_size = taffy::Size {
+            width: containing_block.right - containing_block.left,
+            height: containing_block.bottom - containing_block.top,
+        };
+
+        let style = self.nodes[box_id].layout_style();
+        let margin = style
+            .margin
+            .resolve_or_zero(Some(cb_size.width), resolve_calc_value);
+        let left = style
+            .inset
+            .left
+            .maybe_resolve(cb_size.width, resolve_calc_value);
+        let right = style
+            .inset
+            .right
+            .maybe_resolve(cb_size.width, resolve_calc_value);
+        let top = style
+            .inset
+            .top
+            .maybe_resolve(cb_size.height, resolve_calc_value);
+        let bottom = style
+            .inset
+            .bottom
+            .maybe_resolve(cb_size.height, resolve_calc_value);
+
+        // Boxes with both insets set (and an auto size) are stretched to fill the containing block
+        let known_dimensions =
+            taffy::Size {
+                width: left.zip(right).filter(|_| style.size.width.is_auto()).map(
+                    |(left, right)| {
+                        (cb_size.width - left - right - margin.left - margin.right).max(0.0)
+                    },
+                ),
+                height: top.zip(bottom).filter(|_| style.size.height.is_auto()).map(
+                    |(top, bottom)| {
+                        (cb_size.height - top - bottom - margin.top - margin.bottom).max(0.0)
+                    },
+                ),
+            };
+        let available_width =
+            cb_size.width - left.unwrap_or(0.0) - right.unwrap_or(0.0) - margin.left - margin.right;
+
+        let output = self.compute_child_layout(
+            NodeId::from(box_id),
+            taffy::tree::LayoutInput {
+                known_dimensions,
+                parent_size: cb_size.map(Some),
+                available_space: taffy::Size {
+                    width: AvailableSpace::Definite(available_width.max(0.0)),
+                    height: AvailableSpace::MaxContent,
+                },
+                run_mode: taffy::RunMode::PerformLayout,
+                sizing_mode: taffy::SizingMode::InherentSize,
+                axis: taffy::RequestedAxis::Both,
+                vertical_margins_are_collapsible: taffy::Line::FALSE,
+            },
+        );
+
+        let node = &mut self.nodes[box_id];
+        let padding = node
+            .style
+            .padding
+            .resolve_or_zero(Some(cb_size.width), resolve_calc_value);
+        let border = node
+            .style
+            .border
+            .resolve_or_zero(Some(cb_size.width), resolve_calc_value);
+
+        let x = match (left, right) {
+            (Some(left), _) => containing_block.left + left + margin.left,
+            (None, Some(right)) => {
+                containing_block.right - right - margin.right - output.size.width
+            }
+            (None, None) => static_position.x + margin.left,
+        };
+        let y = match (top, bottom) {
+            (Some(top), _) => containing_block.top + top + margin.top,
+            (None, Some(bottom)) => {
+                containing_block.bottom - bottom - margin.bottom - output.size.height
+            }
+            (None, None) => static_position.y + margin.top,
+        };
+
+        let layout = &mut node.unrounded_layout;
+        layout.size = output.size;
+        layout.location = taffy::Point { x, y };
+        layout.padding = padding;
+        layout.border = border;
+        layout.margin = margin;
+    }
+}
+
+/// How a line has been changed to avoid breaking within `text-wrap-mode: nowrap` text
+#[derive(Debug, Clone, Copy, Default)]
+enum NoWrapFix {
+    #[default]
+    None,
+    /// The line breaks at an earlier space, before the `nowrap` text starts
+    BreakBefore,
+    /// The line doesn't wrap at all (until the space after the `nowrap` text ending at
+    /// `no_wrap_end` has been found)
+    Extend { no_wrap_end: usize },
+    /// The line breaks at the first space after the `nowrap` text ends
+    ExtendTo,
+}
+
+/// The changes made to a line by `break_lines`
+#[derive(Debug, Clone, Copy, Default)]
+struct LineFix {
+    /// Overrides the maximum advance of the line
+    max_advance: Option<f32>,
+    no_wrap: NoWrapFix,
+    /// Whether the maximum advance has been reduced to make room for a hyphen
+    hyphen: bool,
+}
+
+/// The maximum number of times `break_lines` breaks a layout into lines
+const MAX_LINE_BREAKING_PASSES: usize = 32;
+
+/// Break an inline layout into lines of at most `max_advance`.
+///
+/// This extends Parley's line breaking, which can only disable wrapping for an entire layout, to
+/// keep `text-wrap-mode: nowrap` text on one line, and to leave room for the hyphen drawn at the
+/// end of lines which wrap at a soft hyphen. A line which breaks in the wrong place is fixed by
+/// breaking the layout again with a different maximum advance for that line. Fixing a line
+/// doesn't change the lines before it, so each pass only checks the lines from the last fixed
+/// line onwards. Parley can't resume breaking part way through a layout, so the number of passes
+/// is capped: lines after the last fix are left as Parley broke them.
+fn break_lines(text_layout: &mut TextLayout, max_advance: Option<f32>) {
+    let no_wrap_ranges = no_wrap_ranges(&text_layout.layout);
+    let hyphen_width = text_layout
+        .hyphen
+        .as_ref()
+        .map(|hyphen| hyphen.full_width());
+    let max_advance = match max_advance {
+        Some(max_advance) if !no_wrap_ranges.is_empty() || hyphen_width.is_some() => max_advance,
+        _ => {
+            text_layout.layout.break_all_lines(max_advance);
+            return;
+        }
+    };
+
+    let mut fixes: Vec<LineFix> = Vec::new();
+    let mut first_line = 0;
+    for pass in 1..=MAX_LINE_BREAKING_PASSES {
+        let mut breaker = text_layout.layout.break_lines();
+        let mut line_idx = 0;
+        while breaker
+            .break_next(
+                fixes
+                    .get(line_idx)
+                    .and_then(|fix| fix.max_advance)
+                    .unwrap_or(max_advance),
+            )
+            .is_some()
+        {
+            line_idx += 1;
+        }
+        breaker.finish();
+
+        if pass == MAX_LINE_BREAKING_PASSES {
+            return;
+        }
+        let Some((line_idx, fix)) = next_line_fix(
+            text_layout,
+            &fixes,
+            first_line,
+            &no_wrap_ranges,
+            max_advance,
+            hyphen_width,
+        ) else {
+            return;
+        };
+        first_line = line_idx;
+        fixes.resize(line_idx + 1, LineFix::default());
+        fixes[line_idx] = fix;
+    }
+}
+
+/// Find the first line (from `first_line` onwards) which breaks in the wrong place, and how to
+/// fix it
+fn next_line_fix(
+    text_layout: &TextLayout,
+    fixes: &[LineFix],
+    first_line: usize,
+    no_wrap_ranges: &[Range<usize>],
+    max_advance: f32,
+    hyphen_width: Option<f32>,
+) -> Option<(usize, LineFix)> {
+    let text = text_layout.text.as_str();
+    for (line_idx, line) in text_layout.layout.lines().enumerate().skip(first_line) {
+        let range = line.text_range();
+        let fix = fixes.get(line_idx).copied().unwrap_or_default();
+        let break_at = |no_wrap: NoWrapFix, x: f32| {
+            // Allow for rounding errors when summing advances, so the line ends at the space
+            let max_advance = Some(x + 0.01);
+            let fix = LineFix {
+                max_advance,
+                no_wrap,
+                ..fix
+            };
+            Some((line_idx, fix))
+        };
+
+        // A line which was extended to include all of the `nowrap` text is shortened to end at the
+        // first space after it
+        if let NoWrapFix::Extend { no_wrap_end } = fix.no_wrap {
+            let trailing_space = text[..no_wrap_end]
+                .chars()
+                .next_back()
+                .filter(|&c| is_break_space(c))
+                .map(|c| no_wrap_end - c.len_utf8());
+            let space = trailing_space.or_else(|| {
+                let idx = text[no_wrap_end..].find(is_break_space)?;
+                Some(no_wrap_end + idx)
+            });
+            if let Some(x) = space.and_then(|space| line_offset(&line, space)) {
+                return break_at(NoWrapFix::ExtendTo, x);
+            }
+        }
+
+        // Only lines which wrap can break in the wrong place
+        if range.end >= text.len() || text[..range.end].ends_with('\n') {
+            continue;
+        }
+
+        if let Some(no_wrap) = no_wrap_ranges
+            .iter()
+            .find(|no_wrap| breaks_within(text, no_wrap, range.end))
+        {
+            // First try to break at a space before the `nowrap` text. If there isn't one, the
+            // line is extended to include all of the text (as if it didn't wrap at all).
+            let break_before = || {
+                let space = text[range.start..no_wrap.start]
+                    .rfind(is_break_space)
+                    .filter(|&idx| idx > 0)?;
+                line_offset(&line, range.start + space)
+            };
+            let extend = NoWrapFix::Extend {
+                no_wrap_end: no_wrap.end,
+            };
+            match fix.no_wrap {
+                NoWrapFix::None => match break_before() {
+                    Some(x) => return break_at(NoWrapFix::BreakBefore, x),
+                    None => return break_at(extend, f32::INFINITY),
+                },
+                NoWrapFix::BreakBefore => return break_at(extend, f32::INFINITY),
+                NoWrapFix::Extend { .. } | NoWrapFix::ExtendTo => continue,
+            }
+        }
+
+        let line_max_advance = fix.max_advance.unwrap_or(max_advance);
+        if let Some(hyphen_width) = hyphen_width {
+            if !fix.hyphen
+                && text_layout.breaks_at_soft_hyphen(&line)
+                && line.metrics().advance + hyphen_width > line_max_advance
+            {
+                let max_advance = Some(line_max_advance - hyphen_width);
+                return Some((
+                    line_idx,
+                    LineFix {
+                        max_advance,
+                        hyphen: true,
+                        ..fix
+                    },
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// The ranges of text which are marked as `text-wrap-mode: nowrap`. Adjacent ranges are merged.
+fn no_wrap_ranges(layout: &parley::Layout<TextBrush>) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut start = None;
+    for ibox in layout.inline_boxes() {
+        match ibox.id {
+            NO_WRAP_START_BOX_ID => start = Some(ibox.index),
+            NO_WRAP_END_BOX_ID => {
+                let Some(start) = start.take() else {
+                    continue;
+                };
+                match ranges.last_mut() {
+                    Some(prev) if prev.end == start => prev.end = ibox.index,
+                    _ => ranges.push(start..ibox.index),
+                }
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Whether a line which ends at `end` breaks within `nowrap` text. It may break at the edges of
+/// the text after a space, where the surrounding text can wrap.
+fn breaks_within(text: &str, no_wrap: &Range<usize>, end: usize) -> bool {
+    if end == no_wrap.start || end == no_wrap.end {
+        !text[..end].ends_with(is_break_space)
+    } else {
+        no_wrap.contains(&end)
+    }
+}
+
+fn is_break_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\u{200B}')
+}
+
+/// The offset from the start of a line of the cluster which starts at `text_idx` (if it is on the
+/// line)
+fn line_offset(line: &parley::layout::Line<TextBrush>, text_idx: usize) -> Option<f32> {
+    let line_start = line.metrics().offset;
+    line.items().find_map(|item| {
+        let PositionedLayoutItem::GlyphRun(glyph_run) = item else {
+            return None;
+        };
+        let mut x = glyph_run.offset();
+        for cluster in glyph_run.run().visual_clusters() {
+            if cluster.text_range().start == text_idx {
+                return Some(x - line_start);
+            }
+            x += cluster.advance();
+        }
+        None
+    })
+}
+
+/// The width of the widest line of an inline layout, including the hyphen drawn at the end of
+/// lines which wrap at a soft hyphen
+fn content_width(text_layout: &TextLayout) -> f32 {
+    let Some(hyphen) = &text_layout.hyphen else {
+        return text_layout.layout.width();
+    };
+    text_layout
+        .layout
+        .lines()
+        .filter(|line| text_layout.breaks_at_soft_hyphen(line))
+        .map(|line| line.metrics().advance + hyphen.full_width())
+        .fold(text_layout.layout.width(), f32::max)
 }
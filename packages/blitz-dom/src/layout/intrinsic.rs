@@ -0,0 +1,333 @@
+//! Resolution of the intrinsic sizing keywords (`min-content`, `max-content`, `fit-content` and
+//! `stretch`) and of `flex-basis: content`.
+//!
+//! Taffy can't represent these values, so stylo_taffy converts them to `auto`. Before a container
+//! is laid out we measure each child that uses them and write the resulting lengths into a copy
+//! of the child's Taffy style (`Node::intrinsic_style`), which is what Taffy lays the child out
+//! with. The root element is resolved against the viewport in the same way.
+
+use stylo_taffy::convert::{
+    IntrinsicSize, intrinsic_max_size, intrinsic_size, is_content_flex_basis,
+};
+use taffy::{
+    AbsoluteAxis, AvailableSpace, BoxSizing, CacheTree as _, Dimension, Display, FlexDirection,
+    LayoutInput, LayoutPartialTree as _, Line, MaybeMath as _, MaybeResolve as _, NodeId,
+    ResolveOrZero as _, RunMode, Size, SizingMode,
+};
+
+use super::resolve_calc_value;
+use crate::{BaseDocument, Node};
+
+/// The intrinsic sizing keywords used by a single node
+struct IntrinsicSizes {
+    size: Size<Option<IntrinsicSize>>,
+    min_size: Size<Option<IntrinsicSize>>,
+    max_size: Size<Option<IntrinsicSize>>,
+    content_flex_basis: bool,
+}
+
+impl IntrinsicSizes {
+    /// The intrinsic sizing keywords used by a node, or `None` if it doesn't use any
+    fn of(node: &Node, in_flex_container: bool) -> Option<Self> {
+        let styles = node.primary_styles()?;
+        let pos = styles.get_position();
+        let sizes = IntrinsicSizes {
+            size: Size {
+                width: intrinsic_size(&pos.width),
+                height: intrinsic_size(&pos.height),
+            },
+            min_size: Size {
+                width: intrinsic_size(&pos.min_width),
+                height: intrinsic_size(&pos.min_height),
+            },
+            max_size: Size {
+                width: intrinsic_max_size(&pos.max_width),
+                height: intrinsic_max_size(&pos.max_height),
+            },
+            content_flex_basis: in_flex_container && is_content_flex_basis(&pos.flex_basis),
+        };
+        let has_keywords = [sizes.size, sizes.min_size, sizes.max_size]
+            .iter()
+            .any(|s| s.width.is_some() || s.height.is_some())
+            || sizes.content_flex_basis;
+        has_keywords.then_some(sizes)
+    }
+}
+
+impl BaseDocument {
+    /// Resolve the intrinsic sizing keywords of the children of `node_id` against the inner size
+    /// that the node is about to be laid out with.
+    pub(crate) fn resolve_intrinsic_child_sizes(&mut self, node_id: usize, inputs: LayoutInput) {
+        let node = &self.nodes[node_id];
+
+        // Table children are laid out using styles stored in the table's TableContext
+        if node.is_table_root {
+            return;
+        }
+
+        let style = &node.style;
+        let is_flex_container = style.display == Display::Flex;
+
+        let Some(layout_children) = node.layout_children.borrow().clone() else {
+            return;
+        };
+        let mut children: Vec<(usize, IntrinsicSizes)> = Vec::new();
+        for child_id in layout_children {
+            match IntrinsicSizes::of(&self.nodes[child_id], is_flex_container) {
+                Some(sizes) => children.push((child_id, sizes)),
+                None => self.nodes[child_id].intrinsic_style = None,
+            }
+        }
+        if children.is_empty() {
+            return;
+        }
+
+        // Compute the size of the container's content box (where known)
+        let style = &self.nodes[node_id].style;
+        let padding_border = style
+            .padding
+            .resolve_or_zero(inputs.parent_size.width, resolve_calc_value)
+            + style
+                .border
+                .resolve_or_zero(inputs.parent_size.width, resolve_calc_value);
+        let pb_sum = Size {
+            width: padding_border.horizontal_components().sum(),
+            height: padding_border.vertical_components().sum(),
+        };
+        let style_size = style
+            .size
+            .maybe_resolve(inputs.parent_size, resolve_calc_value)
+            .maybe_add(match style.box_sizing {
+                BoxSizing::BorderBox => Size::ZERO,
+                BoxSizing::ContentBox => pb_sum,
+            });
+        let inner_size = Size {
+            width: inputs
+                .known_dimensions
+                .width
+                .or(style_size.width)
+                .or(inputs.available_space.width.into_option()),
+            height: inputs.known_dimensions.height.or(style_size.height),
+        }
+        .maybe_sub(pb_sum)
+        .map(|size| size.map(|size| size.max(0.0)));
+
+        let flex_main_axis = match style.flex_direction {
+            FlexDirection::Row | FlexDirection::RowReverse => AbsoluteAxis::Horizontal,
+            FlexDirection::Column | FlexDirection::ColumnReverse => AbsoluteAxis::Vertical,
+        };
+
+        for (child_id, sizes) in children {
+            self.resolve_intrinsic_sizes(child_id, sizes, inner_size, flex_main_axis);
+        }
+    }
+
+    /// Resolve the intrinsic sizing keywords of the root element against the viewport
+    pub(crate) fn resolve_root_intrinsic_sizes(
+        &mut self,
+        root_id: usize,
+        available_space: Size<AvailableSpace>,
+    ) {
+        match IntrinsicSizes::of(&self.nodes[root_id], false) {
+            Some(sizes) => {
+                let inner_size = available_space.into_options();
+                self.resolve_intrinsic_sizes(root_id, sizes, inner_size, AbsoluteAxis::Horizontal);
+            }
+            None => self.nodes[root_id].intrinsic_style = None,
+        }
+    }
+
+    /// Resolve the intrinsic sizing keywords of a node against the content box size of its
+    /// containing block, and store the resulting style in `Node::intrinsic_style`
+    fn resolve_intrinsic_sizes(
+        &mut self,
+        node_id: usize,
+        sizes: IntrinsicSizes,
+        inner_size: Size<Option<f32>>,
+        flex_main_axis: AbsoluteAxis,
+    ) {
+        // Measure the node with the sizes from its own style
+        self.nodes[node_id].intrinsic_style = None;
+
+        let node_style = &self.nodes[node_id].style;
+        let margin = node_style
+            .margin
+            .resolve_or_zero(inner_size.width, resolve_calc_value);
+        let margin_sum = Size {
+            width: margin.horizontal_components().sum(),
+            height: margin.vertical_components().sum(),
+        };
+        let box_sizing_adjustment = if node_style.box_sizing == BoxSizing::ContentBox {
+            let padding_border = node_style
+                .padding
+                .resolve_or_zero(inner_size.width, resolve_calc_value)
+                + node_style
+                    .border
+                    .resolve_or_zero(inner_size.width, resolve_calc_value);
+            Size {
+                width: padding_border.horizontal_components().sum(),
+                height: padding_border.vertical_components().sum(),
+            }
+        } else {
+            Size::ZERO
+        };
+
+        let mut resolver = IntrinsicSizeResolver {
+            doc: self,
+            node_id,
+            inner_size,
+            margin_sum,
+            box_sizing_adjustment,
+            known_width: None,
+        };
+
+        // Resolve the inline axis first, as the block size of the content depends on it
+        let size_width = resolver.resolve(sizes.size.width, AbsoluteAxis::Horizontal);
+        let min_width = resolver.resolve(sizes.min_size.width, AbsoluteAxis::Horizontal);
+        let max_width = resolver.resolve(sizes.max_size.width, AbsoluteAxis::Horizontal);
+        resolver.known_width = size_width.flatten().or_else(|| {
+            resolver.doc.nodes[node_id]
+                .style
+                .size
+                .width
+                .maybe_resolve(inner_size.width, resolve_calc_value)
+                .maybe_add(box_sizing_adjustment.width)
+        });
+        let size_height = resolver.resolve(sizes.size.height, AbsoluteAxis::Vertical);
+        let min_height = resolver.resolve(sizes.min_size.height, AbsoluteAxis::Vertical);
+        let max_height = resolver.resolve(sizes.max_size.height, AbsoluteAxis::Vertical);
+        let flex_basis = sizes
+            .content_flex_basis
+            .then(|| resolver.measure(flex_main_axis, AvailableSpace::MaxContent));
+
+        // Measuring the node under a content-based sizing mode populates its cache with
+        // results which ignore its own size styles.
+        self.cache_clear(NodeId::from(node_id));
+
+        let to_dimension = |size: Option<f32>, adjustment: f32| {
+            size.map(|size| Dimension::length((size - adjustment).max(0.0)))
+                .unwrap_or(Dimension::auto())
+        };
+        let mut style = self.nodes[node_id].style.clone();
+        let adjustment = box_sizing_adjustment;
+        if let Some(size) = size_width {
+            style.size.width = to_dimension(size, adjustment.width);
+        }
+        if let Some(size) = size_height {
+            style.size.height = to_dimension(size, adjustment.height);
+        }
+        if let Some(size) = min_width {
+            style.min_size.width = to_dimension(size, adjustment.width);
+        }
+        if let Some(size) = min_height {
+            style.min_size.height = to_dimension(size, adjustment.height);
+        }
+        if let Some(size) = max_width {
+            style.max_size.width = to_dimension(size, adjustment.width);
+        }
+        if let Some(size) = max_height {
+            style.max_size.height = to_dimension(size, adjustment.height);
+        }
+        if let Some(basis) = flex_basis {
+            style.flex_basis = to_dimension(Some(basis), adjustment.get_abs(flex_main_axis));
+        }
+        self.nodes[node_id].intrinsic_style = Some(Box::new(style));
+    }
+}
+
+/// Resolves the intrinsic sizing keywords of a single node to border-box lengths
+struct IntrinsicSizeResolver<'a> {
+    doc: &'a mut BaseDocument,
+    node_id: usize,
+    /// The content box size of the containing block
+    inner_size: Size<Option<f32>>,
+    margin_sum: Size<f32>,
+    box_sizing_adjustment: Size<f32>,
+    /// The border-box width of the node (if definite), used when measuring its block size
+    known_width: Option<f32>,
+}
+
+impl IntrinsicSizeResolver<'_> {
+    /// Returns `None` if the value doesn't use a keyword, `Some(None)` if the keyword resolves
+    /// to `auto` and `Some(Some(size))` if it resolves to a length.
+    fn resolve(
+        &mut self,
+        keyword: Option<IntrinsicSize>,
+        axis: AbsoluteAxis,
+    ) -> Option<Option<f32>> {
+        let keyword = keyword?;
+        let stretch_fit = self
+            .inner_size
+            .get_abs(axis)
+            .map(|size| (size - self.margin_sum.get_abs(axis)).max(0.0));
+
+        // The min-content and max-content block sizes of a box are both its content-based size
+        if axis == AbsoluteAxis::Vertical {
+            return Some(match keyword {
+                IntrinsicSize::Stretch => stretch_fit,
+                _ => Some(self.measure(axis, AvailableSpace::MaxContent)),
+            });
+        }
+
+        Some(match keyword {
+            IntrinsicSize::MinContent => Some(self.measure(axis, AvailableSpace::MinContent)),
+            IntrinsicSize::MaxContent => Some(self.measure(axis, AvailableSpace::MaxContent)),
+            IntrinsicSize::FitContent(limit) => {
+                let limit = match limit {
+                    Some(limit) => limit
+                        .maybe_resolve(self.inner_size.width, resolve_calc_value)
+                        .maybe_add(self.box_sizing_adjustment.width),
+                    None => stretch_fit,
+                };
+                let max_content = self.measure(axis, AvailableSpace::MaxContent);
+                Some(match limit {
+                    Some(limit) => {
+                        let min_content = self.measure(axis, AvailableSpace::MinContent);
+                        max_content.min(min_content.max(limit))
+                    }
+                    None => max_content,
+                })
+            }
+            IntrinsicSize::Stretch => stretch_fit,
+        })
+    }
+
+    /// Measure the content-based border-box size of the node in the given axis
+    fn measure(&mut self, axis: AbsoluteAxis, constraint: AvailableSpace) -> f32 {
+        let (known_dimensions, available_space) = match axis {
+            AbsoluteAxis::Horizontal => (
+                Size::NONE,
+                Size {
+                    width: constraint,
+                    height: self.inner_size.height.into(),
+                },
+            ),
+            AbsoluteAxis::Vertical => (
+                Size {
+                    width: self.known_width,
+                    height: None,
+                },
+                Size {
+                    width: self.inner_size.width.into(),
+                    height: constraint,
+                },
+            ),
+        };
+
+        self.doc
+            .compute_child_layout(
+                NodeId::from(self.node_id),
+                LayoutInput {
+                    known_dimensions,
+                    parent_size: self.inner_size,
+                    available_space,
+                    sizing_mode: SizingMode::ContentSize,
+                    axis: axis.into(),
+                    run_mode: RunMode::ComputeSize,
+                    vertical_margins_are_collapsible: Line::FALSE,
+                },
+            )
+            .size
+            .get_abs(axis)
+    }
+}
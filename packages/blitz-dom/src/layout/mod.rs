@@ -4,11 +4,11 @@
 //! However, in Blitz, we do a style pass then a layout pass.
 //! This is slower, yes, but happens fast enough that it's not a huge issue.
 
+use crate::MediaControls;
 use crate::node::{ImageData, NodeData, NodeSpecificData};
 use crate::{document::BaseDocument, node::Node};
 use markup5ever::local_name;
 use std::cell::Ref;
-use std::sync::Arc;
 use style::values::computed::CSSPixelLength;
 use style::values::computed::length_percentage::CalcLengthPercentage;
 use taffy::{
@@ -18,12 +18,14 @@ use taffy::{
 };
 
 pub(crate) mod construct;
+pub(crate) mod counters;
 pub(crate) mod inline;
+pub(crate) mod intrinsic;
 pub(crate) mod replaced;
 pub(crate) mod table;
 
 use self::replaced::{ReplacedContext, replaced_measure_function};
-use self::table::TableTreeWrapper;
+use self::table::compute_table_layout;
 
 pub(crate) fn resolve_calc_value(calc_ptr: *const (), parent_size: f32) -> f32 {
     let calc = unsafe { &*(calc_ptr as *const CalcLengthPercentage) };
@@ -78,7 +80,7 @@ impl LayoutPartialTree for BaseDocument {
         Self: 'a;
 
     fn get_core_container_style(&self, node_id: NodeId) -> &Style {
-        &self.node_from_id(node_id).style
+        self.node_from_id(node_id).layout_style()
     }
 
     fn set_unrounded_layout(&mut self, node_id: NodeId, layout: &Layout) {
@@ -95,6 +97,8 @@ impl LayoutPartialTree for BaseDocument {
         inputs: taffy::tree::LayoutInput,
     ) -> taffy::tree::LayoutOutput {
         compute_cached_layout(self, node_id, inputs, |tree, node_id, inputs| {
+            tree.resolve_intrinsic_child_sizes(node_id.into(), inputs);
+
             let node = &mut tree.nodes[node_id.into()];
 
             let font_styles = node.primary_styles().map(|style| {
@@ -111,6 +115,7 @@ impl LayoutPartialTree for BaseDocument {
             });
             let font_size = font_styles.map(|s| s.0);
             let resolved_line_height = font_styles.map(|s| s.1);
+            let layout_style = node.intrinsic_style.as_deref().unwrap_or(&node.style);
 
             match &mut node.data {
                 NodeData::Text(data) => {
@@ -124,7 +129,7 @@ impl LayoutPartialTree for BaseDocument {
                     taffy::LayoutOutput::HIDDEN
                     // unreachable!();
 
-                    // compute_leaf_layout(inputs, &node.style, |known_dimensions, available_space| {
+                    // compute_leaf_layout(inputs, layout_style, |known_dimensions, available_space| {
                     //     let context = TextContext {
                     //         text_content: &data.content.trim(),
                     //         writing_mode: WritingMode::Horizontal,
@@ -161,7 +166,7 @@ impl LayoutPartialTree for BaseDocument {
 
                         return compute_leaf_layout(
                             inputs,
-                            &node.style,
+                            layout_style,
                             resolve_calc_value,
                             |_known_size, _available_space| taffy::Size {
                                 width: cols
@@ -172,6 +177,21 @@ impl LayoutPartialTree for BaseDocument {
                         );
                     }
 
+                    // Progress bars and meters have a default size relative to the font size
+                    if matches!(element_data.name.local.as_ref(), "progress" | "meter") {
+                        let em = font_size.unwrap_or(16.0);
+                        let width = match element_data.name.local.as_ref() {
+                            "progress" => 10.0 * em,
+                            _ => 5.0 * em,
+                        };
+                        return compute_leaf_layout(
+                            inputs,
+                            layout_style,
+                            resolve_calc_value,
+                            |_known_size, _available_space| taffy::Size { width, height: em },
+                        );
+                    }
+
                     if *element_data.name.local == *"input" {
                         match element_data.attr(local_name!("type")) {
                             // if the input type is hidden, hide it
@@ -182,14 +202,14 @@ impl LayoutPartialTree for BaseDocument {
                             Some("checkbox") => {
                                 return compute_leaf_layout(
                                     inputs,
-                                    &node.style,
+                                    layout_style,
                                     resolve_calc_value,
                                     |_known_size, _available_space| {
-                                        let width = node.style.size.width.resolve_or_zero(
+                                        let width = layout_style.size.width.resolve_or_zero(
                                             inputs.parent_size.width,
                                             resolve_calc_value,
                                         );
-                                        let height = node.style.size.height.resolve_or_zero(
+                                        let height = layout_style.size.height.resolve_or_zero(
                                             inputs.parent_size.height,
                                             resolve_calc_value,
                                         );
@@ -204,7 +224,7 @@ impl LayoutPartialTree for BaseDocument {
                             None | Some("text" | "password" | "email") => {
                                 return compute_leaf_layout(
                                     inputs,
-                                    &node.style,
+                                    layout_style,
                                     resolve_calc_value,
                                     |_known_size, _available_space| taffy::Size {
                                         width: 300.0,
@@ -216,20 +236,42 @@ impl LayoutPartialTree for BaseDocument {
                         }
                     }
 
+                    // Audio elements have a default size for their controls
+                    if *element_data.name.local == *"audio" {
+                        return compute_leaf_layout(
+                            inputs,
+                            layout_style,
+                            resolve_calc_value,
+                            |_known_size, _available_space| taffy::Size {
+                                width: 300.0,
+                                height: MediaControls::HEIGHT as f32,
+                            },
+                        );
+                    }
+
                     if *element_data.name.local == *"img"
+                        || *element_data.name.local == *"iframe"
+                        || *element_data.name.local == *"video"
+                        || *element_data.name.local == *"canvas"
                         || (cfg!(feature = "svg") && *element_data.name.local == *"svg")
                     {
                         // Get width and height attributes on image element
                         //
                         // TODO: smarter sizing using these (depending on object-fit, they shouldn't
                         // necessarily just override the native size)
-                        let attr_size = taffy::Size {
-                            width: element_data
-                                .attr(local_name!("width"))
-                                .and_then(|val| val.parse::<f32>().ok()),
-                            height: element_data
-                                .attr(local_name!("height"))
-                                .and_then(|val| val.parse::<f32>().ok()),
+                        //
+                        // The width and height attributes of canvases are the size of their
+                        // coordinate space, which is only used as their natural size.
+                        let attr_size = match *element_data.name.local == *"canvas" {
+                            true => taffy::Size::NONE,
+                            false => taffy::Size {
+                                width: element_data
+                                    .attr(local_name!("width"))
+                                    .and_then(|val| val.parse::<f32>().ok()),
+                                height: element_data
+                                    .attr(local_name!("height"))
+                                    .and_then(|val| val.parse::<f32>().ok()),
+                            },
                         };
 
                         // Get image's native size
@@ -239,6 +281,13 @@ impl LayoutPartialTree for BaseDocument {
                                     width: image.width as f32,
                                     height: image.height as f32,
                                 },
+                                ImageData::Animated(animation) => {
+                                    let image = &animation.current_frame().image;
+                                    taffy::Size {
+                                        width: image.width as f32,
+                                        height: image.height as f32,
+                                    }
+                                }
                                 #[cfg(feature = "svg")]
                                 ImageData::Svg(svg) => {
                                     let size = svg.size();
@@ -249,12 +298,65 @@ impl LayoutPartialTree for BaseDocument {
                                 }
                                 ImageData::None => taffy::Size::ZERO,
                             },
+                            // The default object size (iframes and videos without a poster)
+                            NodeSpecificData::None
+                                if matches!(
+                                    element_data.name.local.as_ref(),
+                                    "iframe" | "video"
+                                ) =>
+                            {
+                                taffy::Size {
+                                    width: 300.0,
+                                    height: 150.0,
+                                }
+                            }
+                            // Canvases are sized by their coordinate space
+                            NodeSpecificData::None | NodeSpecificData::Canvas(_)
+                                if *element_data.name.local == *"canvas" =>
+                            {
+                                let (width, height) = element_data.canvas_size();
+                                taffy::Size {
+                                    width: width as f32,
+                                    height: height as f32,
+                                }
+                            }
                             NodeSpecificData::None => taffy::Size::ZERO,
                             _ => unreachable!(),
                         };
 
+                        // Images selected from a srcset are scaled by their pixel density
+                        let density = element_data
+                            .image_source
+                            .as_ref()
+                            .map_or(1.0, |source| source.density);
+                        let inherent_size = inherent_size.map(|size| size / density);
+
+                        // Videos are sized by their video once it has loaded (and by their poster
+                        // before then)
+                        let video_size = tree
+                            .media_elements
+                            .get(&usize::from(node_id))
+                            .and_then(|media| media.video_size());
+                        let inherent_size = match video_size {
+                            Some((width, height)) => taffy::Size {
+                                width: width as f32,
+                                height: height as f32,
+                            },
+                            None => inherent_size,
+                        };
+
+                        // Embedded documents (and videos without a video or poster) don't have a
+                        // natural aspect ratio
+                        let has_default_object_size = *element_data.name.local == *"iframe"
+                            || (*element_data.name.local == *"video"
+                                && video_size.is_none()
+                                && element_data.raster_image_data().is_none());
+                        let inherent_aspect_ratio = (!has_default_object_size)
+                            .then(|| inherent_size.width / inherent_size.height);
+
                         let replaced_context = ReplacedContext {
                             inherent_size,
+                            inherent_aspect_ratio,
                             attr_size,
                         };
 
@@ -262,7 +364,7 @@ impl LayoutPartialTree for BaseDocument {
                             inputs.known_dimensions,
                             inputs.parent_size,
                             &replaced_context,
-                            &node.style,
+                            layout_style,
                             false,
                         );
 
@@ -277,21 +379,7 @@ impl LayoutPartialTree for BaseDocument {
                     }
 
                     if node.is_table_root {
-                        let NodeSpecificData::TableRoot(context) = &tree.nodes[node_id.into()]
-                            .data
-                            .downcast_element()
-                            .unwrap()
-                            .node_specific_data
-                        else {
-                            panic!("Node marked as table root but doesn't have TableContext");
-                        };
-                        let context = Arc::clone(context);
-
-                        let mut table_wrapper = TableTreeWrapper {
-                            doc: tree,
-                            ctx: context,
-                        };
-                        return compute_grid_layout(&mut table_wrapper, node_id, inputs);
+                        return compute_table_layout(tree, node_id, inputs);
                     }
 
                     if node.is_inline_root {
@@ -431,7 +519,10 @@ impl PrintTree for BaseDocument {
             // NodeData::Doctype { .. } => return "DOCTYPE",
             NodeData::Text { .. } => node.node_debug_str().leak(),
             NodeData::Comment => "COMMENT",
+            NodeData::ProcessingInstruction { .. } => "PROCESSING INSTRUCTION",
             NodeData::AnonymousBlock(_) => "ANONYMOUS BLOCK",
+            NodeData::ShadowRoot(_) => "SHADOW ROOT",
+            NodeData::DocumentFragment => "DOCUMENT FRAGMENT",
             NodeData::Element(_) => {
                 let display = match style.display {
                     Display::Flex => match style.flex_direction {
@@ -5,6 +5,8 @@ use crate::layout::resolve_calc_value;
 #[derive(Debug, Clone, Copy)]
 pub struct ReplacedContext {
     pub inherent_size: taffy::Size<f32>,
+    /// The natural aspect ratio of the content (if it has one). Images have one, iframes don't.
+    pub inherent_aspect_ratio: Option<f32>,
     pub attr_size: taffy::Size<Option<f32>>,
 }
 
@@ -47,15 +49,13 @@ pub fn replaced_measure_function(
     };
 
     // Use aspect_ratio from style, fall back to inherent aspect ratio
-    let s_aspect_ratio = style.aspect_ratio;
-    let aspect_ratio = s_aspect_ratio.unwrap_or_else(|| inherent_size.width / inherent_size.height);
-    let inv_aspect_ratio = 1.0 / aspect_ratio;
+    let aspect_ratio = style.aspect_ratio.or(image_context.inherent_aspect_ratio);
 
     // Resolve sizes
     let style_size = style
         .size
         .maybe_resolve(parent_size, resolve_calc_value)
-        .maybe_apply_aspect_ratio(Some(aspect_ratio))
+        .maybe_apply_aspect_ratio(aspect_ratio)
         .maybe_sub(box_sizing_adjustment);
     let min_size = style
         .min_size
@@ -68,37 +68,48 @@ pub fn replaced_measure_function(
         .maybe_sub(box_sizing_adjustment);
     let attr_size = image_context.attr_size;
 
+    // Used for axes which aren't determined by the aspect ratio (when there is no aspect ratio)
+    let fallback_size = style_size
+        .zip_map(attr_size, Option::or)
+        .zip_map(inherent_size, |s, inherent| s.unwrap_or(inherent));
+
     let unclamped_size = 'size: {
         if known_dimensions.width.is_some() | known_dimensions.height.is_some() {
             break 'size known_dimensions
-                .maybe_apply_aspect_ratio(Some(aspect_ratio))
-                .map(|s| s.unwrap());
+                .maybe_apply_aspect_ratio(aspect_ratio)
+                .zip_map(fallback_size, |s, fallback| s.unwrap_or(fallback));
         }
 
         if style_size.width.is_some() | style_size.height.is_some() {
             break 'size style_size
                 // .maybe_clamp(min_size, max_size)
-                .maybe_apply_aspect_ratio(Some(aspect_ratio))
-                .map(|s| s.unwrap());
+                .maybe_apply_aspect_ratio(aspect_ratio)
+                .zip_map(fallback_size, |s, fallback| s.unwrap_or(fallback));
         }
 
         if attr_size.width.is_some() | attr_size.height.is_some() {
             break 'size attr_size
                 // .maybe_clamp(min_size, max_size)
-                .maybe_apply_aspect_ratio(Some(aspect_ratio))
-                .map(|s| s.unwrap());
+                .maybe_apply_aspect_ratio(aspect_ratio)
+                .zip_map(fallback_size, |s, fallback| s.unwrap_or(fallback));
         }
 
         inherent_size
             // .maybe_clamp(min_size, max_size)
             .map(Some)
-            .maybe_apply_aspect_ratio(Some(aspect_ratio))
-            .map(|s| s.unwrap())
+            .maybe_apply_aspect_ratio(aspect_ratio)
+            .zip_map(fallback_size, |s, fallback| s.unwrap_or(fallback))
     };
 
     // Floor size at zero
     let size = unclamped_size.map(|s| s.max(0.0));
 
+    // Without an aspect ratio each axis can simply be clamped independently
+    let Some(aspect_ratio) = aspect_ratio else {
+        return size.maybe_clamp(min_size, max_size) + pb_sum;
+    };
+    let inv_aspect_ratio = 1.0 / aspect_ratio;
+
     // Violations
     let width_violation = if size.width < min_size.width.unwrap_or(0.0) {
         Violation::Min
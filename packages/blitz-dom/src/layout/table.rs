@@ -1,26 +1,90 @@
+use std::collections::HashMap;
 use std::{ops::Range, sync::Arc};
 
 use markup5ever::local_name;
+use style::computed_values::border_collapse::T as BorderCollapse;
 use style::computed_values::table_layout::T as TableLayout;
-use style::values::specified::box_::DisplayInside;
+use style::properties::ComputedValues;
+use style::values::computed::table::CaptionSide;
+use style::values::generics::box_::{GenericVerticalAlign, VerticalAlignKeyword};
+use style::values::specified::BorderStyle;
+use style::values::specified::box_::{DisplayInside, DisplayOutside};
 use taffy::{
-    Dimension, LayoutPartialTree as _, NonRepeatedTrackSizingFunction, ResolveOrZero,
-    compute_leaf_layout, style_helpers,
+    CompactLength, Dimension, LayoutPartialTree as _, NonRepeatedTrackSizingFunction,
+    ResolveOrZero, compute_grid_layout, compute_leaf_layout, style_helpers,
 };
 
 use crate::BaseDocument;
+use crate::node::NodeSpecificData;
+use crate::util::{Color, ToColorColor};
 
 use super::resolve_calc_value;
 
+/// The maximum value of the `colspan` attribute (as per the HTML spec)
+const MAX_COLSPAN: u16 = 1000;
+/// The maximum value of the `rowspan` attribute (as per the HTML spec)
+const MAX_ROWSPAN: u16 = 65534;
+
 pub struct TableTreeWrapper<'doc> {
     pub(crate) doc: &'doc mut BaseDocument,
     pub(crate) ctx: Arc<TableContext>,
+    /// The offset of the content of each vertically aligned cell (keyed by item index)
+    cell_offsets: HashMap<usize, f32>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TableContext {
     style: taffy::Style,
     items: Vec<TableItem>,
+    /// The table's captions, which are laid out above or below the table box
+    captions: Vec<(usize, CaptionSide)>,
+    /// The resolved borders of each cell (keyed by node id) in the collapsing border model
+    collapsed_borders: HashMap<usize, CollapsedBorders>,
+    border_collapse: bool,
+}
+
+impl TableContext {
+    /// Whether the table uses the collapsing border model (`border-collapse: collapse`)
+    pub fn is_border_collapse(&self) -> bool {
+        self.border_collapse
+    }
+
+    /// Copy the table's border and padding (which are adjusted for `border-spacing` and collapsed borders)
+    /// to the style used by the table's parent when laying out the table.
+    pub(crate) fn apply_box_style(&self, style: &mut taffy::Style) {
+        style.border = self.style.border;
+        style.padding = self.style.padding;
+    }
+
+    /// The resolved borders of a cell in a table using the collapsing border model.
+    ///
+    /// Collapsed borders are painted by the cells: each edge is centered on the cell's border-box edge.
+    pub fn collapsed_borders(&self, node_id: usize) -> Option<&CollapsedBorders> {
+        self.collapsed_borders.get(&node_id)
+    }
+
+    /// In the collapsing border model cells only own half of each of their borders. Copy these to the
+    /// styles used when laying out the cells.
+    pub(crate) fn apply_cell_box_styles(&self, doc: &mut BaseDocument) {
+        for (&node_id, borders) in &self.collapsed_borders {
+            doc.nodes[node_id].style.border = borders.half_widths();
+        }
+    }
+
+    /// The (margin box) heights of the captions above and below the table box, which the table's
+    /// background and border are drawn around
+    pub fn caption_heights(&self, doc: &BaseDocument) -> (f32, f32) {
+        let mut heights = (0.0, 0.0);
+        for &(node_id, side) in &self.captions {
+            let layout = &doc.nodes[node_id].final_layout;
+            let height = layout.size.height + layout.margin.top + layout.margin.bottom;
+            match side {
+                CaptionSide::Top => heights.0 += height,
+                CaptionSide::Bottom => heights.1 += height,
+            }
+        }
+        heights
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -34,16 +98,312 @@ pub struct TableItem {
     kind: TableItemKind,
     node_id: usize,
     style: taffy::Style,
+    /// Zero-based row index
+    row: usize,
+    /// Zero-based column index
+    col: usize,
+    row_span: u16,
+    col_span: u16,
+    vertical_align: CellVerticalAlign,
+}
+
+/// Vertical alignment of the content within a table cell
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum CellVerticalAlign {
+    // TODO: implement baseline alignment across the cells of a row (currently treated as top)
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl CellVerticalAlign {
+    fn from_style(style: &ComputedValues) -> Self {
+        match style.clone_vertical_align() {
+            GenericVerticalAlign::Keyword(VerticalAlignKeyword::Middle) => Self::Middle,
+            GenericVerticalAlign::Keyword(
+                VerticalAlignKeyword::Bottom | VerticalAlignKeyword::TextBottom,
+            ) => Self::Bottom,
+            _ => Self::Top,
+        }
+    }
+}
+
+/// A single resolved border edge in a table using the collapsing border model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollapsedBorder {
+    pub width: f32,
+    pub style: BorderStyle,
+    pub color: Color,
+}
+
+impl CollapsedBorder {
+    const NONE: Self = Self {
+        width: 0.0,
+        style: BorderStyle::None,
+        color: Color::TRANSPARENT,
+    };
+
+    fn is_visible(&self) -> bool {
+        !self.style.none_or_hidden() && self.width > 0.0
+    }
+}
+
+/// The four resolved border edges of a table cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollapsedBorders {
+    pub top: CollapsedBorder,
+    pub right: CollapsedBorder,
+    pub bottom: CollapsedBorder,
+    pub left: CollapsedBorder,
+}
+
+impl CollapsedBorders {
+    /// In the collapsing border model each side of a shared border is owned by the cell on that side
+    fn half_widths(&self) -> taffy::Rect<taffy::LengthPercentage> {
+        let half = |border: &CollapsedBorder| {
+            let width = if border.is_visible() {
+                border.width
+            } else {
+                0.0
+            };
+            taffy::LengthPercentage::length(width / 2.0)
+        };
+        taffy::Rect {
+            top: half(&self.top),
+            right: half(&self.right),
+            bottom: half(&self.bottom),
+            left: half(&self.left),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Side {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+fn style_borders(style: &ComputedValues) -> [CollapsedBorder; 4] {
+    let border = style.get_border();
+    let current_color = style.clone_color();
+    let edge =
+        |width: f32, style: BorderStyle, color: &style::values::computed::Color| CollapsedBorder {
+            width,
+            style,
+            color: color.resolve_to_absolute(&current_color).as_color_color(),
+        };
+    [
+        edge(
+            border.border_top_width.to_f32_px(),
+            border.border_top_style,
+            &border.border_top_color,
+        ),
+        edge(
+            border.border_right_width.to_f32_px(),
+            border.border_right_style,
+            &border.border_right_color,
+        ),
+        edge(
+            border.border_bottom_width.to_f32_px(),
+            border.border_bottom_style,
+            &border.border_bottom_color,
+        ),
+        edge(
+            border.border_left_width.to_f32_px(),
+            border.border_left_style,
+            &border.border_left_color,
+        ),
+    ]
+}
+
+/// Border conflict resolution for the collapsing border model.
+///
+/// Candidates must be passed in order of decreasing priority (cell, row, row group, column,
+/// column group, table) as the first of two otherwise equal borders wins.
+///
+/// See: <https://www.w3.org/TR/CSS21/tables.html#border-conflict-resolution>
+fn resolve_collapsed_border(candidates: &[CollapsedBorder]) -> CollapsedBorder {
+    // Borders with `border-style: hidden` take precedence over all other conflicting borders
+    if candidates
+        .iter()
+        .any(|border| border.style == BorderStyle::Hidden)
+    {
+        return CollapsedBorder::NONE;
+    }
+
+    let mut winner = CollapsedBorder::NONE;
+    for border in candidates.iter().filter(|border| border.is_visible()) {
+        // BorderStyle's declaration order matches the priority of the styles (inset lowest, double highest)
+        let wins = !winner.is_visible()
+            || border.width > winner.width
+            || (border.width == winner.width && border.style as u8 > winner.style as u8);
+        if wins {
+            winner = *border;
+        }
+    }
+    winner
+}
+
+#[derive(Debug, Clone)]
+struct TableColumn {
+    /// Width from `<col>` or `<colgroup>` elements
+    width: Dimension,
+    /// Width from the cells in the column
+    cell_width: Dimension,
+    node_id: Option<usize>,
+    group_id: Option<usize>,
+}
+
+impl Default for TableColumn {
+    fn default() -> Self {
+        Self {
+            width: Dimension::auto(),
+            cell_width: Dimension::auto(),
+            node_id: None,
+            group_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TableRow {
+    node_id: Option<usize>,
+    group_id: Option<usize>,
+}
+
+/// State accumulated while walking the descendants of a table
+struct TableBuilder {
+    is_fixed: bool,
+    items: Vec<TableItem>,
+    captions: Vec<(usize, CaptionSide)>,
+    columns: Vec<TableColumn>,
+    /// For each column, the number of rows (including the current row) that are occupied by cells with a `rowspan` from a previous row
+    occupied: Vec<u16>,
+    rows: Vec<TableRow>,
+    current_group: Option<usize>,
+    /// Index of the first row in the current row group
+    group_start_row: usize,
+    /// Index of the first item in the current row group
+    group_start_item: usize,
+    col: usize,
+}
+
+impl TableBuilder {
+    fn start_row(&mut self, node_id: Option<usize>) {
+        self.rows.push(TableRow {
+            node_id,
+            group_id: self.current_group,
+        });
+        self.col = 0;
+        for remaining in self.occupied.iter_mut() {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+
+    fn current_row(&self) -> usize {
+        self.rows.len() - 1
+    }
+
+    fn start_row_group(&mut self, group_id: Option<usize>) {
+        self.end_row_group();
+        self.current_group = group_id;
+    }
+
+    /// Row spans may not extend beyond the end of their row group. `rowspan="0"` extends to the end of the group.
+    fn end_row_group(&mut self) {
+        let group_end = self.rows.len();
+        for item in &mut self.items[self.group_start_item..] {
+            if item.kind == TableItemKind::Cell {
+                let max_span = group_end.saturating_sub(item.row).max(1);
+                if item.row_span == 0 || usize::from(item.row_span) > max_span {
+                    // Row spans are at most MAX_ROWSPAN, so a smaller span always fits in a u16
+                    item.row_span = u16::try_from(max_span).unwrap_or(MAX_ROWSPAN);
+                }
+            }
+        }
+        self.occupied
+            .iter_mut()
+            .for_each(|remaining| *remaining = 0);
+        self.current_group = None;
+        self.group_start_row = group_end;
+        self.group_start_item = self.items.len();
+    }
+
+    /// Find the first column from the current position that is not occupied by a cell from a previous row
+    fn next_free_column(&mut self) -> usize {
+        while self
+            .occupied
+            .get(self.col)
+            .is_some_and(|remaining| *remaining > 0)
+        {
+            self.col += 1;
+        }
+        self.col
+    }
+
+    fn ensure_columns(&mut self, count: usize) {
+        if self.columns.len() < count {
+            self.columns.resize_with(count, TableColumn::default);
+        }
+        if self.occupied.len() < count {
+            self.occupied.resize(count, 0);
+        }
+    }
+}
+
+/// Merge a width specified on a cell into the width of a column (percentages beat lengths beat auto)
+fn merge_column_width(current: Dimension, new: Dimension) -> Dimension {
+    match (current.tag(), new.tag()) {
+        (_, CompactLength::AUTO_TAG) => current,
+        (CompactLength::AUTO_TAG, _) => new,
+        (CompactLength::PERCENT_TAG, CompactLength::LENGTH_TAG) => current,
+        (CompactLength::LENGTH_TAG, CompactLength::PERCENT_TAG) => new,
+        (CompactLength::LENGTH_TAG, CompactLength::LENGTH_TAG) => {
+            style_helpers::length(current.value().max(new.value()))
+        }
+        (CompactLength::PERCENT_TAG, CompactLength::PERCENT_TAG) => {
+            style_helpers::percent(current.value().max(new.value()))
+        }
+        _ => current,
+    }
+}
+
+/// The width a cell contributes to its column(s). Lengths include padding and border.
+fn cell_column_width(style: &taffy::Style) -> Dimension {
+    match style.size.width.tag() {
+        CompactLength::LENGTH_TAG => {
+            let mut len = style.size.width.value();
+            if style.box_sizing == taffy::BoxSizing::ContentBox {
+                let padding = style.padding.resolve_or_zero(None, resolve_calc_value);
+                let border = style.border.resolve_or_zero(None, resolve_calc_value);
+                len += padding.left + padding.right + border.left + border.right;
+            }
+            style_helpers::length(len)
+        }
+        CompactLength::PERCENT_TAG => style_helpers::percent(style.size.width.value()),
+        _ => style_helpers::auto(),
+    }
+}
+
+/// The grid line at the start of a zero-based row or column index. Taffy's grid lines are `i16`s, so
+/// the rows and columns beyond that limit all start at the last line.
+fn grid_line(index: usize) -> i16 {
+    i16::try_from(index + 1).unwrap_or(i16::MAX)
+}
+
+fn span_attr(value: Option<&str>, default: u16, max: u16) -> u16 {
+    value
+        .and_then(|val| val.trim().parse::<u16>().ok())
+        .unwrap_or(default)
+        .min(max)
 }
 
 pub(crate) fn build_table_context(
     doc: &mut BaseDocument,
     table_root_node_id: usize,
 ) -> (TableContext, Vec<usize>) {
-    let mut items: Vec<TableItem> = Vec::new();
-    let mut row = 0u16;
-    let mut col = 0u16;
-
     let root_node = &mut doc.nodes[table_root_node_id];
 
     let children = std::mem::take(&mut root_node.children);
@@ -56,53 +416,195 @@ pub(crate) fn build_table_context(
     style.grid_auto_columns = Vec::new();
     style.grid_auto_rows = Vec::new();
 
+    // Fixed table layout only applies to tables with a non-auto width
     let is_fixed = match stylo_styles.clone_table_layout() {
-        TableLayout::Fixed => true,
+        TableLayout::Fixed => style.size.width.tag() != CompactLength::AUTO_TAG,
         TableLayout::Auto => false,
     };
+    let border_collapse = stylo_styles.clone_border_collapse() == BorderCollapse::Collapse;
+    let border_spacing = stylo_styles.clone_border_spacing();
+    let spacing_x = border_spacing.0.width.px();
+    let spacing_y = border_spacing.0.height.px();
 
     drop(stylo_styles);
 
-    let mut column_sizes: Vec<taffy::Dimension> = Vec::new();
-    for child_id in children.iter().copied() {
-        collect_table_cells(
+    let mut builder = TableBuilder {
+        is_fixed,
+        items: Vec::new(),
+        captions: Vec::new(),
+        columns: Vec::new(),
+        occupied: Vec::new(),
+        rows: Vec::new(),
+        current_group: None,
+        group_start_row: 0,
+        group_start_item: 0,
+        col: 0,
+    };
+
+    // The (first) header group is always rendered at the top of the table and the (first) footer group
+    // at the bottom, regardless of their position in the source.
+    let mut ordered_children = children.clone();
+    ordered_children.sort_by_key(|child_id| {
+        let display = doc.nodes[*child_id]
+            .primary_styles()
+            .map(|s| s.clone_display().inside());
+        match display {
+            Some(DisplayInside::TableHeaderGroup) => 0,
+            Some(DisplayInside::TableFooterGroup) => 2,
+            _ => 1,
+        }
+    });
+
+    for child_id in ordered_children.iter().copied() {
+        collect_table_cells(doc, child_id, &mut builder);
+    }
+    builder.end_row_group();
+
+    let column_count = builder
+        .items
+        .iter()
+        .filter(|item| item.kind == TableItemKind::Cell)
+        .map(|cell| cell.col + usize::from(cell.col_span))
+        .max()
+        .unwrap_or(0)
+        .max(builder.columns.len());
+    builder.ensure_columns(column_count);
+    let row_count = builder.rows.len();
+
+    style.grid_template_columns = builder
+        .columns
+        .iter()
+        .map(|column| {
+            let width = match column.width.tag() {
+                CompactLength::AUTO_TAG => column.cell_width,
+                _ => column.width,
+            };
+            let track: NonRepeatedTrackSizingFunction = match width.tag() {
+                CompactLength::LENGTH_TAG if is_fixed => style_helpers::length(width.value()),
+                CompactLength::LENGTH_TAG => style_helpers::minmax(
+                    style_helpers::min_content(),
+                    style_helpers::length(width.value()),
+                ),
+                CompactLength::PERCENT_TAG => style_helpers::percent(width.value()),
+                // In fixed layout the remaining space is distributed evenly between auto columns, ignoring their content
+                _ if is_fixed => style_helpers::flex(1.0),
+                _ => style_helpers::auto(),
+            };
+            track.into()
+        })
+        .collect();
+
+    style.grid_template_rows = vec![style_helpers::auto(); row_count];
+
+    let mut items = builder.items;
+    for item in items.iter_mut() {
+        item.style.grid_row = taffy::Line {
+            start: style_helpers::line(grid_line(item.row)),
+            end: style_helpers::span(item.row_span),
+        };
+        item.style.grid_column = match item.kind {
+            TableItemKind::Cell => taffy::Line {
+                start: style_helpers::line(grid_line(item.col)),
+                end: style_helpers::span(item.col_span),
+            },
+            TableItemKind::Row => taffy::Line {
+                start: style_helpers::line(1),
+                end: style_helpers::line(-1),
+            },
+        };
+    }
+
+    let mut collapsed_borders = HashMap::new();
+    if border_collapse {
+        collapsed_borders = resolve_collapsed_borders(
             doc,
-            child_id,
-            is_fixed,
-            &mut row,
-            &mut col,
+            table_root_node_id,
             &mut items,
-            &mut column_sizes,
+            &builder.columns,
+            &builder.rows,
         );
-    }
-    column_sizes.resize(col as usize, style_helpers::auto());
 
-    style.grid_template_columns = column_sizes
-        .into_iter()
-        .map(|dim| NonRepeatedTrackSizingFunction::from(dim).into())
-        .collect();
-    style.grid_template_rows = vec![style_helpers::auto(); row as usize];
+        // The table's border is half of the outer border of the cells. The other half spills into the margin.
+        let mut outer = taffy::Rect {
+            top: 0.0f32,
+            right: 0.0,
+            bottom: 0.0,
+            left: 0.0,
+        };
+        for item in items.iter() {
+            let Some(borders) = collapsed_borders.get(&item.node_id) else {
+                continue;
+            };
+            if item.row == 0 {
+                outer.top = outer.top.max(borders.top.width);
+            }
+            if item.row + usize::from(item.row_span) == row_count {
+                outer.bottom = outer.bottom.max(borders.bottom.width);
+            }
+            if item.col == 0 {
+                outer.left = outer.left.max(borders.left.width);
+            }
+            if item.col + usize::from(item.col_span) == column_count {
+                outer.right = outer.right.max(borders.right.width);
+            }
+        }
+        style.border = outer.map(|width| taffy::LengthPercentage::length(width / 2.0));
+        style.padding = taffy::Rect::zero();
+    } else {
+        // `border-spacing` applies between cells and between the cells and the table's padding edge
+        style.gap = taffy::Size {
+            width: taffy::LengthPercentage::length(spacing_x),
+            height: taffy::LengthPercentage::length(spacing_y),
+        };
+        let add_spacing = |padding: taffy::LengthPercentage, spacing: f32| {
+            let raw = padding.into_raw();
+            if raw.tag() == CompactLength::LENGTH_TAG {
+                taffy::LengthPercentage::length(raw.value() + spacing)
+            } else {
+                padding
+            }
+        };
+        style.padding = taffy::Rect {
+            top: add_spacing(style.padding.top, spacing_y),
+            bottom: add_spacing(style.padding.bottom, spacing_y),
+            left: add_spacing(style.padding.left, spacing_x),
+            right: add_spacing(style.padding.right, spacing_x),
+        };
+    }
 
-    let layout_children = items
-        .iter()
-        .filter(|item| item.kind == TableItemKind::Cell)
-        .map(|cell| cell.node_id)
+    // Captions are laid out above or below the table box, outside of its border
+    let captions = builder.captions;
+    let captions_on = |side: CaptionSide| {
+        captions
+            .iter()
+            .filter(move |(_, caption_side)| *caption_side == side)
+            .map(|(node_id, _)| *node_id)
+    };
+    let layout_children = captions_on(CaptionSide::Top)
+        .chain(
+            items
+                .iter()
+                .filter(|item| item.kind != TableItemKind::Row)
+                .map(|item| item.node_id),
+        )
+        .chain(captions_on(CaptionSide::Bottom))
         .collect();
     let root_node = &mut doc.nodes[table_root_node_id];
     root_node.children = children;
 
-    (TableContext { style, items }, layout_children)
+    (
+        TableContext {
+            style,
+            items,
+            captions,
+            collapsed_borders,
+            border_collapse,
+        },
+        layout_children,
+    )
 }
 
-pub(crate) fn collect_table_cells(
-    doc: &mut BaseDocument,
-    node_id: usize,
-    is_fixed: bool,
-    row: &mut u16,
-    col: &mut u16,
-    cells: &mut Vec<TableItem>,
-    columns: &mut Vec<Dimension>,
-) {
+fn collect_table_cells(doc: &mut BaseDocument, node_id: usize, builder: &mut TableBuilder) {
     let node = &doc.nodes[node_id];
 
     if !node.is_element() {
@@ -114,99 +616,154 @@ pub(crate) fn collect_table_cells(
         return;
     };
 
+    if display.outside() == DisplayOutside::TableCaption {
+        let caption_side = node.primary_styles().unwrap().clone_caption_side();
+        builder.captions.push((node_id, caption_side));
+        return;
+    }
+
     match display.inside() {
         DisplayInside::TableRowGroup
         | DisplayInside::TableHeaderGroup
-        | DisplayInside::TableFooterGroup
-        | DisplayInside::Contents => {
+        | DisplayInside::TableFooterGroup => {
+            builder.start_row_group(Some(node_id));
+            let children = std::mem::take(&mut doc.nodes[node_id].children);
+            for child_id in children.iter().copied() {
+                collect_table_cells(doc, child_id, builder);
+            }
+            doc.nodes[node_id].children = children;
+            builder.end_row_group();
+        }
+        DisplayInside::Contents => {
+            let children = std::mem::take(&mut doc.nodes[node_id].children);
+            for child_id in children.iter().copied() {
+                collect_table_cells(doc, child_id, builder);
+            }
+            doc.nodes[node_id].children = children;
+        }
+        DisplayInside::TableColumnGroup => {
+            let width = node
+                .primary_styles()
+                .map(|s| stylo_taffy::to_taffy_style(&s).size.width)
+                .unwrap_or(Dimension::auto());
+            let span = span_attr(node.attr(local_name!("span")), 1, MAX_COLSPAN).max(1);
+            let start = builder.columns.len();
+
             let children = std::mem::take(&mut doc.nodes[node_id].children);
             for child_id in children.iter().copied() {
-                collect_table_cells(doc, child_id, is_fixed, row, col, cells, columns);
+                collect_table_cells(doc, child_id, builder);
             }
             doc.nodes[node_id].children = children;
+
+            // A column group without column children represents `span` columns
+            if builder.columns.len() == start {
+                builder.ensure_columns(start + span as usize);
+            }
+            for column in &mut builder.columns[start..] {
+                column.group_id = Some(node_id);
+                if column.width.tag() == CompactLength::AUTO_TAG {
+                    column.width = width;
+                }
+            }
+        }
+        DisplayInside::TableColumn => {
+            let width = node
+                .primary_styles()
+                .map(|s| stylo_taffy::to_taffy_style(&s).size.width)
+                .unwrap_or(Dimension::auto());
+            let span = span_attr(node.attr(local_name!("span")), 1, MAX_COLSPAN).max(1);
+            let start = builder.columns.len();
+            builder.ensure_columns(start + span as usize);
+            for column in &mut builder.columns[start..] {
+                column.width = width;
+                column.node_id = Some(node_id);
+            }
         }
         DisplayInside::TableRow => {
-            *row += 1;
-            *col = 0;
+            builder.start_row(Some(node_id));
 
             {
                 let stylo_style = &node.primary_styles().unwrap();
                 let mut style = stylo_taffy::to_taffy_style(stylo_style);
-                style.grid_column = taffy::Line {
-                    start: style_helpers::line(0),
-                    end: style_helpers::line(-1),
-                };
-                style.grid_row = taffy::Line {
-                    start: style_helpers::line(*row as i16),
-                    end: style_helpers::span(1),
-                };
-                cells.push(TableItem {
+                // Rows do not have padding or margins, and their borders are only painted in the collapsing border model
+                style.padding = taffy::Rect::zero();
+                style.border = taffy::Rect::zero();
+                style.margin = taffy::Rect::zero();
+                builder.items.push(TableItem {
                     kind: TableItemKind::Row,
                     node_id,
                     style,
+                    row: builder.current_row(),
+                    col: 0,
+                    row_span: 1,
+                    col_span: 1,
+                    vertical_align: CellVerticalAlign::Top,
                 });
             }
 
             let children = std::mem::take(&mut doc.nodes[node_id].children);
             for child_id in children.iter().copied() {
-                collect_table_cells(doc, child_id, is_fixed, row, col, cells, columns);
+                collect_table_cells(doc, child_id, builder);
             }
             doc.nodes[node_id].children = children;
         }
         DisplayInside::TableCell => {
+            // Cells outside of a row are wrapped in an (anonymous) row
+            if builder.rows.len() <= builder.group_start_row {
+                builder.start_row(None);
+            }
+
             let stylo_style = &node.primary_styles().unwrap();
-            let colspan: u16 = node
-                .attr(local_name!("colspan"))
-                .and_then(|val| val.parse().ok())
-                .unwrap_or(1);
+            let colspan = span_attr(node.attr(local_name!("colspan")), 1, MAX_COLSPAN).max(1);
+            let rowspan = span_attr(node.attr(local_name!("rowspan")), 1, MAX_ROWSPAN);
+            let vertical_align = CellVerticalAlign::from_style(stylo_style);
             let mut style = stylo_taffy::to_taffy_style(stylo_style);
 
-            // TODO: account for padding/border/margin
-            if *row == 1 {
-                let column = match style.size.width.tag() {
-                    taffy::CompactLength::LENGTH_TAG => {
-                        let len = style.size.width.value();
-                        let padding = style.padding.resolve_or_zero(None, resolve_calc_value);
-                        style_helpers::length(len + padding.left + padding.right)
-                    }
-                    taffy::CompactLength::PERCENT_TAG => {
-                        style_helpers::percent(style.size.width.value())
-                    }
-                    taffy::CompactLength::AUTO_TAG => style_helpers::auto(),
-                    _ => unreachable!(),
-                };
-                columns.push(column);
-            } else if !is_fixed
-                && (*col as usize) < columns.len()
-                && taffy::CompactLength::LENGTH_TAG == style.size.width.tag()
+            let row = builder.current_row();
+            let col = builder.next_free_column();
+            let col_end = col + usize::from(colspan);
+            builder.ensure_columns(col_end);
+
+            let cell_width = cell_column_width(&style);
+            let is_first_row = row == 0;
+            if colspan == 1 {
+                let column = &mut builder.columns[col];
+                if !builder.is_fixed || is_first_row {
+                    column.cell_width = merge_column_width(column.cell_width, cell_width);
+                }
+            } else if builder.is_fixed
+                && is_first_row
+                && cell_width.tag() == CompactLength::LENGTH_TAG
             {
-                let new_len = style.size.width.value();
-                let tag = columns[*col as usize].tag();
-                let value = columns[*col as usize].value();
-                columns[*col as usize] = match tag {
-                    taffy::CompactLength::LENGTH_TAG => style_helpers::length(value.max(new_len)),
-                    taffy::CompactLength::AUTO_TAG => style_helpers::length(new_len),
-                    taffy::CompactLength::PERCENT_TAG => style_helpers::percent(value),
-                    _ => unreachable!(),
+                // Distribute the width of spanning cells in the first row evenly between the spanned columns
+                let share = style_helpers::length(cell_width.value() / colspan as f32);
+                for column in &mut builder.columns[col..col_end] {
+                    column.cell_width = merge_column_width(column.cell_width, share);
                 }
             }
 
-            style.grid_column = taffy::Line {
-                start: style_helpers::line((*col + 1) as i16),
-                end: style_helpers::span(colspan),
-            };
-            style.grid_row = taffy::Line {
-                start: style_helpers::line(*row as i16),
-                end: style_helpers::span(1),
-            };
+            // Mark the columns as occupied for the following rows of this cell's row span.
+            // A rowspan of 0 extends to the end of the row group.
+            let occupied_rows = if rowspan == 0 { u16::MAX } else { rowspan };
+            for remaining in &mut builder.occupied[col..col_end] {
+                *remaining = occupied_rows;
+            }
+
+            // Cells are sized by their column(s) and row(s) and have no margins
             style.size.width = style_helpers::auto();
-            cells.push(TableItem {
+            style.margin = taffy::Rect::zero();
+            builder.items.push(TableItem {
                 kind: TableItemKind::Cell,
                 node_id,
                 style,
+                row,
+                col,
+                row_span: rowspan,
+                col_span: colspan,
+                vertical_align,
             });
 
-            *col += colspan;
+            builder.col = col_end;
         }
         _ => {
             println!("Warning: ignoring non-table typed descendent of table");
@@ -214,6 +771,220 @@ pub(crate) fn collect_table_cells(
     }
 }
 
+/// Resolve the borders of each cell against the borders of the adjacent cells, rows, row groups,
+/// columns, column groups and the table itself.
+fn resolve_collapsed_borders(
+    doc: &BaseDocument,
+    table_root_node_id: usize,
+    items: &mut [TableItem],
+    columns: &[TableColumn],
+    rows: &[TableRow],
+) -> HashMap<usize, CollapsedBorders> {
+    let mut borders: HashMap<usize, [CollapsedBorder; 4]> = HashMap::new();
+    let mut borders_of = |node_id: usize| -> [CollapsedBorder; 4] {
+        *borders.entry(node_id).or_insert_with(|| {
+            doc.nodes[node_id]
+                .primary_styles()
+                .map(|style| style_borders(&style))
+                .unwrap_or([CollapsedBorder::NONE; 4])
+        })
+    };
+
+    // Map each slot in the table's grid to the cell that occupies it
+    let row_count = rows.len();
+    let column_count = columns.len();
+    let mut slots: Vec<Option<usize>> = vec![None; row_count * column_count];
+    for (idx, item) in items.iter().enumerate() {
+        if item.kind != TableItemKind::Cell {
+            continue;
+        }
+        for row in item.row..(item.row + usize::from(item.row_span)) {
+            for col in item.col..(item.col + usize::from(item.col_span)) {
+                if let Some(slot) = slots.get_mut(row * column_count + col) {
+                    slot.get_or_insert(idx);
+                }
+            }
+        }
+    }
+    let slot = |row: usize, col: usize| -> Option<usize> {
+        if row < row_count && col < column_count {
+            slots[row * column_count + col]
+        } else {
+            None
+        }
+    };
+
+    let table_borders = borders_of(table_root_node_id);
+    let mut resolved: Vec<(usize, CollapsedBorders)> = Vec::new();
+    for (idx, item) in items.iter().enumerate() {
+        if item.kind != TableItemKind::Cell {
+            continue;
+        }
+        let first_row = item.row;
+        let last_row = first_row + usize::from(item.row_span) - 1;
+        let first_col = item.col;
+        let last_col = first_col + usize::from(item.col_span) - 1;
+        let cell_borders = borders_of(item.node_id);
+
+        let mut edge = |side: Side| -> CollapsedBorder {
+            let mut candidates: Vec<CollapsedBorder> = Vec::new();
+            let (opposite, index) = match side {
+                Side::Top => (2, 0),
+                Side::Right => (3, 1),
+                Side::Bottom => (0, 2),
+                Side::Left => (1, 3),
+            };
+
+            // Adjacent cells. When two cells conflict, the one further to the top/left wins.
+            let neighbours: Vec<usize> = match side {
+                Side::Top if first_row > 0 => (first_col..=last_col)
+                    .filter_map(|col| slot(first_row - 1, col))
+                    .collect(),
+                Side::Bottom => (first_col..=last_col)
+                    .filter_map(|col| slot(last_row + 1, col))
+                    .collect(),
+                Side::Left if first_col > 0 => (first_row..=last_row)
+                    .filter_map(|row| slot(row, first_col - 1))
+                    .collect(),
+                Side::Right => (first_row..=last_row)
+                    .filter_map(|row| slot(row, last_col + 1))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let neighbour_borders = neighbours
+                .into_iter()
+                .filter(|neighbour| *neighbour != idx)
+                .map(|neighbour| borders_of(items[neighbour].node_id)[opposite]);
+            match side {
+                Side::Top | Side::Left => {
+                    candidates.extend(neighbour_borders);
+                    candidates.push(cell_borders[index]);
+                }
+                Side::Bottom | Side::Right => {
+                    candidates.push(cell_borders[index]);
+                    candidates.extend(neighbour_borders);
+                }
+            }
+
+            // Rows and row groups
+            match side {
+                Side::Top | Side::Bottom => {
+                    let (row, other_row) = match side {
+                        Side::Top => (first_row, first_row.checked_sub(1)),
+                        _ => (last_row, Some(last_row + 1).filter(|r| *r < row_count)),
+                    };
+                    if let Some(node_id) = rows[row].node_id {
+                        candidates.push(borders_of(node_id)[index]);
+                    }
+                    if let Some(node_id) = other_row.and_then(|r| rows[r].node_id) {
+                        candidates.push(borders_of(node_id)[opposite]);
+                    }
+                    let group = rows[row].group_id;
+                    let other_group = other_row.and_then(|r| rows[r].group_id);
+                    if group != other_group || other_row.is_none() {
+                        if let Some(group_id) = group {
+                            candidates.push(borders_of(group_id)[index]);
+                        }
+                        if let Some(group_id) = other_group {
+                            candidates.push(borders_of(group_id)[opposite]);
+                        }
+                    }
+                }
+                Side::Left | Side::Right => {
+                    let at_table_edge = match side {
+                        Side::Left => first_col == 0,
+                        _ => last_col + 1 >= column_count,
+                    };
+                    if at_table_edge {
+                        for row in &rows[first_row..=last_row] {
+                            if let Some(node_id) = row.node_id {
+                                candidates.push(borders_of(node_id)[index]);
+                            }
+                        }
+                        for row in &rows[first_row..=last_row] {
+                            if let Some(group_id) = row.group_id {
+                                candidates.push(borders_of(group_id)[index]);
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Columns and column groups
+            match side {
+                Side::Top | Side::Bottom => {
+                    let at_table_edge = match side {
+                        Side::Top => first_row == 0,
+                        _ => last_row + 1 >= row_count,
+                    };
+                    if at_table_edge {
+                        for column in &columns[first_col..=last_col] {
+                            if let Some(node_id) = column.node_id {
+                                candidates.push(borders_of(node_id)[index]);
+                            }
+                        }
+                        for column in &columns[first_col..=last_col] {
+                            if let Some(group_id) = column.group_id {
+                                candidates.push(borders_of(group_id)[index]);
+                            }
+                        }
+                    }
+                }
+                Side::Left | Side::Right => {
+                    let (col, other_col) = match side {
+                        Side::Left => (first_col, first_col.checked_sub(1)),
+                        _ => (last_col, Some(last_col + 1).filter(|c| *c < column_count)),
+                    };
+                    if let Some(node_id) = columns[col].node_id {
+                        candidates.push(borders_of(node_id)[index]);
+                    }
+                    if let Some(node_id) = other_col.and_then(|c| columns[c].node_id) {
+                        candidates.push(borders_of(node_id)[opposite]);
+                    }
+                    let group = columns[col].group_id;
+                    let other_group = other_col.and_then(|c| columns[c].group_id);
+                    if group != other_group || other_col.is_none() {
+                        if let Some(group_id) = group {
+                            candidates.push(borders_of(group_id)[index]);
+                        }
+                        if let Some(group_id) = other_group {
+                            candidates.push(borders_of(group_id)[opposite]);
+                        }
+                    }
+                }
+            }
+
+            // The table
+            let at_table_edge = match side {
+                Side::Top => first_row == 0,
+                Side::Bottom => last_row + 1 >= row_count,
+                Side::Left => first_col == 0,
+                Side::Right => last_col + 1 >= column_count,
+            };
+            if at_table_edge {
+                candidates.push(table_borders[index]);
+            }
+
+            resolve_collapsed_border(&candidates)
+        };
+
+        let cell = CollapsedBorders {
+            top: edge(Side::Top),
+            right: edge(Side::Right),
+            bottom: edge(Side::Bottom),
+            left: edge(Side::Left),
+        };
+        resolved.push((idx, cell));
+    }
+
+    let mut collapsed_borders = HashMap::with_capacity(resolved.len());
+    for (idx, cell) in resolved {
+        items[idx].style.border = cell.half_widths();
+        collapsed_borders.insert(items[idx].node_id, cell);
+    }
+    collapsed_borders
+}
+
 pub struct RangeIter(Range<usize>);
 
 impl Iterator for RangeIter {
@@ -236,8 +1007,8 @@ impl taffy::TraversePartialTree for TableTreeWrapper<'_> {
     }
 
     #[inline(always)]
-    fn child_count(&self, node_id: taffy::NodeId) -> usize {
-        self.doc.child_count(node_id)
+    fn child_count(&self, _node_id: taffy::NodeId) -> usize {
+        self.ctx.items.len()
     }
 
     #[inline(always)]
@@ -262,8 +1033,14 @@ impl taffy::LayoutPartialTree for TableTreeWrapper<'_> {
     }
 
     fn set_unrounded_layout(&mut self, node_id: taffy::NodeId, layout: &taffy::Layout) {
-        let node_id = taffy::NodeId::from(self.ctx.items[usize::from(node_id)].node_id);
-        self.doc.set_unrounded_layout(node_id, layout)
+        let index = usize::from(node_id);
+        let mut layout = *layout;
+        // The content of vertically aligned cells is offset by extra top padding
+        if let Some(offset) = self.cell_offsets.get(&index) {
+            layout.padding.top += offset;
+        }
+        let node_id = taffy::NodeId::from(self.ctx.items[index].node_id);
+        self.doc.set_unrounded_layout(node_id, &layout)
     }
 
     fn compute_child_layout(
@@ -271,7 +1048,8 @@ impl taffy::LayoutPartialTree for TableTreeWrapper<'_> {
         node_id: taffy::NodeId,
         inputs: taffy::tree::LayoutInput,
     ) -> taffy::LayoutOutput {
-        let cell = &self.ctx.items[usize::from(node_id)];
+        let index = usize::from(node_id);
+        let cell = &self.ctx.items[index];
         match cell.kind {
             TableItemKind::Row => {
                 compute_leaf_layout(inputs, &cell.style, resolve_calc_value, |_, _| {
@@ -279,13 +1057,167 @@ impl taffy::LayoutPartialTree for TableTreeWrapper<'_> {
                 })
             }
             TableItemKind::Cell => {
-                let node_id = taffy::NodeId::from(cell.node_id);
-                self.doc.compute_child_layout(node_id, inputs)
+                let cell_id = cell.node_id;
+                let vertical_align = cell.vertical_align;
+                let node_id = taffy::NodeId::from(cell_id);
+
+                let cell_height = inputs.known_dimensions.height;
+                let Some(cell_height) = cell_height
+                    .filter(|_| inputs.run_mode == taffy::RunMode::PerformLayout)
+                    .filter(|_| vertical_align != CellVerticalAlign::Top)
+                else {
+                    return self.doc.compute_child_layout(node_id, inputs);
+                };
+
+                // Vertically align the cell's content by measuring its natural height and then
+                // offsetting its content (see `set_unrounded_layout`)
+                let mut measure_inputs = inputs;
+                measure_inputs.known_dimensions.height = None;
+                measure_inputs.run_mode = taffy::RunMode::ComputeSize;
+                let natural_height = self
+                    .doc
+                    .compute_child_layout(node_id, measure_inputs)
+                    .size
+                    .height;
+                let extra_space = (cell_height - natural_height).max(0.0);
+                let offset = match vertical_align {
+                    CellVerticalAlign::Middle => extra_space / 2.0,
+                    CellVerticalAlign::Bottom => extra_space,
+                    CellVerticalAlign::Top => 0.0,
+                };
+
+                // The cell's children are laid out afresh (rather than from the cache) so that
+                // they are only offset once
+                self.doc.nodes[cell_id].cache.clear();
+                let output = self.doc.compute_child_layout(node_id, inputs);
+                self.cell_offsets.insert(index, offset);
+                if offset > 0.0 {
+                    let children = self.doc.nodes[cell_id].layout_children.borrow().clone();
+                    for child_id in children.into_iter().flatten() {
+                        let child = &mut self.doc.nodes[child_id];
+                        if child.style.position != taffy::Position::Absolute {
+                            child.unrounded_layout.location.y += offset;
+                        }
+                    }
+                }
+                output
             }
         }
     }
 }
 
+/// Lay out a table: its rows and cells are laid out in a grid (the table box), and its captions
+/// above and below it (in the table wrapper box).
+pub(crate) fn compute_table_layout(
+    doc: &mut BaseDocument,
+    node_id: taffy::NodeId,
+    inputs: taffy::tree::LayoutInput,
+) -> taffy::LayoutOutput {
+    let NodeSpecificData::TableRoot(context) = &doc.nodes[node_id.into()]
+        .data
+        .downcast_element()
+        .unwrap()
+        .node_specific_data
+    else {
+        panic!("Node marked as table root but doesn't have TableContext");
+    };
+    let context = Arc::clone(context);
+
+    // The height of table rows is always determined by their content. Sizing under a
+    // max-content constraint also ensures that rows grow to fit cells that span multiple rows.
+    let mut inputs = inputs;
+    if inputs.available_space.height == taffy::AvailableSpace::MinContent {
+        inputs.available_space.height = taffy::AvailableSpace::MaxContent;
+    }
+
+    let mut table_wrapper = TableTreeWrapper {
+        doc,
+        ctx: Arc::clone(&context),
+        cell_offsets: HashMap::new(),
+    };
+    let mut output = compute_grid_layout(&mut table_wrapper, node_id, inputs);
+    if context.captions.is_empty() {
+        return output;
+    }
+
+    // Captions are as wide as the table box
+    let width = output.size.width;
+    let mut caption_layouts = Vec::with_capacity(context.captions.len());
+    let (mut top_height, mut bottom_height) = (0.0, 0.0);
+    for &(caption_id, side) in &context.captions {
+        let margin = doc.nodes[caption_id]
+            .style
+            .margin
+            .resolve_or_zero(Some(width), resolve_calc_value);
+        let caption_width = (width - margin.left - margin.right).max(0.0);
+        let caption_output = doc.compute_child_layout(
+            taffy::NodeId::from(caption_id),
+            taffy::tree::LayoutInput {
+                known_dimensions: taffy::Size {
+                    width: Some(caption_width),
+                    height: None,
+                },
+                parent_size: taffy::Size {
+                    width: Some(width),
+                    height: None,
+                },
+                available_space: taffy::Size {
+                    width: taffy::AvailableSpace::Definite(caption_width),
+                    height: taffy::AvailableSpace::MaxContent,
+                },
+                run_mode: inputs.run_mode,
+                sizing_mode: taffy::SizingMode::InherentSize,
+                axis: taffy::RequestedAxis::Both,
+                vertical_margins_are_collapsible: taffy::Line::FALSE,
+            },
+        );
+        let height = caption_output.size.height + margin.top + margin.bottom;
+        caption_layouts.push((caption_id, side, caption_output.size, margin));
+        match side {
+            CaptionSide::Top => top_height += height,
+            CaptionSide::Bottom => bottom_height += height,
+        }
+    }
+
+    if inputs.run_mode == taffy::RunMode::PerformLayout {
+        // Move the table box below the top captions
+        for item in &context.items {
+            doc.nodes[item.node_id].unrounded_layout.location.y += top_height;
+        }
+
+        let table_height = output.size.height;
+        let (mut top_y, mut bottom_y) = (0.0, top_height + table_height);
+        for (caption_id, side, size, margin) in caption_layouts {
+            let y = match side {
+                CaptionSide::Top => &mut top_y,
+                CaptionSide::Bottom => &mut bottom_y,
+            };
+            let node = &mut doc.nodes[caption_id];
+            let layout = &mut node.unrounded_layout;
+            layout.size = size;
+            layout.location = taffy::Point {
+                x: margin.left,
+                y: *y + margin.top,
+            };
+            layout.padding = node
+                .style
+                .padding
+                .resolve_or_zero(Some(width), resolve_calc_value);
+            layout.border = node
+                .style
+                .border
+                .resolve_or_zero(Some(width), resolve_calc_value);
+            layout.margin = margin;
+            *y += size.height + margin.top + margin.bottom;
+        }
+    }
+
+    output.size.height += top_height + bottom_height;
+    output.content_size.height += top_height + bottom_height;
+    output.first_baselines.y = output.first_baselines.y.map(|y| y + top_height);
+    output
+}
+
 impl taffy::LayoutGridContainer for TableTreeWrapper<'_> {
     type GridContainerStyle<'a>
         = &'a taffy::Style
@@ -305,3 +1237,42 @@ impl taffy::LayoutGridContainer for TableTreeWrapper<'_> {
         &self.ctx.items[usize::from(child_node_id)].style
     }
 }
+
+#[test]
+fn test_collapsed_border_conflict_resolution() {
+    let border = |width: f32, style: BorderStyle, color: Color| CollapsedBorder {
+        width,
+        style,
+        color,
+    };
+    let red = Color::from_rgb8(255, 0, 0);
+    let blue = Color::from_rgb8(0, 0, 255);
+
+    // Wider borders win
+    let resolved = resolve_collapsed_border(&[
+        border(1.0, BorderStyle::Solid, red),
+        border(3.0, BorderStyle::Dotted, blue),
+    ]);
+    assert_eq!(resolved.width, 3.0);
+
+    // Equal widths are resolved by style
+    let resolved = resolve_collapsed_border(&[
+        border(2.0, BorderStyle::Dashed, red),
+        border(2.0, BorderStyle::Double, blue),
+    ]);
+    assert_eq!(resolved.style, BorderStyle::Double);
+
+    // Equal borders are resolved by the order of the candidates
+    let resolved = resolve_collapsed_border(&[
+        border(2.0, BorderStyle::Solid, red),
+        border(2.0, BorderStyle::Solid, blue),
+    ]);
+    assert_eq!(resolved.color, red);
+
+    // Hidden suppresses all other borders
+    let resolved = resolve_collapsed_border(&[
+        border(5.0, BorderStyle::Solid, red),
+        border(0.0, BorderStyle::Hidden, blue),
+    ]);
+    assert!(!resolved.is_visible());
+}
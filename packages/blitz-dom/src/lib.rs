@@ -23,29 +23,56 @@ mod document;
 /// todo: we want this to use ECS, but we're not done with the design yet.
 pub mod node;
 
+mod canvas;
 mod debug;
+mod details;
+mod dialog;
 mod events;
+mod font_metrics;
+mod form;
+mod iframe;
+mod image_animation;
 /// Integration of taffy and the DOM.
 mod layout;
+mod media;
+mod meter;
+mod popover;
 mod query_selector;
+mod responsive_image;
+mod shadow;
 /// Implementations that interact with servo's style engine
 mod stylo;
 mod stylo_to_cursor_icon;
 mod stylo_to_parley;
+mod template;
+mod top_layer;
 
 pub mod net;
+pub mod scroll;
+pub mod scrollbar;
 pub mod util;
 
 #[cfg(feature = "accessibility")]
 mod accessibility;
 
+pub use canvas::{
+    CanvasCommand, CanvasContext2d, CanvasGlyph, CanvasSurface, CanvasTextAlign, CanvasTextBaseline,
+};
 pub use document::BaseDocument;
+pub use form::ValidityState;
+pub use iframe::{DummyIframeProvider, IframeProvider, IframeSandbox, SubDocumentConfig};
 pub use markup5ever::{
     Namespace, NamespaceStaticSet, Prefix, PrefixStaticSet, QualName, local_name, namespace_prefix,
     namespace_url, ns,
 };
+pub use media::{MediaControls, MediaEvent, MediaState, TestPatternMediaProvider};
+pub use meter::{MeterRegion, MeterValues};
 pub use node::{ElementNodeData, Node, NodeData, TextNodeData};
 pub use parley::FontContext;
+/// The brush, path and stroke types used by the canvas drawing API
+pub use peniko;
+pub use popover::PopoverState;
+pub use responsive_image::ImageSource;
 pub use string_cache::Atom;
 pub use style::invalidation::element::restyle_hints::RestyleHint;
 pub type SelectorList = selectors::SelectorList<style::selector_parser::SelectorImpl>;
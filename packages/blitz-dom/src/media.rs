@@ -0,0 +1,710 @@
+//! `<video>` and `<audio>` elements.
+//!
+//! Media resources are opened and decoded by the embedder through a `MediaProvider`, while the
+//! document owns the playback state of each media element (stored in `BaseDocument::media_elements`,
+//! keyed by the element's node id). Playing media is advanced when the document is polled, and the
+//! media events fired along the way are queued for the embedder to dispatch (see
+//! `BaseDocument::take_media_events`).
+//!
+//! Videos are laid out as replaced elements, sized by their video (or else their poster image).
+//! Elements with a `controls` attribute have a play/pause button and a seek bar drawn over them by
+//! the renderer (see `MediaControls`).
+//!
+//! See <https://html.spec.whatwg.org/multipage/media.html>
+
+use std::sync::Arc;
+use std::task::Context;
+use std::time::{Duration, Instant};
+
+use blitz_traits::media::{
+    BoxedMediaSource, MediaKind, MediaMetadata, MediaProvider, MediaSource, SharedMediaProvider,
+};
+use blitz_traits::net::Request;
+use markup5ever::local_name;
+use peniko::kurbo::{Point, Rect};
+use url::Url;
+
+use crate::BaseDocument;
+use crate::net::ImageHandler;
+use crate::node::{NodeSpecificData, RasterImageData};
+use crate::responsive_image::ImageSource;
+use crate::util::{ImageType, resolve_url};
+
+/// How often `timeupdate` events are fired while media is playing
+const TIME_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+/// How often media elements whose metadata is still loading are polled
+const LOADING_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// The frame rate which redraws of videos without a known frame rate are scheduled at
+const DEFAULT_FRAME_RATE: f32 = 30.0;
+
+/// The events fired at media elements
+///
+/// See <https://html.spec.whatwg.org/multipage/media.html#mediaevents>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaEvent {
+    LoadStart,
+    Error,
+    Emptied,
+    DurationChange,
+    LoadedMetadata,
+    LoadedData,
+    Play,
+    Playing,
+    Pause,
+    Seeking,
+    Seeked,
+    TimeUpdate,
+    Ended,
+}
+
+impl MediaEvent {
+    /// Returns the name of the event ("play", "timeupdate", etc)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::LoadStart => "loadstart",
+            Self::Error => "error",
+            Self::Emptied => "emptied",
+            Self::DurationChange => "durationchange",
+            Self::LoadedMetadata => "loadedmetadata",
+            Self::LoadedData => "loadeddata",
+            Self::Play => "play",
+            Self::Playing => "playing",
+            Self::Pause => "pause",
+            Self::Seeking => "seeking",
+            Self::Seeked => "seeked",
+            Self::TimeUpdate => "timeupdate",
+            Self::Ended => "ended",
+        }
+    }
+}
+
+/// A snapshot of the playback state of a media element
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediaState {
+    pub paused: bool,
+    pub ended: bool,
+    pub current_time: Duration,
+    /// The duration of the media (`None` while loading, or for unbounded streams)
+    pub duration: Option<Duration>,
+    /// Whether the media's metadata has loaded
+    pub has_metadata: bool,
+}
+
+impl Default for MediaState {
+    /// The state of a media element without a resource
+    fn default() -> Self {
+        Self {
+            paused: true,
+            ended: false,
+            current_time: Duration::ZERO,
+            duration: None,
+            has_metadata: false,
+        }
+    }
+}
+
+/// The playback state of a media element
+pub(crate) struct MediaElement {
+    url: Url,
+    /// `None` if the media provider couldn't open the media
+    source: Option<BoxedMediaSource<RasterImageData>>,
+    metadata: Option<MediaMetadata>,
+    /// The most recently decoded video frame
+    frame: Option<RasterImageData>,
+    paused: bool,
+    ended: bool,
+    /// Whether the poster is shown instead of the video (until playback starts or the media is
+    /// seeked)
+    show_poster: bool,
+    current_time: Duration,
+    /// When the current time was last advanced (while playing)
+    last_tick: Option<Instant>,
+    /// The current time when the last `timeupdate` event was fired
+    last_time_update: Duration,
+}
+
+impl MediaElement {
+    /// The natural size of the video (once its metadata has loaded)
+    pub(crate) fn video_size(&self) -> Option<(u32, u32)> {
+        self.metadata.and_then(|metadata| metadata.video_size)
+    }
+
+    fn state(&self) -> MediaState {
+        MediaState {
+            paused: self.paused,
+            ended: self.ended,
+            current_time: self.current_time,
+            duration: self.metadata.and_then(|metadata| metadata.duration),
+            has_metadata: self.metadata.is_some(),
+        }
+    }
+
+    /// How long until the document should next be polled for this element
+    fn next_poll(&self) -> Option<Duration> {
+        self.source.as_ref()?;
+        let Some(metadata) = self.metadata else {
+            return Some(LOADING_POLL_INTERVAL);
+        };
+        if self.paused {
+            return None;
+        }
+
+        let frame_interval = match metadata.video_size {
+            Some(_) => {
+                let frame_rate = metadata.frame_rate.unwrap_or(DEFAULT_FRAME_RATE);
+                Duration::from_secs_f32(1.0 / frame_rate.max(1.0))
+            }
+            None => TIME_UPDATE_INTERVAL,
+        };
+        let until_end = metadata
+            .duration
+            .map(|duration| duration.saturating_sub(self.current_time))
+            .unwrap_or(Duration::MAX);
+        Some(frame_interval.min(until_end))
+    }
+}
+
+/// The layout of the default controls of a media element: a bar along the bottom of its content
+/// box with a play/pause button and a seek track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediaControls {
+    pub bar: Rect,
+    pub play_button: Rect,
+    pub track: Rect,
+}
+
+impl MediaControls {
+    /// The height of the control bar
+    pub const HEIGHT: f64 = 32.0;
+
+    /// Lay out the controls of a media element with the given content box
+    pub fn new(content_box: Rect) -> Self {
+        let bar = Rect::new(
+            content_box.x0,
+            (content_box.y1 - Self::HEIGHT).max(content_box.y0),
+            content_box.x1,
+            content_box.y1,
+        );
+        let play_button =
+            Rect::from_origin_size(bar.origin(), (bar.height(), bar.height())).intersect(bar);
+        let center_y = bar.center().y;
+        let track = Rect::new(
+            play_button.x1 + 4.0,
+            center_y - 2.0,
+            (bar.x1 - 12.0).max(play_button.x1 + 4.0),
+            center_y + 2.0,
+        );
+        Self {
+            bar,
+            play_button,
+            track,
+        }
+    }
+
+    /// The position (between 0 and 1) within the track that a point in the control bar is at, or
+    /// `None` if the point isn't over the track
+    pub fn track_position(&self, point: Point) -> Option<f64> {
+        let over_track = self.bar.contains(point)
+            && point.x >= self.track.x0
+            && point.x <= self.track.x1
+            && self.track.width() > 0.0;
+        over_track.then(|| (point.x - self.track.x0) / self.track.width())
+    }
+}
+
+impl BaseDocument {
+    /// Set the Document's media provider
+    pub fn set_media_provider(&mut self, media_provider: SharedMediaProvider<RasterImageData>) {
+        self.media_provider = media_provider;
+    }
+
+    /// The URL of a media element's resource, from its `src` attribute or else its first `<source>`
+    /// child with a `src` attribute
+    fn select_media_source(&self, node_id: usize) -> Option<Url> {
+        let node = &self.nodes[node_id];
+        let src = node
+            .element_data()?
+            .attr(local_name!("src"))
+            .or_else(|| {
+                node.children
+                    .iter()
+                    .filter_map(|&child_id| self.nodes[child_id].element_data())
+                    .filter(|el| el.name.local == local_name!("source"))
+                    .find_map(|el| el.attr(local_name!("src")))
+            })
+            .filter(|src| !src.is_empty())?;
+        resolve_url(&self.base_url, src)
+    }
+
+    /// Start loading the poster image of a `<video>` element if it has changed
+    fn load_poster(&mut self, node_id: usize) {
+        let poster = self.nodes[node_id]
+            .element_data()
+            .and_then(|el| el.attr(local_name!("poster")))
+            .and_then(|poster| resolve_url(&self.base_url, poster))
+            .map(|url| ImageSource { url, density: 1.0 });
+        let Some(element) = self.nodes[node_id].element_data_mut() else {
+            return;
+        };
+        if element.image_source == poster {
+            return;
+        }
+
+        element.image_source = poster.clone();
+        element.node_specific_data = NodeSpecificData::None;
+        self.nodes[node_id].cache.clear();
+        if let Some(poster) = poster {
+            self.net_provider.fetch(
+                self.id(),
+                Request::get(poster.url),
+                Box::new(ImageHandler::new(node_id, ImageType::Image)),
+            );
+        }
+    }
+
+    /// Select the resource (and poster) of a `<video>` or `<audio>` element and open it with the
+    /// media provider if it has changed. This should be called when the element is connected to the
+    /// document, and when its `src` or `poster` attributes or `<source>` children change.
+    pub fn load_media(&mut self, node_id: usize) {
+        let Some(element) = self.nodes.get(node_id).and_then(|node| node.element_data()) else {
+            return;
+        };
+        let kind = match element.name.local {
+            local_name!("video") => MediaKind::Video,
+            local_name!("audio") => MediaKind::Audio,
+            _ => return,
+        };
+        let muted = element.attr(local_name!("muted")).is_some();
+
+        if kind == MediaKind::Video {
+            self.load_poster(node_id);
+        }
+
+        // Media which couldn't be opened is retried (the media provider may have changed)
+        let url = self.select_media_source(node_id);
+        let current_url = self
+            .media_elements
+            .get(&node_id)
+            .filter(|media| media.source.is_some())
+            .map(|media| &media.url);
+        if url.is_some() && current_url == url.as_ref() {
+            return;
+        }
+
+        if self.media_elements.remove(&node_id).is_some() {
+            self.media_events.push((node_id, MediaEvent::Emptied));
+        }
+        self.nodes[node_id].cache.clear();
+        let Some(url) = url else {
+            return;
+        };
+
+        self.media_events.push((node_id, MediaEvent::LoadStart));
+        let mut source = self.media_provider.open(self.id(), url.clone(), kind);
+        match &mut source {
+            Some(source) => source.set_muted(muted),
+            None => self.media_events.push((node_id, MediaEvent::Error)),
+        }
+        self.media_elements.insert(
+            node_id,
+            MediaElement {
+                url,
+                source,
+                metadata: None,
+                frame: None,
+                paused: true,
+                ended: false,
+                show_poster: true,
+                current_time: Duration::ZERO,
+                last_tick: None,
+                last_time_update: Duration::ZERO,
+            },
+        );
+
+        // Poll the element until its metadata has loaded
+        self.frame_waker.wake_at(Instant::now());
+    }
+
+    /// Mute or unmute a media element after its `muted` attribute has changed
+    pub fn update_media_muted(&mut self, node_id: usize) {
+        let muted = self.nodes[node_id]
+            .element_data()
+            .is_some_and(|el| el.attr(local_name!("muted")).is_some());
+        let source = self
+            .media_elements
+            .get_mut(&node_id)
+            .and_then(|media| media.source.as_mut());
+        if let Some(source) = source {
+            source.set_muted(muted);
+        }
+    }
+
+    /// The playback state of a media element (if it has a resource)
+    pub fn media_state(&self, node_id: usize) -> Option<MediaState> {
+        self.media_elements.get(&node_id).map(MediaElement::state)
+    }
+
+    /// The video frame which should be displayed by a `<video>` element, or `None` if it should
+    /// display its poster image (or nothing)
+    pub fn media_frame(&self, node_id: usize) -> Option<&RasterImageData> {
+        let media = self.media_elements.get(&node_id)?;
+        let has_poster = self.nodes[node_id]
+            .element_data()
+            .is_some_and(|el| el.raster_image_data().is_some());
+        if media.show_poster && has_poster {
+            return None;
+        }
+        media.frame.as_ref()
+    }
+
+    /// Start (or resume) playing a media element
+    pub fn play_media(&mut self, node_id: usize) {
+        let looping = self.nodes[node_id]
+            .element_data()
+            .is_some_and(|el| el.attr(local_name!("loop")).is_some());
+        let Some(media) = self.media_elements.get_mut(&node_id) else {
+            return;
+        };
+        let Some(source) = media.source.as_mut() else {
+            return;
+        };
+        if !media.paused {
+            return;
+        }
+
+        // Playing media which has ended restarts it
+        if media.ended && !looping {
+            media.current_time = Duration::ZERO;
+            media.last_time_update = Duration::ZERO;
+        }
+        media.paused = false;
+        media.ended = false;
+        media.show_poster = false;
+        media.last_tick = Some(Instant::now());
+        source.set_playing(true, media.current_time);
+
+        self.media_events.push((node_id, MediaEvent::Play));
+        if media.metadata.is_some() {
+            self.media_events.push((node_id, MediaEvent::Playing));
+        }
+        self.frame_waker.wake_at(Instant::now());
+    }
+
+    /// Pause a media element
+    pub fn pause_media(&mut self, node_id: usize) {
+        self.tick_media_element(node_id, Instant::now());
+        let Some(media) = self.media_elements.get_mut(&node_id) else {
+            return;
+        };
+        let Some(source) = media.source.as_mut() else {
+            return;
+        };
+        if media.paused {
+            return;
+        }
+
+        media.paused = true;
+        media.last_tick = None;
+        source.set_playing(false, media.current_time);
+        self.media_events.push((node_id, MediaEvent::TimeUpdate));
+        self.media_events.push((node_id, MediaEvent::Pause));
+    }
+
+    /// Seek a media element to `time` (which is clamped to its duration)
+    pub fn seek_media(&mut self, node_id: usize, time: Duration) {
+        let Some(media) = self.media_elements.get_mut(&node_id) else {
+            return;
+        };
+        let Some(source) = media.source.as_mut() else {
+            return;
+        };
+
+        let duration = media.metadata.and_then(|metadata| metadata.duration);
+        let time = duration.map_or(time, |duration| time.min(duration));
+        media.current_time = time;
+        media.last_time_update = time;
+        media.ended = false;
+        media.show_poster = false;
+        if !media.paused {
+            media.last_tick = Some(Instant::now());
+        }
+        if let Some(frame) = source.video_frame(time) {
+            media.frame = Some(frame);
+        }
+        source.set_playing(!media.paused, time);
+
+        self.media_events.extend([
+            (node_id, MediaEvent::Seeking),
+            (node_id, MediaEvent::TimeUpdate),
+            (node_id, MediaEvent::Seeked),
+        ]);
+    }
+
+    /// Take the media events which have been fired since they were last taken, along with the ids
+    /// of the elements they were fired at
+    pub fn take_media_events(&mut self) -> Vec<(usize, MediaEvent)> {
+        std::mem::take(&mut self.media_events)
+    }
+
+    /// Toggle playback or seek a media element with a `controls` attribute if `(x, y)` (relative
+    /// to its border box) is over its controls. Returns whether the controls were clicked.
+    pub(crate) fn click_media_controls(&mut self, node_id: usize, x: f32, y: f32) -> bool {
+        let node = &self.nodes[node_id];
+        let has_controls = node
+            .element_data()
+            .is_some_and(|el| el.attr(local_name!("controls")).is_some());
+        let Some(state) = self.media_state(node_id).filter(|_| has_controls) else {
+            return false;
+        };
+
+        let layout = node.final_layout;
+        let content_box = Rect::new(
+            (layout.border.left + layout.padding.left) as f64,
+            (layout.border.top + layout.padding.top) as f64,
+            (layout.size.width - layout.border.right - layout.padding.right) as f64,
+            (layout.size.height - layout.border.bottom - layout.padding.bottom) as f64,
+        );
+        let controls = MediaControls::new(content_box);
+        let point = Point::new(x as f64, y as f64);
+
+        if controls.play_button.contains(point) {
+            if state.paused {
+                self.play_media(node_id);
+            } else {
+                self.pause_media(node_id);
+            }
+            return true;
+        }
+        if let Some(position) = controls.track_position(point) {
+            if let Some(duration) = state.duration {
+                self.seek_media(node_id, duration.mul_f64(position));
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Advance a media element to `now`: load its metadata, advance its current time (if it is
+    /// playing) and decode its current frame. Returns whether it needs to be redrawn.
+    fn tick_media_element(&mut self, node_id: usize, now: Instant) -> bool {
+        let Some(element) = self.nodes.get(node_id).and_then(|node| node.element_data()) else {
+            return false;
+        };
+        let looping = element.attr(local_name!("loop")).is_some();
+        let autoplay = element.attr(local_name!("autoplay")).is_some();
+        let Some(media) = self.media_elements.get_mut(&node_id) else {
+            return false;
+        };
+        let Some(source) = media.source.as_mut() else {
+            return false;
+        };
+
+        let mut changed = false;
+        let mut events = Vec::new();
+        let mut should_autoplay = false;
+        if media.metadata.is_none() {
+            let Some(metadata) = source.metadata() else {
+                return false;
+            };
+            media.metadata = Some(metadata);
+            if metadata.duration.is_some() {
+                events.push(MediaEvent::DurationChange);
+            }
+            events.push(MediaEvent::LoadedMetadata);
+            media.frame = source.video_frame(media.current_time);
+            events.push(MediaEvent::LoadedData);
+            if media.paused {
+                should_autoplay = autoplay;
+            } else {
+                events.push(MediaEvent::Playing);
+            }
+
+            // Videos are sized by their natural size
+            self.nodes[node_id].cache.clear();
+            changed = true;
+        }
+
+        if !media.paused {
+            let elapsed = media
+                .last_tick
+                .map(|last_tick| now.saturating_duration_since(last_tick))
+                .unwrap_or_default();
+            media.last_tick = Some(now);
+            media.current_time += elapsed;
+
+            let duration = media.metadata.and_then(|metadata| metadata.duration);
+            if let Some(duration) = duration.filter(|&duration| media.current_time >= duration) {
+                if looping && !duration.is_zero() {
+                    media.current_time = Duration::from_nanos(
+                        (media.current_time.as_nanos() % duration.as_nanos()) as u64,
+                    );
+                    media.last_time_update = media.current_time;
+                    source.set_playing(true, media.current_time);
+                    events.extend([
+                        MediaEvent::Seeking,
+                        MediaEvent::TimeUpdate,
+                        MediaEvent::Seeked,
+                    ]);
+                } else {
+                    media.current_time = duration;
+                    media.paused = true;
+                    media.ended = true;
+                    media.last_tick = None;
+                    source.set_playing(false, duration);
+                    events.extend([MediaEvent::TimeUpdate, MediaEvent::Pause, MediaEvent::Ended]);
+                }
+                media.last_time_update = media.current_time;
+            }
+
+            if media.current_time.abs_diff(media.last_time_update) >= TIME_UPDATE_INTERVAL {
+                media.last_time_update = media.current_time;
+                events.push(MediaEvent::TimeUpdate);
+            }
+
+            if let Some(frame) = source.video_frame(media.current_time) {
+                media.frame = Some(frame);
+            }
+            // The controls show the current time
+            changed = true;
+        }
+
+        self.media_events
+            .extend(events.into_iter().map(|event| (node_id, event)));
+        if should_autoplay {
+            self.play_media(node_id);
+        }
+        changed
+    }
+
+    /// Advance the media elements in the document to `now`. Returns whether any of them need to be
+    /// redrawn.
+    pub fn tick_media(&mut self, now: Instant) -> bool {
+        let node_ids: Vec<usize> = self.media_elements.keys().copied().collect();
+        let mut changed = false;
+        for node_id in node_ids {
+            changed |= self.tick_media_element(node_id, now);
+        }
+        changed
+    }
+
+    /// When the document should next be polled to advance its media elements (if it should)
+    pub fn next_media_frame_time(&self) -> Option<Instant> {
+        self.media_elements
+            .values()
+            .filter_map(|media| {
+                let last_tick = media.last_tick.unwrap_or_else(Instant::now);
+                Some(last_tick + media.next_poll()?)
+            })
+            .min()
+    }
+
+    /// Advance media elements (in this document and its nested documents) and schedule the task
+    /// polling the document to be woken when they next need to be advanced. Returns whether a
+    /// redraw is needed.
+    pub fn poll_media(&mut self, cx: &Context) -> bool {
+        let mut changed = self.tick_media(Instant::now());
+        for sub_document in self.sub_documents.values_mut() {
+            changed |= sub_document.doc.poll_media(cx);
+            // Nested documents have no script to dispatch media events to
+            sub_document.doc.media_events.clear();
+        }
+
+        self.frame_waker.set_waker(cx.waker());
+        if let Some(next_frame_time) = self.next_media_frame_time() {
+            self.frame_waker.wake_at(next_frame_time);
+        }
+        changed
+    }
+}
+
+/// A software `MediaProvider` which plays a generated test pattern instead of decoding media, for
+/// testing media elements headlessly. Every resource it opens has the same metadata, and each
+/// video frame is filled with a different color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TestPatternMediaProvider {
+    pub width: u32,
+    pub height: u32,
+    pub duration: Duration,
+    pub frame_rate: f32,
+}
+
+impl Default for TestPatternMediaProvider {
+    fn default() -> Self {
+        Self {
+            width: 320,
+            height: 180,
+            duration: Duration::from_secs(10),
+            frame_rate: 30.0,
+        }
+    }
+}
+
+impl MediaProvider for TestPatternMediaProvider {
+    type Frame = RasterImageData;
+    fn open(
+        &self,
+        _doc_id: usize,
+        _url: Url,
+        kind: MediaKind,
+    ) -> Option<BoxedMediaSource<RasterImageData>> {
+        Some(Box::new(TestPatternSource {
+            provider: *self,
+            kind,
+            last_frame: None,
+        }))
+    }
+}
+
+struct TestPatternSource {
+    provider: TestPatternMediaProvider,
+    kind: MediaKind,
+    /// The index of the last frame returned
+    last_frame: Option<u64>,
+}
+
+impl TestPatternSource {
+    /// The color of a frame of the test pattern
+    fn frame_color(index: u64) -> [u8; 4] {
+        const COLORS: [[u8; 4]; 6] = [
+            [255, 0, 0, 255],
+            [255, 255, 0, 255],
+            [0, 255, 0, 255],
+            [0, 255, 255, 255],
+            [0, 0, 255, 255],
+            [255, 0, 255, 255],
+        ];
+        COLORS[(index % COLORS.len() as u64) as usize]
+    }
+}
+
+impl MediaSource for TestPatternSource {
+    type Frame = RasterImageData;
+
+    fn metadata(&mut self) -> Option<MediaMetadata> {
+        let TestPatternMediaProvider {
+            width,
+            height,
+            duration,
+            frame_rate,
+        } = self.provider;
+        Some(MediaMetadata {
+            duration: Some(duration),
+            video_size: (self.kind == MediaKind::Video).then_some((width, height)),
+            frame_rate: Some(frame_rate),
+        })
+    }
+
+    fn video_frame(&mut self, time: Duration) -> Option<RasterImageData> {
+        if self.kind != MediaKind::Video {
+            return None;
+        }
+        let index = (time.as_secs_f64() * self.provider.frame_rate as f64) as u64;
+        if self.last_frame.replace(index) == Some(index) {
+            return None;
+        }
+
+        let TestPatternMediaProvider { width, height, .. } = self.provider;
+        let data = Self::frame_color(index).repeat((width * height) as usize);
+        Some(RasterImageData::new(width, height, Arc::new(data)))
+    }
+}
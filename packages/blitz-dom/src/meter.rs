@@ -0,0 +1,148 @@
+//! `<progress>` and `<meter>` elements.
+//!
+//! Both elements are laid out as replaced-like widgets (their contents are only fallback content for
+//! browsers which don't support them) and painted natively by the renderer. The bar is drawn in the
+//! element's `color` over its background, so it can be styled with `:indeterminate` (for progress
+//! bars) and `:-moz-meter-optimum`, `:-moz-meter-sub-optimum` and `:-moz-meter-sub-sub-optimum`
+//! (for meters).
+//!
+//! See <https://html.spec.whatwg.org/multipage/form-elements.html#the-progress-element> and
+//! <https://html.spec.whatwg.org/multipage/form-elements.html#the-meter-element>
+
+use markup5ever::{LocalName, local_name};
+use style_dom::ElementState;
+
+use crate::form::parse_number;
+use crate::node::ElementNodeData;
+
+/// Which region of a `<meter>` element's range its value is in, relative to its optimum value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeterRegion {
+    Optimum,
+    SubOptimum,
+    SubSubOptimum,
+}
+
+impl MeterRegion {
+    pub(crate) fn element_state(self) -> ElementState {
+        match self {
+            MeterRegion::Optimum => ElementState::OPTIMUM,
+            MeterRegion::SubOptimum => ElementState::SUB_OPTIMUM,
+            MeterRegion::SubSubOptimum => ElementState::SUB_SUB_OPTIMUM,
+        }
+    }
+}
+
+/// The resolved `min`, `max`, `low`, `high`, `optimum` and `value` of a `<meter>` element
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterValues {
+    pub min: f64,
+    pub max: f64,
+    pub low: f64,
+    pub high: f64,
+    pub optimum: f64,
+    pub value: f64,
+}
+
+impl MeterValues {
+    /// The position of the value within the meter's range (between 0 and 1)
+    pub fn position(&self) -> f64 {
+        if self.max > self.min {
+            (self.value - self.min) / (self.max - self.min)
+        } else {
+            0.0
+        }
+    }
+
+    /// The region of the range that the value is in
+    pub fn region(&self) -> MeterRegion {
+        let MeterValues {
+            low,
+            high,
+            optimum,
+            value,
+            ..
+        } = *self;
+        if optimum < low {
+            if value <= low {
+                MeterRegion::Optimum
+            } else if value <= high {
+                MeterRegion::SubOptimum
+            } else {
+                MeterRegion::SubSubOptimum
+            }
+        } else if optimum > high {
+            if value >= high {
+                MeterRegion::Optimum
+            } else if value >= low {
+                MeterRegion::SubOptimum
+            } else {
+                MeterRegion::SubSubOptimum
+            }
+        } else if value >= low && value <= high {
+            MeterRegion::Optimum
+        } else {
+            MeterRegion::SubOptimum
+        }
+    }
+}
+
+impl ElementNodeData {
+    fn number_attr(&self, name: LocalName) -> Option<f64> {
+        self.attr(name).and_then(parse_number)
+    }
+
+    /// The position of a `<progress>` element's bar (between 0 and 1), or `None` if the element is
+    /// not a progress bar or is indeterminate (has no `value` attribute)
+    pub fn progress_position(&self) -> Option<f64> {
+        if self.name.local != local_name!("progress") {
+            return None;
+        }
+        self.attr(local_name!("value"))?;
+
+        let max = self
+            .number_attr(local_name!("max"))
+            .filter(|&max| max > 0.0)
+            .unwrap_or(1.0);
+        let value = self
+            .number_attr(local_name!("value"))
+            .unwrap_or(0.0)
+            .clamp(0.0, max);
+        Some(value / max)
+    }
+
+    /// The resolved values of a `<meter>` element, or `None` if the element is not a meter
+    pub fn meter_values(&self) -> Option<MeterValues> {
+        if self.name.local != local_name!("meter") {
+            return None;
+        }
+
+        let min = self.number_attr(local_name!("min")).unwrap_or(0.0);
+        let max = self.number_attr(local_name!("max")).unwrap_or(1.0).max(min);
+        let value = self
+            .number_attr(local_name!("value"))
+            .unwrap_or(0.0)
+            .clamp(min, max);
+        let low = self
+            .number_attr(local_name!("low"))
+            .unwrap_or(min)
+            .clamp(min, max);
+        let high = self
+            .number_attr(local_name!("high"))
+            .unwrap_or(max)
+            .clamp(low, max);
+        let optimum = self
+            .number_attr(local_name!("optimum"))
+            .unwrap_or((min + max) / 2.0)
+            .clamp(min, max);
+
+        Some(MeterValues {
+            min,
+            max,
+            low,
+            high,
+            optimum,
+            value,
+        })
+    }
+}
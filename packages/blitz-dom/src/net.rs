@@ -1,5 +1,5 @@
 use selectors::context::QuirksMode;
-use std::{io::Cursor, sync::Arc, sync::atomic::AtomicBool};
+use std::{io::Cursor, sync::Arc, sync::atomic::AtomicBool, time::Duration};
 use style::{
     font_face::{FontFaceSourceFormat, FontFaceSourceFormatKeyword, Source},
     media_queries::MediaList,
@@ -16,19 +16,44 @@ use style::{
     values::{CssUrl, SourceLocation},
 };
 
-use blitz_traits::net::{Bytes, NetHandler, Request, SharedCallback, SharedProvider};
+use blitz_traits::encoding::{decode_css, decode_html};
+use blitz_traits::net::{
+    BoxedHandler, Bytes, NetCallback, NetHandler, NetProvider, Request, Response, SharedCallback,
+    SharedProvider,
+};
 
 use url::Url;
 
+use crate::node::{ImageFrame, RasterImageData};
 use crate::util::ImageType;
 
 #[derive(Clone, Debug)]
 pub enum Resource {
     Image(usize, ImageType, u32, u32, Arc<Vec<u8>>),
+    /// The frames of an animated image
+    AnimatedImage(usize, ImageType, Vec<ImageFrame>),
     #[cfg(feature = "svg")]
     Svg(usize, ImageType, Box<usvg::Tree>),
     Css(usize, DocumentStyleSheet),
     Font(Bytes),
+    /// The HTML source of an `<iframe>`'s nested document
+    Html {
+        node_id: usize,
+        /// The generation of the load which requested it (see `BaseDocument::load_iframe`)
+        generation: u64,
+        url: Url,
+        /// The `Content-Type` of the response (if it had one)
+        content_type: Option<String>,
+        html: String,
+    },
+    /// A resource for the nested document of the `<iframe>` element with the given node id
+    SubDocument {
+        node_id: usize,
+        /// The generation of the load which created the nested document (see
+        /// `BaseDocument::load_iframe`)
+        generation: u64,
+        resource: Box<Resource>,
+    },
     None,
 }
 pub struct CssHandler {
@@ -97,11 +122,17 @@ impl ServoStylesheetLoader for StylesheetLoader {
                 bytes: Bytes,
                 callback: SharedCallback<Self::Data>,
             ) {
-                let Ok(css) = std::str::from_utf8(&bytes) else {
-                    callback.call(doc_id, Err(Some(String::from("Invalid UTF8"))));
-                    return;
-                };
-                let escaped_css = html_escape::decode_html_entities(css);
+                self.response(doc_id, Response::new(bytes, None), callback)
+            }
+
+            fn response(
+                self: Box<Self>,
+                doc_id: usize,
+                response: Response,
+                callback: SharedCallback<Self::Data>,
+            ) {
+                let css = decode_css(&response.bytes, response.content_type.as_deref());
+                let escaped_css = html_escape::decode_html_entities(&css);
                 Stylesheet::update_from_str(
                     &self.sheet,
                     &escaped_css,
@@ -133,12 +164,17 @@ impl ServoStylesheetLoader for StylesheetLoader {
 impl NetHandler for CssHandler {
     type Data = Resource;
     fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
-        let Ok(css) = std::str::from_utf8(&bytes) else {
-            callback.call(doc_id, Err(Some(String::from("Invalid UTF8"))));
-            return;
-        };
+        self.response(doc_id, Response::new(bytes, None), callback)
+    }
 
-        let escaped_css = html_escape::decode_html_entities(css);
+    fn response(
+        self: Box<Self>,
+        doc_id: usize,
+        response: Response,
+        callback: SharedCallback<Resource>,
+    ) {
+        let css = decode_css(&response.bytes, response.content_type.as_deref());
+        let escaped_css = html_escape::decode_html_entities(&css);
         let sheet = Stylesheet::from_str(
             &escaped_css,
             self.source_url.into(),
@@ -295,6 +331,79 @@ fn fetch_font_face(
         });
 }
 
+/// Frames displayed for less than this are displayed for `DEFAULT_FRAME_DELAY` instead (as in
+/// browsers, because many GIFs specify a delay of zero)
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// The maximum number of frames decoded from an animated image
+const MAX_ANIMATION_FRAMES: u64 = 1000;
+/// The maximum size of the decoded frames of an animated image (each frame is an RGBA8 image of
+/// the whole canvas)
+const MAX_ANIMATION_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Decode the frames of an animated GIF, APNG or WebP image. Returns `None` if the image isn't an
+/// animation (or has a single frame), in which case it should be decoded as a still image.
+///
+/// At most `MAX_ANIMATION_FRAMES` frames totalling `MAX_ANIMATION_BYTES` are decoded. Longer
+/// animations are truncated, and images too large for more than one frame are shown as a still.
+fn decode_animated_image(bytes: &[u8]) -> Option<Vec<ImageFrame>> {
+    use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+    use image::{AnimationDecoder, ImageDecoder};
+
+    let format = image::guess_format(bytes).ok()?;
+    let (dimensions, frames) = match format {
+        image::ImageFormat::Gif => {
+            let decoder = GifDecoder::new(Cursor::new(bytes)).ok()?;
+            (decoder.dimensions(), decoder.into_frames())
+        }
+        image::ImageFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(bytes)).ok()?;
+            if !decoder.is_apng().ok()? {
+                return None;
+            }
+            (decoder.dimensions(), decoder.apng().ok()?.into_frames())
+        }
+        image::ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(bytes)).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            (decoder.dimensions(), decoder.into_frames())
+        }
+        _ => return None,
+    };
+
+    let (width, height) = dimensions;
+    let frame_bytes = u64::from(width) * u64::from(height) * 4;
+    let max_frames = MAX_ANIMATION_FRAMES.min(MAX_ANIMATION_BYTES / frame_bytes.max(1));
+    if max_frames < 2 {
+        return None;
+    }
+    let frames = frames.take(max_frames as usize);
+
+    let frames: Vec<ImageFrame> = frames
+        .map(|frame| {
+            let frame = frame.ok()?;
+            let delay = Duration::from(frame.delay());
+            let image = frame.into_buffer();
+            Some(ImageFrame {
+                image: RasterImageData::new(
+                    image.width(),
+                    image.height(),
+                    Arc::new(image.into_raw()),
+                ),
+                delay: if delay < MIN_FRAME_DELAY {
+                    DEFAULT_FRAME_DELAY
+                } else {
+                    delay
+                },
+            })
+        })
+        .collect::<Option<_>>()?;
+    (frames.len() > 1).then_some(frames)
+}
+
 pub struct ImageHandler(usize, ImageType);
 impl ImageHandler {
     pub fn new(node_id: usize, kind: ImageType) -> Self {
@@ -304,6 +413,12 @@ impl ImageHandler {
 impl NetHandler for ImageHandler {
     type Data = Resource;
     fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        // Try parse animated image
+        if let Some(frames) = decode_animated_image(&bytes) {
+            callback.call(doc_id, Ok(Resource::AnimatedImage(self.0, self.1, frames)));
+            return;
+        }
+
         // Try parse image
         if let Ok(image) = image::ImageReader::new(Cursor::new(&bytes))
             .with_guessed_format()
@@ -336,3 +451,133 @@ impl NetHandler for ImageHandler {
         callback.call(doc_id, Err(Some(String::from("Could not parse image"))))
     }
 }
+
+pub struct HtmlHandler {
+    pub node: usize,
+    pub generation: u64,
+    pub url: Url,
+}
+impl NetHandler for HtmlHandler {
+    type Data = Resource;
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        self.response(doc_id, Response::new(bytes, None), callback)
+    }
+
+    fn response(
+        self: Box<Self>,
+        doc_id: usize,
+        response: Response,
+        callback: SharedCallback<Resource>,
+    ) {
+        let html = decode_html(&response.bytes, response.content_type.as_deref());
+        let resource = Resource::Html {
+            node_id: self.node,
+            generation: self.generation,
+            url: self.url,
+            content_type: response.content_type,
+            html,
+        };
+        callback.call(doc_id, Ok(resource))
+    }
+}
+
+/// The net provider of an `<iframe>`'s nested document.
+///
+/// Requests are made on behalf of the parent document (as embedders only know about top-level
+/// documents), and the resulting resources are wrapped in `Resource::SubDocument` so that the parent
+/// document can forward them to the nested document.
+pub(crate) struct SubDocumentNetProvider {
+    pub(crate) parent_doc_id: usize,
+    pub(crate) iframe_node_id: usize,
+    pub(crate) generation: u64,
+    pub(crate) provider: SharedProvider<Resource>,
+}
+impl NetProvider for SubDocumentNetProvider {
+    type Data = Resource;
+    fn fetch(&self, _doc_id: usize, request: Request, handler: BoxedHandler<Resource>) {
+        self.provider.fetch(
+            self.parent_doc_id,
+            request,
+            Box::new(SubDocumentHandler {
+                iframe_node_id: self.iframe_node_id,
+                generation: self.generation,
+                handler,
+            }),
+        );
+    }
+}
+
+struct SubDocumentHandler {
+    iframe_node_id: usize,
+    generation: u64,
+    handler: BoxedHandler<Resource>,
+}
+impl NetHandler for SubDocumentHandler {
+    type Data = Resource;
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        self.response(doc_id, Response::new(bytes, None), callback)
+    }
+
+    fn response(
+        self: Box<Self>,
+        doc_id: usize,
+        response: Response,
+        callback: SharedCallback<Resource>,
+    ) {
+        let callback = Arc::new(SubDocumentCallback {
+            iframe_node_id: self.iframe_node_id,
+            generation: self.generation,
+            callback,
+        });
+        self.handler.response(doc_id, response, callback)
+    }
+}
+
+struct SubDocumentCallback {
+    iframe_node_id: usize,
+    generation: u64,
+    callback: SharedCallback<Resource>,
+}
+impl NetCallback for SubDocumentCallback {
+    type Data = Resource;
+    fn call(&self, doc_id: usize, result: Result<Resource, Option<String>>) {
+        let result = result.map(|resource| Resource::SubDocument {
+            node_id: self.iframe_node_id,
+            generation: self.generation,
+            resource: Box::new(resource),
+        });
+        self.callback.call(doc_id, result)
+    }
+}
+
+#[test]
+fn limits_the_frames_decoded_from_animated_images() {
+    use image::codecs::gif::{GifDecoder, GifEncoder};
+    use image::{Frame, RgbaImage};
+
+    let encode_gif = |frame_count: usize| {
+        let mut bytes = Vec::new();
+        let mut encoder = GifEncoder::new(&mut bytes);
+        let frames = (0..frame_count).map(|i| {
+            let pixel = image::Rgba([i as u8, 0, 0, 255]);
+            Frame::new(RgbaImage::from_pixel(1, 1, pixel))
+        });
+        encoder.encode_frames(frames).unwrap();
+        drop(encoder);
+        bytes
+    };
+
+    let frames = decode_animated_image(&encode_gif(3)).unwrap();
+    assert_eq!(frames.len(), 3);
+
+    // Long animations are truncated
+    let frames = decode_animated_image(&encode_gif(1100)).unwrap();
+    assert_eq!(frames.len(), MAX_ANIMATION_FRAMES as usize);
+
+    // Animations whose canvas is too large to hold two frames are treated as still images
+    let mut bytes = encode_gif(3);
+    bytes[6..10].copy_from_slice(&[0x10, 0x27, 0x10, 0x27]);
+    let decoder = GifDecoder::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(image::ImageDecoder::dimensions(&decoder), (10000, 10000));
+    assert!(decode_animated_image(&bytes).is_none());
+}
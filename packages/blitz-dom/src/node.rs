@@ -11,12 +11,14 @@ use std::fmt::Write;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 use style::Atom;
 use style::invalidation::element::restyle_hints::RestyleHint;
 use style::properties::ComputedValues;
 use style::properties::generated::longhands::position::computed_value::T as Position;
 use style::selector_parser::PseudoElement;
 use style::stylesheets::UrlExtraData;
+use style::stylist::CascadeData;
 use style::values::computed::Display;
 use style::values::specified::box_::{DisplayInside, DisplayOutside};
 use style::{
@@ -34,7 +36,9 @@ use taffy::{
 };
 use url::Url;
 
-use crate::layout::table::TableContext;
+use crate::canvas::CanvasSurface;
+pub use crate::layout::table::{CollapsedBorder, CollapsedBorders, TableContext};
+use crate::responsive_image::ImageSource;
 use blitz_traits::{BlitzMouseButtonEvent, DomEventData, HitResult};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -75,9 +79,22 @@ pub struct Node {
     // Pseudo element nodes
     pub before: Option<usize>,
     pub after: Option<usize>,
+    /// The `::backdrop` of an element in the top layer
+    pub backdrop: Option<usize>,
+
+    // Shadow DOM
+    /// The shadow root attached to this element (if it is a shadow host)
+    pub shadow_root: Option<usize>,
+    /// The `<slot>` element this node is assigned to (if it is slotted into a shadow tree)
+    pub assigned_slot: Option<usize>,
+    /// The nodes assigned to this node (if it is a `<slot>` element)
+    pub assigned_nodes: Vec<usize>,
 
     // Taffy layout data:
     pub style: Style,
+    /// A copy of `style` with the intrinsic sizing keywords of the node resolved to lengths (see
+    /// `layout::intrinsic`). Taffy reads this in place of `style` when it is set.
+    pub(crate) intrinsic_style: Option<Box<Style>>,
     pub has_snapshot: bool,
     pub snapshot_handled: AtomicBool,
     pub display_outer: DisplayOuter,
@@ -116,8 +133,14 @@ impl Node {
 
             before: None,
             after: None,
+            backdrop: None,
+
+            shadow_root: None,
+            assigned_slot: None,
+            assigned_nodes: Vec::new(),
 
             style: Default::default(),
+            intrinsic_style: None,
             has_snapshot: false,
             snapshot_handled: AtomicBool::new(false),
             display_outer: DisplayOuter::Block,
@@ -146,10 +169,40 @@ impl Node {
         }
     }
 
+    /// The children of this node in the flat tree. This is the children of the node's shadow root if
+    /// it is a shadow host, the nodes assigned to it if it is a `<slot>` with assigned nodes, and
+    /// its regular children otherwise.
+    pub fn flat_tree_children(&self) -> &[usize] {
+        if let Some(shadow_root_id) = self.shadow_root {
+            &self.tree()[shadow_root_id].children
+        } else if !self.assigned_nodes.is_empty() {
+            &self.assigned_nodes
+        } else {
+            &self.children
+        }
+    }
+
+    /// The Taffy style the node is laid out with: `style` with any intrinsic sizing keywords
+    /// resolved
+    pub(crate) fn layout_style(&self) -> &Style {
+        self.intrinsic_style.as_deref().unwrap_or(&self.style)
+    }
+
     pub(crate) fn display_style(&self) -> Option<Display> {
         Some(self.primary_styles().as_ref()?.clone_display())
     }
 
+    /// Whether the node is out-of-flow due to `position: absolute` or `position: fixed`
+    pub fn is_absolutely_positioned(&self) -> bool {
+        self.primary_styles()
+            .is_some_and(|style| style.clone_position().is_absolutely_positioned())
+    }
+
+    pub fn is_whitespace_text(&self) -> bool {
+        self.text_data()
+            .is_some_and(|text| text.content.chars().all(|c| c.is_ascii_whitespace()))
+    }
+
     pub fn is_or_contains_block(&self) -> bool {
         let style = self.primary_styles();
         let style = style.as_ref();
@@ -211,9 +264,10 @@ impl Node {
         self.element_state.contains(ElementState::HOVER)
     }
 
+    /// Mark the node as focussed. This doesn't make it match `:focus-visible`: the document only
+    /// does that for keyboard focus and text inputs.
     pub fn focus(&mut self) {
-        self.element_state
-            .insert(ElementState::FOCUS | ElementState::FOCUSRING);
+        self.element_state.insert(ElementState::FOCUS);
         self.set_restyle_hint(RestyleHint::restyle_subtree());
     }
 
@@ -249,6 +303,9 @@ pub enum NodeKind {
     AnonymousBlock,
     Text,
     Comment,
+    ProcessingInstruction,
+    ShadowRoot,
+    DocumentFragment,
 }
 
 /// The different kinds of nodes in the DOM.
@@ -268,6 +325,15 @@ pub enum NodeData {
 
     /// A comment.
     Comment,
+
+    /// A processing instruction (XML documents only), such as `<?xml-stylesheet href="a.css"?>`
+    ProcessingInstruction { target: String, data: String },
+
+    /// The root of a shadow tree attached to an element
+    ShadowRoot(ShadowRootData),
+
+    /// A document fragment, such as the contents of a `<template>` element
+    DocumentFragment,
     // Comment { contents: String },
 
     // /// A `DOCTYPE` with name, public id, and system id. See
@@ -317,6 +383,47 @@ impl NodeData {
             NodeData::AnonymousBlock(_) => NodeKind::AnonymousBlock,
             NodeData::Text(_) => NodeKind::Text,
             NodeData::Comment => NodeKind::Comment,
+            NodeData::ProcessingInstruction { .. } => NodeKind::ProcessingInstruction,
+            NodeData::ShadowRoot(_) => NodeKind::ShadowRoot,
+            NodeData::DocumentFragment => NodeKind::DocumentFragment,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowRootMode {
+    Open,
+    Closed,
+}
+
+impl ShadowRootMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "open" => Some(Self::Open),
+            "closed" => Some(Self::Closed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadowRootData {
+    /// The element this shadow root is attached to
+    pub host: usize,
+    pub mode: ShadowRootMode,
+    /// The cascade data computed from the stylesheets within the shadow tree
+    pub(crate) style_data: Option<ServoArc<CascadeData>>,
+    /// The `<slot>` elements in the shadow tree when the host's children were last assigned
+    pub(crate) slots: Vec<usize>,
+}
+
+impl ShadowRootData {
+    pub fn new(host: usize, mode: ShadowRootMode) -> Self {
+        Self {
+            host,
+            mode,
+            style_data: None,
+            slots: Vec::new(),
         }
     }
 }
@@ -370,6 +477,10 @@ pub struct ElementNodeData {
 
     /// The element's template contents (\<template\> elements only)
     pub template_contents: Option<usize>,
+
+    /// The source selected from the element's `src` and `srcset` attributes (\<img\> elements), or
+    /// the element's `poster` attribute (\<video\> elements)
+    pub image_source: Option<ImageSource>,
     // /// Whether the node is a [HTML integration point] (https://html.spec.whatwg.org/multipage/#html-integration-point)
     // pub mathml_annotation_xml_integration_point: bool,
 }
@@ -392,6 +503,7 @@ impl ElementNodeData {
             list_item_data: None,
             node_specific_data: NodeSpecificData::None,
             template_contents: None,
+            image_source: None,
             background_images: Vec::new(),
         };
         data.flush_is_focussable();
@@ -427,10 +539,7 @@ impl ElementNodeData {
     }
 
     pub fn raster_image_data(&self) -> Option<&RasterImageData> {
-        match self.image_data()? {
-            ImageData::Raster(data) => Some(data),
-            _ => None,
-        }
+        self.image_data()?.raster()
     }
 
     pub fn raster_image_data_mut(&mut self) -> Option<&mut RasterImageData> {
@@ -470,6 +579,13 @@ impl ElementNodeData {
         }
     }
 
+    pub fn table_context(&self) -> Option<&TableContext> {
+        match &self.node_specific_data {
+            NodeSpecificData::TableRoot(data) => Some(&**data),
+            _ => None,
+        }
+    }
+
     pub fn checkbox_input_checked(&self) -> Option<bool> {
         match self.node_specific_data {
             NodeSpecificData::CheckboxInput(checked) => Some(checked),
@@ -501,13 +617,14 @@ impl ElementNodeData {
                     if [local_name!("a"), local_name!("area")].contains(&self.name.local) {
                         self.attr(local_name!("href")).is_some()
                     } else {
-                        const DEFAULT_FOCUSSABLE_ELEMENTS: [LocalName; 6] = [
+                        const DEFAULT_FOCUSSABLE_ELEMENTS: [LocalName; 7] = [
                             local_name!("button"),
                             local_name!("input"),
                             local_name!("select"),
                             local_name!("textarea"),
                             local_name!("frame"),
                             local_name!("iframe"),
+                            local_name!("summary"),
                         ];
                         DEFAULT_FOCUSSABLE_ELEMENTS.contains(&self.name.local)
                     }
@@ -557,13 +674,59 @@ impl RasterImageData {
     }
 }
 
+/// A frame of an animated image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageFrame {
+    /// The frame's image (the full canvas of the animation)
+    pub image: RasterImageData,
+    /// How long the frame is displayed for
+    pub delay: Duration,
+}
+
+/// The frames of an animated image (GIF, APNG or animated WebP), and its current frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedImageData {
+    pub frames: Vec<ImageFrame>,
+    /// The index of the frame which is currently displayed
+    pub current_frame: usize,
+    /// How long the current frame has been displayed for
+    pub frame_elapsed: Duration,
+}
+
+impl AnimatedImageData {
+    pub fn new(frames: Vec<ImageFrame>) -> Self {
+        Self {
+            frames,
+            current_frame: 0,
+            frame_elapsed: Duration::ZERO,
+        }
+    }
+
+    /// The frame which is currently displayed
+    pub fn current_frame(&self) -> &ImageFrame {
+        &self.frames[self.current_frame]
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ImageData {
     Raster(RasterImageData),
+    Animated(Box<AnimatedImageData>),
     #[cfg(feature = "svg")]
     Svg(Box<usvg::Tree>),
     None,
 }
+
+impl ImageData {
+    /// The raster image to display (the current frame of an animated image)
+    pub fn raster(&self) -> Option<&RasterImageData> {
+        match self {
+            ImageData::Raster(data) => Some(data),
+            ImageData::Animated(data) => Some(&data.current_frame().image),
+            _ => None,
+        }
+    }
+}
 #[cfg(feature = "svg")]
 impl From<usvg::Tree> for ImageData {
     fn from(value: usvg::Tree) -> Self {
@@ -603,6 +766,8 @@ pub struct TextInputData {
     pub editor: Box<parley::PlainEditor<TextBrush>>,
     /// Whether the input is a singleline or multiline input
     pub is_multiline: bool,
+    /// The laid out `placeholder` text, shown while the input is empty
+    pub placeholder: Option<Box<parley::layout::Layout<TextBrush>>>,
 }
 
 // FIXME: Implement Clone for PlainEditor
@@ -618,6 +783,7 @@ impl TextInputData {
         Self {
             editor,
             is_multiline,
+            placeholder: None,
         }
     }
 
@@ -645,6 +811,8 @@ pub enum NodeSpecificData {
     TextInput(TextInputData),
     /// Checkbox checked state
     CheckboxInput(bool),
+    /// Drawing surface (\<canvas\> elements only)
+    Canvas(CanvasSurface),
     /// No data (for nodes that don't need any node-specific data)
     None,
 }
@@ -654,6 +822,7 @@ impl std::fmt::Debug for NodeSpecificData {
         match self {
             NodeSpecificData::Image(data) => match **data {
                 ImageData::Raster(_) => f.write_str("NodeSpecificData::Image(Raster)"),
+                ImageData::Animated(_) => f.write_str("NodeSpecificData::Image(Animated)"),
                 #[cfg(feature = "svg")]
                 ImageData::Svg(_) => f.write_str("NodeSpecificData::Image(Svg)"),
                 ImageData::None => f.write_str("NodeSpecificData::Image(None)"),
@@ -661,6 +830,7 @@ impl std::fmt::Debug for NodeSpecificData {
             NodeSpecificData::TableRoot(_) => f.write_str("NodeSpecificData::TableRoot"),
             NodeSpecificData::TextInput(_) => f.write_str("NodeSpecificData::TextInput"),
             NodeSpecificData::CheckboxInput(_) => f.write_str("NodeSpecificData::CheckboxInput"),
+            NodeSpecificData::Canvas(_) => f.write_str("NodeSpecificData::Canvas"),
             NodeSpecificData::None => f.write_str("NodeSpecificData::None"),
         }
     }
@@ -676,6 +846,10 @@ impl Default for NodeSpecificData {
 pub struct ListItemLayout {
     pub marker: Marker,
     pub position: ListItemLayoutPosition,
+    /// The `list-item` counter value and style which the marker was created from. The marker is
+    /// only recreated if either of these change.
+    pub(crate) value: i32,
+    pub(crate) style: ServoArc<ComputedValues>,
 }
 
 //We seperate chars from strings in order to optimise rendering - ie not needing to
@@ -727,6 +901,20 @@ impl TextBrush {
 pub struct TextLayout {
     pub text: String,
     pub layout: parley::layout::Layout<TextBrush>,
+    /// The laid out `text-overflow` string (if any), drawn in place of content that overflows the
+    /// end of a line
+    pub ellipsis: Option<parley::layout::Layout<TextBrush>>,
+    /// The laid out hyphen drawn at the end of lines which break at a soft hyphen (if the text
+    /// contains any)
+    pub hyphen: Option<parley::layout::Layout<TextBrush>>,
+}
+
+impl TextLayout {
+    /// Whether a line of the layout wraps at a soft hyphen (and so ends with a visible hyphen)
+    pub fn breaks_at_soft_hyphen(&self, line: &parley::layout::Line<TextBrush>) -> bool {
+        let end = line.text_range().end;
+        end < self.text.len() && self.text[..end].ends_with('\u{00AD}')
+    }
 }
 
 impl std::fmt::Debug for TextLayout {
@@ -888,7 +1076,10 @@ impl Node {
                 "COMMENT",
                 // &std::str::from_utf8(data.contents.as_bytes().split_at(10).0).unwrap_or("INVALID UTF8")
             ),
+            NodeData::ProcessingInstruction { target, .. } => write!(s, "PI {target}"),
             NodeData::AnonymousBlock(_) => write!(s, "AnonymousBlock"),
+            NodeData::ShadowRoot(data) => write!(s, "#shadow-root ({:?})", data.mode),
+            NodeData::DocumentFragment => write!(s, "#document-fragment"),
             NodeData::Element(data) => {
                 let name = &data.name;
                 let class = self.attr(local_name!("class")).unwrap_or("");
@@ -901,7 +1092,7 @@ impl Node {
                 } else {
                     write!(s, "<{}> ({:?})", name.local, self.display_outer)
                 }
-            } // NodeData::ProcessingInstruction { .. } => write!(s, "ProcessingInstruction"),
+            }
         }
         .unwrap();
         s
@@ -914,7 +1105,6 @@ impl Node {
     }
 
     pub fn write_outer_html(&self, writer: &mut String) {
-        let has_children = !self.children.is_empty();
         let current_color = self
             .primary_styles()
             .map(|style| style.clone_color())
@@ -923,12 +1113,24 @@ impl Node {
         match &self.data {
             NodeData::Document => {}
             NodeData::Comment => {}
+            NodeData::ProcessingInstruction { target, data } => {
+                write!(writer, "<?{target} {data}?>").unwrap();
+            }
             NodeData::AnonymousBlock(_) => {}
+            NodeData::ShadowRoot(_) => {}
+            NodeData::DocumentFragment => {}
             // NodeData::Doctype { name, .. } => write!(s, "DOCTYPE {name}"),
             NodeData::Text(data) => {
                 writer.push_str(data.content.as_str());
             }
             NodeData::Element(data) => {
+                // The contents of a template are serialized as its children
+                let children = match data.template_contents {
+                    Some(fragment_id) => &self.tree()[fragment_id].children,
+                    None => &self.children,
+                };
+                let has_children = !children.is_empty();
+
                 writer.push('<');
                 writer.push_str(&data.name.local);
 
@@ -954,7 +1156,7 @@ impl Node {
                 writer.push('>');
 
                 if has_children {
-                    for &child_id in &self.children {
+                    for &child_id in children {
                         self.tree()[child_id].write_outer_html(writer);
                     }
 
@@ -0,0 +1,185 @@
+//! The popover API.
+//!
+//! Elements with a `popover` attribute are hidden until they are shown, either with `show_popover`
+//! or by activating a button whose `popovertarget` attribute references them. Open popovers are in
+//! the top layer.
+//!
+//! Opening an "auto" popover closes any other auto popovers which don't contain it, and open auto
+//! popovers are "light dismissed" by clicking outside of them or pressing Escape. "Manual" popovers
+//! are only closed explicitly.
+//!
+//! See <https://html.spec.whatwg.org/multipage/popover.html>
+
+use markup5ever::{LocalName, local_name};
+use style::invalidation::element::restyle_hints::RestyleHint;
+use style_dom::ElementState;
+
+use crate::BaseDocument;
+
+/// The state of an element's `popover` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopoverState {
+    Auto,
+    Manual,
+}
+
+impl BaseDocument {
+    /// The popover state of an element, or `None` if it doesn't have a `popover` attribute
+    pub fn popover_state(&self, node_id: usize) -> Option<PopoverState> {
+        let value = self.nodes.get(node_id)?.attr(LocalName::from("popover"))?;
+        if value.is_empty() || value.eq_ignore_ascii_case("auto") {
+            Some(PopoverState::Auto)
+        } else {
+            Some(PopoverState::Manual)
+        }
+    }
+
+    /// Whether a popover is showing
+    pub fn is_popover_open(&self, node_id: usize) -> bool {
+        self.nodes
+            .get(node_id)
+            .is_some_and(|node| node.element_state.contains(ElementState::POPOVER_OPEN))
+    }
+
+    /// Show a popover (the equivalent of `element.showPopover()`)
+    pub fn show_popover(&mut self, node_id: usize) {
+        let Some(state) = self.popover_state(node_id) else {
+            return;
+        };
+        if self.is_popover_open(node_id) || !self.is_connected(node_id) {
+            return;
+        }
+
+        // Showing an auto popover closes other auto popovers, except for those which contain it
+        if state == PopoverState::Auto {
+            self.hide_auto_popovers_except(Some(node_id));
+        }
+
+        self.snapshot_node_and(node_id, |node| {
+            node.element_state.insert(ElementState::POPOVER_OPEN);
+            node.set_restyle_hint(RestyleHint::restyle_subtree());
+        });
+        self.add_to_top_layer(node_id);
+    }
+
+    /// Hide a popover (the equivalent of `element.hidePopover()`)
+    pub fn hide_popover(&mut self, node_id: usize) {
+        if !self.is_popover_open(node_id) {
+            return;
+        }
+
+        // Auto popovers nested within this one are hidden along with it
+        if self.popover_state(node_id) == Some(PopoverState::Auto) {
+            let nested: Vec<usize> = self
+                .top_layer()
+                .filter(|&id| {
+                    id != node_id
+                        && self.is_popover_open(id)
+                        && self.popover_state(id) == Some(PopoverState::Auto)
+                        && self.is_inclusive_ancestor(node_id, id)
+                })
+                .collect();
+            for id in nested.into_iter().rev() {
+                self.hide_popover(id);
+            }
+        }
+
+        self.snapshot_node_and(node_id, |node| {
+            node.element_state.remove(ElementState::POPOVER_OPEN);
+            node.set_restyle_hint(RestyleHint::restyle_subtree());
+        });
+        self.remove_from_top_layer(node_id);
+    }
+
+    /// Show a popover if it is hidden, or hide it if it is showing
+    pub fn toggle_popover(&mut self, node_id: usize) {
+        if self.is_popover_open(node_id) {
+            self.hide_popover(node_id);
+        } else {
+            self.show_popover(node_id);
+        }
+    }
+
+    /// Hide all open auto popovers, except for those which contain `node_id`
+    pub(crate) fn hide_auto_popovers_except(&mut self, node_id: Option<usize>) {
+        let to_hide: Vec<usize> = self
+            .top_layer()
+            .filter(|&id| {
+                self.is_popover_open(id)
+                    && self.popover_state(id) == Some(PopoverState::Auto)
+                    && !node_id.is_some_and(|node_id| self.is_inclusive_ancestor(id, node_id))
+            })
+            .collect();
+        for id in to_hide.into_iter().rev() {
+            self.hide_popover(id);
+        }
+    }
+
+    /// Light dismiss open auto popovers in response to a click on `target`. Popovers which contain
+    /// the target are kept open, as is the popover controlled by a `popovertarget` button which
+    /// contains the target (which the button will then toggle).
+    pub(crate) fn light_dismiss_popovers(&mut self, target: Option<usize>) {
+        let invoked_popover = target.and_then(|target| {
+            let mut current = Some(target);
+            while let Some(id) = current {
+                if let Some(popover_id) = self.popover_target(id) {
+                    return Some(popover_id);
+                }
+                current = self.nodes[id].parent;
+            }
+            None
+        });
+
+        let to_hide: Vec<usize> = self
+            .top_layer()
+            .filter(|&id| {
+                self.is_popover_open(id)
+                    && self.popover_state(id) == Some(PopoverState::Auto)
+                    && Some(id) != invoked_popover
+                    && !target.is_some_and(|target| self.is_inclusive_ancestor(id, target))
+            })
+            .collect();
+        for id in to_hide.into_iter().rev() {
+            self.hide_popover(id);
+        }
+    }
+
+    /// The popover controlled by a button with a `popovertarget` attribute
+    fn popover_target(&self, invoker_id: usize) -> Option<usize> {
+        let element = self.nodes[invoker_id].element_data()?;
+        let is_button = match element.name.local {
+            local_name!("button") => true,
+            local_name!("input") => matches!(
+                element.attr(local_name!("type")),
+                Some("button" | "submit" | "reset" | "image")
+            ),
+            _ => false,
+        };
+        if !is_button || element.attr(local_name!("disabled")).is_some() {
+            return None;
+        }
+
+        let target_id = self.get_element_by_id(element.attr(LocalName::from("popovertarget"))?)?;
+        self.popover_state(target_id).map(|_| target_id)
+    }
+
+    /// Show, hide or toggle the popover controlled by a `popovertarget` button (depending on the
+    /// button's `popovertargetaction` attribute). Returns whether there was a popover to invoke.
+    pub(crate) fn invoke_popover_target(&mut self, invoker_id: usize) -> bool {
+        let Some(target_id) = self.popover_target(invoker_id) else {
+            return false;
+        };
+
+        let action = self.nodes[invoker_id]
+            .attr(LocalName::from("popovertargetaction"))
+            .unwrap_or("toggle");
+        if action.eq_ignore_ascii_case("show") {
+            self.show_popover(target_id);
+        } else if action.eq_ignore_ascii_case("hide") {
+            self.hide_popover(target_id);
+        } else {
+            self.toggle_popover(target_id);
+        }
+        true
+    }
+}
@@ -0,0 +1,365 @@
+//! Responsive images: selecting the source of an `<img>` element from its `srcset` and `sizes`
+//! attributes and the `<source>` elements of a `<picture>` element.
+//!
+//! Sources are selected using the document's device (for `media` and `sizes` media conditions and
+//! the device pixel ratio), and are reselected whenever the viewport changes.
+//!
+//! See <https://html.spec.whatwg.org/multipage/images.html#selecting-an-image-source>
+
+use std::borrow::Cow;
+
+use blitz_traits::net::Request;
+use markup5ever::local_name;
+use style::{
+    context::QuirksMode,
+    media_queries::MediaList,
+    parser::ParserContext,
+    stylesheets::{CssRuleType, Origin, UrlExtraData},
+    values::specified::source_size_list::SourceSizeList,
+};
+use style_traits::ParsingMode;
+use url::Url;
+
+use crate::BaseDocument;
+use crate::net::ImageHandler;
+use crate::node::NodeSpecificData;
+use crate::util::{ImageType, resolve_url};
+
+/// The image source selected for an `<img>` element
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageSource {
+    /// The URL of the selected image
+    pub url: Url,
+    /// The pixel density of the image, which its natural size is divided by
+    pub density: f32,
+}
+
+/// The descriptor of an image candidate in a `srcset` attribute
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Descriptor {
+    Density(f32),
+    Width(f32),
+}
+
+/// An image candidate string from a `srcset` attribute
+#[derive(Debug, Clone, PartialEq)]
+struct ImageCandidate<'a> {
+    url: &'a str,
+    descriptor: Option<Descriptor>,
+}
+
+/// Parse a `srcset` attribute into its image candidates. Invalid candidates are skipped.
+///
+/// See <https://html.spec.whatwg.org/multipage/images.html#parsing-a-srcset-attribute>
+fn parse_srcset(srcset: &str) -> Vec<ImageCandidate<'_>> {
+    let mut candidates = Vec::new();
+    let mut input = srcset;
+    loop {
+        input = input.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if input.is_empty() {
+            return candidates;
+        }
+
+        let url_end = input
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(input.len());
+        let (mut url, rest) = input.split_at(url_end);
+        input = rest;
+
+        // A URL ending in a comma has no descriptors. Otherwise the descriptors run until the next
+        // comma which isn't within parentheses.
+        let mut descriptors = "";
+        if url.ends_with(',') {
+            url = url.trim_end_matches(',');
+        } else {
+            let mut depth = 0;
+            let end = input
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    c == ',' && depth <= 0
+                })
+                .map(|(idx, _)| idx)
+                .unwrap_or(input.len());
+            descriptors = &input[..end];
+            input = &input[end..];
+        }
+
+        if let Some(descriptor) = parse_descriptors(descriptors) {
+            candidates.push(ImageCandidate { url, descriptor });
+        }
+    }
+}
+
+/// Parse the descriptors of an image candidate. Returns `None` if they are invalid, and `Some(None)`
+/// if there are no (supported) descriptors.
+fn parse_descriptors(descriptors: &str) -> Option<Option<Descriptor>> {
+    let mut result = None;
+    for token in descriptors.split_ascii_whitespace() {
+        let (unit_idx, unit) = token.char_indices().next_back()?;
+        let value = &token[..unit_idx];
+        let descriptor = match unit {
+            'w' => Descriptor::Width(value.parse::<u32>().ok().filter(|&w| w > 0)? as f32),
+            'x' => Descriptor::Density(
+                value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|&x| x.is_finite() && x >= 0.0)?,
+            ),
+            // Height descriptors are only meaningful alongside a width descriptor, and are unused
+            'h' => continue,
+            _ => return None,
+        };
+        if result.replace(descriptor).is_some() {
+            return None;
+        }
+    }
+    Some(result)
+}
+
+/// Whether an image with the MIME type from a `<source>` element's `type` attribute can be decoded
+fn is_supported_image_type(mime_type: &str) -> bool {
+    let mime_type = mime_type.trim().to_ascii_lowercase();
+    if cfg!(feature = "svg") && mime_type == "image/svg+xml" {
+        return true;
+    }
+    image::ImageFormat::from_mime_type(&mime_type).is_some_and(|format| format.reading_enabled())
+}
+
+impl BaseDocument {
+    /// A parser context for evaluating the `media` and `sizes` attributes of images
+    fn with_image_parser_context<R>(&self, f: impl FnOnce(&ParserContext) -> R) -> R {
+        let url_data = UrlExtraData::from(
+            self.base_url
+                .clone()
+                .unwrap_or_else(|| "about:blank".parse::<Url>().unwrap()),
+        );
+        let context = ParserContext::new(
+            Origin::Author,
+            &url_data,
+            Some(CssRuleType::Media),
+            ParsingMode::DEFAULT,
+            QuirksMode::NoQuirks,
+            Cow::Owned(Default::default()),
+            None,
+            None,
+        );
+        f(&context)
+    }
+
+    /// Whether a `media` attribute matches the document's device
+    fn matches_media_attribute(&self, media: &str) -> bool {
+        self.with_image_parser_context(|context| {
+            let mut input = cssparser::ParserInput::new(media);
+            let media_list = MediaList::parse(context, &mut cssparser::Parser::new(&mut input));
+            media_list.evaluate(self.stylist.device(), QuirksMode::NoQuirks)
+        })
+    }
+
+    /// The width (in CSS pixels) that an image will be displayed at according to a `sizes` attribute
+    fn evaluate_sizes_attribute(&self, sizes: Option<&str>) -> f32 {
+        self.with_image_parser_context(|context| {
+            let mut input = cssparser::ParserInput::new(sizes.unwrap_or(""));
+            let source_sizes =
+                SourceSizeList::parse(context, &mut cssparser::Parser::new(&mut input));
+            source_sizes
+                .evaluate(self.stylist.device(), QuirksMode::NoQuirks)
+                .to_f32_px()
+        })
+    }
+
+    /// Select the source of an `<img>` element from its `src`, `srcset` and `sizes` attributes, or
+    /// from the first matching `<source>` element if it is in a `<picture>` element.
+    pub fn select_image_source(&self, img_id: usize) -> Option<ImageSource> {
+        let node = self.nodes.get(img_id)?;
+        let img = node.element_data()?;
+        if img.name.local != local_name!("img") {
+            return None;
+        }
+
+        // The <source> elements preceding the image in a <picture> element are tried in order
+        let picture_sources = node
+            .parent
+            .filter(|&parent_id| {
+                self.nodes[parent_id]
+                    .data
+                    .is_element_with_tag_name(&local_name!("picture"))
+            })
+            .map(|parent_id| {
+                self.nodes[parent_id]
+                    .children
+                    .iter()
+                    .copied()
+                    .take_while(|&child_id| child_id != img_id)
+                    .filter_map(|child_id| self.nodes[child_id].element_data())
+                    .filter(|el| el.name.local == local_name!("source"))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for source in picture_sources {
+            let Some(srcset) = source.attr(local_name!("srcset")) else {
+                continue;
+            };
+            if source
+                .attr(local_name!("media"))
+                .is_some_and(|media| !self.matches_media_attribute(media))
+            {
+                continue;
+            }
+            if source
+                .attr(local_name!("type"))
+                .is_some_and(|mime_type| !is_supported_image_type(mime_type))
+            {
+                continue;
+            }
+
+            let candidates = parse_srcset(srcset);
+            if let Some(selected) =
+                self.select_image_candidate(&candidates, source.attr(local_name!("sizes")))
+            {
+                return Some(selected);
+            }
+        }
+
+        let mut candidates = img
+            .attr(local_name!("srcset"))
+            .map(parse_srcset)
+            .unwrap_or_default();
+
+        // The src attribute is a 1x candidate, unless the srcset already has one or uses widths
+        let src = img.attr(local_name!("src")).filter(|src| !src.is_empty());
+        let has_1x_or_width = candidates
+            .iter()
+            .any(|candidate| match candidate.descriptor {
+                None | Some(Descriptor::Width(_)) => true,
+                Some(Descriptor::Density(density)) => density == 1.0,
+            });
+        if let Some(src) = src.filter(|_| !has_1x_or_width) {
+            candidates.push(ImageCandidate {
+                url: src,
+                descriptor: None,
+            });
+        }
+
+        self.select_image_candidate(&candidates, img.attr(local_name!("sizes")))
+    }
+
+    /// Select the candidate with the lowest density which is at least the device pixel ratio (or
+    /// else the one with the highest density)
+    fn select_image_candidate(
+        &self,
+        candidates: &[ImageCandidate],
+        sizes: Option<&str>,
+    ) -> Option<ImageSource> {
+        let uses_widths = candidates
+            .iter()
+            .any(|candidate| matches!(candidate.descriptor, Some(Descriptor::Width(_))));
+        let source_size = uses_widths.then(|| self.evaluate_sizes_attribute(sizes));
+
+        let device_pixel_ratio = self.viewport.scale();
+        let mut selected: Option<(&str, f32)> = None;
+        for candidate in candidates {
+            let density = match (candidate.descriptor, source_size) {
+                (Some(Descriptor::Width(width)), Some(source_size)) if source_size > 0.0 => {
+                    width / source_size
+                }
+                (Some(Descriptor::Width(_)), _) => continue,
+                (Some(Descriptor::Density(density)), _) => density,
+                (None, _) => 1.0,
+            };
+            let is_better = match selected {
+                None => true,
+                Some((_, selected_density)) if selected_density < device_pixel_ratio => {
+                    density > selected_density
+                }
+                Some((_, selected_density)) => {
+                    density >= device_pixel_ratio && density < selected_density
+                }
+            };
+            if is_better {
+                selected = Some((candidate.url, density));
+            }
+        }
+
+        let (url, density) = selected?;
+        Some(ImageSource {
+            url: resolve_url(&self.base_url, url)?,
+            density: if density > 0.0 { density } else { 1.0 },
+        })
+    }
+
+    /// Select the source of an `<img>` element and start loading it if it has changed. This should
+    /// be called when the image is connected to the document, when its `src`, `srcset` or `sizes`
+    /// attributes change, or when the `<source>` elements of its `<picture>` element change.
+    pub fn load_image(&mut self, img_id: usize) {
+        let source = self.select_image_source(img_id);
+        let Some(element) = self.nodes[img_id].element_data_mut() else {
+            return;
+        };
+        if element.image_source == source {
+            return;
+        }
+
+        element.image_source = source.clone();
+        element.node_specific_data = NodeSpecificData::None;
+        self.nodes[img_id].cache.clear();
+        if let Some(source) = source {
+            self.net_provider.fetch(
+                self.id(),
+                Request::get(source.url),
+                Box::new(ImageHandler::new(img_id, ImageType::Image)),
+            );
+        }
+    }
+
+    /// Reselect the sources of responsive images (those with a `srcset` attribute or in a
+    /// `<picture>` element) after the viewport has changed
+    pub(crate) fn reselect_image_sources(&mut self) {
+        let img_ids: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| {
+                node.element_data().is_some_and(|el| {
+                    el.name.local == local_name!("img")
+                        && (el.attr(local_name!("srcset")).is_some()
+                            || node.parent.is_some_and(|parent_id| {
+                                self.nodes[parent_id]
+                                    .data
+                                    .is_element_with_tag_name(&local_name!("picture"))
+                            }))
+                })
+            })
+            .map(|(node_id, _)| node_id)
+            .filter(|&node_id| self.is_connected(node_id))
+            .collect();
+        for img_id in img_ids {
+            self.load_image(img_id);
+        }
+    }
+}
+
+#[test]
+fn parses_srcset_descriptors() {
+    let candidates = parse_srcset("a.png 1.5x, b.png 200w 100h, c.png, d.png 1\u{e9}, e.png 2x 3x");
+    assert_eq!(
+        candidates,
+        vec![
+            ImageCandidate {
+                url: "a.png",
+                descriptor: Some(Descriptor::Density(1.5)),
+            },
+            ImageCandidate {
+                url: "b.png",
+                descriptor: Some(Descriptor::Width(200.0)),
+            },
+            ImageCandidate {
+                url: "c.png",
+                descriptor: None,
+            },
+        ]
+    );
+}
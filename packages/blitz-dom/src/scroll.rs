@@ -0,0 +1,535 @@
+//! Programmatic, keyboard and animated scrolling
+
+use std::time::{Duration, Instant};
+
+use blitz_traits::BlitzKeyEvent;
+use keyboard_types::{Key, Modifiers};
+use peniko::kurbo::{Point, Rect, Vec2};
+use style::values::computed::Overflow;
+
+use crate::{BaseDocument, scrollbar::ScrollbarOwner};
+
+/// The distance scrolled by the arrow keys (in CSS pixels)
+const LINE_SCROLL_DISTANCE: f64 = 40.0;
+/// The height of a line of text as a multiple of the font size (as for `line-height: normal`),
+/// used as the distance scrolled by each line of mouse wheel scrolling
+const WHEEL_LINE_HEIGHT: f64 = 1.2;
+/// The fraction of the scrollport that is scrolled by PageUp/PageDown/Space
+const PAGE_SCROLL_FRACTION: f64 = 0.875;
+/// The duration of a smooth scroll
+const SMOOTH_SCROLL_DURATION: Duration = Duration::from_millis(200);
+/// The time constant of the exponential decay of an inertial (fling) scroll
+const FLING_TIME_CONSTANT: f64 = 0.325;
+/// Flings are stopped once their velocity drops below this value (in CSS pixels per second)
+const FLING_MIN_VELOCITY: f64 = 10.0;
+
+/// Whether a scroll should be animated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScrollBehavior {
+    /// Use the `scroll-behavior` of the scroll container
+    #[default]
+    Auto,
+    Instant,
+    Smooth,
+}
+
+/// Where to align an element within a scrollport along a given axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScrollLogicalPosition {
+    #[default]
+    Start,
+    Center,
+    End,
+    /// Scroll as little as possible to bring the element into view
+    Nearest,
+}
+
+/// Options for [`BaseDocument::scroll_into_view`]. Mirrors the `ScrollIntoViewOptions` dictionary from the CSSOM View spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrollIntoViewOptions {
+    pub behavior: ScrollBehavior,
+    /// Alignment in the block (vertical) axis
+    pub block: ScrollLogicalPosition,
+    /// Alignment in the inline (horizontal) axis
+    pub inline: ScrollLogicalPosition,
+}
+
+impl Default for ScrollIntoViewOptions {
+    fn default() -> Self {
+        Self {
+            behavior: ScrollBehavior::Auto,
+            block: ScrollLogicalPosition::Start,
+            inline: ScrollLogicalPosition::Nearest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScrollAnimationKind {
+    /// Eases the scroll offset of a scroll container to a target offset
+    Smooth {
+        owner: ScrollbarOwner,
+        from: Point,
+        to: Point,
+    },
+    /// Continues a touchpad scroll with exponentially decaying velocity. Scrolling bubbles from
+    /// the node in the same way as wheel scrolling.
+    Fling {
+        node_id: Option<usize>,
+        velocity: Vec2,
+        applied: Vec2,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScrollAnimation {
+    kind: ScrollAnimationKind,
+    start: Instant,
+}
+
+/// Compute the change in scroll offset required to align the range `start..end` within `port_start..port_end`
+fn align_delta(
+    start: f64,
+    end: f64,
+    port_start: f64,
+    port_end: f64,
+    position: ScrollLogicalPosition,
+) -> f64 {
+    match position {
+        ScrollLogicalPosition::Start => start - port_start,
+        ScrollLogicalPosition::End => end - port_end,
+        ScrollLogicalPosition::Center => (start + end) / 2.0 - (port_start + port_end) / 2.0,
+        ScrollLogicalPosition::Nearest => {
+            let is_larger = end - start > port_end - port_start;
+            if start < port_start && end > port_end {
+                0.0
+            } else if (start < port_start && !is_larger) || (end > port_end && is_larger) {
+                start - port_start
+            } else if (start < port_start && is_larger) || (end > port_end && !is_larger) {
+                end - port_end
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+fn ease_out_cubic(t: f64) -> f64 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+impl BaseDocument {
+    /// The current scroll offset of a scroll container
+    pub fn scroll_offset(&self, owner: ScrollbarOwner) -> Point {
+        match owner {
+            ScrollbarOwner::Viewport => self.viewport_scroll,
+            ScrollbarOwner::Node(node_id) => self.nodes[node_id].scroll_offset,
+        }
+    }
+
+    /// The maximum scroll offset of a scroll container
+    pub fn max_scroll_offset(&self, owner: ScrollbarOwner) -> Point {
+        match owner {
+            ScrollbarOwner::Viewport => {
+                let Some(root_element) = self.try_root_element() else {
+                    return Point::ZERO;
+                };
+                let content_size = root_element.final_layout.size;
+                let scale = self.viewport.scale_f64();
+                Point {
+                    x: (content_size.width as f64 - self.viewport.window_size.0 as f64 / scale)
+                        .max(0.0),
+                    y: (content_size.height as f64 - self.viewport.window_size.1 as f64 / scale)
+                        .max(0.0),
+                }
+            }
+            ScrollbarOwner::Node(node_id) => {
+                let layout = &self.nodes[node_id].final_layout;
+                Point {
+                    x: layout.scroll_width() as f64,
+                    y: layout.scroll_height() as f64,
+                }
+            }
+        }
+    }
+
+    /// Set the scroll offset of a scroll container (clamped to its scrollable range)
+    pub fn set_scroll_offset(&mut self, owner: ScrollbarOwner, offset: Point) {
+        let max = self.max_scroll_offset(owner);
+        let offset = Point {
+            x: offset.x.clamp(0.0, max.x),
+            y: offset.y.clamp(0.0, max.y),
+        };
+        match owner {
+            ScrollbarOwner::Viewport => self.viewport_scroll = offset,
+            ScrollbarOwner::Node(node_id) => self.nodes[node_id].scroll_offset = offset,
+        }
+    }
+
+    /// Scroll a scroll container to the given offset, animating the scroll if requested
+    pub fn scroll_to(&mut self, owner: ScrollbarOwner, offset: Point, behavior: ScrollBehavior) {
+        self.cancel_scroll_animation(owner);
+
+        if self.resolve_scroll_behavior(owner, behavior) == ScrollBehavior::Smooth {
+            let from = self.scroll_offset(owner);
+            let max = self.max_scroll_offset(owner);
+            let to = Point {
+                x: offset.x.clamp(0.0, max.x),
+                y: offset.y.clamp(0.0, max.y),
+            };
+            if from != to {
+                self.scroll_animations.push(ScrollAnimation {
+                    kind: ScrollAnimationKind::Smooth { owner, from, to },
+                    start: Instant::now(),
+                });
+            }
+        } else {
+            self.set_scroll_offset(owner, offset);
+        }
+    }
+
+    /// Resolve `ScrollBehavior::Auto` using the `scroll-behavior` of the scroll container
+    // TODO: read the `scroll-behavior` property once Stylo supports it with the servo engine
+    fn resolve_scroll_behavior(
+        &self,
+        _owner: ScrollbarOwner,
+        behavior: ScrollBehavior,
+    ) -> ScrollBehavior {
+        match behavior {
+            ScrollBehavior::Auto => ScrollBehavior::Instant,
+            behavior => behavior,
+        }
+    }
+
+    /// Scroll the ancestors of a node (and the viewport) such that the node is visible
+    pub fn scroll_into_view(&mut self, node_id: usize, options: ScrollIntoViewOptions) {
+        let Some(node) = self.nodes.get(node_id) else {
+            return;
+        };
+
+        // The node's border box, in the coordinate space of the border box of the current ancestor
+        let size = node.final_layout.size;
+        let mut rect = Rect::new(0.0, 0.0, size.width as f64, size.height as f64);
+        let mut child_id = node_id;
+
+        while let Some(parent_id) = self.nodes[child_id].layout_parent.get() {
+            let child = &self.nodes[child_id];
+            let parent = &self.nodes[parent_id];
+            let mut offset = Vec2::new(
+                child.final_layout.location.x as f64 - parent.scroll_offset.x,
+                child.final_layout.location.y as f64 - parent.scroll_offset.y,
+            );
+            if parent.is_inline_root {
+                let layout = &parent.final_layout;
+                offset.x += (layout.padding.left + layout.border.left) as f64;
+                offset.y += (layout.padding.top + layout.border.top) as f64;
+            }
+            rect = rect + offset;
+            child_id = parent_id;
+
+            let Some((can_x_scroll, can_y_scroll)) = parent.primary_styles().map(|styles| {
+                let is_scrollable = |overflow| {
+                    matches!(
+                        overflow,
+                        Overflow::Scroll | Overflow::Auto | Overflow::Hidden
+                    )
+                };
+                (
+                    is_scrollable(styles.clone_overflow_x()),
+                    is_scrollable(styles.clone_overflow_y()),
+                )
+            }) else {
+                continue;
+            };
+            if !can_x_scroll && !can_y_scroll {
+                continue;
+            }
+
+            // The scrollport is the padding box, excluding any scrollbar gutters
+            let layout = &parent.final_layout;
+            let port = Rect::new(
+                layout.border.left as f64,
+                layout.border.top as f64,
+                (layout.size.width - layout.border.right - layout.scrollbar_size.width) as f64,
+                (layout.size.height - layout.border.bottom - layout.scrollbar_size.height) as f64,
+            );
+
+            let owner = ScrollbarOwner::Node(parent_id);
+            let current = self.scroll_offset(owner);
+            let target = Point {
+                x: current.x
+                    + if can_x_scroll {
+                        align_delta(rect.x0, rect.x1, port.x0, port.x1, options.inline)
+                    } else {
+                        0.0
+                    },
+                y: current.y
+                    + if can_y_scroll {
+                        align_delta(rect.y0, rect.y1, port.y0, port.y1, options.block)
+                    } else {
+                        0.0
+                    },
+            };
+            let max = self.max_scroll_offset(owner);
+            let target = Point {
+                x: target.x.clamp(0.0, max.x),
+                y: target.y.clamp(0.0, max.y),
+            };
+            self.scroll_to(owner, target, options.behavior);
+
+            // Later ancestors see the node at its final (post-scroll) position
+            rect = rect - (target - current);
+        }
+
+        // Finally scroll the viewport. The rect is now relative to the root element, which is
+        // positioned at the origin of the document.
+        let current = self.viewport_scroll;
+        let scale = self.viewport.scale_f64();
+        let port = Rect::new(
+            current.x,
+            current.y,
+            current.x + self.viewport.window_size.0 as f64 / scale,
+            current.y + self.viewport.window_size.1 as f64 / scale,
+        );
+        let target = Point {
+            x: current.x + align_delta(rect.x0, rect.x1, port.x0, port.x1, options.inline),
+            y: current.y + align_delta(rect.y0, rect.y1, port.y0, port.y1, options.block),
+        };
+        self.scroll_to(ScrollbarOwner::Viewport, target, options.behavior);
+    }
+
+    /// Continue a touchpad scroll with inertia, starting at the given velocity (in CSS pixels
+    /// per second, with the same sign convention as [`BaseDocument::scroll_node_by`])
+    pub fn fling(&mut self, node_id: Option<usize>, velocity_x: f64, velocity_y: f64) {
+        self.cancel_fling();
+        let velocity = Vec2::new(velocity_x, velocity_y);
+        if velocity.hypot() < FLING_MIN_VELOCITY {
+            return;
+        }
+        self.scroll_animations.push(ScrollAnimation {
+            kind: ScrollAnimationKind::Fling {
+                node_id,
+                velocity,
+                applied: Vec2::ZERO,
+            },
+            start: Instant::now(),
+        });
+    }
+
+    /// Whether any scroll animations are in progress
+    pub fn has_active_scroll_animations(&self) -> bool {
+        !self.scroll_animations.is_empty()
+    }
+
+    /// Stop all scroll animations at their current position
+    pub fn cancel_scroll_animations(&mut self) {
+        self.scroll_animations.clear();
+    }
+
+    fn cancel_scroll_animation(&mut self, owner: ScrollbarOwner) {
+        self.scroll_animations.retain(|animation| {
+            !matches!(animation.kind, ScrollAnimationKind::Smooth { owner: o, .. } if o == owner)
+        });
+    }
+
+    fn cancel_fling(&mut self) {
+        self.scroll_animations
+            .retain(|animation| !matches!(animation.kind, ScrollAnimationKind::Fling { .. }));
+    }
+
+    /// Advance scroll animations to the current time.
+    ///
+    /// Returns true if any animations are still in progress (and another frame should be scheduled)
+    pub fn tick_scroll_animations(&mut self) -> bool {
+        self.tick_scroll_animations_at(Instant::now())
+    }
+
+    /// Advance scroll animations to the time `now`. See `tick_scroll_animations`.
+    pub fn tick_scroll_animations_at(&mut self, now: Instant) -> bool {
+        let mut animations = std::mem::take(&mut self.scroll_animations);
+
+        animations.retain_mut(|animation| {
+            let elapsed = now.saturating_duration_since(animation.start).as_secs_f64();
+            match &mut animation.kind {
+                ScrollAnimationKind::Smooth { owner, from, to } => {
+                    let t = (elapsed / SMOOTH_SCROLL_DURATION.as_secs_f64()).min(1.0);
+                    let offset = from.lerp(*to, ease_out_cubic(t));
+                    self.set_scroll_offset(*owner, offset);
+                    t < 1.0
+                }
+                ScrollAnimationKind::Fling {
+                    node_id,
+                    velocity,
+                    applied,
+                } => {
+                    let decay = (-elapsed / FLING_TIME_CONSTANT).exp();
+                    let total = *velocity * FLING_TIME_CONSTANT * (1.0 - decay);
+                    let delta = total - *applied;
+                    *applied = total;
+                    match node_id {
+                        Some(node_id) => self.scroll_node_by(*node_id, delta.x, delta.y),
+                        None => self.scroll_viewport_by(delta.x, delta.y),
+                    }
+                    velocity.hypot() * decay >= FLING_MIN_VELOCITY
+                }
+            }
+        });
+
+        // Keep any animations which were started while ticking
+        animations.append(&mut self.scroll_animations);
+        self.scroll_animations = animations;
+
+        self.has_active_scroll_animations()
+    }
+
+    /// Find the nearest scroll container (starting at `node_id`) which can scroll in the given
+    /// direction, falling back to the viewport.
+    fn scroll_container_for(&self, node_id: usize, direction: Vec2) -> ScrollbarOwner {
+        let mut maybe_node_id = Some(node_id);
+        while let Some(node_id) = maybe_node_id {
+            let node = &self.nodes[node_id];
+            maybe_node_id = node.layout_parent.get().or(node.parent);
+
+            let Some(styles) = node.primary_styles() else {
+                continue;
+            };
+            let is_scrollable = |overflow| matches!(overflow, Overflow::Scroll | Overflow::Auto);
+            let owner = ScrollbarOwner::Node(node_id);
+            let offset = self.scroll_offset(owner);
+            let max = self.max_scroll_offset(owner);
+
+            let can_scroll_x = is_scrollable(styles.clone_overflow_x())
+                && ((direction.x < 0.0 && offset.x > 0.0)
+                    || (direction.x > 0.0 && offset.x < max.x));
+            let can_scroll_y = is_scrollable(styles.clone_overflow_y())
+                && ((direction.y < 0.0 && offset.y > 0.0)
+                    || (direction.y > 0.0 && offset.y < max.y));
+            if can_scroll_x || can_scroll_y {
+                return owner;
+            }
+        }
+
+        ScrollbarOwner::Viewport
+    }
+
+    /// The size of the visible area of a scroll container
+    fn scrollport_size(&self, owner: ScrollbarOwner) -> Vec2 {
+        match owner {
+            ScrollbarOwner::Viewport => {
+                let scale = self.viewport.scale_f64();
+                Vec2::new(
+                    self.viewport.window_size.0 as f64 / scale,
+                    self.viewport.window_size.1 as f64 / scale,
+                )
+            }
+            ScrollbarOwner::Node(node_id) => {
+                let layout = &self.nodes[node_id].final_layout;
+                Vec2::new(
+                    (layout.size.width
+                        - layout.border.left
+                        - layout.border.right
+                        - layout.scrollbar_size.width) as f64,
+                    (layout.size.height
+                        - layout.border.top
+                        - layout.border.bottom
+                        - layout.scrollbar_size.height) as f64,
+                )
+            }
+        }
+    }
+
+    /// The distance (in CSS pixels) scrolled by one line of mouse wheel scrolling over a node (or
+    /// over the root element if `node_id` is `None`): the height of a line of text in the node's
+    /// font.
+    pub fn line_scroll_distance(&self, node_id: Option<usize>) -> f64 {
+        let mut maybe_node_id = node_id.or(self.try_root_element().map(|el| el.id));
+        while let Some(node_id) = maybe_node_id {
+            let node = &self.nodes[node_id];
+            if let Some(styles) = node.primary_styles() {
+                let font_size = styles.get_font().font_size.used_size.0.px() as f64;
+                return font_size * WHEEL_LINE_HEIGHT;
+            }
+            maybe_node_id = node.parent;
+        }
+        16.0 * WHEEL_LINE_HEIGHT
+    }
+
+    /// Scroll in response to a keypress (arrow keys, PageUp/PageDown, Space, Home/End),
+    /// starting at the scroll container nearest to `node_id`.
+    ///
+    /// Returns true if the key is a scrolling key.
+    pub(crate) fn scroll_for_keypress(&mut self, node_id: usize, event: &BlitzKeyEvent) -> bool {
+        if !event.state.is_pressed() {
+            return false;
+        }
+        if event
+            .modifiers
+            .intersects(Modifiers::CONTROL | Modifiers::ALT | Modifiers::SUPER)
+        {
+            return false;
+        }
+
+        enum Amount {
+            Line,
+            Page,
+            Document,
+        }
+
+        let shift = event.modifiers.contains(Modifiers::SHIFT);
+        let (direction, amount) = match &event.key {
+            Key::ArrowUp => (Vec2::new(0.0, -1.0), Amount::Line),
+            Key::ArrowDown => (Vec2::new(0.0, 1.0), Amount::Line),
+            Key::ArrowLeft => (Vec2::new(-1.0, 0.0), Amount::Line),
+            Key::ArrowRight => (Vec2::new(1.0, 0.0), Amount::Line),
+            Key::PageUp => (Vec2::new(0.0, -1.0), Amount::Page),
+            Key::PageDown => (Vec2::new(0.0, 1.0), Amount::Page),
+            Key::Character(c) if c == " " && shift => (Vec2::new(0.0, -1.0), Amount::Page),
+            Key::Character(c) if c == " " => (Vec2::new(0.0, 1.0), Amount::Page),
+            Key::Home => (Vec2::new(0.0, -1.0), Amount::Document),
+            Key::End => (Vec2::new(0.0, 1.0), Amount::Document),
+            _ => return false,
+        };
+
+        let owner = self.scroll_container_for(node_id, direction);
+
+        // Accumulate repeated keypresses onto any in-progress smooth scroll
+        let current = self
+            .scroll_animations
+            .iter()
+            .find_map(|animation| match animation.kind {
+                ScrollAnimationKind::Smooth { owner: o, to, .. } if o == owner => Some(to),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.scroll_offset(owner));
+
+        let distance = match amount {
+            Amount::Line => Vec2::new(LINE_SCROLL_DISTANCE, LINE_SCROLL_DISTANCE),
+            Amount::Page => {
+                let size = self.scrollport_size(owner);
+                Vec2::new(
+                    (size.x * PAGE_SCROLL_FRACTION).max(LINE_SCROLL_DISTANCE),
+                    (size.y * PAGE_SCROLL_FRACTION).max(LINE_SCROLL_DISTANCE),
+                )
+            }
+            Amount::Document => self.max_scroll_offset(owner).to_vec2(),
+        };
+        let target = current + Vec2::new(direction.x * distance.x, direction.y * distance.y);
+
+        self.scroll_to(owner, target, ScrollBehavior::Smooth);
+        true
+    }
+}
+
+#[test]
+fn test_align_delta_nearest() {
+    use ScrollLogicalPosition::Nearest;
+
+    // Already visible
+    assert_eq!(align_delta(10.0, 20.0, 0.0, 100.0, Nearest), 0.0);
+    // Below the scrollport: align the end edges
+    assert_eq!(align_delta(150.0, 170.0, 0.0, 100.0, Nearest), 70.0);
+    // Above the scrollport: align the start edges
+    assert_eq!(align_delta(-50.0, -30.0, 0.0, 100.0, Nearest), -50.0);
+    // Larger than the scrollport and covering it: don't scroll
+    assert_eq!(align_delta(-50.0, 150.0, 0.0, 100.0, Nearest), 0.0);
+}
@@ -0,0 +1,564 @@
+//! Scrollbar geometry and interaction
+//!
+//! Scrollbar geometry is derived from the final layout of a scroll container so that the renderer
+//! and event handling always agree on where the track and thumb are.
+
+use blitz_traits::ColorScheme;
+use markup5ever::local_name;
+use peniko::kurbo::{Point, Rect};
+use style::values::computed::Overflow;
+
+use crate::{BaseDocument, util::Color};
+
+/// The minimum length of a scrollbar thumb (in CSS pixels)
+const MIN_THUMB_LENGTH: f64 = 20.0;
+
+/// How scrollbars are presented
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScrollbarMode {
+    /// Scrollbars reserve a gutter in layout and draw a track behind the thumb
+    #[default]
+    Classic,
+    /// Scrollbars are drawn on top of content and take up no space in layout
+    Overlay,
+}
+
+impl ScrollbarMode {
+    /// The thickness of a scrollbar (in CSS pixels)
+    pub fn thickness(self) -> f64 {
+        match self {
+            ScrollbarMode::Classic => 12.0,
+            ScrollbarMode::Overlay => 8.0,
+        }
+    }
+
+    /// The space reserved for a scrollbar in layout (in CSS pixels)
+    pub fn gutter(self) -> f32 {
+        match self {
+            ScrollbarMode::Classic => self.thickness() as f32,
+            ScrollbarMode::Overlay => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollbarAxis {
+    Horizontal,
+    Vertical,
+}
+
+/// The box that a scrollbar scrolls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollbarOwner {
+    Viewport,
+    Node(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Scrollbar {
+    pub axis: ScrollbarAxis,
+    /// The track. Relative to the border box of the scroll container (or to the viewport).
+    pub track: Rect,
+    /// The thumb. In the same coordinate space as the track.
+    pub thumb: Rect,
+    /// The length of the scrollport along the scrollbar's axis
+    visible_length: f64,
+    /// The maximum scroll offset along the scrollbar's axis
+    max_scroll: f64,
+}
+
+impl Scrollbar {
+    /// The distance scrolled for each pixel that the thumb is moved by
+    fn scroll_per_thumb_px(&self) -> f64 {
+        let travel = self.axis_length(self.track) - self.axis_length(self.thumb);
+        if travel > 0.0 {
+            self.max_scroll / travel
+        } else {
+            0.0
+        }
+    }
+
+    fn axis_length(&self, rect: Rect) -> f64 {
+        match self.axis {
+            ScrollbarAxis::Horizontal => rect.width(),
+            ScrollbarAxis::Vertical => rect.height(),
+        }
+    }
+
+    fn axis_start(&self, rect: Rect) -> f64 {
+        match self.axis {
+            ScrollbarAxis::Horizontal => rect.x0,
+            ScrollbarAxis::Vertical => rect.y0,
+        }
+    }
+
+    fn axis_coord(&self, point: Point) -> f64 {
+        match self.axis {
+            ScrollbarAxis::Horizontal => point.x,
+            ScrollbarAxis::Vertical => point.y,
+        }
+    }
+}
+
+/// The scrollbars of a single scroll container
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scrollbars {
+    pub horizontal: Option<Scrollbar>,
+    pub vertical: Option<Scrollbar>,
+}
+
+impl Scrollbars {
+    pub fn iter(&self) -> impl Iterator<Item = &Scrollbar> {
+        self.horizontal.iter().chain(self.vertical.iter())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.horizontal.is_none() && self.vertical.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScrollbarColors {
+    pub thumb: Color,
+    pub track: Color,
+}
+
+/// State for an in-progress thumb drag
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScrollbarDrag {
+    owner: ScrollbarOwner,
+    axis: ScrollbarAxis,
+    /// Viewport-relative pointer position (along the axis) when the drag started
+    start_pointer: f64,
+    /// Scroll offset (along the axis) when the drag started
+    start_offset: f64,
+    scroll_per_thumb_px: f64,
+}
+
+/// Compute scrollbar geometry for a scrollport
+///
+/// `area` is the region the scrollbars are placed in (the padding box plus any reserved gutter),
+/// `max_scroll` is the scrollable overflow in each axis, and `show` is whether each axis wants a scrollbar.
+fn compute_scrollbars(
+    mode: ScrollbarMode,
+    area: Rect,
+    max_scroll: (f64, f64),
+    offset: Point,
+    show: (bool, bool),
+) -> Scrollbars {
+    let thickness = mode.thickness();
+    let gutter = mode.gutter() as f64;
+    let (show_x, show_y) = show;
+
+    let thumb = |track: Rect, axis: ScrollbarAxis, visible: f64, max: f64, offset: f64| {
+        let track_length = match axis {
+            ScrollbarAxis::Horizontal => track.width(),
+            ScrollbarAxis::Vertical => track.height(),
+        };
+        let length = (track_length * visible / (visible + max))
+            .max(MIN_THUMB_LENGTH)
+            .min(track_length);
+        let progress = if max > 0.0 {
+            (offset / max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let start = (track_length - length) * progress;
+        match axis {
+            ScrollbarAxis::Horizontal => Rect::new(
+                track.x0 + start,
+                track.y0,
+                track.x0 + start + length,
+                track.y1,
+            ),
+            ScrollbarAxis::Vertical => Rect::new(
+                track.x0,
+                track.y0 + start,
+                track.x1,
+                track.y0 + start + length,
+            ),
+        }
+    };
+
+    // Leave room for the other scrollbar where both are shown
+    let corner_x = if show_y { thickness } else { 0.0 };
+    let corner_y = if show_x { thickness } else { 0.0 };
+
+    let horizontal = show_x.then(|| {
+        let track = Rect::new(area.x0, area.y1 - thickness, area.x1 - corner_x, area.y1);
+        let visible_length = area.width() - if show_y { gutter } else { 0.0 };
+        Scrollbar {
+            axis: ScrollbarAxis::Horizontal,
+            track,
+            thumb: thumb(
+                track,
+                ScrollbarAxis::Horizontal,
+                visible_length,
+                max_scroll.0,
+                offset.x,
+            ),
+            visible_length,
+            max_scroll: max_scroll.0,
+        }
+    });
+
+    let vertical = show_y.then(|| {
+        let track = Rect::new(area.x1 - thickness, area.y0, area.x1, area.y1 - corner_y);
+        let visible_length = area.height() - if show_x { gutter } else { 0.0 };
+        Scrollbar {
+            axis: ScrollbarAxis::Vertical,
+            track,
+            thumb: thumb(
+                track,
+                ScrollbarAxis::Vertical,
+                visible_length,
+                max_scroll.1,
+                offset.y,
+            ),
+            visible_length,
+            max_scroll: max_scroll.1,
+        }
+    });
+
+    Scrollbars {
+        horizontal,
+        vertical,
+    }
+}
+
+impl BaseDocument {
+    pub fn scrollbar_mode(&self) -> ScrollbarMode {
+        self.scrollbar_mode
+    }
+
+    /// Set whether scrollbars are classic (reserving space in layout) or overlaid on top of content.
+    /// Takes effect on the next call to `resolve`.
+    pub fn set_scrollbar_mode(&mut self, mode: ScrollbarMode) {
+        self.scrollbar_mode = mode;
+    }
+
+    /// The colors used to paint scrollbars, which depend on the scrollbar mode and color scheme.
+    ///
+    /// Note: Stylo's servo build doesn't support the `scrollbar-color`, `scrollbar-width` or
+    /// `scrollbar-gutter` properties (they are gecko-only), so scrollbars can't be styled by CSS.
+    pub fn scrollbar_colors(&self) -> ScrollbarColors {
+        match (self.scrollbar_mode, self.viewport.color_scheme) {
+            (ScrollbarMode::Classic, ColorScheme::Light) => ScrollbarColors {
+                thumb: Color::from_rgba8(193, 193, 193, 255),
+                track: Color::from_rgba8(241, 241, 241, 255),
+            },
+            (ScrollbarMode::Classic, ColorScheme::Dark) => ScrollbarColors {
+                thumb: Color::from_rgba8(107, 107, 107, 255),
+                track: Color::from_rgba8(43, 43, 43, 255),
+            },
+            (ScrollbarMode::Overlay, ColorScheme::Light) => ScrollbarColors {
+                thumb: Color::from_rgba8(0, 0, 0, 128),
+                track: Color::TRANSPARENT,
+            },
+            (ScrollbarMode::Overlay, ColorScheme::Dark) => ScrollbarColors {
+                thumb: Color::from_rgba8(255, 255, 255, 128),
+                track: Color::TRANSPARENT,
+            },
+        }
+    }
+
+    /// Whether a scroll container shows a scrollbar in an axis with the given `overflow` and
+    /// scrollable overflow. Overlay scrollbars are only shown if there is something to scroll.
+    fn wants_scrollbar(&self, overflow: Overflow, max_scroll: f64) -> bool {
+        match overflow {
+            Overflow::Scroll => self.scrollbar_mode == ScrollbarMode::Classic || max_scroll > 0.0,
+            Overflow::Auto => max_scroll > 0.0,
+            _ => false,
+        }
+    }
+
+    /// The `overflow` of the viewport in each axis. This is taken from the root element, or from
+    /// the `<body>` if the root element's `overflow` is `visible`, which means `auto` here.
+    ///
+    /// See <https://drafts.csswg.org/css-overflow-3/#overflow-propagation>
+    fn viewport_overflow(&self) -> (Overflow, Overflow) {
+        let overflow = |node_id: usize| {
+            let styles = self.nodes[node_id].primary_styles()?;
+            Some((styles.clone_overflow_x(), styles.clone_overflow_y()))
+        };
+        let visible = (Overflow::Visible, Overflow::Visible);
+
+        let root_overflow = self
+            .try_root_element()
+            .and_then(|root| overflow(root.id))
+            .unwrap_or(visible);
+        let overflow = if root_overflow == visible {
+            self.try_root_element()
+                .and_then(|root| {
+                    root.children.iter().copied().find(|&child_id| {
+                        self.nodes[child_id]
+                            .data
+                            .is_element_with_tag_name(&local_name!("body"))
+                    })
+                })
+                .and_then(overflow)
+                .unwrap_or(visible)
+        } else {
+            root_overflow
+        };
+
+        let visible_as_auto = |overflow| match overflow {
+            Overflow::Visible => Overflow::Auto,
+            overflow => overflow,
+        };
+        (visible_as_auto(overflow.0), visible_as_auto(overflow.1))
+    }
+
+    /// The scrollbars of an `overflow: auto` or `overflow: scroll` element.
+    /// Geometry is relative to the element's border box.
+    pub fn node_scrollbars(&self, node_id: usize) -> Scrollbars {
+        let Some(node) = self.nodes.get(node_id) else {
+            return Scrollbars::default();
+        };
+        let Some(styles) = node.primary_styles() else {
+            return Scrollbars::default();
+        };
+
+        let layout = &node.final_layout;
+        let max_scroll = (layout.scroll_width() as f64, layout.scroll_height() as f64);
+
+        let show = (
+            self.wants_scrollbar(styles.clone_overflow_x(), max_scroll.0),
+            self.wants_scrollbar(styles.clone_overflow_y(), max_scroll.1),
+        );
+        if !show.0 && !show.1 {
+            return Scrollbars::default();
+        }
+
+        let area = Rect::new(
+            layout.border.left as f64,
+            layout.border.top as f64,
+            (layout.size.width - layout.border.right) as f64,
+            (layout.size.height - layout.border.bottom) as f64,
+        );
+
+        compute_scrollbars(
+            self.scrollbar_mode,
+            area,
+            max_scroll,
+            node.scroll_offset,
+            show,
+        )
+    }
+
+    /// The scrollbars of the viewport. Geometry is relative to the viewport.
+    pub fn viewport_scrollbars(&self) -> Scrollbars {
+        let window_width = self.viewport.window_size.0 as f64 / self.viewport.scale_f64();
+        let window_height = self.viewport.window_size.1 as f64 / self.viewport.scale_f64();
+        let max_scroll = self.max_scroll_offset(ScrollbarOwner::Viewport);
+        let max_scroll = (max_scroll.x, max_scroll.y);
+        let (overflow_x, overflow_y) = self.viewport_overflow();
+        let show = (
+            self.wants_scrollbar(overflow_x, max_scroll.0),
+            self.wants_scrollbar(overflow_y, max_scroll.1),
+        );
+        if !show.0 && !show.1 {
+            return Scrollbars::default();
+        }
+
+        let area = Rect::new(0.0, 0.0, window_width, window_height);
+        compute_scrollbars(
+            self.scrollbar_mode,
+            area,
+            max_scroll,
+            self.viewport_scroll,
+            show,
+        )
+    }
+
+    /// Find the topmost scrollbar at the given document-relative coordinates.
+    ///
+    /// Returns the scrollbar along with the point in the scrollbar's coordinate space.
+    pub(crate) fn hit_scrollbar(
+        &self,
+        x: f32,
+        y: f32,
+    ) -> Option<(ScrollbarOwner, Scrollbar, Point)> {
+        // Viewport scrollbars are painted on top of everything else
+        let viewport_point = Point {
+            x: x as f64 - self.viewport_scroll.x,
+            y: y as f64 - self.viewport_scroll.y,
+        };
+        if let Some(scrollbar) = self
+            .viewport_scrollbars()
+            .iter()
+            .find(|scrollbar| scrollbar.track.contains(viewport_point))
+        {
+            return Some((ScrollbarOwner::Viewport, *scrollbar, viewport_point));
+        }
+
+        let root_id = self.try_root_element()?.id;
+        self.hit_node_scrollbar(root_id, x as f64, y as f64)
+    }
+
+    /// Recursive helper for `hit_scrollbar`. Coordinates are relative to the content box of the parent,
+    /// mirroring `Node::hit`.
+    fn hit_node_scrollbar(
+        &self,
+        node_id: usize,
+        x: f64,
+        y: f64,
+    ) -> Option<(ScrollbarOwner, Scrollbar, Point)> {
+        let node = &self.nodes[node_id];
+        let layout = &node.final_layout;
+        let point = Point {
+            x: x - layout.location.x as f64,
+            y: y - layout.location.y as f64,
+        };
+
+        let in_box = point.x >= 0.0
+            && point.y >= 0.0
+            && point.x <= layout.size.width as f64
+            && point.y <= layout.size.height as f64;
+
+        // A node's own scrollbars are painted above its children
+        let scrollbars = self.node_scrollbars(node_id);
+        if let Some(scrollbar) = scrollbars
+            .iter()
+            .find(|scrollbar| in_box && scrollbar.track.contains(point))
+        {
+            return Some((ScrollbarOwner::Node(node_id), *scrollbar, point));
+        }
+
+        // Children of a scroll container are clipped to it
+        if !scrollbars.is_empty() && !in_box {
+            return None;
+        }
+
+        let mut child_x = point.x + node.scroll_offset.x;
+        let mut child_y = point.y + node.scroll_offset.y;
+        if node.is_inline_root {
+            child_x -= (layout.padding.left + layout.border.left) as f64;
+            child_y -= (layout.padding.top + layout.border.top) as f64;
+        }
+
+        node.paint_children
+            .borrow()
+            .iter()
+            .flatten()
+            .rev()
+            .find_map(|&child_id| self.hit_node_scrollbar(child_id, child_x, child_y))
+    }
+
+    /// Handle a mouse down at the given document-relative coordinates.
+    ///
+    /// Pressing on a thumb starts a drag, and pressing on a track scrolls by a page towards the
+    /// pointer. Returns true if the event was handled by a scrollbar.
+    pub(crate) fn start_scrollbar_interaction(&mut self, x: f32, y: f32) -> bool {
+        let Some((owner, scrollbar, point)) = self.hit_scrollbar(x, y) else {
+            return false;
+        };
+        self.cancel_scroll_animations();
+
+        let offset = self.scroll_offset_for(owner, scrollbar.axis);
+        if scrollbar.thumb.contains(point) {
+            self.scrollbar_drag = Some(ScrollbarDrag {
+                owner,
+                axis: scrollbar.axis,
+                start_pointer: scrollbar.axis_coord(self.viewport_relative(x, y)),
+                start_offset: offset,
+                scroll_per_thumb_px: scrollbar.scroll_per_thumb_px(),
+            });
+        } else {
+            let page = scrollbar.visible_length;
+            let new_offset = if scrollbar.axis_coord(point) < scrollbar.axis_start(scrollbar.thumb)
+            {
+                offset - page
+            } else {
+                offset + page
+            };
+            self.set_scroll_offset_for(owner, scrollbar.axis, new_offset);
+        }
+
+        true
+    }
+
+    /// Continue an in-progress thumb drag. Returns true if anything was scrolled.
+    pub(crate) fn drag_scrollbar(&mut self, x: f32, y: f32) -> bool {
+        let Some(drag) = self.scrollbar_drag else {
+            return false;
+        };
+
+        let pointer = match drag.axis {
+            ScrollbarAxis::Horizontal => self.viewport_relative(x, y).x,
+            ScrollbarAxis::Vertical => self.viewport_relative(x, y).y,
+        };
+        let new_offset =
+            drag.start_offset + (pointer - drag.start_pointer) * drag.scroll_per_thumb_px;
+
+        let old_offset = self.scroll_offset_for(drag.owner, drag.axis);
+        self.set_scroll_offset_for(drag.owner, drag.axis, new_offset);
+        old_offset != self.scroll_offset_for(drag.owner, drag.axis)
+    }
+
+    pub(crate) fn end_scrollbar_drag(&mut self) {
+        self.scrollbar_drag = None;
+    }
+
+    pub(crate) fn is_dragging_scrollbar(&self) -> bool {
+        self.scrollbar_drag.is_some()
+    }
+
+    fn viewport_relative(&self, x: f32, y: f32) -> Point {
+        Point {
+            x: x as f64 - self.viewport_scroll.x,
+            y: y as f64 - self.viewport_scroll.y,
+        }
+    }
+
+    fn scroll_offset_for(&self, owner: ScrollbarOwner, axis: ScrollbarAxis) -> f64 {
+        let offset = self.scroll_offset(owner);
+        match axis {
+            ScrollbarAxis::Horizontal => offset.x,
+            ScrollbarAxis::Vertical => offset.y,
+        }
+    }
+
+    fn set_scroll_offset_for(&mut self, owner: ScrollbarOwner, axis: ScrollbarAxis, value: f64) {
+        let mut offset = self.scroll_offset(owner);
+        match axis {
+            ScrollbarAxis::Horizontal => offset.x = value,
+            ScrollbarAxis::Vertical => offset.y = value,
+        }
+        self.set_scroll_offset(owner, offset);
+    }
+}
+
+#[test]
+fn test_thumb_size_and_position() {
+    let area = Rect::new(0.0, 0.0, 100.0, 200.0);
+    let scrollbars = compute_scrollbars(
+        ScrollbarMode::Classic,
+        area,
+        (0.0, 200.0),
+        Point::new(0.0, 100.0),
+        (false, true),
+    );
+    assert!(scrollbars.horizontal.is_none());
+
+    let vertical = scrollbars.vertical.unwrap();
+    assert_eq!(vertical.track, Rect::new(88.0, 0.0, 100.0, 200.0));
+    // Half of the content is visible, and we are scrolled halfway
+    assert_eq!(vertical.thumb, Rect::new(88.0, 50.0, 100.0, 150.0));
+    assert_eq!(vertical.scroll_per_thumb_px(), 2.0);
+}
+
+#[test]
+fn test_thumb_min_length() {
+    let area = Rect::new(0.0, 0.0, 100.0, 100.0);
+    let scrollbars = compute_scrollbars(
+        ScrollbarMode::Overlay,
+        area,
+        (100_000.0, 0.0),
+        Point::ZERO,
+        (true, false),
+    );
+    let horizontal = scrollbars.horizontal.unwrap();
+    assert_eq!(horizontal.track, Rect::new(0.0, 92.0, 100.0, 100.0));
+    assert_eq!(horizontal.thumb.width(), MIN_THUMB_LENGTH);
+}
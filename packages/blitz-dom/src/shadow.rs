@@ -0,0 +1,262 @@
+//! Shadow DOM: attaching shadow roots, slot assignment and shadow tree stylesheets.
+//!
+//! Shadow roots are stored as `NodeData::ShadowRoot` nodes which are not part of their host's
+//! children. Styling and layout operate on the flat tree (see `Node::flat_tree_children`), in which
+//! a shadow host's children are replaced by those of its shadow root, and `<slot>` elements contain
+//! the nodes assigned to them.
+//!
+//! See <https://dom.spec.whatwg.org/#shadow-trees>
+
+use std::collections::HashMap;
+
+use markup5ever::local_name;
+use style::invalidation::element::restyle_hints::RestyleHint;
+use style::stylist::CascadeData;
+
+use crate::node::{ShadowRootData, ShadowRootMode};
+use crate::{BaseDocument, Node, NodeData};
+
+/// Whether an element with the given tag name can have a shadow root attached
+///
+/// See <https://dom.spec.whatwg.org/#dom-element-attachshadow>
+fn is_valid_shadow_host_name(name: &str) -> bool {
+    // Autonomous custom elements have a hyphen in their name
+    name.contains('-')
+        || matches!(
+            name,
+            "article"
+                | "aside"
+                | "blockquote"
+                | "body"
+                | "div"
+                | "footer"
+                | "h1"
+                | "h2"
+                | "h3"
+                | "h4"
+                | "h5"
+                | "h6"
+                | "header"
+                | "main"
+                | "nav"
+                | "p"
+                | "section"
+                | "span"
+        )
+}
+
+impl BaseDocument {
+    /// Attach a shadow root to an element, returning the id of the shadow root node. Children can
+    /// be added to the shadow root like any other node.
+    ///
+    /// Returns `None` if the element already has a shadow root or cannot host one.
+    pub fn attach_shadow(&mut self, host_id: usize, mode: ShadowRootMode) -> Option<usize> {
+        let host = &self.nodes[host_id];
+        let can_host = host
+            .element_data()
+            .is_some_and(|el| is_valid_shadow_host_name(&el.name.local));
+        if !can_host || host.shadow_root.is_some() {
+            return None;
+        }
+
+        let shadow_root_id =
+            self.create_node(NodeData::ShadowRoot(ShadowRootData::new(host_id, mode)));
+        let host = &mut self.nodes[host_id];
+        host.shadow_root = Some(shadow_root_id);
+        host.set_restyle_hint(RestyleHint::restyle_subtree());
+        self.dirty_slot_hosts.insert(host_id);
+
+        Some(shadow_root_id)
+    }
+
+    /// The shadow root attached to an element. Unlike `Element.shadowRoot` this also returns closed
+    /// shadow roots.
+    pub fn shadow_root(&self, host_id: usize) -> Option<usize> {
+        self.nodes.get(host_id)?.shadow_root
+    }
+
+    /// The shadow host of a shadow root
+    pub fn shadow_host(&self, shadow_root_id: usize) -> Option<usize> {
+        match &self.nodes.get(shadow_root_id)?.data {
+            NodeData::ShadowRoot(data) => Some(data.host),
+            _ => None,
+        }
+    }
+
+    /// The shadow root of the shadow tree a node is in (if it is in a shadow tree)
+    pub fn containing_shadow_root(&self, node_id: usize) -> Option<usize> {
+        let mut current = self.nodes.get(node_id);
+        while let Some(node) = current {
+            if matches!(node.data, NodeData::ShadowRoot(_)) {
+                return Some(node.id);
+            }
+            current = node.parent.and_then(|id| self.nodes.get(id));
+        }
+        None
+    }
+
+    /// The `<slot>` elements in a shadow tree, in tree order
+    fn slots_in_shadow_tree(&self, shadow_root_id: usize) -> Vec<usize> {
+        let mut slots = Vec::new();
+        let mut stack: Vec<usize> = self.nodes[shadow_root_id]
+            .children
+            .iter()
+            .rev()
+            .copied()
+            .collect();
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            if node.data.is_element_with_tag_name(&local_name!("slot")) {
+                slots.push(id);
+            }
+            stack.extend(node.children.iter().rev().copied());
+        }
+        slots
+    }
+
+    /// Mark a node whose attributes or children have changed, so that the slot assignments of the
+    /// shadow tree it affects are recomputed by the next restyle. This is the node itself if it is a
+    /// shadow host, its parent if that is a shadow host (its `slot` attribute may have changed) and
+    /// the host of the shadow tree it is in (its slots may have changed).
+    pub fn mark_slots_dirty(&mut self, node_id: usize) {
+        let Some(node) = self.nodes.get(node_id) else {
+            return;
+        };
+        if node.shadow_root.is_some() {
+            self.dirty_slot_hosts.insert(node_id);
+        }
+        if let Some(parent_id) = node.parent {
+            if self.nodes[parent_id].shadow_root.is_some() {
+                self.dirty_slot_hosts.insert(parent_id);
+            }
+        }
+        if let Some(host_id) = self
+            .containing_shadow_root(node_id)
+            .and_then(|shadow_root_id| self.shadow_host(shadow_root_id))
+        {
+            self.dirty_slot_hosts.insert(host_id);
+        }
+    }
+
+    /// Assign the children of the shadow hosts marked by `mark_slots_dirty` to slots in their
+    /// shadow trees
+    fn assign_slots(&mut self) {
+        for host_id in std::mem::take(&mut self.dirty_slot_hosts) {
+            self.assign_slots_for_host(host_id);
+        }
+    }
+
+    /// Assign the children of a shadow host to slots in its shadow tree
+    ///
+    /// See <https://dom.spec.whatwg.org/#assign-slotables-for-a-tree>
+    fn assign_slots_for_host(&mut self, host_id: usize) {
+        let Some(shadow_root_id) = self.nodes.get(host_id).and_then(|host| host.shadow_root) else {
+            return;
+        };
+
+        let slots = self.slots_in_shadow_tree(shadow_root_id);
+        let mut assigned_nodes: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut assigned_slots: HashMap<usize, usize> = HashMap::new();
+        for &child_id in &self.nodes[host_id].children {
+            let child = &self.nodes[child_id];
+            let name = match &child.data {
+                NodeData::Element(el) => el.attr(local_name!("slot")).unwrap_or(""),
+                NodeData::Text(_) => "",
+                _ => continue,
+            };
+            let slot = slots.iter().copied().find(|&slot_id| {
+                self.nodes[slot_id].attr(local_name!("name")).unwrap_or("") == name
+            });
+            if let Some(slot_id) = slot {
+                assigned_nodes.entry(slot_id).or_default().push(child_id);
+                assigned_slots.insert(child_id, slot_id);
+            }
+        }
+
+        // Unassign the nodes of slots which have been removed from the shadow tree, and nodes
+        // which have been removed from the host
+        let NodeData::ShadowRoot(data) = &mut self.nodes[shadow_root_id].data else {
+            unreachable!()
+        };
+        let old_slots = std::mem::replace(&mut data.slots, slots.clone());
+        for old_slot_id in old_slots {
+            let Some(old_slot) = self.nodes.get_mut(old_slot_id) else {
+                continue;
+            };
+            let old_assigned_nodes = match slots.contains(&old_slot_id) {
+                true => old_slot.assigned_nodes.clone(),
+                false => {
+                    old_slot.set_restyle_hint(RestyleHint::restyle_subtree());
+                    std::mem::take(&mut old_slot.assigned_nodes)
+                }
+            };
+            for node_id in old_assigned_nodes {
+                if assigned_slots.contains_key(&node_id) {
+                    continue;
+                }
+                if let Some(node) = self.nodes.get_mut(node_id) {
+                    if node.assigned_slot == Some(old_slot_id) {
+                        node.assigned_slot = None;
+                        node.set_restyle_hint(RestyleHint::restyle_subtree());
+                    }
+                }
+            }
+        }
+
+        for slot_id in slots {
+            let new_assigned_nodes = assigned_nodes.remove(&slot_id).unwrap_or_default();
+            let slot = &mut self.nodes[slot_id];
+            if slot.assigned_nodes != new_assigned_nodes {
+                slot.assigned_nodes = new_assigned_nodes;
+                slot.set_restyle_hint(RestyleHint::restyle_subtree());
+            }
+        }
+        let children = self.nodes[host_id].children.clone();
+        for child_id in children {
+            let new_assigned_slot = assigned_slots.get(&child_id).copied();
+            let child = &mut self.nodes[child_id];
+            if child.assigned_slot != new_assigned_slot {
+                child.assigned_slot = new_assigned_slot;
+                child.set_restyle_hint(RestyleHint::restyle_subtree());
+            }
+        }
+    }
+
+    /// Rebuild the cascade data of shadow roots whose stylesheets have changed
+    fn flush_shadow_stylesheets(&mut self) {
+        let guard = self.guard.read();
+        for (&shadow_root_id, sheets) in self.shadow_stylesheets.iter_mut() {
+            if !sheets.dirty() {
+                continue;
+            }
+            let Some(node) = self.nodes.get_mut(shadow_root_id) else {
+                continue;
+            };
+            let NodeData::ShadowRoot(data) = &mut node.data else {
+                continue;
+            };
+
+            let flusher = sheets.flush::<&Node>(None, None);
+            let old_data = data.style_data.clone();
+            let empty_data = CascadeData::new();
+            let result = self.stylist.rebuild_author_data(
+                old_data.as_deref().unwrap_or(&empty_data),
+                flusher.sheets,
+                &guard,
+            );
+            if let Ok(Some(new_data)) = result {
+                data.style_data = Some(new_data);
+            }
+
+            // The rules in the shadow tree can match anything in the host's subtree
+            let host_id = data.host;
+            self.nodes[host_id].set_restyle_hint(RestyleHint::restyle_subtree());
+        }
+    }
+
+    /// Update slot assignments and shadow tree styles ahead of a restyle
+    pub(crate) fn flush_shadow_dom(&mut self) {
+        self.assign_slots();
+        self.flush_shadow_stylesheets();
+    }
+}
@@ -2,13 +2,14 @@
 //!
 
 use std::ptr::NonNull;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::node::BackgroundImageData;
 use crate::node::Node;
 
 use crate::net::ImageHandler;
 use crate::node::NodeData;
+use crate::node::NodeSpecificData;
 use crate::util::ImageType;
 use atomic_refcell::{AtomicRef, AtomicRefMut};
 use markup5ever::{LocalName, LocalNameStaticSet, Namespace, NamespaceStaticSet, local_name};
@@ -28,7 +29,7 @@ use style::servo::url::ComputedUrl;
 use style::stylesheets::layer_rule::LayerOrder;
 use style::stylesheets::scope_rule::ImplicitScopeRoot;
 use style::values::AtomString;
-use style::values::computed::Percentage;
+use style::values::computed::{Overflow, Percentage};
 use style::values::generics::image::Image as StyloImage;
 use style::values::specified::box_::DisplayOutside;
 use style::{
@@ -58,6 +59,7 @@ impl crate::document::BaseDocument {
     /// Walk the whole tree, converting styles to layout
     pub fn flush_styles_to_layout(&mut self, node_id: usize) {
         let doc_id = self.id();
+        let scrollbar_gutter = self.scrollbar_mode.gutter();
 
         let display = {
             let node = self.nodes.get_mut(node_id).unwrap();
@@ -71,6 +73,13 @@ impl crate::document::BaseDocument {
             };
 
             node.style = stylo_taffy::to_taffy_style(style);
+            // Classic scrollbars reserve a gutter in scroll containers
+            let is_scroll_container = [style.clone_overflow_x(), style.clone_overflow_y()]
+                .iter()
+                .any(|overflow| matches!(overflow, Overflow::Scroll | Overflow::Auto));
+            if is_scroll_container {
+                node.style.scrollbar_width = scrollbar_gutter;
+            }
 
             node.display_outer = match style.clone_display().outside() {
                 DisplayOutside::None => crate::node::DisplayOuter::None,
@@ -116,6 +125,15 @@ impl crate::document::BaseDocument {
                 }
             }
 
+            // Tables adjust their border and padding for border-spacing and collapsed borders
+            if let Some(table) = node
+                .data
+                .downcast_element()
+                .and_then(|el| el.table_context())
+            {
+                table.apply_box_style(&mut node.style);
+            }
+
             // Clear Taffy cache
             // TODO: smarter cache invalidation
             node.cache.clear();
@@ -131,6 +149,18 @@ impl crate::document::BaseDocument {
                 self.flush_styles_to_layout(*child);
             }
 
+            // In the collapsing border model table cells only own half of each of their borders
+            let table =
+                self.nodes[node_id]
+                    .element_data()
+                    .and_then(|el| match &el.node_specific_data {
+                        NodeSpecificData::TableRoot(table) => Some(std::sync::Arc::clone(table)),
+                        _ => None,
+                    });
+            if let Some(table) = table {
+                table.apply_cell_box_styles(self);
+            }
+
             // If the node is a Flexbox or Grid node then sort by css order property
             if matches!(display, taffy::Display::Flex | taffy::Display::Grid) {
                 children.sort_by(|left, right| {
@@ -213,11 +243,15 @@ impl crate::document::BaseDocument {
         // dbg!(root);
         let token = RecalcStyle::pre_traverse(root, &context);
 
+        let mut styles_changed = false;
         if token.should_traverse() {
             // Style the elements, resolving their data
             let traverser = RecalcStyle::new(context);
             style::driver::traverse_dom(&traverser, token, None);
+            styles_changed = traverser.styles_changed.load(Ordering::Relaxed);
         }
+        // Counters and generated content depend on styles
+        self.generated_content_dirty |= styles_changed;
 
         style::thread_state::exit(ThreadState::LAYOUT);
     }
@@ -267,14 +301,20 @@ impl<'a> TShadowRoot for BlitzNode<'a> {
     }
 
     fn host(&self) -> <Self::ConcreteNode as TNode>::ConcreteElement {
-        todo!("Shadow roots not implemented")
+        match &self.data {
+            NodeData::ShadowRoot(data) => self.with(data.host),
+            _ => panic!("Not a shadow root"),
+        }
     }
 
     fn style_data<'b>(&self) -> Option<&'b style::stylist::CascadeData>
     where
         Self: 'b,
     {
-        todo!("Shadow roots not implemented")
+        match &self.data {
+            NodeData::ShadowRoot(data) => data.style_data.as_deref(),
+            _ => None,
+        }
     }
 }
 
@@ -312,12 +352,12 @@ impl<'a> TNode for BlitzNode<'a> {
         true
     }
 
-    // I think this is the same as parent_node only in the cases when the direct parent is not a real element, forcing us
-    // to travel upwards
-    //
-    // For the sake of this demo, we're just going to return the parent node ann
+    // The parent in the flat tree: the slot a node is assigned to, or the host of a shadow root
     fn traversal_parent(&self) -> Option<Self::ConcreteElement> {
-        self.parent_node().and_then(|node| node.as_element())
+        if let Some(slot_id) = self.assigned_slot {
+            return Some(self.with(slot_id));
+        }
+        self.parent_element_or_host()
     }
 
     fn opaque(&self) -> OpaqueNode {
@@ -343,8 +383,10 @@ impl<'a> TNode for BlitzNode<'a> {
     }
 
     fn as_shadow_root(&self) -> Option<Self::ConcreteShadowRoot> {
-        // TODO: implement shadow DOM
-        None
+        match self.data {
+            NodeData::ShadowRoot(_) => Some(self),
+            _ => None,
+        }
     }
 }
 
@@ -359,15 +401,20 @@ impl selectors::Element for BlitzNode<'_> {
     }
 
     fn parent_element(&self) -> Option<Self> {
-        TElement::traversal_parent(self)
+        TNode::parent_element(self)
     }
 
     fn parent_node_is_shadow_root(&self) -> bool {
-        false
+        self.parent_node()
+            .is_some_and(|parent| parent.as_shadow_root().is_some())
     }
 
     fn containing_shadow_host(&self) -> Option<Self> {
-        None
+        TElement::containing_shadow(self).map(|shadow_root| shadow_root.host())
+    }
+
+    fn assigned_slot(&self) -> Option<Self> {
+        self.assigned_slot.map(|slot_id| self.with(slot_id))
     }
 
     fn is_pseudo_element(&self) -> bool {
@@ -481,17 +528,21 @@ impl selectors::Element for BlitzNode<'_> {
                 .downcast_element()
                 .and_then(|elem| elem.checkbox_input_checked())
                 .unwrap_or(false),
-            NonTSPseudoClass::Valid => false,
-            NonTSPseudoClass::Invalid => false,
+            NonTSPseudoClass::Valid => self.element_state.contains(ElementState::VALID),
+            NonTSPseudoClass::Invalid => self.element_state.contains(ElementState::INVALID),
             NonTSPseudoClass::Defined => false,
-            NonTSPseudoClass::Disabled => false,
-            NonTSPseudoClass::Enabled => false,
+            NonTSPseudoClass::Disabled => self.element_state.contains(ElementState::DISABLED),
+            NonTSPseudoClass::Enabled => self.element_state.contains(ElementState::ENABLED),
             NonTSPseudoClass::Focus => self.element_state.contains(ElementState::FOCUS),
-            NonTSPseudoClass::FocusWithin => false,
-            NonTSPseudoClass::FocusVisible => false,
+            NonTSPseudoClass::FocusWithin => {
+                self.element_state.contains(ElementState::FOCUS_WITHIN)
+            }
+            NonTSPseudoClass::FocusVisible => self.element_state.contains(ElementState::FOCUSRING),
             NonTSPseudoClass::Fullscreen => false,
             NonTSPseudoClass::Hover => self.element_state.contains(ElementState::HOVER),
-            NonTSPseudoClass::Indeterminate => false,
+            NonTSPseudoClass::Indeterminate => {
+                self.element_state.contains(ElementState::INDETERMINATE)
+            }
             NonTSPseudoClass::Lang(_) => false,
             NonTSPseudoClass::CustomState(_) => false,
             NonTSPseudoClass::Link => self
@@ -502,26 +553,34 @@ impl selectors::Element for BlitzNode<'_> {
                         && elem.attr(local_name!("href")).is_some()
                 })
                 .unwrap_or(false),
-            NonTSPseudoClass::PlaceholderShown => false,
-            NonTSPseudoClass::ReadWrite => false,
-            NonTSPseudoClass::ReadOnly => false,
+            NonTSPseudoClass::PlaceholderShown => {
+                self.element_state.contains(ElementState::PLACEHOLDER_SHOWN)
+            }
+            NonTSPseudoClass::ReadWrite => self.element_state.contains(ElementState::READWRITE),
+            NonTSPseudoClass::ReadOnly => self.element_state.contains(ElementState::READONLY),
             NonTSPseudoClass::ServoNonZeroBorder => false,
             NonTSPseudoClass::Target => false,
             NonTSPseudoClass::Visited => false,
             NonTSPseudoClass::Autofill => false,
             NonTSPseudoClass::Default => false,
 
-            NonTSPseudoClass::InRange => false,
-            NonTSPseudoClass::Modal => false,
-            NonTSPseudoClass::Optional => false,
-            NonTSPseudoClass::OutOfRange => false,
-            NonTSPseudoClass::PopoverOpen => false,
-            NonTSPseudoClass::Required => false,
+            NonTSPseudoClass::InRange => self.element_state.contains(ElementState::INRANGE),
+            NonTSPseudoClass::Modal => self.element_state.contains(ElementState::MODAL),
+            NonTSPseudoClass::Optional => self.element_state.contains(ElementState::OPTIONAL_),
+            NonTSPseudoClass::OutOfRange => self.element_state.contains(ElementState::OUTOFRANGE),
+            NonTSPseudoClass::PopoverOpen => {
+                self.element_state.contains(ElementState::POPOVER_OPEN)
+            }
+            NonTSPseudoClass::Required => self.element_state.contains(ElementState::REQUIRED),
             NonTSPseudoClass::UserInvalid => false,
             NonTSPseudoClass::UserValid => false,
-            NonTSPseudoClass::MozMeterOptimum => false,
-            NonTSPseudoClass::MozMeterSubOptimum => false,
-            NonTSPseudoClass::MozMeterSubSubOptimum => false,
+            NonTSPseudoClass::MozMeterOptimum => self.element_state.contains(ElementState::OPTIMUM),
+            NonTSPseudoClass::MozMeterSubOptimum => {
+                self.element_state.contains(ElementState::SUB_OPTIMUM)
+            }
+            NonTSPseudoClass::MozMeterSubSubOptimum => {
+                self.element_state.contains(ElementState::SUB_SUB_OPTIMUM)
+            }
         }
     }
 
@@ -530,6 +589,9 @@ impl selectors::Element for BlitzNode<'_> {
         pe: &PseudoElement,
         _context: &mut MatchingContext<Self::Impl>,
     ) -> bool {
+        // Note: `::before`, `::after` and `::selection` are eagerly cascaded against their
+        // originating element, so they never need to be matched here. Stylo's servo build can't
+        // parse `::first-line`, `::first-letter`, `::marker` or `::placeholder`.
         match self.data {
             NodeData::AnonymousBlock(_) => *pe == PseudoElement::ServoAnonymousBox,
             _ => false,
@@ -557,7 +619,7 @@ impl selectors::Element for BlitzNode<'_> {
     }
 
     fn is_html_slot_element(&self) -> bool {
-        false
+        self.data.is_element_with_tag_name(&local_name!("slot"))
     }
 
     fn has_id(
@@ -592,13 +654,18 @@ impl selectors::Element for BlitzNode<'_> {
 
     fn imported_part(
         &self,
-        _name: &<Self::Impl as selectors::SelectorImpl>::Identifier,
+        name: &<Self::Impl as selectors::SelectorImpl>::Identifier,
     ) -> Option<<Self::Impl as selectors::SelectorImpl>::Identifier> {
-        None
+        exported_parts(self)
+            .find(|(_, outer)| *outer == &*name.0)
+            .map(|(inner, _)| AtomIdent::from(inner))
     }
 
-    fn is_part(&self, _name: &<Self::Impl as selectors::SelectorImpl>::Identifier) -> bool {
-        false
+    // Note: Stylo's servo build doesn't currently parse `::part()` selectors
+    fn is_part(&self, name: &<Self::Impl as selectors::SelectorImpl>::Identifier) -> bool {
+        self.data
+            .attr(LocalName::from("part"))
+            .is_some_and(|parts| parts.split_ascii_whitespace().any(|part| part == &*name.0))
     }
 
     fn is_empty(&self) -> bool {
@@ -607,8 +674,7 @@ impl selectors::Element for BlitzNode<'_> {
 
     fn is_root(&self) -> bool {
         self.parent_node()
-            .and_then(|parent| parent.parent_node())
-            .is_none()
+            .is_some_and(|parent| parent.as_document().is_some())
     }
 
     fn has_custom_state(
@@ -639,18 +705,24 @@ impl<'a> TElement for BlitzNode<'a> {
         // We cannot currently implement this as we are using the NodeId as the OpaqueElement,
         // and need a reference to the Slab to convert it back into an Element
         //
-        // Luckily it is only needed for shadow dom.
-        todo!();
+        // TODO: support prelude-less `@scope` rules in shadow tree stylesheets
+        None
     }
 
     fn traversal_children(&self) -> style::dom::LayoutIterator<Self::TraversalChildrenIterator> {
         LayoutIterator(Traverser {
             // dom: self.tree(),
             parent: self,
+            children: self.flat_tree_children(),
             child_index: 0,
         })
     }
 
+    // Styles are inherited through the flat tree (e.g. slotted elements inherit from their slot)
+    fn inheritance_parent(&self) -> Option<Self> {
+        TElement::traversal_parent(self)
+    }
+
     fn is_html_element(&self) -> bool {
         self.is_element()
     }
@@ -692,11 +764,11 @@ impl<'a> TElement for BlitzNode<'a> {
     }
 
     fn has_part_attr(&self) -> bool {
-        false
+        self.data.attr(LocalName::from("part")).is_some()
     }
 
     fn exports_any_part(&self) -> bool {
-        false
+        self.data.attr(LocalName::from("exportparts")).is_some()
     }
 
     fn id(&self) -> Option<&style::Atom> {
@@ -717,6 +789,30 @@ impl<'a> TElement for BlitzNode<'a> {
         }
     }
 
+    fn each_part<F>(&self, mut callback: F)
+    where
+        F: FnMut(&style::values::AtomIdent),
+    {
+        if let Some(part_attr) = self.data.attr(LocalName::from("part")) {
+            for part in part_attr.split_ascii_whitespace() {
+                let atom = Atom::from(part);
+                callback(AtomIdent::cast(&atom));
+            }
+        }
+    }
+
+    fn each_exported_part<F>(&self, name: &style::values::AtomIdent, mut callback: F)
+    where
+        F: FnMut(&style::values::AtomIdent),
+    {
+        for (inner, outer) in exported_parts(self) {
+            if inner == &*name.0 {
+                let atom = Atom::from(outer);
+                callback(AtomIdent::cast(&atom));
+            }
+        }
+    }
+
     fn each_attr_name<F>(&self, mut callback: F)
     where
         F: FnMut(&style::LocalName),
@@ -819,10 +915,17 @@ impl<'a> TElement for BlitzNode<'a> {
     }
 
     fn shadow_root(&self) -> Option<<Self::ConcreteNode as TNode>::ConcreteShadowRoot> {
-        None
+        self.shadow_root.map(|id| self.with(id))
     }
 
     fn containing_shadow(&self) -> Option<<Self::ConcreteNode as TNode>::ConcreteShadowRoot> {
+        let mut current = self.parent_node();
+        while let Some(node) = current {
+            if node.as_shadow_root().is_some() {
+                return Some(node);
+            }
+            current = node.parent_node();
+        }
         None
     }
 
@@ -920,6 +1023,10 @@ impl<'a> TElement for BlitzNode<'a> {
             )))
         }
 
+        // The width and height attributes of canvases set the size of their coordinate space
+        // rather than their CSS size
+        let maps_size_attrs = elem.name.local != local_name!("canvas");
+
         for attr in elem.attrs() {
             let name = &attr.name.local;
             let value = attr.value.as_str();
@@ -938,7 +1045,7 @@ impl<'a> TElement for BlitzNode<'a> {
                 }
             }
 
-            if *name == local_name!("width") {
+            if *name == local_name!("width") && maps_size_attrs {
                 if let Some(width) = parse_size_attr(value) {
                     use style::values::generics::{NonNegative, length::Size};
                     push_style(PropertyDeclaration::Width(Size::LengthPercentage(
@@ -947,7 +1054,7 @@ impl<'a> TElement for BlitzNode<'a> {
                 }
             }
 
-            if *name == local_name!("height") {
+            if *name == local_name!("height") && maps_size_attrs {
                 if let Some(height) = parse_size_attr(value) {
                     use style::values::generics::{NonNegative, length::Size};
                     push_style(PropertyDeclaration::Height(Size::LengthPercentage(
@@ -1042,9 +1149,26 @@ impl<'a> TElement for BlitzNode<'a> {
     // }
 }
 
+/// The part name mappings of an element's `exportparts` attribute, as (inner, outer) pairs. For
+/// example, `exportparts="label: title"` exposes the part `label` of the element's shadow tree as
+/// the part `title`.
+fn exported_parts(node: &Node) -> impl Iterator<Item = (&str, &str)> {
+    node.data
+        .attr(LocalName::from("exportparts"))
+        .into_iter()
+        .flat_map(|attr| attr.split(','))
+        .filter_map(|mapping| {
+            let (inner, outer) = mapping.split_once(':').unwrap_or((mapping, mapping));
+            let (inner, outer) = (inner.trim(), outer.trim());
+            (!inner.is_empty() && !outer.is_empty()).then_some((inner, outer))
+        })
+}
+
 pub struct Traverser<'a> {
     // dom: &'a Slab<Node>,
     parent: BlitzNode<'a>,
+    /// The flat tree children of `parent`
+    children: &'a [usize],
     child_index: usize,
 }
 
@@ -1052,7 +1176,7 @@ impl<'a> Iterator for Traverser<'a> {
     type Item = BlitzNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let node_id = self.parent.children.get(self.child_index)?;
+        let node_id = self.children.get(self.child_index)?;
         let node = self.parent.with(*node_id);
 
         self.child_index += 1;
@@ -1082,12 +1206,35 @@ use style::traversal::recalc_style_at;
 
 pub struct RecalcStyle<'a> {
     context: SharedStyleContext<'a>,
+    /// Whether the styles of any element (or its pseudo-elements) were replaced by the traversal
+    styles_changed: AtomicBool,
 }
 
 impl<'a> RecalcStyle<'a> {
     pub fn new(context: SharedStyleContext<'a>) -> Self {
-        RecalcStyle { context }
+        RecalcStyle {
+            context,
+            styles_changed: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Whether two sets of element styles are the same (rather than just equal)
+fn same_styles(a: &style::data::ElementStyles, b: &style::data::ElementStyles) -> bool {
+    type Style = Option<Arc<style::properties::ComputedValues>>;
+    fn same(a: &Style, b: &Style) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
     }
+    same(&a.primary, &b.primary)
+        && a.pseudos
+            .as_array()
+            .iter()
+            .zip(b.pseudos.as_array().iter())
+            .all(|(a, b)| same(a, b))
 }
 
 #[allow(unsafe_code)]
@@ -1110,7 +1257,11 @@ where
         let el = node.as_element().unwrap();
         // let mut data = el.mutate_data().unwrap();
         let mut data = unsafe { el.ensure_data() };
+        let old_styles = data.styles.clone();
         recalc_style_at(self, traversal_data, context, el, &mut data, note_child);
+        if !same_styles(&old_styles, &data.styles) {
+            self.styles_changed.store(true, Ordering::Relaxed);
+        }
 
         // Gets set later on
         unsafe { el.unset_dirty_descendants() }
@@ -25,12 +25,52 @@ pub(crate) fn white_space_collapse(input: stylo::WhiteSpaceCollapse) -> parley::
         stylo::WhiteSpaceCollapse::Collapse => parley::WhiteSpaceCollapse::Collapse,
         stylo::WhiteSpaceCollapse::Preserve => parley::WhiteSpaceCollapse::Preserve,
 
-        // TODO: Implement PreserveBreaks and BreakSpaces modes
-        stylo::WhiteSpaceCollapse::PreserveBreaks => parley::WhiteSpaceCollapse::Preserve,
+        // Segment breaks are preserved by build_inline_layout, which pushes them as preserved text
+        stylo::WhiteSpaceCollapse::PreserveBreaks => parley::WhiteSpaceCollapse::Collapse,
+        // TODO: Parley always hangs trailing spaces, so preserved spaces can't wrap onto the next line
         stylo::WhiteSpaceCollapse::BreakSpaces => parley::WhiteSpaceCollapse::Preserve,
     }
 }
 
+pub(crate) fn font_style(input: stylo::FontStyle) -> parley::FontStyle {
+    match input {
+        stylo::FontStyle::NORMAL => parley::FontStyle::Normal,
+        stylo::FontStyle::ITALIC => parley::FontStyle::Italic,
+        val => parley::FontStyle::Oblique(Some(val.oblique_degrees())),
+    }
+}
+
+pub(crate) fn font_family(family: &stylo::SingleFontFamily) -> parley::FontFamily<'static> {
+    match family {
+        stylo::SingleFontFamily::FamilyName(name) => {
+            'ret: {
+                let name = name.name.as_ref();
+
+                // Legacy web compatibility
+                #[cfg(target_vendor = "apple")]
+                if name == "-apple-system" {
+                    break 'ret parley::FontFamily::Generic(parley::GenericFamily::SystemUi);
+                }
+                #[cfg(target_os = "macos")]
+                if name == "BlinkMacSystemFont" {
+                    break 'ret parley::FontFamily::Generic(parley::GenericFamily::SystemUi);
+                }
+
+                break 'ret parley::FontFamily::Named(Cow::Owned(name.to_string()));
+            }
+        }
+        stylo::SingleFontFamily::Generic(generic) => parley::FontFamily::Generic(match generic {
+            stylo::GenericFontFamily::None => parley::GenericFamily::SansSerif,
+            stylo::GenericFontFamily::Serif => parley::GenericFamily::Serif,
+            stylo::GenericFontFamily::SansSerif => parley::GenericFamily::SansSerif,
+            stylo::GenericFontFamily::Monospace => parley::GenericFamily::Monospace,
+            stylo::GenericFontFamily::Cursive => parley::GenericFamily::Cursive,
+            stylo::GenericFontFamily::Fantasy => parley::GenericFamily::Fantasy,
+            stylo::GenericFontFamily::SystemUi => parley::GenericFamily::SystemUi,
+        }),
+    }
+}
+
 pub(crate) fn style(
     span_id: usize,
     style: &stylo::ComputedValues,
@@ -57,11 +97,7 @@ pub(crate) fn style(
 
     // Convert Bold/Italic
     let font_weight = parley::FontWeight::new(font_styles.font_weight.value());
-    let font_style = match font_styles.font_style {
-        stylo::FontStyle::NORMAL => parley::FontStyle::Normal,
-        stylo::FontStyle::ITALIC => parley::FontStyle::Italic,
-        val => parley::FontStyle::Oblique(Some(val.oblique_degrees())),
-    };
+    let font_style = font_style(font_styles.font_style);
     let font_width = parley::FontWidth::from_percentage(font_styles.font_stretch.0.to_float());
     let font_variations: Vec<_> = font_styles
         .font_variation_settings
@@ -79,36 +115,7 @@ pub(crate) fn style(
         .families
         .list
         .iter()
-        .map(|family| match family {
-            stylo::SingleFontFamily::FamilyName(name) => {
-                'ret: {
-                    let name = name.name.as_ref();
-
-                    // Legacy web compatibility
-                    #[cfg(target_vendor = "apple")]
-                    if name == "-apple-system" {
-                        break 'ret parley::FontFamily::Generic(parley::GenericFamily::SystemUi);
-                    }
-                    #[cfg(target_os = "macos")]
-                    if name == "BlinkMacSystemFont" {
-                        break 'ret parley::FontFamily::Generic(parley::GenericFamily::SystemUi);
-                    }
-
-                    break 'ret parley::FontFamily::Named(Cow::Owned(name.to_string()));
-                }
-            }
-            stylo::SingleFontFamily::Generic(generic) => {
-                parley::FontFamily::Generic(match generic {
-                    stylo::GenericFontFamily::None => parley::GenericFamily::SansSerif,
-                    stylo::GenericFontFamily::Serif => parley::GenericFamily::Serif,
-                    stylo::GenericFontFamily::SansSerif => parley::GenericFamily::SansSerif,
-                    stylo::GenericFontFamily::Monospace => parley::GenericFamily::Monospace,
-                    stylo::GenericFontFamily::Cursive => parley::GenericFamily::Cursive,
-                    stylo::GenericFontFamily::Fantasy => parley::GenericFamily::Fantasy,
-                    stylo::GenericFontFamily::SystemUi => parley::GenericFamily::SystemUi,
-                })
-            }
-        })
+        .map(font_family)
         .collect();
 
     // Convert text colour
@@ -0,0 +1,94 @@
+//! `<template>` elements and their contents.
+//!
+//! The contents of a template are stored in a `NodeData::DocumentFragment` node which is owned by
+//! the template element (see `ElementNodeData::template_contents`) but which is not a child of it.
+//! As the fragment is not connected to the document, its contents are inert: they are not styled,
+//! laid out or rendered, and resources they reference are not loaded.
+//!
+//! See <https://html.spec.whatwg.org/multipage/scripting.html#the-template-element>
+
+use markup5ever::local_name;
+
+use crate::{BaseDocument, NodeData};
+
+impl BaseDocument {
+    /// The document fragment holding the contents of a `<template>` element
+    pub fn template_contents(&self, template_id: usize) -> Option<usize> {
+        self.nodes
+            .get(template_id)?
+            .element_data()?
+            .template_contents
+    }
+
+    /// Whether a node is connected to the document (including via shadow roots)
+    pub fn is_connected(&self, node_id: usize) -> bool {
+        let mut current = self.nodes.get(node_id);
+        while let Some(node) = current {
+            let next_id = match &node.data {
+                NodeData::Document => return true,
+                NodeData::ShadowRoot(data) => Some(data.host),
+                _ => node.parent,
+            };
+            current = next_id.and_then(|id| self.nodes.get(id));
+        }
+        false
+    }
+
+    /// Deep clone the contents of a `<template>` element. The returned nodes are not attached
+    /// to any parent and can be inserted into the document with `insert_before` or `append`.
+    ///
+    /// Returns an empty list if the node is not a template.
+    pub fn clone_template_contents(&mut self, template_id: usize) -> Vec<usize> {
+        let Some(fragment_id) = self.template_contents(template_id) else {
+            return Vec::new();
+        };
+
+        let children = self.nodes[fragment_id].children.clone();
+        children
+            .into_iter()
+            .map(|child_id| self.deep_clone_node(child_id))
+            .collect()
+    }
+
+    /// Clone the contents of a `<template>` element and append them to `parent_id`. Stylesheets,
+    /// images and iframes within the cloned nodes are loaded once they are connected to the
+    /// document.
+    ///
+    /// Returns the ids of the newly inserted nodes.
+    pub fn instantiate_template(&mut self, template_id: usize, parent_id: usize) -> Vec<usize> {
+        let new_node_ids = self.clone_template_contents(template_id);
+        self.append_children(parent_id, &new_node_ids);
+        self.changed.insert(parent_id);
+
+        if self.is_connected(parent_id) {
+            let in_shadow_tree = self.containing_shadow_root(parent_id).is_some();
+            for &node_id in &new_node_ids {
+                self.connect_subtree(node_id, in_shadow_tree);
+            }
+        }
+
+        new_node_ids
+    }
+
+    /// Set up a subtree which has just been connected to the document: register the ids of its
+    /// elements (unless it is in a shadow tree), process its `<style>` elements and start loading
+    /// the resources of its other elements
+    fn connect_subtree(&mut self, root_id: usize, in_shadow_tree: bool) {
+        let mut stack = vec![root_id];
+        while let Some(node_id) = stack.pop() {
+            let node = &self.nodes[node_id];
+            stack.extend(node.children.iter().rev().copied());
+            let Some(element) = node.element_data() else {
+                continue;
+            };
+
+            if let Some(id) = element.id.as_ref().filter(|_| !in_shadow_tree) {
+                self.nodes_to_id.entry(id.to_string()).or_insert(node_id);
+            }
+            match element.name.local {
+                local_name!("style") => self.process_style_element(node_id),
+                _ => self.load_element_resources(node_id),
+            }
+        }
+    }
+}
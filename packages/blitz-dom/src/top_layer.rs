@@ -0,0 +1,463 @@
+//! The top layer: modal dialogs and open popovers.
+//!
+//! Elements in the top layer are rendered above the rest of the document in the order that they
+//! were added, each directly above its `::backdrop`. Regardless of where they are in the tree, they
+//! are laid out against the viewport (as though they were `position: fixed` with the viewport as
+//! their containing block) and are taken out of the paint order of their layout parent.
+//!
+//! While a modal dialog is open, the rest of the document is inert: it can't be hit by the pointer
+//! (the dialog's backdrop catches it instead) and can't be focussed.
+//!
+//! See <https://drafts.csswg.org/css-position-4/#top-layer>
+
+use blitz_traits::HitResult;
+use markup5ever::{QualName, local_name, namespace_url, ns};
+use style::data::ElementData;
+use style::selector_parser::PseudoElement;
+use style::shared_lock::StylesheetGuards;
+use style::stylist::RuleInclusion;
+use style::thread_state::ThreadState;
+use style::values::generics::length::GenericSize;
+use style_dom::ElementState;
+use taffy::{
+    AvailableSpace, LayoutPartialTree as _, MaybeResolve as _, NodeId, ResolveOrZero as _,
+};
+
+use crate::layout::resolve_calc_value;
+use crate::popover::PopoverState;
+use crate::{BaseDocument, ElementNodeData, NodeData};
+
+const BACKDROP_NAME: QualName = QualName {
+    prefix: None,
+    ns: ns!(html),
+    local: local_name!("div"),
+};
+
+/// An element in the top layer
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TopLayerElement {
+    pub(crate) node_id: usize,
+    /// The node which was focussed when the element was added to the top layer
+    pub(crate) previously_focussed: Option<usize>,
+}
+
+impl BaseDocument {
+    /// The elements in the top layer, from bottom to top
+    pub fn top_layer(&self) -> impl Iterator<Item = usize> + '_ {
+        self.top_layer.iter().map(|element| element.node_id)
+    }
+
+    pub fn is_in_top_layer(&self, node_id: usize) -> bool {
+        self.top_layer().any(|id| id == node_id)
+    }
+
+    /// The topmost modal dialog (if any)
+    pub fn topmost_modal_dialog(&self) -> Option<usize> {
+        self.top_layer
+            .iter()
+            .rev()
+            .map(|element| element.node_id)
+            .find(|&id| self.nodes[id].element_state.contains(ElementState::MODAL))
+    }
+
+    /// Whether a node is inert because a modal dialog which doesn't contain it is open
+    pub fn is_inert(&self, node_id: usize) -> bool {
+        self.topmost_modal_dialog()
+            .is_some_and(|dialog_id| !self.is_inclusive_ancestor(dialog_id, node_id))
+    }
+
+    /// Whether `ancestor_id` is `node_id` or one of its ancestors (including the hosts of shadow
+    /// roots)
+    pub(crate) fn is_inclusive_ancestor(&self, ancestor_id: usize, node_id: usize) -> bool {
+        let mut current = Some(node_id);
+        while let Some(id) = current {
+            if id == ancestor_id {
+                return true;
+            }
+            let node = &self.nodes[id];
+            current = match &node.data {
+                NodeData::ShadowRoot(data) => Some(data.host),
+                _ => node.parent,
+            };
+        }
+        false
+    }
+
+    /// Add an element to the top of the top layer
+    pub(crate) fn add_to_top_layer(&mut self, node_id: usize) {
+        self.top_layer.retain(|element| element.node_id != node_id);
+        self.top_layer.push(TopLayerElement {
+            node_id,
+            previously_focussed: self.focus_node_id,
+        });
+    }
+
+    /// Remove an element from the top layer. If focus was within the element, then it is returned
+    /// to the node which was focussed when the element was added.
+    pub(crate) fn remove_from_top_layer(&mut self, node_id: usize) {
+        let Some(index) = self
+            .top_layer
+            .iter()
+            .position(|element| element.node_id == node_id)
+        else {
+            return;
+        };
+        let element = self.top_layer.remove(index);
+
+        if let Some(backdrop_id) = self.nodes[node_id].backdrop.take() {
+            self.remove_and_drop_node(backdrop_id);
+        }
+
+        let focus_within = match self.focus_node_id {
+            Some(focus_id) => self.is_inclusive_ancestor(node_id, focus_id),
+            None => true,
+        };
+        if focus_within {
+            let previously_focussed = element
+                .previously_focussed
+                .filter(|&id| self.nodes.contains(id) && self.is_connected(id));
+            match previously_focussed {
+                Some(id) if !self.is_inert(id) => {
+                    self.set_focus_to(id);
+                }
+                _ => self.clear_focus(),
+            }
+        }
+    }
+
+    /// Hit test the elements in the top layer (from top to bottom). While a modal dialog is open this
+    /// always returns a hit, as its backdrop covers the (inert) rest of the document.
+    pub(crate) fn hit_top_layer(&self, x: f32, y: f32) -> Option<HitResult> {
+        for element in self.top_layer.iter().rev() {
+            let node = &self.nodes[element.node_id];
+            if let Some(hit) = node.hit(x, y) {
+                return Some(hit);
+            }
+
+            if node.element_state.contains(ElementState::MODAL) {
+                let location = node.final_layout.location;
+                return Some(HitResult {
+                    node_id: node.id,
+                    x: x - location.x,
+                    y: y - location.y,
+                });
+            }
+        }
+        None
+    }
+
+    /// Close the topmost modal dialog or auto popover in response to the Escape key. Returns
+    /// whether anything was closed.
+    pub(crate) fn close_top_layer_on_escape(&mut self) -> bool {
+        let topmost = self
+            .top_layer
+            .iter()
+            .rev()
+            .map(|element| element.node_id)
+            .find(|&id| {
+                self.nodes[id].element_state.contains(ElementState::MODAL)
+                    || self.popover_state(id) == Some(PopoverState::Auto)
+            });
+
+        match topmost {
+            Some(id) if self.nodes[id].element_state.contains(ElementState::MODAL) => {
+                self.close_dialog(id);
+                true
+            }
+            Some(id) => {
+                self.hide_popover(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Style and lay out the elements in the top layer (and their backdrops). Must be called after
+    /// the layout of the rest of the document has been resolved.
+    pub(crate) fn resolve_top_layer(&mut self) {
+        // Elements which have been removed from the document leave the top layer
+        let disconnected: Vec<usize> = self
+            .top_layer()
+            .filter(|&id| !self.is_connected(id))
+            .collect();
+        for node_id in disconnected {
+            self.snapshot_node_and(node_id, |node| {
+                node.element_state
+                    .remove(ElementState::MODAL | ElementState::POPOVER_OPEN);
+            });
+            self.remove_from_top_layer(node_id);
+        }
+
+        if self.top_layer.is_empty() {
+            return;
+        }
+
+        let viewport_size = self.stylist.device().au_viewport_size();
+        let containing_block = taffy::Size {
+            width: viewport_size.width.to_f32_px(),
+            height: viewport_size.height.to_f32_px(),
+        };
+        let node_ids: Vec<usize> = self.top_layer().collect();
+        for node_id in node_ids {
+            // Take the element out of the paint order of its layout parent
+            if let Some(parent_id) = self.nodes[node_id].layout_parent.take() {
+                let mut paint_children = self.nodes[parent_id].paint_children.borrow_mut();
+                if let Some(children) = paint_children.as_mut() {
+                    children.retain(|&id| id != node_id);
+                }
+            }
+
+            self.flush_backdrop(node_id);
+            if let Some(backdrop_id) = self.nodes[node_id].backdrop {
+                self.layout_top_layer_box(backdrop_id, containing_block);
+            }
+            self.layout_top_layer_box(node_id, containing_block);
+        }
+    }
+
+    /// Create, update or remove the `::backdrop` of an element in the top layer
+    fn flush_backdrop(&mut self, node_id: usize) {
+        let backdrop_style = {
+            let node = &self.nodes[node_id];
+            let Some(originating_style) = node.primary_styles() else {
+                return;
+            };
+
+            style::thread_state::enter(ThreadState::LAYOUT);
+            let guard = &self.guard;
+            let guards = StylesheetGuards {
+                author: &guard.read(),
+                ua_or_user: &guard.read(),
+            };
+            let style = self.stylist.lazily_compute_pseudo_element_style(
+                &guards,
+                node,
+                &PseudoElement::Backdrop,
+                RuleInclusion::All,
+                &originating_style,
+                false,
+                None,
+            );
+            style::thread_state::exit(ThreadState::LAYOUT);
+            style
+        };
+
+        let backdrop_id = match (self.nodes[node_id].backdrop, backdrop_style) {
+            (None, None) => return,
+            (Some(backdrop_id), None) => {
+                self.remove_and_drop_node(backdrop_id);
+                self.nodes[node_id].backdrop = None;
+                return;
+            }
+            (None, Some(style)) => {
+                let backdrop_id = self.create_node(NodeData::AnonymousBlock(ElementNodeData::new(
+                    BACKDROP_NAME,
+                    Vec::new(),
+                )));
+                let backdrop = &mut self.nodes[backdrop_id];
+                backdrop.parent = Some(node_id);
+                *backdrop.layout_children.borrow_mut() = Some(Vec::new());
+                *backdrop.paint_children.borrow_mut() = Some(Vec::new());
+
+                let mut element_data = ElementData::default();
+                element_data.styles.primary = Some(style);
+                element_data.set_restyled();
+                *backdrop.stylo_element_data.borrow_mut() = Some(element_data);
+
+                self.nodes[node_id].backdrop = Some(backdrop_id);
+                backdrop_id
+            }
+            (Some(backdrop_id), Some(style)) => {
+                let mut element_data = self.nodes[backdrop_id].stylo_element_data.borrow_mut();
+                let element_data = element_data.as_mut().unwrap();
+                element_data.styles.primary = Some(style);
+                element_data.set_restyled();
+                backdrop_id
+            }
+        };
+
+        self.flush_styles_to_layout(backdrop_id);
+    }
+
+    /// Size and position a box in the top layer, using the viewport as its containing block. Boxes
+    /// with both insets set in an axis and a `fit-content` size are centered by auto margins.
+    fn layout_top_layer_box(&mut self, node_id: usize, containing_block: taffy::Size<f32>) {
+        let node = &self.nodes[node_id];
+
+        // Boxes with an auto size (rather than `fit-content`) stretch between their insets
+        let stretch = node
+            .primary_styles()
+            .map(|styles| taffy::Size {
+                width: matches!(styles.clone_width(), GenericSize::Auto),
+                height: matches!(styles.clone_height(), GenericSize::Auto),
+            })
+            .unwrap_or(taffy::Size {
+                width: true,
+                height: true,
+            });
+
+        let style = &node.style;
+        let margin = taffy::Rect {
+            left: style
+                .margin
+                .left
+                .maybe_resolve(containing_block.width, resolve_calc_value),
+            right: style
+                .margin
+                .right
+                .maybe_resolve(containing_block.width, resolve_calc_value),
+            top: style
+                .margin
+                .top
+                .maybe_resolve(containing_block.width, resolve_calc_value),
+            bottom: style
+                .margin
+                .bottom
+                .maybe_resolve(containing_block.width, resolve_calc_value),
+        };
+        let mut inset = taffy::Rect {
+            left: style
+                .inset
+                .left
+                .maybe_resolve(containing_block.width, resolve_calc_value),
+            right: style
+                .inset
+                .right
+                .maybe_resolve(containing_block.width, resolve_calc_value),
+            top: style
+                .inset
+                .top
+                .maybe_resolve(containing_block.height, resolve_calc_value),
+            bottom: style
+                .inset
+                .bottom
+                .maybe_resolve(containing_block.height, resolve_calc_value),
+        };
+        // Stylo's selector parser doesn't support `:modal`, so the block insets from the UA
+        // stylesheet's `dialog:modal` rule are applied here instead
+        if self.is_dialog_modal(node_id) && inset.top.is_none() && inset.bottom.is_none() {
+            inset.top = Some(0.0);
+            inset.bottom = Some(0.0);
+        }
+        let padding = style
+            .padding
+            .resolve_or_zero(Some(containing_block.width), resolve_calc_value);
+        let border = style
+            .border
+            .resolve_or_zero(Some(containing_block.width), resolve_calc_value);
+
+        let available_size = taffy::Size {
+            width: containing_block.width
+                - inset.left.unwrap_or(0.0)
+                - inset.right.unwrap_or(0.0)
+                - margin.left.unwrap_or(0.0)
+                - margin.right.unwrap_or(0.0),
+            height: containing_block.height
+                - inset.top.unwrap_or(0.0)
+                - inset.bottom.unwrap_or(0.0)
+                - margin.top.unwrap_or(0.0)
+                - margin.bottom.unwrap_or(0.0),
+        };
+        let known_dimensions = taffy::Size {
+            width: (stretch.width && inset.left.is_some() && inset.right.is_some())
+                .then_some(available_size.width.max(0.0)),
+            height: (stretch.height && inset.top.is_some() && inset.bottom.is_some())
+                .then_some(available_size.height.max(0.0)),
+        };
+
+        let output = self.compute_child_layout(
+            NodeId::from(node_id),
+            taffy::tree::LayoutInput {
+                known_dimensions,
+                parent_size: containing_block.map(Some),
+                available_space: available_size.map(|size| AvailableSpace::Definite(size.max(0.0))),
+                run_mode: taffy::RunMode::PerformLayout,
+                sizing_mode: taffy::SizingMode::InherentSize,
+                axis: taffy::RequestedAxis::Both,
+                vertical_margins_are_collapsible: taffy::Line::FALSE,
+            },
+        );
+
+        let (x, margin_left, margin_right) = resolve_top_layer_axis(
+            containing_block.width,
+            (inset.left, inset.right),
+            (margin.left, margin.right),
+            output.size.width,
+        );
+        let (y, margin_top, margin_bottom) = resolve_top_layer_axis(
+            containing_block.height,
+            (inset.top, inset.bottom),
+            (margin.top, margin.bottom),
+            output.size.height,
+        );
+
+        // Top layer boxes are fixed to the viewport, so they are positioned relative to its scroll
+        // position in document coordinates
+        let viewport_scroll = self.viewport_scroll;
+        let layout = &mut self.nodes[node_id].unrounded_layout;
+        layout.size = output.size;
+        layout.content_size = output.content_size;
+        layout.location = taffy::Point {
+            x: x + viewport_scroll.x as f32,
+            y: y + viewport_scroll.y as f32,
+        };
+        layout.padding = padding;
+        layout.border = border;
+        layout.margin = taffy::Rect {
+            left: margin_left,
+            right: margin_right,
+            top: margin_top,
+            bottom: margin_bottom,
+        };
+
+        taffy::round_layout(self, NodeId::from(node_id));
+    }
+}
+
+/// Resolve the position and margins of a top layer box in one axis. Auto margins absorb the free
+/// space between the insets (centering the box if both are auto).
+fn resolve_top_layer_axis(
+    containing_block: f32,
+    (inset_start, inset_end): (Option<f32>, Option<f32>),
+    (margin_start, margin_end): (Option<f32>, Option<f32>),
+    size: f32,
+) -> (f32, f32, f32) {
+    match (inset_start, inset_end) {
+        (Some(start), Some(end)) => {
+            let free_space = containing_block
+                - start
+                - end
+                - size
+                - margin_start.unwrap_or(0.0)
+                - margin_end.unwrap_or(0.0);
+            let (margin_start, margin_end) = match (margin_start, margin_end) {
+                (None, None) if free_space >= 0.0 => (free_space / 2.0, free_space / 2.0),
+                (None, None) => (0.0, free_space),
+                (None, Some(margin_end)) => (free_space, margin_end),
+                (Some(margin_start), None) => (margin_start, free_space),
+                (Some(margin_start), Some(margin_end)) => (margin_start, margin_end),
+            };
+            (start + margin_start, margin_start, margin_end)
+        }
+        (Some(start), None) => {
+            let margin_start = margin_start.unwrap_or(0.0);
+            (
+                start + margin_start,
+                margin_start,
+                margin_end.unwrap_or(0.0),
+            )
+        }
+        (None, Some(end)) => {
+            let margin_end = margin_end.unwrap_or(0.0);
+            (
+                containing_block - end - margin_end - size,
+                margin_start.unwrap_or(0.0),
+                margin_end,
+            )
+        }
+        (None, None) => {
+            let margin_start = margin_start.unwrap_or(0.0);
+            (margin_start, margin_start, margin_end.unwrap_or(0.0))
+        }
+    }
+}
@@ -64,8 +64,14 @@ pub fn walk_tree(indent: usize, node: &Node) {
 
         NodeData::Comment => println!("<!-- COMMENT {id} -->"),
 
+        NodeData::ProcessingInstruction { target, data } => println!("<?{target} {data}?> {id}"),
+
         NodeData::AnonymousBlock(_) => println!("{id} AnonymousBlock"),
 
+        NodeData::ShadowRoot(data) => println!("#shadow-root ({:?}) {id}", data.mode),
+
+        NodeData::DocumentFragment => println!("#document-fragment {id}"),
+
         NodeData::Element(data) => {
             print!("<{} {id}", data.name.local);
             for attr in data.attrs.iter() {
@@ -84,6 +90,14 @@ pub fn walk_tree(indent: usize, node: &Node) {
           // NodeData::ProcessingInstruction { .. } => unreachable!(),
     }
 
+    if let Some(shadow_root_id) = node.shadow_root {
+        walk_tree(indent + 2, node.with(shadow_root_id));
+    }
+
+    if let Some(fragment_id) = node.element_data().and_then(|el| el.template_contents) {
+        walk_tree(indent + 2, node.with(fragment_id));
+    }
+
     if !node.children.is_empty() {
         for child_id in node.children.iter() {
             walk_tree(indent + 2, node.with(*child_id));
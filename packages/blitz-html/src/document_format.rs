@@ -0,0 +1,64 @@
+//! Selecting whether a document is parsed as HTML or as XML.
+
+/// The syntax a document's source is parsed with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocumentFormat {
+    /// Parse with the HTML parser (html5ever)
+    #[default]
+    Html,
+    /// Parse with the XML parser (xml5ever). Used for XHTML documents and other XML documents
+    /// (such as EPUB chapters), which may contain elements from other namespaces and processing
+    /// instructions.
+    Xhtml,
+}
+
+impl DocumentFormat {
+    /// The format of a document served with a `Content-Type` header. Returns `None` for types
+    /// that aren't HTML or XML.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime_type = content_type.split(';').next().unwrap_or_default().trim();
+        let mime_type = mime_type.to_ascii_lowercase();
+        match mime_type.as_str() {
+            "text/html" => Some(DocumentFormat::Html),
+            "application/xhtml+xml" | "application/xml" | "text/xml" => Some(DocumentFormat::Xhtml),
+            _ if mime_type.ends_with("+xml") => Some(DocumentFormat::Xhtml),
+            _ => None,
+        }
+    }
+
+    /// The format of a document loaded from a file path or url, based on its extension. Returns
+    /// `None` for extensions that don't indicate an XML document.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+        let (_, extension) = file_name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "xhtml" | "xht" | "xml" => Some(DocumentFormat::Xhtml),
+            _ => None,
+        }
+    }
+
+    /// Guess the format of a document from its source: documents starting with an XML
+    /// declaration or an XHTML doctype are parsed as XML.
+    pub fn sniff(source: &str) -> Self {
+        let source = source.trim_start_matches('\u{feff}');
+        let is_xml = source.starts_with("<?xml")
+            || source.starts_with("<!DOCTYPE") && {
+                let first_line = source.lines().next().unwrap();
+                first_line.contains("XHTML") || first_line.contains("xhtml")
+            };
+        match is_xml {
+            true => DocumentFormat::Xhtml,
+            false => DocumentFormat::Html,
+        }
+    }
+
+    /// Select the format of a document from its `Content-Type` (if known), then the extension of
+    /// its url (if known), then its source
+    pub fn detect(source: &str, url: Option<&str>, content_type: Option<&str>) -> Self {
+        content_type
+            .and_then(DocumentFormat::from_content_type)
+            .or_else(|| url.and_then(DocumentFormat::from_path))
+            .unwrap_or_else(|| DocumentFormat::sniff(source))
+    }
+}
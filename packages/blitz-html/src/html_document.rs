@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
-use crate::DocumentHtmlParser;
+use crate::{DocumentFormat, DocumentHtmlParser};
 
-use blitz_dom::{BaseDocument, DEFAULT_CSS, FontContext, net::Resource};
+use blitz_dom::{
+    BaseDocument, DEFAULT_CSS, FontContext, IframeProvider, SubDocumentConfig, net::Resource,
+};
 use blitz_traits::{
     ColorScheme, Document, DomEvent, Viewport, navigation::NavigationProvider, net::SharedProvider,
 };
@@ -30,6 +32,16 @@ impl From<HtmlDocument> for BaseDocument {
 }
 impl Document for HtmlDocument {
     type Doc = BaseDocument;
+
+    fn poll(&mut self, cx: std::task::Context) -> bool {
+        let animated = self.inner.poll_image_animations(&cx);
+        let media_changed = self.inner.poll_media(&cx);
+        // There is no script to dispatch media events to
+        self.inner.take_media_events();
+        let canvas_changed = self.inner.poll_canvases(&cx);
+        animated || media_changed || canvas_changed
+    }
+
     fn handle_event(&mut self, event: &mut DomEvent) {
         self.inner.as_mut().handle_event(event)
     }
@@ -40,6 +52,8 @@ impl Document for HtmlDocument {
 }
 
 impl HtmlDocument {
+    /// Parse a document. It is parsed as XML if its base url has an XML extension (such as
+    /// `.xhtml`) or it starts with an XML declaration or XHTML doctype, and as HTML otherwise.
     pub fn from_html(
         html: &str,
         base_url: Option<String>,
@@ -47,6 +61,48 @@ impl HtmlDocument {
         net_provider: SharedProvider<Resource>,
         font_ctx: Option<FontContext>,
         navigation_provider: Arc<dyn NavigationProvider>,
+    ) -> Self {
+        let format = DocumentFormat::detect(html, base_url.as_deref(), None);
+        Self::from_source(
+            html,
+            format,
+            base_url,
+            stylesheets,
+            net_provider,
+            font_ctx,
+            navigation_provider,
+        )
+    }
+
+    /// Parse an XHTML (or other XML) document
+    pub fn from_xhtml(
+        xhtml: &str,
+        base_url: Option<String>,
+        stylesheets: Vec<String>,
+        net_provider: SharedProvider<Resource>,
+        font_ctx: Option<FontContext>,
+        navigation_provider: Arc<dyn NavigationProvider>,
+    ) -> Self {
+        Self::from_source(
+            xhtml,
+            DocumentFormat::Xhtml,
+            base_url,
+            stylesheets,
+            net_provider,
+            font_ctx,
+            navigation_provider,
+        )
+    }
+
+    /// Parse a document in the given format
+    pub fn from_source(
+        source: &str,
+        format: DocumentFormat,
+        base_url: Option<String>,
+        stylesheets: Vec<String>,
+        net_provider: SharedProvider<Resource>,
+        font_ctx: Option<FontContext>,
+        navigation_provider: Arc<dyn NavigationProvider>,
     ) -> Self {
         // Spin up the virtualdom and include the default stylesheet
         let viewport = Viewport::new(0, 0, 1.0, ColorScheme::Light);
@@ -66,6 +122,9 @@ impl HtmlDocument {
         // Set the navigation provider
         doc.set_navigation_provider(navigation_provider.clone());
 
+        // Parse the nested documents of iframes as HTML
+        doc.set_iframe_provider(Arc::new(HtmlIframeProvider::new(stylesheets.clone())));
+
         // Include default and user-specified stylesheets
         doc.add_user_agent_stylesheet(DEFAULT_CSS);
         for ss in &stylesheets {
@@ -73,8 +132,186 @@ impl HtmlDocument {
         }
 
         // Parse HTML string into document
-        DocumentHtmlParser::parse_into_doc(&mut doc, html, net_provider);
+        DocumentHtmlParser::parse_into_doc_with_format(&mut doc, source, format, net_provider);
 
         HtmlDocument { inner: doc }
     }
 }
+
+/// An `IframeProvider` which parses the nested documents of iframes as HTML
+pub struct HtmlIframeProvider {
+    /// Stylesheets to add to nested documents (in addition to the default stylesheet)
+    stylesheets: Vec<String>,
+}
+
+impl HtmlIframeProvider {
+    pub fn new(stylesheets: Vec<String>) -> Self {
+        Self { stylesheets }
+    }
+}
+
+impl IframeProvider for HtmlIframeProvider {
+    fn create_document(&self, html: &str, config: SubDocumentConfig) -> Option<BaseDocument> {
+        let mut doc = config.create_document();
+        doc.add_user_agent_stylesheet(DEFAULT_CSS);
+        for ss in &self.stylesheets {
+            doc.add_user_agent_stylesheet(ss);
+        }
+        let url = config.url.as_ref().map(|url| url.as_str());
+        let content_type = config.content_type.as_deref();
+        let format = DocumentFormat::detect(html, url, content_type);
+        DocumentHtmlParser::parse_into_doc_with_format(
+            &mut doc,
+            html,
+            format,
+            config.net_provider(),
+        );
+        Some(doc)
+    }
+}
+
+#[test]
+fn parses_srcdoc_iframes_as_html() {
+    use blitz_traits::{navigation::DummyNavigationProvider, net::DummyNetProvider};
+
+    // srcdoc is always HTML, even if it starts with an XML declaration
+    let html = "<iframe srcdoc=\"<?xml version='1.0'?><p id='inner'>hello</p>\"></iframe>";
+    let doc = HtmlDocument::from_html(
+        html,
+        None,
+        Vec::new(),
+        Arc::new(DummyNetProvider::default()),
+        None,
+        Arc::new(DummyNavigationProvider),
+    );
+    let doc = doc.as_ref();
+    let iframe_id = doc.query_selector("iframe").unwrap().unwrap();
+    let sub_document = doc.sub_document(iframe_id).unwrap();
+    assert!(
+        sub_document
+            .query_selector("body > #inner")
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[test]
+fn parses_iframe_documents_by_content_type() {
+    use blitz_traits::navigation::DummyNavigationProvider;
+    use blitz_traits::net::{
+        BoxedHandler, Bytes, NetCallback, NetProvider, Request, Response, SharedCallback,
+    };
+    use std::sync::Mutex;
+
+    /// Responds to requests with an XHTML document
+    struct XhtmlNetProvider(SharedCallback<Resource>);
+    impl NetProvider for XhtmlNetProvider {
+        type Data = Resource;
+        fn fetch(&self, doc_id: usize, _request: Request, handler: BoxedHandler<Resource>) {
+            let xhtml = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+                <div id="empty"/><p id="para"/>
+            </body></html>"#;
+            let content_type = Some(String::from("application/xhtml+xml"));
+            let response = Response::new(Bytes::from(xhtml), content_type);
+            handler.response(doc_id, response, self.0.clone());
+        }
+    }
+
+    #[derive(Default)]
+    struct CollectingCallback(Mutex<Vec<Resource>>);
+    impl NetCallback for CollectingCallback {
+        type Data = Resource;
+        fn call(&self, _doc_id: usize, result: Result<Resource, Option<String>>) {
+            self.0.lock().unwrap().extend(result.ok());
+        }
+    }
+
+    let callback = Arc::new(CollectingCallback::default());
+    let mut doc = HtmlDocument::from_html(
+        r#"<iframe src="https://example.com/chapter"></iframe>"#,
+        None,
+        Vec::new(),
+        Arc::new(XhtmlNetProvider(callback.clone())),
+        None,
+        Arc::new(DummyNavigationProvider),
+    );
+    let doc = doc.as_mut();
+    let resources = std::mem::take(&mut *callback.0.lock().unwrap());
+    for resource in resources {
+        doc.load_resource(resource);
+    }
+
+    // The url has no extension, so the document is parsed as XML because of its Content-Type
+    let iframe_id = doc.query_selector("iframe").unwrap().unwrap();
+    let sub_document = doc.sub_document(iframe_id).unwrap();
+    let empty = sub_document.get_element_by_id("empty").unwrap();
+    assert!(sub_document.nodes[empty].children.is_empty());
+}
+
+#[test]
+fn parses_xhtml_documents() {
+    use blitz_dom::NodeData;
+    use blitz_traits::{navigation::DummyNavigationProvider, net::DummyNetProvider};
+
+    assert_eq!(
+        DocumentFormat::from_content_type("application/xhtml+xml; charset=utf-8"),
+        Some(DocumentFormat::Xhtml)
+    );
+    assert_eq!(
+        DocumentFormat::from_content_type("text/html"),
+        Some(DocumentFormat::Html)
+    );
+    assert_eq!(
+        DocumentFormat::from_path("https://example.com/OEBPS/chapter1.xhtml?page=2"),
+        Some(DocumentFormat::Xhtml)
+    );
+    assert_eq!(DocumentFormat::from_path("index.html"), None);
+
+    let xhtml = r#"<?xml-stylesheet type="text/css" href="book.css"?>
+        <html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+            <head><style>p { margin: 0 }</style></head>
+            <body>
+                <div id="empty"/>
+                <p id="para">Chapter <epub:switch id="switch"/></p>
+            </body>
+        </html>"#;
+    let doc = HtmlDocument::from_html(
+        xhtml,
+        Some(String::from("file:///book/OEBPS/chapter1.xhtml")),
+        Vec::new(),
+        Arc::new(DummyNetProvider::default()),
+        None,
+        Arc::new(DummyNavigationProvider),
+    );
+    let doc = doc.as_ref();
+
+    // Self-closing elements are empty in XML
+    let empty = doc.get_element_by_id("empty").unwrap();
+    let para = doc.get_element_by_id("para").unwrap();
+    assert!(doc.nodes[empty].children.is_empty());
+    assert_eq!(doc.nodes[para].parent, doc.nodes[empty].parent);
+
+    // Elements keep their namespaces
+    let html = doc.nodes[para].element_data().unwrap();
+    assert_eq!(&*html.name.ns, "http://www.w3.org/1999/xhtml");
+    let switch = doc.get_element_by_id("switch").unwrap();
+    let switch = doc.nodes[switch].element_data().unwrap();
+    assert_eq!(&*switch.name.ns, "http://www.idpf.org/2007/ops");
+    assert_eq!(&*switch.name.local, "switch");
+
+    // Processing instructions are kept in the tree
+    let instruction = doc.nodes[0]
+        .children
+        .iter()
+        .find_map(|&id| match &doc.nodes[id].data {
+            NodeData::ProcessingInstruction { target, data } => Some((target, data)),
+            _ => None,
+        });
+    assert_eq!(
+        instruction,
+        Some((
+            &String::from("xml-stylesheet"),
+            &String::from(r#"type="text/css" href="book.css""#)
+        ))
+    );
+}
@@ -1,16 +1,16 @@
 //! An implementation for Html5ever's sink trait, allowing us to parse HTML into a DOM.
 
-use blitz_dom::net::{CssHandler, ImageHandler, Resource};
-use blitz_dom::util::ImageType;
+use blitz_dom::net::Resource;
 use std::borrow::Cow;
 use std::cell::{Cell, Ref, RefCell, RefMut};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use crate::DocumentFormat;
 use blitz_dom::BaseDocument;
-use blitz_dom::node::{Attribute, ElementNodeData, Node, NodeData};
-use blitz_traits::net::{Request, SharedProvider};
+use blitz_dom::node::{Attribute, ElementNodeData, Node, NodeData, ShadowRootMode};
+use blitz_traits::net::SharedProvider;
 use html5ever::{
-    QualName, local_name,
+    QualName, local_name, namespace_url, ns,
     tendril::{StrTendril, TendrilSink},
     tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink},
 };
@@ -24,60 +24,104 @@ fn html5ever_to_blitz_attr(attr: html5ever::Attribute) -> Attribute {
     }
 }
 
+/// The value of a pseudo-attribute (such as `href="style.css"`) in the data of a processing
+/// instruction
+fn pseudo_attribute<'a>(data: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = data;
+    while let Some(eq) = rest.find('=') {
+        let attr_name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            // Skip pairs with unquoted values
+            rest = &value[value.find(char::is_whitespace).unwrap_or(value.len())..];
+            continue;
+        };
+        let end = value[1..].find(quote)? + 1;
+        if attr_name == name {
+            return Some(&value[1..end]);
+        }
+        rest = &value[end + 1..];
+    }
+    None
+}
+
 pub struct DocumentHtmlParser<'a> {
-    doc_id: usize,
     doc: RefCell<&'a mut BaseDocument>,
     style_nodes: RefCell<Vec<usize>>,
 
+    /// `<link>`, `<img>`, `<iframe>`, `<video>` and `<audio>` elements whose resources should be loaded
+    /// once parsing is complete
+    /// (if they end up connected to the document)
+    resource_nodes: RefCell<Vec<usize>>,
+
+    /// `<?xml-stylesheet?>` processing instructions whose stylesheets should be loaded once parsing
+    /// is complete
+    stylesheet_instructions: RefCell<Vec<usize>>,
+
+    /// Maps `<template shadowrootmode>` elements to the shadow roots they declare
+    declarative_shadow_roots: RefCell<HashMap<usize, usize>>,
+
     /// Errors that occurred during parsing.
     pub errors: RefCell<Vec<Cow<'static, str>>>,
 
     /// The document's quirks mode.
     pub quirks_mode: Cell<QuirksMode>,
     pub is_xml: bool,
-
-    net_provider: SharedProvider<Resource>,
 }
 
 impl DocumentHtmlParser<'_> {
+    /// Create a parser for `doc`. Resources referenced by the document are loaded with
+    /// `net_provider`.
     pub fn new(
         doc: &mut BaseDocument,
         net_provider: SharedProvider<Resource>,
     ) -> DocumentHtmlParser {
+        doc.set_net_provider(net_provider);
         DocumentHtmlParser {
-            doc_id: doc.id(),
             doc: RefCell::new(doc),
             style_nodes: RefCell::new(Vec::new()),
+            resource_nodes: RefCell::new(Vec::new()),
+            stylesheet_instructions: RefCell::new(Vec::new()),
+            declarative_shadow_roots: RefCell::new(HashMap::new()),
             errors: RefCell::new(Vec::new()),
             quirks_mode: Cell::new(QuirksMode::NoQuirks),
-            net_provider,
             is_xml: false,
         }
     }
 
+    /// Parse a document into `doc`, guessing whether it is HTML or XML from its source
     pub fn parse_into_doc<'d>(
         doc: &'d mut BaseDocument,
         html: &str,
         net_provider: SharedProvider<Resource>,
+    ) -> &'d mut BaseDocument {
+        let format = DocumentFormat::sniff(html);
+        Self::parse_into_doc_with_format(doc, html, format, net_provider)
+    }
+
+    /// Parse a document into `doc` as either HTML or XML
+    pub fn parse_into_doc_with_format<'d>(
+        doc: &'d mut BaseDocument,
+        source: &str,
+        format: DocumentFormat,
+        net_provider: SharedProvider<Resource>,
     ) -> &'d mut BaseDocument {
         let mut sink = Self::new(doc, net_provider);
-        if html.starts_with("<?xml")
-            || html.starts_with("<!DOCTYPE") && {
-                let first_line = html.lines().next().unwrap();
-                first_line.contains("XHTML") || first_line.contains("xhtml")
+        match format {
+            DocumentFormat::Xhtml => {
+                sink.is_xml = true;
+                xml5ever::driver::parse_document(sink, Default::default())
+                    .from_utf8()
+                    .read_from(&mut source.as_bytes())
+                    .unwrap()
+            }
+            DocumentFormat::Html => {
+                sink.is_xml = false;
+                html5ever::parse_document(sink, Default::default())
+                    .from_utf8()
+                    .read_from(&mut source.as_bytes())
+                    .unwrap()
             }
-        {
-            sink.is_xml = true;
-            xml5ever::driver::parse_document(sink, Default::default())
-                .from_utf8()
-                .read_from(&mut html.as_bytes())
-                .unwrap()
-        } else {
-            sink.is_xml = false;
-            html5ever::parse_document(sink, Default::default())
-                .from_utf8()
-                .read_from(&mut html.as_bytes())
-                .unwrap()
         }
     }
 
@@ -120,44 +164,22 @@ impl DocumentHtmlParser<'_> {
         self.node(parent_id).children.last().copied()
     }
 
-    fn load_linked_stylesheet(&self, target_id: usize) {
+    /// Load the stylesheet of an `<?xml-stylesheet href="..."?>` processing instruction
+    fn load_xml_stylesheet(&self, target_id: usize) {
         let node = self.node(target_id);
-
-        let rel_attr = node.attr(local_name!("rel"));
-        let href_attr = node.attr(local_name!("href"));
-
-        let (Some(rels), Some(href)) = (rel_attr, href_attr) else {
+        let NodeData::ProcessingInstruction { data, .. } = &node.data else {
             return;
         };
-        if !rels.split_ascii_whitespace().any(|rel| rel == "stylesheet") {
-            return;
-        }
 
-        let url = self.doc.borrow().resolve_url(href);
-        self.net_provider.fetch(
-            self.doc_id,
-            Request::get(url.clone()),
-            Box::new(CssHandler {
-                node: target_id,
-                source_url: url,
-                guard: self.doc.borrow().guard.clone(),
-                provider: self.net_provider.clone(),
-            }),
-        );
-    }
+        // Only CSS stylesheets are supported (not XSLT)
+        let is_css = pseudo_attribute(data, "type").is_none_or(|ty| ty == "text/css");
+        let Some(href) = pseudo_attribute(data, "href").filter(|_| is_css) else {
+            return;
+        };
 
-    fn load_image(&self, target_id: usize) {
-        let node = self.node(target_id);
-        if let Some(raw_src) = node.attr(local_name!("src")) {
-            if !raw_src.is_empty() {
-                let src = self.doc.borrow().resolve_url(raw_src);
-                self.net_provider.fetch(
-                    self.doc.borrow().id(),
-                    Request::get(src),
-                    Box::new(ImageHandler::new(target_id, ImageType::Image)),
-                );
-            }
-        }
+        let href = href.to_string();
+        drop(node);
+        self.doc.borrow_mut().load_stylesheet(target_id, &href);
     }
 
     fn process_button_input(&self, target_id: usize) {
@@ -195,13 +217,48 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
         Self: 'a;
 
     fn finish(self) -> Self::Output {
+        // Nodes within template contents are inert, so their resources are never loaded
+        for &id in self.resource_nodes.borrow().iter() {
+            if self.doc.borrow().is_connected(id) {
+                self.doc.borrow_mut().load_element_resources(id);
+            }
+        }
+
+        for &id in self.stylesheet_instructions.borrow().iter() {
+            if self.doc.borrow().is_connected(id) {
+                self.load_xml_stylesheet(id);
+            }
+        }
+
+        // The templates declaring shadow roots are never inserted into the document
+        for &template_id in self.declarative_shadow_roots.borrow().keys() {
+            let mut doc = self.doc.borrow_mut();
+            if doc.nodes[template_id].parent.is_none() {
+                doc.remove_and_drop_node(template_id);
+            }
+        }
+
         let doc = self.doc.into_inner();
 
         // Add inline stylesheets (<style> elements)
-        for id in self.style_nodes.borrow().iter() {
-            doc.process_style_element(*id);
+        for &id in self.style_nodes.borrow().iter() {
+            if doc.is_connected(id) {
+                doc.process_style_element(id);
+            }
         }
 
+        // Elements within template contents and shadow trees can't be found by id
+        let mut nodes_to_id = HashMap::new();
+        let mut stack = vec![0];
+        while let Some(id) = stack.pop() {
+            let node = &doc.nodes[id];
+            if let Some(id_attr) = node.attr(local_name!("id")) {
+                nodes_to_id.entry(id_attr.to_string()).or_insert(id);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        doc.nodes_to_id = nodes_to_id;
+
         for error in self.errors.borrow().iter() {
             println!("ERROR: {}", error);
         }
@@ -250,10 +307,22 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
             self.doc.borrow_mut().nodes_to_id.insert(id_attr, id);
         }
 
+        // Elements from other namespaces in XML documents (e.g. SVG's <style>) don't get the
+        // special handling of HTML elements
+        if self.is_xml && name.ns != ns!(html) {
+            return id;
+        }
+
         // Custom post-processing by element tag name
         match name.local.as_ref() {
-            "link" => self.load_linked_stylesheet(id),
-            "img" => self.load_image(id),
+            "link" | "img" | "iframe" | "video" | "audio" => {
+                self.resource_nodes.borrow_mut().push(id)
+            }
+            "template" => {
+                let fragment_id = self.create_node(NodeData::DocumentFragment);
+                let mut node = self.node_mut(id);
+                node.element_data_mut().unwrap().template_contents = Some(fragment_id);
+            }
             "input" => self.process_button_input(id),
             "style" => self.style_nodes.borrow_mut().push(id),
             _ => {}
@@ -266,8 +335,16 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
         self.create_node(NodeData::Comment)
     }
 
-    fn create_pi(&self, _target: StrTendril, _data: StrTendril) -> Self::Handle {
-        self.create_node(NodeData::Comment)
+    fn create_pi(&self, target: StrTendril, data: StrTendril) -> Self::Handle {
+        let is_stylesheet = &*target == "xml-stylesheet";
+        let id = self.create_node(NodeData::ProcessingInstruction {
+            target: target.to_string(),
+            data: data.to_string(),
+        });
+        if is_stylesheet {
+            self.stylesheet_instructions.borrow_mut().push(id);
+        }
+        id
     }
 
     fn append(&self, parent_id: &Self::Handle, child: NodeOrText<Self::Handle>) {
@@ -349,8 +426,48 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
     }
 
     fn get_template_contents(&self, target: &Self::Handle) -> Self::Handle {
-        // TODO: implement templates properly. This should allow to function like regular elements.
-        *target
+        // The contents of a declarative shadow root template are parsed into the shadow root
+        if let Some(shadow_root_id) = self.declarative_shadow_roots.borrow().get(target) {
+            return *shadow_root_id;
+        }
+
+        self.node(*target)
+            .element_data()
+            .and_then(|el| el.template_contents)
+            .expect("TreeSink::get_template_contents called on a node which is not a template!")
+    }
+
+    fn attach_declarative_shadow(
+        &self,
+        location: &Self::Handle,
+        template: &Self::Handle,
+        attrs: Vec<html5ever::Attribute>,
+    ) -> Result<(), String> {
+        let mode = attrs
+            .iter()
+            .find(|attr| attr.name.local == local_name!("shadowrootmode"))
+            .and_then(|attr| ShadowRootMode::parse(&attr.value))
+            .ok_or_else(|| String::from("Invalid shadowrootmode"))?;
+
+        let shadow_root_id = self
+            .doc
+            .borrow_mut()
+            .attach_shadow(*location, mode)
+            .ok_or_else(|| String::from("Element cannot have a shadow root attached"))?;
+        self.declarative_shadow_roots
+            .borrow_mut()
+            .insert(*template, shadow_root_id);
+
+        // The template's contents are parsed into the shadow root, so it doesn't need a fragment
+        let fragment_id = self
+            .node_mut(*template)
+            .element_data_mut()
+            .and_then(|el| el.template_contents.take());
+        if let Some(fragment_id) = fragment_id {
+            self.doc.borrow_mut().remove_and_drop_node(fragment_id);
+        }
+
+        Ok(())
     }
 
     fn same_node(&self, x: &Self::Handle, y: &Self::Handle) -> bool {
@@ -420,3 +537,84 @@ fn parses_some_html() {
 
     // Now our tree should have some nodes in it
 }
+
+#[test]
+fn parses_declarative_shadow_root() {
+    use blitz_traits::{ColorScheme, Viewport, net::DummyNetProvider};
+    use std::sync::Arc;
+
+    let html = "<div><template shadowrootmode=\"open\"><p>shadow</p><slot></slot></template><span>light</span></div>";
+    let viewport = Viewport::new(800, 600, 1.0, ColorScheme::Light);
+    let mut doc = BaseDocument::new(viewport);
+    DocumentHtmlParser::parse_into_doc(&mut doc, html, Arc::new(DummyNetProvider::default()));
+
+    let host_id = doc.query_selector("div").unwrap().unwrap();
+    let shadow_root_id = doc.shadow_root(host_id).unwrap();
+    assert_eq!(doc.shadow_host(shadow_root_id), Some(host_id));
+
+    // The template's contents are parsed into the shadow root rather than the host
+    let shadow_children: Vec<_> = doc.nodes[shadow_root_id]
+        .children
+        .iter()
+        .map(|&id| doc.nodes[id].element_data().unwrap().name.local.to_string())
+        .collect();
+    assert_eq!(shadow_children, ["p", "slot"]);
+    let host_children: Vec<_> = doc.nodes[host_id]
+        .children
+        .iter()
+        .map(|&id| doc.nodes[id].element_data().unwrap().name.local.to_string())
+        .collect();
+    assert_eq!(host_children, ["span"]);
+
+    // The template element itself is dropped along with its (unused) contents fragment
+    assert!(doc.query_selector("template").unwrap().is_none());
+    assert!(
+        !doc.nodes
+            .iter()
+            .any(|(_, node)| matches!(node.data, NodeData::DocumentFragment)
+                || node.data.is_element_with_tag_name(&local_name!("template")))
+    );
+}
+
+#[test]
+fn parses_template_contents_into_fragment() {
+    use blitz_traits::{ColorScheme, Viewport, net::DummyNetProvider};
+    use std::sync::Arc;
+
+    let html = "<body><template id=\"tpl\"><p id=\"item\">item</p></template><ul></ul></body>";
+    let viewport = Viewport::new(800, 600, 1.0, ColorScheme::Light);
+    let mut doc = BaseDocument::new(viewport);
+    DocumentHtmlParser::parse_into_doc(&mut doc, html, Arc::new(DummyNetProvider::default()));
+
+    // The template's contents are parsed into an inert fragment rather than the template itself
+    let template_id = doc.query_selector("template").unwrap().unwrap();
+    assert!(doc.nodes[template_id].children.is_empty());
+    let fragment_id = doc.template_contents(template_id).unwrap();
+    let item_id = doc.nodes[fragment_id].children[0];
+    assert!(!doc.is_connected(item_id));
+    assert!(doc.query_selector("#item").unwrap().is_none());
+    assert!(!doc.nodes_to_id.contains_key("item"));
+
+    // Instantiating the template clones its contents into the live tree
+    let list_id = doc.query_selector("ul").unwrap().unwrap();
+    let new_ids = doc.instantiate_template(template_id, list_id);
+    assert_eq!(new_ids.len(), 1);
+    assert_ne!(new_ids[0], item_id);
+    assert!(doc.is_connected(new_ids[0]));
+    assert_eq!(doc.nodes[list_id].children, new_ids);
+    assert_eq!(doc.nodes[new_ids[0]].text_content(), "item");
+    assert_eq!(doc.nodes_to_id.get("item"), Some(&new_ids[0]));
+}
+
+#[test]
+fn reads_pseudo_attributes() {
+    let data = r#"type="text/css" href='book.css'"#;
+    assert_eq!(pseudo_attribute(data, "type"), Some("text/css"));
+    assert_eq!(pseudo_attribute(data, "href"), Some("book.css"));
+    assert_eq!(pseudo_attribute(data, "media"), None);
+
+    // Pairs with unquoted values are skipped
+    let data = r#"media=print href="print.css""#;
+    assert_eq!(pseudo_attribute(data, "media"), None);
+    assert_eq!(pseudo_attribute(data, "href"), Some("print.css"));
+}
@@ -1,5 +1,7 @@
+mod document_format;
 mod html_document;
 mod html_sink;
 
-pub use html_document::HtmlDocument;
+pub use document_format::DocumentFormat;
+pub use html_document::{HtmlDocument, HtmlIframeProvider};
 pub use html_sink::DocumentHtmlParser;
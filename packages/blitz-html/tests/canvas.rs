@@ -0,0 +1,66 @@
+mod util;
+
+use std::task::{Context, Waker};
+
+use blitz_dom::CanvasCommand;
+use blitz_dom::peniko::{
+    Color,
+    kurbo::{Rect, Shape},
+};
+
+#[test]
+fn draws_to_a_canvas() {
+    let html = r#"<canvas id="canvas" width="200" height="100" style="width: 400px">
+        <p>Fallback content</p>
+    </canvas>"#;
+    let mut doc = util::parse(html);
+    let doc = doc.as_mut();
+    doc.set_viewport(util::viewport());
+    let canvas = doc.get_element_by_id("canvas").unwrap();
+
+    // The canvas is sized by the aspect ratio of its coordinate space
+    doc.resolve();
+    assert_eq!(doc.nodes[canvas].final_layout.size.height, 200.0);
+    assert!(doc.canvas_context(doc.root_element().id).is_none());
+
+    let cx = Context::from_waker(Waker::noop());
+    let ctx = doc.canvas_context(canvas).unwrap();
+    assert!(!doc.poll_canvases(&cx));
+
+    ctx.set_fill_style(Color::from_rgba8(255, 0, 0, 255));
+    ctx.fill_rect(10.0, 10.0, 50.0, 50.0);
+    ctx.save();
+    ctx.translate(100.0, 0.0);
+    ctx.begin_path();
+    ctx.arc(20.0, 20.0, 10.0, 0.0, std::f64::consts::TAU, false);
+    ctx.clip();
+    ctx.stroke();
+    ctx.restore();
+    assert!(doc.poll_canvases(&cx));
+    assert!(!doc.poll_canvases(&cx));
+
+    let surface = doc.nodes[canvas]
+        .element_data()
+        .unwrap()
+        .canvas_surface()
+        .unwrap();
+    surface.with_display_list(|commands| {
+        assert_eq!(commands.len(), 4);
+        let CanvasCommand::Fill { path, .. } = &commands[0] else {
+            panic!("expected a fill");
+        };
+        assert_eq!(path.bounding_box(), Rect::new(10.0, 10.0, 60.0, 60.0));
+        // The clip path is in the translated coordinate space
+        let CanvasCommand::PushClip { path } = &commands[1] else {
+            panic!("expected a clip");
+        };
+        let bounds = path.bounding_box();
+        assert!((bounds.x0 - 110.0).abs() < 0.01 && (bounds.x1 - 130.0).abs() < 0.01);
+        assert!(matches!(commands[2], CanvasCommand::Stroke { .. }));
+        assert!(matches!(commands[3], CanvasCommand::PopClip));
+    });
+
+    // Clearing the whole canvas discards its display list
+    ctx.clear_rect(0.0, 0.0, f64::MAX, f64::MAX);
+    surface.with_display_list(|commands| assert!(commands.is_empty()));
+}
@@ -0,0 +1,41 @@
+mod util;
+
+use blitz_dom::BaseDocument;
+use blitz_dom::node::{ListItemLayout, Marker};
+
+fn marker(doc: &BaseDocument, id: usize) -> Option<&ListItemLayout> {
+    doc.nodes[id].element_data()?.list_item_data.as_deref()
+}
+
+#[test]
+fn renumbers_list_items_only_when_the_tree_changes() {
+    let mut doc = util::layout("<ol><li>One</li><li>Two</li><li>Three</li></ol>");
+    let doc = doc.as_mut();
+
+    let items: Vec<usize> = doc.nodes[doc.query_selector("ol").unwrap().unwrap()]
+        .children
+        .iter()
+        .copied()
+        .filter(|&id| doc.nodes[id].is_element())
+        .collect();
+    let last_id = items[2];
+    assert_eq!(
+        marker(doc, last_id).unwrap().marker,
+        Marker::String(String::from("3. "))
+    );
+
+    // Resolving an unchanged document reuses the existing markers
+    let before = marker(doc, last_id).unwrap() as *const ListItemLayout;
+    doc.resolve();
+    assert_eq!(
+        marker(doc, last_id).unwrap() as *const ListItemLayout,
+        before
+    );
+
+    doc.remove_and_drop_node(items[0]);
+    doc.resolve();
+    assert_eq!(
+        marker(doc, last_id).unwrap().marker,
+        Marker::String(String::from("2. "))
+    );
+}
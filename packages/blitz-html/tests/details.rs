@@ -0,0 +1,27 @@
+mod util;
+
+use blitz_dom::BaseDocument;
+
+#[test]
+fn renders_summary_of_closed_details_only() {
+    let mut doc = util::layout("<details><summary>Summary</summary><p>Contents</p></details>");
+    let doc = doc.as_mut();
+
+    let details_id = doc.query_selector("details").unwrap().unwrap();
+    let summary_id = doc.query_selector("summary").unwrap().unwrap();
+    let paragraph_id = doc.query_selector("p").unwrap().unwrap();
+    let layout_children = |doc: &BaseDocument| {
+        doc.nodes[details_id]
+            .layout_children
+            .borrow()
+            .clone()
+            .unwrap()
+    };
+    assert_eq!(doc.summary_details(summary_id), Some(details_id));
+    assert_eq!(layout_children(doc), vec![summary_id]);
+
+    doc.toggle_details(details_id);
+    doc.resolve();
+    assert!(doc.is_details_open(details_id));
+    assert!(layout_children(doc).contains(&paragraph_id));
+}
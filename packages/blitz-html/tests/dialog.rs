@@ -0,0 +1,29 @@
+mod util;
+
+#[test]
+fn centers_modal_dialog_in_the_top_layer() {
+    let html = r#"<button id="before">Before</button>
+        <dialog style="width: 200px; height: 100px"><button id="inside">Inside</button></dialog>"#;
+    let mut doc = util::layout(html);
+    let doc = doc.as_mut();
+
+    let dialog_id = doc.query_selector("dialog").unwrap().unwrap();
+    let before_id = doc.get_element_by_id("before").unwrap();
+    let inside_id = doc.get_element_by_id("inside").unwrap();
+    doc.show_modal_dialog(dialog_id);
+    doc.resolve();
+
+    // The dialog is centered in the viewport, and focus is trapped within it
+    let layout = doc.nodes[dialog_id].final_layout;
+    assert_eq!(layout.location.x + layout.size.width / 2.0, 400.0);
+    assert_eq!(layout.location.y + layout.size.height / 2.0, 300.0);
+    assert!(doc.nodes[dialog_id].backdrop.is_some());
+    assert_eq!(doc.get_focussed_node_id(), Some(inside_id));
+    assert!(doc.is_inert(before_id));
+    assert!(!doc.set_focus_to(before_id));
+
+    doc.close_dialog(dialog_id);
+    doc.resolve();
+    assert_eq!(doc.top_layer().count(), 0);
+    assert!(!doc.is_inert(before_id));
+}
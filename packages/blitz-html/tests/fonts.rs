@@ -0,0 +1,21 @@
+mod util;
+
+use blitz_dom::BaseDocument;
+use blitz_dom::net::Resource;
+
+#[test]
+fn restyles_when_fonts_load() {
+    let mut doc = util::layout(r#"<div style="font-family: Bullet; width: 10ex"></div>"#);
+    let doc = doc.as_mut();
+    let root_id = doc.root_element().id;
+    let will_recascade = |doc: &BaseDocument| {
+        let element_data = doc.nodes[root_id].stylo_element_data.borrow();
+        element_data.as_ref().unwrap().hint.will_recascade_subtree()
+    };
+    assert!(!will_recascade(doc));
+
+    // Font-relative units depend on the loaded fonts, so the whole document is restyled
+    let font = include_bytes!("../../blitz-dom/assets/moz-bullet-font.otf");
+    doc.load_resource(Resource::Font(font.to_vec().into()));
+    assert!(will_recascade(doc));
+}
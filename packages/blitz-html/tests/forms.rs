@@ -0,0 +1,160 @@
+mod util;
+
+use blitz_dom::{BaseDocument, peniko};
+
+fn matches(doc: &BaseDocument, selector: &str) -> Option<usize> {
+    doc.query_selector(selector).unwrap()
+}
+
+#[test]
+fn updates_form_states_of_changed_controls() {
+    let html = r#"<form>
+            <input id="text" value="abc" pattern="[a-z]+">
+            <input id="first" type="radio" name="choice" required>
+            <input id="second" type="radio" name="choice">
+        </form>"#;
+    let mut doc = util::layout(html);
+    let doc = doc.as_mut();
+
+    let text_id = doc.get_element_by_id("text").unwrap();
+    let second_id = doc.get_element_by_id("second").unwrap();
+
+    // A required radio group is invalid (for each of its buttons) until one of them is checked
+    assert!(matches(doc, "#text:valid").is_some());
+    assert!(matches(doc, "#second:invalid").is_some());
+    assert!(matches(doc, "form:invalid").is_some());
+    doc.toggle_radio("choice".to_string(), second_id);
+    doc.resolve();
+    assert!(matches(doc, "#first:valid").is_some());
+    assert!(matches(doc, "form:valid").is_some());
+
+    // Changing an attribute only takes effect once the control is marked dirty
+    let element = doc.nodes[text_id].element_data_mut().unwrap();
+    let pattern = element
+        .attrs
+        .iter_mut()
+        .find(|attr| attr.name.local.as_ref() == "pattern")
+        .unwrap();
+    pattern.value = "[0-9]+".to_string();
+    doc.mark_form_state_dirty(text_id);
+    doc.resolve();
+    assert!(matches(doc, "#text:invalid").is_some());
+    assert!(matches(doc, "form:invalid").is_some());
+
+    // Only keyboard focus matches `:focus-visible` (text inputs always match it)
+    let first_id = doc.get_element_by_id("first").unwrap();
+    doc.set_focus_to(first_id);
+    doc.resolve();
+    assert!(matches(doc, "#first:focus").is_some());
+    assert!(matches(doc, ":focus-visible").is_none());
+    doc.clear_focus();
+    assert_eq!(doc.focus_next_node(), Some(text_id));
+    doc.resolve();
+    assert!(matches(doc, "#text:focus-visible").is_some());
+    assert!(matches(doc, "form:focus-within").is_some());
+}
+
+#[test]
+fn lays_out_placeholder_again_when_it_changes() {
+    let mut doc = util::layout(r#"<input id="input" type="text" placeholder="Name">"#);
+    let doc = doc.as_mut();
+
+    let input_id = doc.get_element_by_id("input").unwrap();
+    let placeholder_width = |doc: &BaseDocument| {
+        let element = doc.nodes[input_id].element_data().unwrap();
+        let input_data = element.text_input_data().unwrap();
+        input_data.placeholder.as_ref().map(|layout| layout.width())
+    };
+    let initial_width = placeholder_width(doc).unwrap();
+    assert!(initial_width > 0.0);
+
+    let set_placeholder = |doc: &mut BaseDocument, value: &str| {
+        let element = doc.nodes[input_id].element_data_mut().unwrap();
+        let placeholder = element
+            .attrs
+            .iter_mut()
+            .find(|attr| attr.name.local.as_ref() == "placeholder")
+            .unwrap();
+        placeholder.value = value.to_string();
+        doc.update_placeholder(input_id);
+        doc.resolve();
+    };
+    set_placeholder(doc, "Full name");
+    assert!(placeholder_width(doc).unwrap() > initial_width);
+    set_placeholder(doc, "");
+    assert_eq!(placeholder_width(doc), None);
+}
+
+#[test]
+fn styles_placeholders_with_the_ua_stylesheet() {
+    let doc = util::layout(
+        r#"<input id="input" type="text" placeholder="Name" style="color: rgb(0, 0, 255)">"#,
+    );
+    let doc = doc.as_ref();
+
+    let input_id = doc.get_element_by_id("input").unwrap();
+    let element = doc.nodes[input_id].element_data().unwrap();
+    let placeholder = element.text_input_data().unwrap().placeholder.as_ref();
+    let brush = &placeholder.unwrap().styles()[0].brush.brush;
+
+    // The placeholder inherits the input's color, drawn at the UA stylesheet's opacity
+    let peniko::Brush::Solid(color) = brush else {
+        panic!("Placeholder is not drawn with a solid color");
+    };
+    assert_eq!(color.components, [0.0, 0.0, 1.0, 0.54]);
+}
+
+#[test]
+fn starts_inputs_without_a_value_empty() {
+    let html = r#"<input id="empty" type="text" placeholder="Name" required>
+        <input id="filled" type="text" value="Name" required>"#;
+    let mut doc = util::layout(html);
+    let doc = doc.as_mut();
+    let empty_id = doc.get_element_by_id("empty").unwrap();
+    let filled_id = doc.get_element_by_id("filled").unwrap();
+
+    // An empty input shows its placeholder and is missing its required value
+    let element = doc.nodes[empty_id].element_data().unwrap();
+    assert_eq!(
+        element.text_input_data().unwrap().editor.text().to_string(),
+        ""
+    );
+    assert_eq!(matches(doc, ":placeholder-shown"), Some(empty_id));
+    assert_eq!(matches(doc, ":invalid"), Some(empty_id));
+
+    // ...but is laid out at the same height as one with text, and still draws a caret
+    let size = |id: usize| doc.nodes[id].final_layout.size;
+    assert_eq!(size(empty_id), size(filled_id));
+    doc.set_focus_to(empty_id);
+    let element = doc.nodes[empty_id].element_data_mut().unwrap();
+    let editor = &mut element.text_input_data_mut().unwrap().editor;
+    assert!(
+        editor
+            .cursor_geometry(1.0)
+            .is_some_and(|caret| caret.height() > 0.0)
+    );
+}
+
+#[test]
+fn only_shows_focus_rings_for_keyboard_focus() {
+    let html = r#"<button id="button">Button</button><input id="input" type="text">"#;
+    let mut doc = util::layout(html);
+    let doc = doc.as_mut();
+    let button_id = doc.get_element_by_id("button").unwrap();
+    let input_id = doc.get_element_by_id("input").unwrap();
+
+    // Focussing a button with the mouse (or from script) doesn't show a focus ring...
+    doc.set_focus_to(button_id);
+    doc.resolve();
+    assert_eq!(matches(doc, ":focus"), Some(button_id));
+    assert_eq!(matches(doc, ":focus-visible"), None);
+
+    // ...but text inputs always show one, as does anything focussed with the keyboard
+    doc.set_focus_to(input_id);
+    doc.resolve();
+    assert_eq!(matches(doc, ":focus-visible"), Some(input_id));
+    doc.clear_focus();
+    assert_eq!(doc.focus_next_node(), Some(button_id));
+    doc.resolve();
+    assert_eq!(matches(doc, ":focus-visible"), Some(button_id));
+}
@@ -0,0 +1,121 @@
+mod util;
+
+use std::sync::{Arc, Mutex};
+
+use blitz_dom::net::Resource;
+use blitz_traits::net::{BoxedHandler, Bytes, NetCallback, NetProvider, Request};
+
+/// Holds onto requests until the test responds to them
+#[derive(Default)]
+struct DeferredNetProvider(Mutex<Vec<(String, BoxedHandler<Resource>)>>);
+impl NetProvider for DeferredNetProvider {
+    type Data = Resource;
+    fn fetch(&self, _doc_id: usize, request: Request, handler: BoxedHandler<Resource>) {
+        let url = request.url.path().to_string();
+        self.0.lock().unwrap().push((url, handler));
+    }
+}
+
+#[derive(Default)]
+struct CollectingCallback(Mutex<Vec<Resource>>);
+impl NetCallback for CollectingCallback {
+    type Data = Resource;
+    fn call(&self, _doc_id: usize, result: Result<Resource, Option<String>>) {
+        self.0.lock().unwrap().extend(result.ok());
+    }
+}
+
+#[test]
+fn creates_sub_document_for_srcdoc_iframe() {
+    let mut doc = util::layout("<iframe srcdoc=\"<p id='inner'>hello</p>\"></iframe>");
+    let doc = doc.as_mut();
+
+    // The iframe is laid out at the default object size, and its nested document fills it
+    let iframe_id = doc.query_selector("iframe").unwrap().unwrap();
+    let iframe = &doc.nodes[iframe_id];
+    assert_eq!(iframe.final_layout.content_box_width(), 300.0);
+    assert_eq!(iframe.final_layout.content_box_height(), 150.0);
+    let sub_document = doc.sub_document(iframe_id).unwrap();
+    assert_eq!(sub_document.get_viewport().window_size, (300, 150));
+    assert!(sub_document.nodes_to_id.contains_key("inner"));
+}
+
+#[test]
+fn drops_responses_for_superseded_iframe_loads() {
+    let net_provider = Arc::new(DeferredNetProvider::default());
+    let mut doc = util::parse_with_net_provider(
+        r#"<iframe src="https://example.com/a.html"></iframe>"#,
+        net_provider.clone(),
+    );
+    let doc = doc.as_mut();
+    let iframe_id = doc.query_selector("iframe").unwrap().unwrap();
+
+    // Change the iframe's src before the first response arrives
+    let iframe = doc.nodes[iframe_id].element_data_mut().unwrap();
+    iframe.attrs[0].value = String::from("https://example.com/b.html");
+    doc.load_iframe(iframe_id);
+
+    let callback = Arc::new(CollectingCallback::default());
+    let requests = std::mem::take(&mut *net_provider.0.lock().unwrap());
+    for (url, handler) in requests {
+        let html = format!("<p id='{}'></p>", &url[1..2]);
+        handler.bytes(doc.id(), Bytes::from(html), callback.clone());
+    }
+    let mut resources = std::mem::take(&mut *callback.0.lock().unwrap());
+
+    // The response for the superseded src is dropped, even if it arrives last
+    resources.reverse();
+    for resource in resources {
+        doc.load_resource(resource);
+    }
+    let sub_document = doc.sub_document(iframe_id).unwrap();
+    assert!(sub_document.nodes_to_id.contains_key("b"));
+    assert!(!sub_document.nodes_to_id.contains_key("a"));
+}
+
+#[test]
+fn drops_subresources_of_replaced_sub_documents() {
+    // A 1x1 transparent PNG
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01\x08\x06\x00\x00\x00\x1f\x15\xc4\x89\x00\x00\x00\x0bIDAT\x78\x9c\x63\x60\x00\x02\x00\x00\x05\x00\x01\x7a\x5e\xab\x3f\x00\x00\x00\x00IEND\xae\x42\x60\x82";
+
+    let net_provider = Arc::new(DeferredNetProvider::default());
+    let mut doc = util::parse_with_net_provider(
+        r#"<iframe srcdoc="<p>a</p><p>b</p><img id='img' src='https://example.com/a.png'>"></iframe>"#,
+        net_provider.clone(),
+    );
+    let doc = doc.as_mut();
+    let iframe_id = doc.query_selector("iframe").unwrap().unwrap();
+    let respond = |doc: &mut blitz_dom::BaseDocument| {
+        let callback = Arc::new(CollectingCallback::default());
+        let requests = std::mem::take(&mut *net_provider.0.lock().unwrap());
+        for (_, handler) in requests {
+            handler.bytes(doc.id(), Bytes::from_static(PNG), callback.clone());
+        }
+        let resources = std::mem::take(&mut *callback.0.lock().unwrap());
+        assert_eq!(resources.len(), 1);
+        for resource in resources {
+            doc.load_resource(resource);
+        }
+    };
+
+    // The image of a nested document is loaded into it
+    respond(doc);
+    let sub_document = doc.sub_document(iframe_id).unwrap();
+    let img_id = sub_document.get_element_by_id("img").unwrap();
+    let img = sub_document.nodes[img_id].element_data().unwrap();
+    assert!(img.image_data().is_some());
+
+    // Reload the nested document, and replace it again (with a document without the image) before
+    // the reloaded document's request is answered
+    doc.load_iframe(iframe_id);
+    let iframe = doc.nodes[iframe_id].element_data_mut().unwrap();
+    iframe.attrs[0].value = String::from("<p id='replaced'></p>");
+    doc.load_iframe(iframe_id);
+    respond(doc);
+    let sub_document = doc.sub_document(iframe_id).unwrap();
+    assert!(sub_document.nodes_to_id.contains_key("replaced"));
+    assert!(sub_document.nodes.iter().all(|(_, node)| {
+        node.element_data()
+            .is_none_or(|element| element.image_data().is_none())
+    }));
+}
@@ -0,0 +1,94 @@
+mod util;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use blitz_dom::BaseDocument;
+use blitz_dom::net::Resource;
+use blitz_dom::node::{ImageData, ImageFrame, RasterImageData};
+use blitz_dom::util::ImageType;
+use blitz_traits::{ColorScheme, Viewport};
+
+#[test]
+fn selects_responsive_image_sources() {
+    let html = r#"<img id="density" src="https://example.com/a.png" srcset="https://example.com/b.png 2x">
+        <img id="width" srcset="https://example.com/400.png 400w, https://example.com/800.png 800w"
+            sizes="(max-width: 500px) 100vw, 400px">
+        <picture>
+            <source media="(min-width: 1000px)" srcset="https://example.com/wide.png">
+            <source type="image/x-unsupported" srcset="https://example.com/unsupported.png">
+            <img id="picture" src="https://example.com/narrow.png">
+        </picture>"#;
+    let mut doc = util::parse(html);
+    let doc = doc.as_mut();
+    let selected_url = |doc: &BaseDocument, id: &str| {
+        let node = &doc.nodes[doc.get_element_by_id(id).unwrap()];
+        let source = node.element_data().unwrap().image_source.clone().unwrap();
+        (source.url.path().to_string(), source.density)
+    };
+
+    doc.set_viewport(util::viewport());
+    assert_eq!(selected_url(doc, "density"), ("/a.png".to_string(), 1.0));
+    assert_eq!(selected_url(doc, "width"), ("/400.png".to_string(), 1.0));
+    assert_eq!(
+        selected_url(doc, "picture"),
+        ("/narrow.png".to_string(), 1.0)
+    );
+
+    // Sources are reselected when the viewport's scale or size changes
+    doc.set_viewport(Viewport::new(2400, 1200, 2.0, ColorScheme::Light));
+    assert_eq!(selected_url(doc, "density"), ("/b.png".to_string(), 2.0));
+    assert_eq!(selected_url(doc, "width"), ("/800.png".to_string(), 2.0));
+    assert_eq!(selected_url(doc, "picture"), ("/wide.png".to_string(), 1.0));
+}
+
+#[test]
+fn animates_visible_images() {
+    let html = r#"<img id="visible">
+        <img id="offscreen" style="position: absolute; top: 2000px">"#;
+    let mut doc = util::parse(html);
+    let doc = doc.as_mut();
+    doc.set_viewport(util::viewport());
+
+    let frame = || ImageFrame {
+        image: RasterImageData::new(10, 10, Arc::new(vec![0; 400])),
+        delay: Duration::from_millis(100),
+    };
+    let visible = doc.get_element_by_id("visible").unwrap();
+    let offscreen = doc.get_element_by_id("offscreen").unwrap();
+    for node_id in [visible, offscreen] {
+        doc.load_resource(Resource::AnimatedImage(
+            node_id,
+            ImageType::Image,
+            vec![frame(), frame(), frame()],
+        ));
+    }
+    doc.resolve();
+    let current_frame = |doc: &BaseDocument, node_id: usize| match doc.nodes[node_id]
+        .element_data()
+        .unwrap()
+        .image_data()
+    {
+        Some(ImageData::Animated(animation)) => animation.current_frame,
+        _ => panic!("image is not animated"),
+    };
+
+    let start = Instant::now();
+    assert!(!doc.tick_image_animations(start));
+    assert_eq!(
+        doc.next_image_frame_time(),
+        Some(start + Duration::from_millis(100))
+    );
+
+    // Only visible images are advanced, and whole loops of the animation are skipped
+    assert!(doc.tick_image_animations(start + Duration::from_millis(150)));
+    assert_eq!(current_frame(doc, visible), 1);
+    assert_eq!(current_frame(doc, offscreen), 0);
+    assert!(doc.tick_image_animations(start + Duration::from_millis(1250)));
+    assert_eq!(current_frame(doc, visible), 0);
+    assert_eq!(current_frame(doc, offscreen), 0);
+    assert_eq!(
+        doc.next_image_frame_time(),
+        Some(start + Duration::from_millis(1300))
+    );
+}
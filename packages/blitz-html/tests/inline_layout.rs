@@ -0,0 +1,67 @@
+mod util;
+
+use blitz_dom::BaseDocument;
+
+/// The text of each line of the inline layout of an element
+fn lines(doc: &BaseDocument, id: &str) -> Vec<String> {
+    let node_id = doc.get_element_by_id(id).unwrap();
+    let element = doc.nodes[node_id].data.downcast_element().unwrap();
+    let text_layout = element.inline_layout_data.as_ref().unwrap();
+    text_layout
+        .layout
+        .lines()
+        .map(|line| text_layout.text[line.text_range()].to_string())
+        .collect()
+}
+
+#[test]
+fn positions_absolute_boxes_in_static_inline_roots() {
+    let html = r#"<div id="container" style="position: relative; width: 400px; height: 300px; padding: 10px">
+        <p>Text <span id="abs" style="position: absolute; right: 0; bottom: 0; width: 10px; height: 10px"></span></p>
+    </div>"#;
+    let doc = util::layout(html);
+    let doc = doc.as_ref();
+
+    // The containing block is the padding box of the positioned div, not the static paragraph
+    let container = doc.get_element_by_id("container").unwrap();
+    let abs = doc.get_element_by_id("abs").unwrap();
+    let container_position = doc.nodes[container].absolute_position(0.0, 0.0);
+    let abs_position = doc.nodes[abs].absolute_position(0.0, 0.0);
+    assert_eq!(abs_position.x - container_position.x, 410.0);
+    assert_eq!(abs_position.y - container_position.y, 310.0);
+}
+
+#[test]
+fn keeps_nowrap_text_on_one_line_and_makes_room_for_hyphens() {
+    let html = r#"<style>div { font: 10px monospace; width: 11.5ch }</style>
+        <div id="nowrap">aaa <span style="white-space: nowrap">bbb ccc ddd</span> eee</div>
+        <div id="nowrap-after-word">aaa bbb<span style="white-space: nowrap">ccc ddd</span></div>
+        <div id="wide-nowrap"><span style="white-space: nowrap">aaa bbb ccc ddd</span> eee</div>
+        <div id="soft-hyphen">xx aaaaaaaa&shy;bbbb</div>"#;
+    let doc = util::layout(html);
+    let doc = doc.as_ref();
+
+    // Lines only wrap around `nowrap` text, whose spaces are left as-is
+    assert_eq!(lines(doc, "nowrap"), ["aaa ", "bbb ccc ddd ", "eee"]);
+    assert_eq!(lines(doc, "nowrap-after-word"), ["aaa ", "bbbccc ddd"]);
+    assert_eq!(lines(doc, "wide-nowrap"), ["aaa bbb ccc ddd ", "eee"]);
+
+    // "xx aaaaaaaa" fits on a line, but not with the hyphen shown when breaking at the soft hyphen
+    assert_eq!(lines(doc, "soft-hyphen"), ["xx ", "aaaaaaaa\u{AD}", "bbbb"]);
+}
+
+#[test]
+fn fixes_the_lines_of_long_paragraphs_with_nowrap_text() {
+    let html = format!(
+        r#"<div id="long" style="font: 10px monospace; width: 11.5ch">{}</div>"#,
+        r#"aa <span style="white-space: nowrap">bb cc</span> "#.repeat(20)
+    );
+    let doc = util::layout(&html);
+
+    // Every line after the first has to be fixed to wrap before the `nowrap` text
+    let lines = lines(doc.as_ref(), "long");
+    assert_eq!(lines.len(), 20);
+    assert_eq!(lines[0], "aa bb cc aa ");
+    assert!(lines[1..19].iter().all(|line| line == "bb cc aa "));
+    assert_eq!(lines[19], "bb cc");
+}
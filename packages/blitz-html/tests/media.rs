@@ -0,0 +1,77 @@
+mod util;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use blitz_dom::{MediaEvent, TestPatternMediaProvider};
+
+#[test]
+fn plays_video_with_a_media_provider() {
+    let html =
+        r#"<video id="video" src="https://example.com/video.webm" style="width: 640px"></video>"#;
+    let mut doc = util::parse(html);
+    let doc = doc.as_mut();
+    doc.set_viewport(util::viewport());
+    let video = doc.get_element_by_id("video").unwrap();
+
+    // Without a media provider the video can't be played
+    assert!(
+        doc.take_media_events()
+            .contains(&(video, MediaEvent::Error))
+    );
+    doc.resolve();
+    assert_eq!(doc.nodes[video].final_layout.size.height, 150.0);
+
+    doc.set_media_provider(Arc::new(TestPatternMediaProvider {
+        width: 320,
+        height: 180,
+        duration: Duration::from_secs(2),
+        frame_rate: 10.0,
+    }));
+    doc.load_media(video);
+    doc.tick_media(Instant::now());
+    let events: Vec<&str> = doc
+        .take_media_events()
+        .into_iter()
+        .map(|(_, event)| event.name())
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "emptied",
+            "loadstart",
+            "durationchange",
+            "loadedmetadata",
+            "loadeddata"
+        ]
+    );
+
+    // The video is sized by its natural aspect ratio
+    doc.resolve();
+    assert_eq!(doc.nodes[video].final_layout.size.height, 360.0);
+
+    doc.play_media(video);
+    let start = Instant::now();
+    doc.tick_media(start + Duration::from_millis(1050));
+    let state = doc.media_state(video).unwrap();
+    assert!(!state.paused);
+    assert!(state.current_time >= Duration::from_millis(1050));
+    // The 11th frame of the test pattern is blue
+    assert_eq!(doc.media_frame(video).unwrap().data[..4], [0, 0, 255, 255]);
+
+    // Playback stops at the end of the video
+    doc.tick_media(start + Duration::from_secs(3));
+    let state = doc.media_state(video).unwrap();
+    assert!(state.paused && state.ended);
+    assert_eq!(state.current_time, Duration::from_secs(2));
+    let events = doc.take_media_events();
+    assert_eq!(
+        events[events.len() - 2..],
+        [(video, MediaEvent::Pause), (video, MediaEvent::Ended)]
+    );
+
+    doc.seek_media(video, Duration::from_millis(500));
+    let state = doc.media_state(video).unwrap();
+    assert!(!state.ended);
+    assert_eq!(state.current_time, Duration::from_millis(500));
+}
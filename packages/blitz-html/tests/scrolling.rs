@@ -0,0 +1,88 @@
+mod util;
+
+use blitz_traits::{BlitzKeyEvent, Document, DomEvent, DomEventData, KeyState};
+use keyboard_types::{Code, Key, Location, Modifiers};
+use std::time::{Duration, Instant};
+
+fn key_event(key: Key, code: Code) -> DomEventData {
+    DomEventData::KeyPress(BlitzKeyEvent {
+        key,
+        code,
+        modifiers: Modifiers::empty(),
+        location: Location::Standard,
+        is_auto_repeating: false,
+        is_composing: false,
+        state: KeyState::Pressed,
+        text: None,
+    })
+}
+
+#[test]
+fn scrolls_the_hovered_container_with_the_keyboard() {
+    let html = r#"<button id="button">Button</button>
+        <div id="scroller" style="height: 100px; overflow: auto; font-size: 10px">
+            <div style="height: 1000px"></div>
+        </div>
+        <div style="height: 2000px"></div>"#;
+    let mut doc = util::layout(html);
+    let doc = doc.as_mut();
+    let button = doc.get_element_by_id("button").unwrap();
+    let scroller = doc.get_element_by_id("scroller").unwrap();
+
+    // Without a focussed node, keypresses go to the root element and scroll the hovered container
+    let root = doc.root_element().id;
+    assert_eq!(doc.get_focussed_node_id(), Some(root));
+    let scroller_top = doc.nodes[scroller].final_layout.location.y;
+    doc.set_hover_to(10.0, scroller_top + 10.0);
+    doc.handle_event(&mut DomEvent::new(
+        root,
+        key_event(Key::ArrowDown, Code::ArrowDown),
+    ));
+    let finished = Instant::now() + Duration::from_secs(1);
+    assert!(!doc.tick_scroll_animations_at(finished));
+    assert!(doc.nodes[scroller].scroll_offset.y > 0.0);
+    assert_eq!(doc.viewport_scroll().y, 0.0);
+
+    // Wheel lines are as tall as a line of text in the hovered node's font
+    let hovered = doc.get_hover_node_id();
+    assert_eq!(doc.line_scroll_distance(hovered), 12.0);
+
+    // Space activates a focussed button instead of scrolling
+    doc.set_focus_to(button);
+    assert_eq!(doc.get_focussed_node_id(), Some(button));
+    doc.handle_event(&mut DomEvent::new(
+        button,
+        key_event(Key::Character(" ".into()), Code::Space),
+    ));
+    assert!(!doc.tick_scroll_animations_at(finished));
+    assert_eq!(doc.viewport_scroll().y, 0.0);
+}
+
+#[test]
+fn only_shows_scrollbars_for_scrollable_overflow() {
+    let html = r#"<div id="scroller" style="height: 100px; overflow: scroll">
+            <div id="static" style="height: 1000px"></div>
+        </div>"#;
+    let mut doc = util::layout(html);
+    let doc = doc.as_mut();
+    let scroller = doc.get_element_by_id("scroller").unwrap();
+    let static_id = doc.get_element_by_id("static").unwrap();
+
+    // Classic scrollbars only reserve a gutter in scroll containers
+    assert_eq!(doc.nodes[scroller].style.scrollbar_width, 12.0);
+    assert_eq!(doc.nodes[static_id].style.scrollbar_width, 0.0);
+    assert!(doc.nodes[scroller].final_layout.scrollbar_size.width > 0.0);
+
+    // The viewport takes its overflow from the root element (or the body)
+    let tall_page = r#"<div style="height: 2000px"></div>"#;
+    let doc = util::layout(tall_page);
+    assert!(doc.as_ref().viewport_scrollbars().vertical.is_some());
+    let doc = util::layout(&format!(
+        r#"<html style="overflow: hidden">{tall_page}</html>"#
+    ));
+    assert!(doc.as_ref().viewport_scrollbars().is_empty());
+    let doc = util::layout(&format!(
+        r#"<body style="overflow-y: hidden">{tall_page}</body>"#
+    ));
+    assert!(doc.as_ref().viewport_scrollbars().is_empty());
+}
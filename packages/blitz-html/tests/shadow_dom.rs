@@ -0,0 +1,71 @@
+mod util;
+
+use blitz_dom::BaseDocument;
+
+#[test]
+fn assigns_slots_and_scopes_shadow_tree_styles() {
+    let html = r#"<div id="host"><template shadowrootmode="open">
+            <style>p { color: rgb(0, 128, 0) } ::slotted(span) { color: rgb(0, 0, 255) }</style>
+            <p id="inner">Shadow</p><slot name="extra"></slot><slot></slot>
+        </template><span id="light">Light</span><span id="named" slot="extra">Named</span></div>
+        <p id="outer">Outer</p>
+        <span id="green" style="color: rgb(0, 128, 0)"></span><span id="blue" style="color: rgb(0, 0, 255)"></span>"#;
+    let mut doc = util::layout(html);
+    let doc = doc.as_mut();
+
+    let host_id = doc.get_element_by_id("host").unwrap();
+    let light_id = doc.get_element_by_id("light").unwrap();
+    let named_id = doc.get_element_by_id("named").unwrap();
+    let shadow_root_id = doc.shadow_root(host_id).unwrap();
+    let shadow_elements: Vec<usize> = doc.nodes[shadow_root_id]
+        .children
+        .iter()
+        .copied()
+        .filter(|&id| doc.nodes[id].is_element())
+        .collect();
+    let [_, inner_id, extra_slot_id, default_slot_id] = shadow_elements[..] else {
+        panic!("unexpected shadow tree children: {shadow_elements:?}");
+    };
+
+    // Elements in shadow trees can't be found by id
+    assert_eq!(doc.get_element_by_id("inner"), None);
+
+    // Children are assigned to the slot with a matching name, events propagate through the slot
+    assert_eq!(doc.nodes[extra_slot_id].assigned_nodes, [named_id]);
+    assert_eq!(doc.nodes[default_slot_id].assigned_nodes, [light_id]);
+    assert_eq!(doc.nodes[light_id].assigned_slot, Some(default_slot_id));
+    assert_eq!(
+        doc.node_chain(light_id)[..3],
+        [light_id, default_slot_id, host_id]
+    );
+
+    // Shadow tree styles apply to the shadow tree and to slotted elements only
+    let color =
+        |doc: &BaseDocument, id: usize| doc.nodes[id].primary_styles().unwrap().clone_color();
+    let green_id = doc.get_element_by_id("green").unwrap();
+    let blue_id = doc.get_element_by_id("blue").unwrap();
+    let outer_id = doc.get_element_by_id("outer").unwrap();
+    assert_eq!(color(doc, inner_id), color(doc, green_id));
+    assert_ne!(color(doc, outer_id), color(doc, green_id));
+    assert_eq!(color(doc, light_id), color(doc, blue_id));
+    assert_ne!(color(doc, outer_id), color(doc, blue_id));
+
+    // Changing a `slot` attribute reassigns the host's children once it is marked dirty
+    let element = doc.nodes[named_id].element_data_mut().unwrap();
+    element
+        .attrs
+        .retain(|attr| attr.name.local.as_ref() != "slot");
+    doc.mark_slots_dirty(named_id);
+    doc.resolve();
+    assert!(doc.nodes[extra_slot_id].assigned_nodes.is_empty());
+    assert_eq!(
+        doc.nodes[default_slot_id].assigned_nodes,
+        [light_id, named_id]
+    );
+
+    // Removing a child unassigns it
+    doc.remove_node(light_id);
+    doc.resolve();
+    assert_eq!(doc.nodes[default_slot_id].assigned_nodes, [named_id]);
+    assert_eq!(doc.nodes[light_id].assigned_slot, None);
+}
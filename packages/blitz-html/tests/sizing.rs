@@ -0,0 +1,41 @@
+mod util;
+
+use blitz_dom::BaseDocument;
+
+fn width(doc: &BaseDocument, id: &str) -> f32 {
+    let node_id = doc.get_element_by_id(id).unwrap();
+    doc.nodes[node_id].final_layout.size.width
+}
+
+#[test]
+fn resolves_intrinsic_sizing_keywords() {
+    let boxes = r#"<span style="display: inline-block; width: 120px; height: 10px"></span><span style="display: inline-block; width: 80px; height: 10px"></span>"#;
+    let html = format!(
+        r#"<body style="margin: 0">
+            <div id="max" style="width: max-content">{boxes}</div>
+            <div id="min" style="width: min-content">{boxes}</div>
+            <div id="fit" style="width: fit-content; max-width: 150px">{boxes}</div>
+            <div id="stretch" style="display: inline-block; width: stretch; margin: 0 10px"></div>
+        </body>"#
+    );
+    let mut doc = util::layout(&html);
+    let doc = doc.as_mut();
+    assert_eq!(width(doc, "max"), 200.0);
+    assert_eq!(width(doc, "min"), 120.0);
+    assert_eq!(width(doc, "fit"), 150.0);
+    assert_eq!(width(doc, "stretch"), 780.0);
+
+    // The keywords are resolved again when the available space changes, and the node's own Taffy
+    // style keeps the `auto` it was converted to
+    doc.set_viewport(util::viewport_with_width(400));
+    doc.resolve();
+    assert_eq!(width(doc, "stretch"), 380.0);
+    let stretch_id = doc.get_element_by_id("stretch").unwrap();
+    assert!(doc.nodes[stretch_id].style.size.width.is_auto());
+
+    // The root element is resolved against the viewport
+    let doc = util::layout(&format!(
+        r#"<html id="root" style="width: max-content"><body style="margin: 0">{boxes}</body></html>"#
+    ));
+    assert_eq!(width(doc.as_ref(), "root"), 200.0);
+}
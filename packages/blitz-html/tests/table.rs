@@ -0,0 +1,74 @@
+mod util;
+
+#[test]
+fn lays_out_table_spans_collapsed_borders_and_captions() {
+    let html = r#"<style>
+            table { border-collapse: collapse; border: 4px solid black }
+            td { padding: 0 }
+        </style>
+        <table>
+            <caption style="height: 20px">Top</caption>
+            <caption style="caption-side: bottom; height: 10px">Bottom</caption>
+            <tr><td id="a" rowspan="2" style="border: 2px solid red"></td><td id="b" style="height: 30px"></td></tr>
+            <tr><td id="c" style="vertical-align: bottom"><div id="inner" style="height: 10px"></div></td><td id="e" style="height: 40px"></td></tr>
+            <tr><td id="d" colspan="3"></td></tr>
+        </table>"#;
+    let doc = util::layout(html);
+    let doc = doc.as_ref();
+
+    let table_id = doc.query_selector("table").unwrap().unwrap();
+    let layout = |id: &str| doc.nodes[doc.get_element_by_id(id).unwrap()].final_layout;
+    let (a, b, c, d, e) = (
+        layout("a"),
+        layout("b"),
+        layout("c"),
+        layout("d"),
+        layout("e"),
+    );
+
+    // Cells span rows and columns
+    assert_eq!(a.location.y, b.location.y);
+    assert_eq!(a.location.y + a.size.height, c.location.y + c.size.height);
+    assert_eq!(c.location.x, b.location.x);
+    assert_eq!(d.location.x, a.location.x);
+    assert_eq!(d.location.x + d.size.width, e.location.x + e.size.width);
+
+    // The table's border wins over the cell's narrower border, and each cell lays out with half of
+    // its borders
+    let a_id = doc.get_element_by_id("a").unwrap();
+    let table = doc.nodes[table_id]
+        .element_data()
+        .unwrap()
+        .table_context()
+        .unwrap();
+    let borders = table.collapsed_borders(a_id).unwrap();
+    assert_eq!((borders.left.width, borders.right.width), (4.0, 2.0));
+    assert_eq!((a.border.left, a.border.right), (2.0, 1.0));
+
+    // The content of bottom-aligned cells is offset to the bottom of the cell
+    let inner = layout("inner");
+    assert_eq!(
+        inner.location.y + inner.size.height,
+        c.size.height - c.border.bottom
+    );
+    assert_eq!(c.padding.top, inner.location.y - c.border.top);
+
+    // Captions are laid out outside of the table box
+    let table_layout = doc.nodes[table_id].final_layout;
+    let captions = doc.query_selector_all("caption").unwrap();
+    let (top, bottom) = (
+        doc.nodes[captions[0]].final_layout,
+        doc.nodes[captions[1]].final_layout,
+    );
+    assert_eq!(table.caption_heights(doc), (20.0, 10.0));
+    assert_eq!(
+        (top.location.y, top.size.width),
+        (0.0, table_layout.size.width)
+    );
+    assert_eq!(b.location.y, 20.0 + table_layout.border.top);
+    assert_eq!(bottom.location.y, table_layout.size.height - 10.0);
+    assert_eq!(
+        d.location.y + d.size.height + table_layout.border.bottom,
+        bottom.location.y
+    );
+}
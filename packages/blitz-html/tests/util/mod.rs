@@ -0,0 +1,50 @@
+//! Helpers for creating documents in integration tests
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use blitz_dom::net::Resource;
+use blitz_html::HtmlDocument;
+use blitz_traits::navigation::DummyNavigationProvider;
+use blitz_traits::net::{DummyNetProvider, SharedProvider};
+use blitz_traits::{ColorScheme, Viewport};
+
+/// Parse a document without loading any of its resources
+pub fn parse(html: &str) -> HtmlDocument {
+    parse_with_net_provider(html, Arc::new(DummyNetProvider::default()))
+}
+
+/// Parse a document which loads its resources with `net_provider`
+pub fn parse_with_net_provider(html: &str, net_provider: SharedProvider<Resource>) -> HtmlDocument {
+    HtmlDocument::from_html(
+        html,
+        None,
+        Vec::new(),
+        net_provider,
+        None,
+        Arc::new(DummyNavigationProvider),
+    )
+}
+
+/// An 800x600 viewport
+pub fn viewport() -> Viewport {
+    viewport_with_width(800)
+}
+
+/// A viewport with the given width and a height of 600
+pub fn viewport_with_width(width: u32) -> Viewport {
+    Viewport::new(width, 600, 1.0, ColorScheme::Light)
+}
+
+/// Parse a document and resolve its styles and layout in an 800x600 viewport
+pub fn layout(html: &str) -> HtmlDocument {
+    layout_with_viewport(html, viewport())
+}
+
+/// Parse a document and resolve its styles and layout in `viewport`
+pub fn layout_with_viewport(html: &str, viewport: Viewport) -> HtmlDocument {
+    let mut doc = parse(html);
+    doc.as_mut().set_viewport(viewport);
+    doc.as_mut().resolve();
+    doc
+}
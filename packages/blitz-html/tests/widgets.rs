@@ -0,0 +1,32 @@
+mod util;
+
+#[test]
+fn lays_out_progress_and_meter_as_widgets() {
+    let html = r#"<progress value="3" max="4">75%</progress>
+        <meter min="0" max="100" low="20" high="80" optimum="90" value="10">10</meter>"#;
+    let doc = util::layout(html);
+    let doc = doc.as_ref();
+
+    let progress_id = doc.query_selector("progress").unwrap().unwrap();
+    let progress = &doc.nodes[progress_id];
+    assert_eq!(progress.unrounded_layout.content_box_width(), 160.0);
+    assert_eq!(progress.unrounded_layout.content_box_height(), 16.0);
+    assert_eq!(progress.layout_children.borrow().as_deref(), Some(&[][..]));
+    assert_eq!(
+        progress.element_data().unwrap().progress_position(),
+        Some(0.75)
+    );
+
+    // The meter's value is on the far side of the low boundary from its optimum
+    let meter_id = doc.query_selector("meter").unwrap().unwrap();
+    let meter = &doc.nodes[meter_id];
+    assert_eq!(meter.unrounded_layout.content_box_width(), 80.0);
+    let values = meter.element_data().unwrap().meter_values().unwrap();
+    assert_eq!(values.position(), 0.1);
+    assert_eq!(values.region(), blitz_dom::MeterRegion::SubSubOptimum);
+    assert!(
+        doc.query_selector("meter:-moz-meter-sub-sub-optimum")
+            .unwrap()
+            .is_some()
+    );
+}
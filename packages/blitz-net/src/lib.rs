@@ -1,4 +1,7 @@
-use blitz_traits::net::{BoxedHandler, Bytes, NetCallback, NetProvider, Request, SharedCallback};
+use blitz_traits::encoding::decode_html;
+use blitz_traits::net::{
+    BoxedHandler, Bytes, NetCallback, NetProvider, Request, Response, SharedCallback,
+};
 use data_url::DataUrl;
 use reqwest::Client;
 use std::sync::Arc;
@@ -9,16 +12,31 @@ use tokio::{
 
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/81.0";
 
+/// Fetch a document, decoding it using its detected character encoding
 pub async fn get_text(url: &str) -> String {
-    Client::new()
+    get_document(url).await.0
+}
+
+/// Fetch a document, returning its text (decoded using its detected character encoding) and the
+/// value of its `Content-Type` header
+pub async fn get_document(url: &str) -> (String, Option<String>) {
+    let response = Client::new()
         .get(url)
         .header("User-Agent", USER_AGENT)
         .send()
         .await
-        .unwrap()
-        .text()
-        .await
-        .unwrap()
+        .unwrap();
+    let content_type = content_type(&response);
+    let bytes = response.bytes().await.unwrap();
+    (decode_html(&bytes, content_type.as_deref()), content_type)
+}
+
+fn content_type(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
 }
 
 pub struct Provider<D> {
@@ -52,8 +70,10 @@ impl<D: 'static> Provider<D> {
         match request.url.scheme() {
             "data" => {
                 let data_url = DataUrl::process(request.url.as_str())?;
+                let content_type = data_url.mime_type().to_string();
                 let decoded = data_url.decode_to_vec()?;
-                handler.bytes(doc_id, Bytes::from(decoded.0), res_callback);
+                let response = Response::new(Bytes::from(decoded.0), Some(content_type));
+                handler.response(doc_id, response, res_callback);
             }
             "file" => {
                 let file_content = std::fs::read(request.url.path())?;
@@ -68,7 +88,9 @@ impl<D: 'static> Provider<D> {
                     .send()
                     .await?;
 
-                handler.bytes(doc_id, response.bytes().await?, res_callback);
+                let content_type = content_type(&response);
+                let response = Response::new(response.bytes().await?, content_type);
+                handler.response(doc_id, response, res_callback);
             }
         }
         Ok(())
@@ -3,16 +3,18 @@ use std::sync::atomic::{self, AtomicUsize};
 use super::multicolor_rounded_rect::{Edge, ElementFrame};
 use crate::util::{Color, ToColorColor};
 use blitz_dom::node::{
-    ImageData, ListItemLayout, ListItemLayoutPosition, Marker, NodeData, RasterImageData,
-    TextBrush, TextInputData, TextNodeData,
+    CollapsedBorder, CollapsedBorders, ListItemLayout, ListItemLayoutPosition, Marker, NodeData,
+    RasterImageData, TextBrush, TextInputData, TextNodeData,
 };
-use blitz_dom::{BaseDocument, ElementNodeData, Node, local_name};
+use blitz_dom::scrollbar::{ScrollbarMode, Scrollbars};
+use blitz_dom::{BaseDocument, CanvasCommand, ElementNodeData, MediaControls, Node, local_name};
 use blitz_traits::Devtools;
 
 use color::DynamicColor;
 use euclid::Transform3D;
 use parley::Line;
 use style::color::AbsoluteColor;
+use style::selector_parser::PseudoElement;
 use style::{
     OwnedSlice,
     dom::TElement,
@@ -191,13 +193,26 @@ impl VelloSceneGenerator<'_> {
             scene.fill(Fill::NonZero, Affine::IDENTITY, bg_color, None, &rect);
         }
 
-        self.render_element(
+        let location = Point {
+            x: -viewport_scroll.x,
+            y: -viewport_scroll.y,
+        };
+        self.render_element(scene, root_id, location);
+
+        // Modal dialogs and popovers are drawn above the rest of the document (each above its backdrop)
+        for node_id in self.dom.top_layer() {
+            if let Some(backdrop_id) = self.dom.as_ref().tree()[node_id].backdrop {
+                self.render_element(scene, backdrop_id, location);
+            }
+            self.render_element(scene, node_id, location);
+        }
+
+        // Viewport scrollbars are drawn on top of the document
+        draw_scrollbars(
             scene,
-            root_id,
-            Point {
-                x: -viewport_scroll.x,
-                y: -viewport_scroll.y,
-            },
+            Affine::scale(self.scale),
+            self.dom,
+            &self.dom.viewport_scrollbars(),
         );
 
         // Render debug overlay
@@ -473,6 +488,9 @@ impl VelloSceneGenerator<'_> {
         cx.draw_inset_box_shadow(scene);
         cx.stroke_devtools(scene);
 
+        // Scrollbars are positioned relative to the (unscrolled) border box
+        let border_box_transform = cx.transform;
+
         // Now that background has been drawn, offset pos and cx in order to draw our contents scrolled
         let content_position = Point {
             x: content_position.x - node.scroll_offset.x,
@@ -487,9 +505,13 @@ impl VelloSceneGenerator<'_> {
             y: -node.scroll_offset.y,
         });
         cx.draw_image(scene);
+        cx.draw_iframe(scene);
         #[cfg(feature = "svg")]
         cx.draw_svg(scene);
         cx.draw_input(scene);
+        cx.draw_progress(scene);
+        cx.draw_media(scene);
+        cx.draw_canvas(scene);
 
         cx.draw_text_input_text(scene, content_position);
         cx.draw_inline_layout(scene, content_position);
@@ -500,6 +522,15 @@ impl VelloSceneGenerator<'_> {
             scene.pop_layer();
             CLIP_DEPTH.fetch_sub(1, atomic::Ordering::SeqCst);
         }
+
+        if should_clip {
+            draw_scrollbars(
+                scene,
+                border_box_transform * Affine::scale(self.scale),
+                self.dom,
+                &self.dom.node_scrollbars(node_id),
+            );
+        }
     }
 
     fn render_node(&self, scene: &mut Scene, node_id: usize, location: Point) {
@@ -514,9 +545,10 @@ impl VelloSceneGenerator<'_> {
                 // (they should always be rendered as part of an inline layout)
                 // unreachable!()
             }
-            NodeData::Document => {}
+            NodeData::Document | NodeData::ShadowRoot(_) | NodeData::DocumentFragment => {}
             // NodeData::Doctype => {}
-            NodeData::Comment => {} // NodeData::ProcessingInstruction { .. } => {}
+            NodeData::Comment => {}
+            NodeData::ProcessingInstruction { .. } => {}
         }
     }
 
@@ -537,14 +569,27 @@ impl VelloSceneGenerator<'_> {
 
         let scale = self.scale;
 
+        // The background and border of tables are drawn around the table box, which excludes the
+        // captions above and below it
+        let (caption_top, caption_bottom) = node
+            .element_data()
+            .and_then(|el| el.table_context())
+            .map_or((0.0, 0.0), |ctx| ctx.caption_heights(self.dom));
+        let mut frame_layout = layout;
+        frame_layout.size.height = (layout.size.height - caption_top - caption_bottom).max(0.0);
+
         // todo: maybe cache this so we don't need to constantly be figuring it out
         // It is quite a bit of math to calculate during render/traverse
         // Also! we can cache the bezpaths themselves, saving us a bunch of work
-        let frame = ElementFrame::new(&style, &layout, scale);
+        let frame = ElementFrame::new(&style, &frame_layout, scale);
 
         // the bezpaths for every element are (potentially) cached (not yet, tbd)
         // By performing the transform, we prevent the cache from becoming invalid when the page shifts around
-        let mut transform = Affine::translate(box_position.to_vec2() * scale);
+        let frame_position = Point {
+            x: box_position.x,
+            y: box_position.y + caption_top as f64,
+        };
+        let mut transform = Affine::translate(frame_position.to_vec2() * scale);
 
         // Apply CSS transform property (where transforms are 2d)
         //
@@ -584,6 +629,19 @@ impl VelloSceneGenerator<'_> {
 
         let element = node.element_data().unwrap();
 
+        // In the collapsing border model, borders are painted by the table's cells rather than by the table itself
+        let is_collapsed_table = element
+            .table_context()
+            .is_some_and(|ctx| ctx.is_border_collapse());
+        let collapsed_borders = node
+            .layout_parent
+            .get()
+            .and_then(|parent_id| self.dom.get_node(parent_id))
+            .and_then(|parent| parent.element_data())
+            .and_then(|parent| parent.table_context())
+            .and_then(|ctx| ctx.collapsed_borders(node.id))
+            .copied();
+
         ElementCx {
             context: self,
             frame,
@@ -597,6 +655,8 @@ impl VelloSceneGenerator<'_> {
             svg: element.svg_data(),
             text_input: element.text_input_data(),
             list_item: element.list_item_data.as_deref(),
+            is_collapsed_table,
+            collapsed_borders,
             devtools: &self.devtools,
         }
     }
@@ -695,6 +755,45 @@ fn compute_background_size(
     }
 }
 
+/// Draw the scrollbars of a scroll container. `transform` maps from the coordinate space of the
+/// scrollbars (CSS pixels) to the scene.
+fn draw_scrollbars(
+    scene: &mut Scene,
+    transform: Affine,
+    dom: &BaseDocument,
+    scrollbars: &Scrollbars,
+) {
+    if scrollbars.is_empty() {
+        return;
+    }
+
+    let colors = dom.scrollbar_colors();
+    let thumb_inset = match dom.scrollbar_mode() {
+        ScrollbarMode::Classic => 2.0,
+        ScrollbarMode::Overlay => 1.0,
+    };
+
+    for scrollbar in scrollbars.iter() {
+        scene.fill(
+            Fill::NonZero,
+            transform,
+            colors.track,
+            None,
+            &scrollbar.track,
+        );
+
+        let thumb = scrollbar.thumb.inset(-thumb_inset);
+        let radius = thumb.width().min(thumb.height()) / 2.0;
+        scene.fill(
+            Fill::NonZero,
+            transform,
+            colors.thumb,
+            None,
+            &thumb.to_rounded_rect(radius),
+        );
+    }
+}
+
 /// Ensure that the `resized_image` field has a correctly sized image
 fn to_peniko_image(image: &RasterImageData) -> peniko::Image {
     peniko::Image {
@@ -709,6 +808,26 @@ fn to_peniko_image(image: &RasterImageData) -> peniko::Image {
     }
 }
 
+/// The x position at which a glyph run (starting at `offset`, with glyphs of the given advances)
+/// ends when glyphs which end beyond `max_x` (if set) are omitted. Returns `None` if none of the
+/// run's glyphs are drawn.
+fn drawn_run_end(
+    offset: f32,
+    advances: impl IntoIterator<Item = f32>,
+    max_x: Option<f32>,
+) -> Option<f32> {
+    let mut x = offset;
+    let mut end_x = None;
+    for advance in advances {
+        x += advance;
+        if max_x.is_some_and(|max_x| x > max_x) {
+            break;
+        }
+        end_x = Some(x);
+    }
+    end_x
+}
+
 /// A context of loaded and hot data to draw the element from
 struct ElementCx<'a> {
     context: &'a VelloSceneGenerator<'a>,
@@ -723,6 +842,8 @@ struct ElementCx<'a> {
     svg: Option<&'a usvg::Tree>,
     text_input: Option<&'a TextInputData>,
     list_item: Option<&'a ListItemLayout>,
+    is_collapsed_table: bool,
+    collapsed_borders: Option<CollapsedBorders>,
     devtools: &'a Devtools,
 }
 
@@ -766,7 +887,41 @@ impl ElementCx<'_> {
                 });
 
             // Render text
-            self.stroke_text(scene, text_layout.layout.lines(), pos);
+            let ellipsis = text_layout
+                .ellipsis
+                .as_ref()
+                .filter(|_| self.style.get_box().overflow_x != Overflow::Visible);
+            if ellipsis.is_none() && text_layout.hyphen.is_none() {
+                self.stroke_text(scene, text_layout.layout.lines(), pos);
+                return;
+            }
+
+            let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
+            let available_width = self.frame.content_box.width() as f32;
+            for line in text_layout.layout.lines() {
+                // text-overflow: replace content which overflows the end of a line with the ellipsis
+                let ellipsis = ellipsis.filter(|_| line.metrics().advance > available_width);
+                let max_x = ellipsis.map(|e| (available_width - e.full_width()).max(0.0));
+                let end_x = self.stroke_line(scene, &line, transform, max_x);
+
+                // Lines which break at a soft hyphen end with a visible hyphen
+                let trailing = ellipsis.or_else(|| {
+                    text_layout
+                        .hyphen
+                        .as_ref()
+                        .filter(|_| text_layout.breaks_at_soft_hyphen(&line))
+                });
+                let Some(trailing) = trailing else {
+                    continue;
+                };
+                let trailing_baseline = trailing.lines().next().map(|l| l.metrics().baseline);
+                let y_offset = line.metrics().baseline - trailing_baseline.unwrap_or(0.0);
+                let trailing_pos = Point {
+                    x: pos.x + (end_x as f64 / self.scale),
+                    y: pos.y + (y_offset as f64 / self.scale),
+                };
+                self.stroke_text(scene, trailing.lines(), trailing_pos);
+            }
         }
     }
 
@@ -776,23 +931,41 @@ impl ElementCx<'_> {
             let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
 
             if self.node.is_focussed() {
-                // Render selection/caret
+                // Render selection/caret using the background color from the `::selection`
+                // pseudo-element (if any)
+                // TODO: render selected text using the `::selection` text color
+                let selection_color = self
+                    .node
+                    .stylo_element_data
+                    .borrow()
+                    .as_ref()
+                    .and_then(|data| data.styles.pseudos.get(&PseudoElement::Selection).cloned())
+                    .map(|style| {
+                        style
+                            .clone_background_color()
+                            .resolve_to_absolute(&style.clone_color())
+                            .as_srgb_color()
+                    })
+                    .unwrap_or(color::palette::css::STEEL_BLUE);
                 for rect in input_data.editor.selection_geometry().iter() {
-                    scene.fill(
-                        Fill::NonZero,
-                        transform,
-                        color::palette::css::STEEL_BLUE,
-                        None,
-                        &rect,
-                    );
+                    scene.fill(Fill::NonZero, transform, selection_color, None, &rect);
                 }
                 if let Some(cursor) = input_data.editor.cursor_geometry(1.5) {
                     scene.fill(Fill::NonZero, transform, Color::BLACK, None, &cursor);
                 };
             }
 
-            // Render text
-            self.stroke_text(scene, input_data.editor.try_layout().unwrap().lines(), pos);
+            // Render text (or the placeholder if the input is empty)
+            let placeholder = input_data
+                .placeholder
+                .as_ref()
+                .filter(|_| (input_data.editor.text().into_iter()).all(|chunk| chunk.is_empty()));
+            match placeholder {
+                Some(placeholder) => self.stroke_text(scene, placeholder.lines(), pos),
+                None => {
+                    self.stroke_text(scene, input_data.editor.try_layout().unwrap().lines(), pos)
+                }
+            }
         }
     }
 
@@ -800,6 +973,7 @@ impl ElementCx<'_> {
         if let Some(ListItemLayout {
             marker,
             position: ListItemLayoutPosition::Outside(layout),
+            ..
         }) = self.list_item
         {
             // Right align and pad the bullet when rendering outside
@@ -848,74 +1022,100 @@ impl ElementCx<'_> {
         let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
 
         for line in lines {
-            for item in line.items() {
-                if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
-                    let mut x = glyph_run.offset();
-                    let y = glyph_run.baseline();
-
-                    let run = glyph_run.run();
-                    let font = run.font();
-                    let font_size = run.font_size();
-                    let metrics = run.metrics();
-                    let style = glyph_run.style();
-                    let synthesis = run.synthesis();
-                    let glyph_xform = synthesis
-                        .skew()
-                        .map(|angle| Affine::skew(angle.to_radians().tan() as f64, 0.0));
-
-                    scene
-                        .draw_glyphs(font)
-                        .brush(&style.brush.brush)
-                        .hint(true)
-                        .transform(transform)
-                        .glyph_transform(glyph_xform)
-                        .font_size(font_size)
-                        .normalized_coords(run.normalized_coords())
-                        .draw(
-                            Fill::NonZero,
-                            glyph_run.glyphs().map(|glyph| {
-                                let gx = x + glyph.x;
-                                let gy = y - glyph.y;
-                                x += glyph.advance;
-
-                                vello::Glyph {
-                                    id: glyph.id as _,
-                                    x: gx,
-                                    y: gy,
-                                }
-                            }),
-                        );
+            self.stroke_line(scene, &line, transform, None);
+        }
+    }
 
-                    let mut draw_decoration_line = |offset: f32, size: f32, brush: &TextBrush| {
-                        let x = glyph_run.offset() as f64;
-                        let w = glyph_run.advance() as f64;
-                        let y = (glyph_run.baseline() - offset + size / 2.0) as f64;
-                        let line = kurbo::Line::new((x, y), (x + w, y));
-                        scene.stroke(
-                            &Stroke::new(size as f64),
-                            transform,
-                            &brush.brush,
-                            None,
-                            &line,
-                        )
-                    };
+    /// Draw a single line of text. Glyphs which end beyond `max_x` (if set) are omitted.
+    ///
+    /// Returns the x position at which the last drawn glyph ends.
+    fn stroke_line(
+        &self,
+        scene: &mut Scene,
+        line: &Line<'_, TextBrush>,
+        transform: Affine,
+        max_x: Option<f32>,
+    ) -> f32 {
+        let mut end_x: f32 = 0.0;
+        for item in line.items() {
+            if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                let mut x = glyph_run.offset();
+                let y = glyph_run.baseline();
+
+                let run = glyph_run.run();
+                let font = run.font();
+                let font_size = run.font_size();
+                let metrics = run.metrics();
+                let style = glyph_run.style();
+                let synthesis = run.synthesis();
+                let glyph_xform = synthesis
+                    .skew()
+                    .map(|angle| Affine::skew(angle.to_radians().tan() as f64, 0.0));
+
+                // Runs whose first glyph already ends beyond `max_x` are skipped entirely
+                let advances = glyph_run.glyphs().map(|glyph| glyph.advance);
+                let Some(run_end_x) = drawn_run_end(x, advances, max_x) else {
+                    continue;
+                };
+                end_x = end_x.max(run_end_x);
+
+                scene
+                    .draw_glyphs(font)
+                    .brush(&style.brush.brush)
+                    .hint(true)
+                    .transform(transform)
+                    .glyph_transform(glyph_xform)
+                    .font_size(font_size)
+                    .normalized_coords(run.normalized_coords())
+                    .draw(
+                        Fill::NonZero,
+                        glyph_run.glyphs().map_while(|glyph| {
+                            let gx = x + glyph.x;
+                            let gy = y - glyph.y;
+                            x += glyph.advance;
 
-                    if let Some(underline) = &style.underline {
-                        let offset = underline.offset.unwrap_or(metrics.underline_offset);
-                        let size = underline.size.unwrap_or(metrics.underline_size);
+                            if max_x.is_some_and(|max_x| x > max_x) {
+                                return None;
+                            }
 
-                        // TODO: intercept line when crossing an descending character like "gqy"
-                        draw_decoration_line(offset, size, &underline.brush);
-                    }
-                    if let Some(strikethrough) = &style.strikethrough {
-                        let offset = strikethrough.offset.unwrap_or(metrics.strikethrough_offset);
-                        let size = strikethrough.size.unwrap_or(metrics.strikethrough_size);
+                            Some(vello::Glyph {
+                                id: glyph.id as _,
+                                x: gx,
+                                y: gy,
+                            })
+                        }),
+                    );
 
-                        draw_decoration_line(offset, size, &strikethrough.brush);
-                    }
+                let mut draw_decoration_line = |offset: f32, size: f32, brush: &TextBrush| {
+                    let x = glyph_run.offset() as f64;
+                    let w = (run_end_x - glyph_run.offset()) as f64;
+                    let y = (glyph_run.baseline() - offset + size / 2.0) as f64;
+                    let line = kurbo::Line::new((x, y), (x + w, y));
+                    scene.stroke(
+                        &Stroke::new(size as f64),
+                        transform,
+                        &brush.brush,
+                        None,
+                        &line,
+                    )
+                };
+
+                if let Some(underline) = &style.underline {
+                    let offset = underline.offset.unwrap_or(metrics.underline_offset);
+                    let size = underline.size.unwrap_or(metrics.underline_size);
+
+                    // TODO: intercept line when crossing an descending character like "gqy"
+                    draw_decoration_line(offset, size, &underline.brush);
+                }
+                if let Some(strikethrough) = &style.strikethrough {
+                    let offset = strikethrough.offset.unwrap_or(metrics.strikethrough_offset);
+                    let size = strikethrough.size.unwrap_or(metrics.strikethrough_size);
+
+                    draw_decoration_line(offset, size, &strikethrough.brush);
                 }
             }
         }
+        end_x
     }
 
     #[cfg(feature = "svg")]
@@ -944,6 +1144,7 @@ impl ElementCx<'_> {
 
     #[cfg(feature = "svg")]
     fn draw_svg_bg_image(&self, scene: &mut Scene, idx: usize) {
+        use blitz_dom::node::ImageData;
         use style::{Zero as _, values::computed::Length};
 
         let bg_image = self.element.background_images.get(idx);
@@ -1007,6 +1208,11 @@ impl ElementCx<'_> {
     }
 
     fn draw_image(&self, scene: &mut Scene) {
+        // Video posters are drawn by draw_media
+        if self.element.name.local == local_name!("video") {
+            return;
+        }
+
         if let Some(image) = self.element.raster_image_data() {
             let width = self.frame.content_box.width() as u32;
             let height = self.frame.content_box.height() as u32;
@@ -1024,6 +1230,29 @@ impl ElementCx<'_> {
         }
     }
 
+    /// Draw the nested document of an iframe, clipped to the iframe's content box
+    fn draw_iframe(&self, scene: &mut Scene) {
+        let Some(doc) = self.context.dom.sub_document(self.node.id) else {
+            return;
+        };
+
+        let content_box = self.frame.content_box;
+        let generator = VelloSceneGenerator {
+            dom: doc,
+            scale: self.scale,
+            width: content_box.width() as u32,
+            height: content_box.height() as u32,
+            devtools: *self.devtools,
+        };
+        let mut iframe_scene = Scene::new();
+        generator.generate_vello_scene(&mut iframe_scene);
+
+        scene.push_layer(Mix::Clip, 1.0, self.transform, &content_box);
+        let transform = self.transform.pre_translate(content_box.origin().to_vec2());
+        scene.append(&iframe_scene, Some(transform));
+        scene.pop_layer();
+    }
+
     fn draw_raster_bg_image(&self, scene: &mut Scene, idx: usize) {
         use style::{Zero as _, values::computed::Length};
 
@@ -1032,7 +1261,7 @@ impl ElementCx<'_> {
         let Some(Some(bg_image)) = bg_image.as_ref() else {
             return;
         };
-        let ImageData::Raster(image_data) = &bg_image.image else {
+        let Some(image_data) = bg_image.image.raster() else {
             return;
         };
 
@@ -1673,11 +1902,85 @@ impl ElementCx<'_> {
     ///
     /// The border-style property can have from one to four values (for the top border, right border, bottom border, and the left border).
     fn stroke_border(&self, sb: &mut Scene) {
+        if self.is_collapsed_table {
+            return;
+        }
+        if let Some(borders) = &self.collapsed_borders {
+            self.stroke_collapsed_borders(sb, borders);
+            return;
+        }
         for edge in [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left] {
             self.stroke_border_edge(sb, edge);
         }
     }
 
+    /// Paint the resolved borders of a cell in a table with `border-collapse: collapse`.
+    ///
+    /// Each edge is centered on the edge of the cell's border box, so that the borders of adjacent
+    /// cells overlap exactly.
+    fn stroke_collapsed_borders(&self, sb: &mut Scene, borders: &CollapsedBorders) {
+        let border_box = self.frame.border_box;
+        let scale = self.scale;
+        let half = |border: &CollapsedBorder| border.width as f64 * scale / 2.0;
+        let (top, right, bottom, left) = (
+            half(&borders.top),
+            half(&borders.right),
+            half(&borders.bottom),
+            half(&borders.left),
+        );
+
+        // Horizontal edges span the corners. Vertical edges are painted between them.
+        let edges = [
+            (
+                &borders.top,
+                Rect::new(
+                    border_box.x0 - left,
+                    border_box.y0 - top,
+                    border_box.x1 + right,
+                    border_box.y0 + top,
+                ),
+            ),
+            (
+                &borders.bottom,
+                Rect::new(
+                    border_box.x0 - left,
+                    border_box.y1 - bottom,
+                    border_box.x1 + right,
+                    border_box.y1 + bottom,
+                ),
+            ),
+            (
+                &borders.left,
+                Rect::new(
+                    border_box.x0 - left,
+                    border_box.y0 + top,
+                    border_box.x0 + left,
+                    border_box.y1 - bottom,
+                ),
+            ),
+            (
+                &borders.right,
+                Rect::new(
+                    border_box.x1 - right,
+                    border_box.y0 + top,
+                    border_box.x1 + right,
+                    border_box.y1 - bottom,
+                ),
+            ),
+        ];
+
+        for (border, rect) in edges {
+            if border.style.none_or_hidden() || border.width <= 0.0 {
+                continue;
+            }
+            // TODO: support border styles other than solid
+            let color = border.color;
+            if color.components[3] != 0.0 {
+                sb.fill(Fill::NonZero, self.transform, color, None, &rect);
+            }
+        }
+    }
+
     /// The border-style property specifies what kind of border to display.
     ///
     /// [Border](https://www.w3schools.com/css/css_border.asp)
@@ -2090,7 +2393,240 @@ impl ElementCx<'_> {
             }
         }
     }
+
+    /// Draw the bar of a `<progress>` or `<meter>` element (over its background) in its color
+    fn draw_progress(&self, scene: &mut Scene) {
+        let position = match self.element.meter_values() {
+            Some(meter) => meter.position(),
+            None => match self.element.progress_position() {
+                Some(position) => position,
+                None => return,
+            },
+        };
+
+        let content_box = self.frame.content_box;
+        let bar_width = content_box.width() * position;
+        let is_rtl = matches!(
+            self.style.get_inherited_box().direction,
+            style::computed_values::direction::T::Rtl
+        );
+        let bar = if is_rtl {
+            Rect::new(
+                content_box.x1 - bar_width,
+                content_box.y0,
+                content_box.x1,
+                content_box.y1,
+            )
+        } else {
+            Rect::new(
+                content_box.x0,
+                content_box.y0,
+                content_box.x0 + bar_width,
+                content_box.y1,
+            )
+        };
+
+        let color = self.style.clone_color().as_srgb_color();
+        scene.push_layer(Mix::Clip, 1.0, self.transform, &self.frame.frame_padding());
+        scene.fill(Fill::NonZero, self.transform, color, None, &bar);
+        scene.pop_layer();
+    }
+
+    /// Draw the current frame (or poster image) of a `<video>` element, and the default controls of
+    /// media elements with a `controls` attribute
+    fn draw_media(&self, scene: &mut Scene) {
+        let is_video = self.element.name.local == local_name!("video");
+        if !is_video && self.element.name.local != local_name!("audio") {
+            return;
+        }
+
+        let content_box = self.frame.content_box;
+        let state = self.context.dom.media_state(self.node.id);
+        let frame = self.context.dom.media_frame(self.node.id);
+        if let Some(image) = frame
+            .or(self.element.raster_image_data())
+            .filter(|_| is_video)
+        {
+            // Videos are letterboxed within their content box
+            let scale = f64::min(
+                content_box.width() / image.width as f64,
+                content_box.height() / image.height as f64,
+            );
+            let width = image.width as f64 * scale;
+            let height = image.height as f64 * scale;
+            let origin = Point {
+                x: content_box.x0 + (content_box.width() - width) / 2.0,
+                y: content_box.y0 + (content_box.height() - height) / 2.0,
+            };
+            let transform = self
+                .transform
+                .pre_translate(origin.to_vec2())
+                .pre_scale(scale);
+            scene.draw_image(&to_peniko_image(image), transform);
+        }
+
+        if self.element.attr(local_name!("controls")).is_none() {
+            return;
+        }
+
+        let controls = MediaControls::new(content_box);
+        let (bar_color, icon_color) = if is_video {
+            (Color::from_rgba8(0, 0, 0, 140), Color::WHITE)
+        } else {
+            (Color::from_rgba8(241, 243, 244, 255), Color::BLACK)
+        };
+        scene.fill(
+            Fill::NonZero,
+            self.transform,
+            bar_color,
+            None,
+            &controls.bar,
+        );
+
+        // A play button while paused, or a pause button while playing
+        let paused = state.is_none_or(|state| state.paused);
+        let center = controls.play_button.center();
+        if paused {
+            let mut triangle = BezPath::new();
+            triangle.move_to((center.x - 4.0, center.y - 6.0));
+            triangle.line_to((center.x + 6.0, center.y));
+            triangle.line_to((center.x - 4.0, center.y + 6.0));
+            triangle.close_path();
+            scene.fill(Fill::NonZero, self.transform, icon_color, None, &triangle);
+        } else {
+            for x in [center.x - 5.0, center.x + 1.0] {
+                let bar = Rect::new(x, center.y - 6.0, x + 4.0, center.y + 6.0);
+                scene.fill(Fill::NonZero, self.transform, icon_color, None, &bar);
+            }
+        }
+
+        // The seek track, filled up to the current time
+        let track = controls.track.to_rounded_rect(2.0);
+        let track_color = icon_color.with_alpha(0.3);
+        scene.fill(Fill::NonZero, self.transform, track_color, None, &track);
+        let position = state
+            .and_then(|state| {
+                let duration = state.duration.filter(|duration| !duration.is_zero())?;
+                Some(state.current_time.as_secs_f64() / duration.as_secs_f64())
+            })
+            .unwrap_or(0.0);
+        let filled = controls
+            .track
+            .with_size((controls.track.width() * position, controls.track.height()))
+            .to_rounded_rect(2.0);
+        scene.fill(Fill::NonZero, self.transform, icon_color, None, &filled);
+    }
+
+    /// Draw the display list of a `<canvas>` element as a sub-scene scaled to its content box
+    fn draw_canvas(&self, scene: &mut Scene) {
+        if self.element.name.local != local_name!("canvas") {
+            return;
+        }
+        let Some(surface) = self.element.canvas_surface() else {
+            return;
+        };
+
+        let (width, height) = self.element.canvas_size();
+        if width == 0 || height == 0 {
+            return;
+        }
+        let canvas_rect = Rect::new(0.0, 0.0, width as f64, height as f64);
+
+        // The canvas is drawn in an isolated layer so that clearing it doesn't affect the content
+        // behind it
+        let mut canvas_scene = Scene::new();
+        canvas_scene.push_layer(Mix::Normal, 1.0, Affine::IDENTITY, &canvas_rect);
+        let mut clips = 0;
+        surface.with_display_list(|commands| {
+            for command in commands {
+                match command {
+                    CanvasCommand::Fill { path, fill, brush } => {
+                        canvas_scene.fill(*fill, Affine::IDENTITY, brush, None, path);
+                    }
+                    CanvasCommand::Stroke {
+                        path,
+                        stroke,
+                        brush,
+                        transform,
+                    } => {
+                        canvas_scene.stroke(stroke, *transform, brush, None, path);
+                    }
+                    CanvasCommand::Glyphs {
+                        font,
+                        font_size,
+                        normalized_coords,
+                        glyphs,
+                        brush,
+                        transform,
+                    } => {
+                        canvas_scene
+                            .draw_glyphs(font)
+                            .brush(brush)
+                            .transform(*transform)
+                            .font_size(*font_size)
+                            .normalized_coords(normalized_coords)
+                            .draw(
+                                Fill::NonZero,
+                                glyphs.iter().map(|glyph| vello::Glyph {
+                                    id: glyph.id,
+                                    x: glyph.x,
+                                    y: glyph.y,
+                                }),
+                            );
+                    }
+                    CanvasCommand::Image {
+                        image,
+                        alpha,
+                        transform,
+                    } => {
+                        let mut image = to_peniko_image(image);
+                        image.alpha = *alpha;
+                        canvas_scene.draw_image(&image, *transform);
+                    }
+                    CanvasCommand::Clear { path } => {
+                        let clear = peniko::BlendMode::new(Mix::Normal, peniko::Compose::Clear);
+                        canvas_scene.push_layer(clear, 1.0, Affine::IDENTITY, path);
+                        canvas_scene.fill(
+                            Fill::NonZero,
+                            Affine::IDENTITY,
+                            Color::BLACK,
+                            None,
+                            path,
+                        );
+                        canvas_scene.pop_layer();
+                    }
+                    CanvasCommand::PushClip { path } => {
+                        canvas_scene.push_layer(Mix::Clip, 1.0, Affine::IDENTITY, path);
+                        clips += 1;
+                    }
+                    CanvasCommand::PopClip => {
+                        if clips > 0 {
+                            canvas_scene.pop_layer();
+                            clips -= 1;
+                        }
+                    }
+                }
+            }
+        });
+        for _ in 0..clips {
+            canvas_scene.pop_layer();
+        }
+        canvas_scene.pop_layer();
+
+        let content_box = self.frame.content_box;
+        let transform = self
+            .transform
+            .pre_translate(content_box.origin().to_vec2())
+            .pre_scale_non_uniform(
+                content_box.width() / width as f64,
+                content_box.height() / height as f64,
+            );
+        scene.push_layer(Mix::Clip, 1.0, self.transform, &content_box);
+        scene.append(&canvas_scene, Some(transform));
+        scene.pop_layer();
+    }
 }
+
 impl<'a> std::ops::Deref for ElementCx<'a> {
     type Target = VelloSceneGenerator<'a>;
     fn deref(&self) -> &Self::Target {
@@ -2102,3 +2638,23 @@ enum BackgroundSizeComputeMode {
     Auto,
     Size(f32, f32),
 }
+
+#[test]
+fn truncates_lines_at_the_first_overflowing_glyph() {
+    // A line of three runs of 10px wide glyphs: the overflow starts in the second run, and the third
+    // run lies entirely beyond the limit
+    let runs: [(f32, &[f32]); 3] = [(0.0, &[10.0, 10.0]), (20.0, &[10.0, 10.0]), (40.0, &[10.0])];
+    let line_end = |max_x: Option<f32>| {
+        runs.iter()
+            .filter_map(|(offset, advances)| {
+                drawn_run_end(*offset, advances.iter().copied(), max_x)
+            })
+            .fold(0.0, f32::max)
+    };
+
+    assert_eq!(line_end(None), 50.0);
+    assert_eq!(line_end(Some(35.0)), 30.0);
+    assert_eq!(line_end(Some(20.0)), 20.0);
+    assert_eq!(line_end(Some(5.0)), 0.0);
+    assert_eq!(drawn_run_end(40.0, [10.0], Some(35.0)), None);
+}
@@ -12,7 +12,8 @@ use winit::keyboard::PhysicalKey;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::task::Waker;
-use winit::event::{ElementState, MouseButton};
+use std::time::Instant;
+use winit::event::{ElementState, MouseButton, TouchPhase};
 use winit::event_loop::{ActiveEventLoop, EventLoopProxy};
 use winit::window::{Theme, WindowAttributes, WindowId};
 use winit::{event::Modifiers, event::WindowEvent, keyboard::KeyCode, window::Window};
@@ -73,6 +74,11 @@ pub struct View<Doc: Document<Doc = D>, Rend: DocumentRenderer<Doc = D>> {
     dom_mouse_pos: (f32, f32),
     mouse_down_node: Option<usize>,
 
+    /// Estimated velocity of touchpad scrolling, used to continue scrolling with inertia
+    /// once the gesture ends
+    pixel_scroll_velocity: (f64, f64),
+    last_pixel_scroll: Option<Instant>,
+
     #[cfg(feature = "accessibility")]
     /// Accessibility adapter for `accesskit`.
     accessibility: AccessibilityState,
@@ -116,6 +122,8 @@ impl<Doc: Document<Doc = D>, Rend: DocumentRenderer<Doc = D>> View<Doc, Rend> {
             mouse_pos: Default::default(),
             dom_mouse_pos: Default::default(),
             mouse_down_node: None,
+            pixel_scroll_velocity: (0.0, 0.0),
+            last_pixel_scroll: None,
 
             #[cfg(feature = "accessibility")]
             accessibility: AccessibilityState::new(&winit_window, proxy.clone()),
@@ -214,6 +222,8 @@ impl<Doc: Document<Doc = D>, Rend: DocumentRenderer<Doc = D>> View<Doc, Rend> {
     }
 
     pub fn redraw(&mut self) {
+        let animating = self.doc.as_mut().tick_scroll_animations();
+
         self.doc.as_mut().resolve();
         let (width, height) = self.viewport.window_size;
         self.renderer.render(
@@ -223,6 +233,11 @@ impl<Doc: Document<Doc = D>, Rend: DocumentRenderer<Doc = D>> View<Doc, Rend> {
             height,
             self.devtools,
         );
+
+        // Keep rendering frames until scroll animations have finished
+        if animating {
+            self.request_redraw();
+        }
     }
 
     pub fn window_id(&self) -> WindowId {
@@ -338,6 +353,43 @@ impl<Doc: Document<Doc = D>, Rend: DocumentRenderer<Doc = D>> View<Doc, Rend> {
         }
     }
 
+    /// Track the velocity of touchpad scrolling and start an inertial scroll when the gesture ends.
+    ///
+    /// macOS delivers its own momentum scroll events, so we don't add inertia there.
+    fn track_pixel_scroll(&mut self, x: f64, y: f64, phase: TouchPhase) {
+        let now = Instant::now();
+        match phase {
+            TouchPhase::Started => {
+                self.pixel_scroll_velocity = (0.0, 0.0);
+                self.last_pixel_scroll = Some(now);
+            }
+            TouchPhase::Moved => {
+                if let Some(last) = self.last_pixel_scroll {
+                    let dt = now.duration_since(last).as_secs_f64();
+                    if dt > 0.0 {
+                        // Exponentially weighted average to smooth out noisy deltas
+                        let (vx, vy) = self.pixel_scroll_velocity;
+                        self.pixel_scroll_velocity =
+                            (0.8 * (x / dt) + 0.2 * vx, 0.8 * (y / dt) + 0.2 * vy);
+                    }
+                }
+                self.last_pixel_scroll = Some(now);
+            }
+            TouchPhase::Ended => {
+                let (vx, vy) = std::mem::take(&mut self.pixel_scroll_velocity);
+                self.last_pixel_scroll = None;
+                if cfg!(not(target_os = "macos")) {
+                    let hover_node_id = self.doc.as_ref().get_hover_node_id();
+                    self.doc.as_mut().fling(hover_node_id, vx, vy);
+                }
+            }
+            TouchPhase::Cancelled => {
+                self.pixel_scroll_velocity = (0.0, 0.0);
+                self.last_pixel_scroll = None;
+            }
+        }
+    }
+
     pub fn click(&mut self, button: MouseEventButton) {
         let Some(node_id) = self.doc.as_ref().get_hover_node_id() else {
             return;
@@ -516,13 +568,23 @@ impl<Doc: Document<Doc = D>, Rend: DocumentRenderer<Doc = D>> View<Doc, Rend> {
                     self.request_redraw();
                 }
             }
-            WindowEvent::MouseWheel { delta, .. } => {
+            WindowEvent::MouseWheel { delta, phase, .. } => {
+                // Manual scrolling interrupts any in-progress smooth or inertial scrolling
+                self.doc.as_mut().cancel_scroll_animations();
+
+                let hover_node_id = self.doc.as_ref().get_hover_node_id();
                 let (scroll_x, scroll_y)= match delta {
-                    winit::event::MouseScrollDelta::LineDelta(x, y) => (x as f64 * 20.0, y as f64 * 20.0),
-                    winit::event::MouseScrollDelta::PixelDelta(offsets) => (offsets.x, offsets.y)
+                    winit::event::MouseScrollDelta::LineDelta(x, y) => {
+                        let line = self.doc.as_ref().line_scroll_distance(hover_node_id);
+                        (x as f64 * line, y as f64 * line)
+                    }
+                    winit::event::MouseScrollDelta::PixelDelta(offsets) => {
+                        self.track_pixel_scroll(offsets.x, offsets.y, phase);
+                        (offsets.x, offsets.y)
+                    }
                 };
 
-                if let Some(hover_node_id)= self.doc.as_ref().get_hover_node_id() {
+                if let Some(hover_node_id) = hover_node_id {
                     self.doc.as_mut().scroll_node_by(hover_node_id, scroll_x, scroll_y);
                 } else {
                     self.doc.as_mut().scroll_viewport_by(scroll_x, scroll_y);
@@ -0,0 +1,299 @@
+//! Detecting the character encoding of documents and stylesheets, and decoding them.
+//!
+//! See <https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding>
+//! and <https://drafts.csswg.org/css-syntax/#input-byte-stream>
+
+pub use encoding_rs::Encoding;
+use encoding_rs::{UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252, X_USER_DEFINED};
+
+/// The number of bytes at the start of a document which are searched for a `<meta charset>`
+const PRESCAN_LENGTH: usize = 1024;
+
+/// The encoding named by the `charset` parameter of a `Content-Type` header value (such as
+/// `text/html; charset=Shift_JIS`)
+pub fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        let value = value.trim().trim_matches(['"', '\'']);
+        Encoding::for_label(value.as_bytes())
+    })
+}
+
+/// Detect the encoding of an HTML (or XML) document from (in order of precedence) its byte order
+/// mark, the charset of its `Content-Type`, and a `<meta charset>` or XML declaration near its
+/// start. Documents without any of these are decoded as UTF-8 if they are valid UTF-8, and as
+/// windows-1252 otherwise.
+pub fn detect_html_encoding(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = content_type.and_then(charset_from_content_type) {
+        return encoding;
+    }
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LENGTH)]) {
+        return encoding;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
+
+/// Decode an HTML (or XML) document, detecting its encoding with `detect_html_encoding`
+pub fn decode_html(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = detect_html_encoding(bytes, content_type);
+    let (html, _, _) = encoding.decode(bytes);
+    html.into_owned()
+}
+
+/// Decode a stylesheet, detecting its encoding from (in order of precedence) its byte order mark,
+/// the charset of its `Content-Type` and its `@charset` rule. Stylesheets without any of these
+/// are decoded as UTF-8.
+pub fn decode_css(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(charset_from_content_type)
+        .or_else(|| charset_rule(bytes))
+        .unwrap_or(UTF_8);
+    // A byte order mark takes precedence over the encoding passed to `decode`
+    let (css, _, _) = encoding.decode(bytes);
+    css.into_owned()
+}
+
+/// The encoding of an `@charset "...";` rule at the very start of a stylesheet
+fn charset_rule(bytes: &[u8]) -> Option<&'static Encoding> {
+    let rest = bytes.strip_prefix(b"@charset \"")?;
+    let end = rest.iter().position(|&b| b == b'"')?;
+    if !rest[end..].starts_with(b"\";") {
+        return None;
+    }
+    let encoding = Encoding::for_label(&rest[..end])?;
+    match encoding == UTF_16BE || encoding == UTF_16LE {
+        true => Some(UTF_8),
+        false => Some(encoding),
+    }
+}
+
+/// The encodings which can be declared in a document are restricted to ones which are compatible
+/// with ASCII (as the declaration itself must be readable)
+fn declared_encoding(label: &[u8]) -> Option<&'static Encoding> {
+    let encoding = Encoding::for_label(label)?;
+    if encoding == UTF_16BE || encoding == UTF_16LE {
+        Some(UTF_8)
+    } else if encoding == X_USER_DEFINED {
+        Some(WINDOWS_1252)
+    } else {
+        Some(encoding)
+    }
+}
+
+/// Search the start of a document for a `<meta charset>`, `<meta http-equiv="Content-Type">` or
+/// an XML declaration with an encoding
+///
+/// See <https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding>
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.starts_with(b"<?xml") {
+        let end = find(bytes, b"?>").unwrap_or(bytes.len());
+        let mut pos = 5;
+        while let Some((name, value, next)) = next_attribute(&bytes[..end], pos) {
+            if name == b"encoding" {
+                return declared_encoding(&value);
+            }
+            pos = next;
+        }
+    }
+
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"<!--") {
+            // Move to the end of the comment's closing "-->"
+            pos += find(&rest[2..], b"-->").map_or(bytes.len(), |end| end + 4);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .is_some_and(|&b| b.is_ascii_whitespace() || b == b'/')
+        {
+            pos += 6;
+            let mut charset = None;
+            let mut http_equiv = None;
+            let mut content = None;
+            while let Some((name, value, next)) = next_attribute(bytes, pos) {
+                pos = next;
+                match name.as_slice() {
+                    b"charset" => charset = charset.or(Some(value)),
+                    b"http-equiv" => http_equiv = http_equiv.or(Some(value)),
+                    b"content" => content = content.or(Some(value)),
+                    _ => {}
+                }
+            }
+            let encoding = match (charset, http_equiv, content) {
+                (Some(charset), _, _) => declared_encoding(&charset),
+                (None, Some(http_equiv), Some(content))
+                    if http_equiv.eq_ignore_ascii_case(b"content-type") =>
+                {
+                    charset_from_meta_content(&content)
+                }
+                _ => None,
+            };
+            if encoding.is_some() {
+                return encoding;
+            }
+        } else if rest.len() > 1 && rest[0] == b'<' && rest[1].is_ascii_alphabetic()
+            || rest.len() > 2 && rest.starts_with(b"</") && rest[2].is_ascii_alphabetic()
+        {
+            // Skip the tag name and attributes of other tags
+            pos += rest
+                .iter()
+                .position(|&b| b.is_ascii_whitespace() || b == b'>')
+                .unwrap_or(rest.len());
+            while let Some((_, _, next)) = next_attribute(bytes, pos) {
+                pos = next;
+            }
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            pos += rest.iter().position(|&b| b == b'>').unwrap_or(rest.len());
+        }
+        pos += 1;
+    }
+    None
+}
+
+/// Parse the attribute at `pos` in a tag, returning its (lowercased) name, its value and the
+/// position after it. Returns `None` at the end of the tag.
+///
+/// See <https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing>
+fn next_attribute(bytes: &[u8], mut pos: usize) -> Option<(Vec<u8>, Vec<u8>, usize)> {
+    while bytes
+        .get(pos)
+        .is_some_and(|&b| b.is_ascii_whitespace() || b == b'/')
+    {
+        pos += 1;
+    }
+    if bytes.get(pos).is_none_or(|&b| b == b'>') {
+        return None;
+    }
+
+    let mut name = Vec::new();
+    while let Some(&b) = bytes.get(pos) {
+        if b == b'=' && !name.is_empty() {
+            break;
+        }
+        if b.is_ascii_whitespace() || b == b'/' || b == b'>' {
+            break;
+        }
+        name.push(b.to_ascii_lowercase());
+        pos += 1;
+    }
+    while bytes.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        pos += 1;
+    }
+    if bytes.get(pos) != Some(&b'=') {
+        return Some((name, Vec::new(), pos));
+    }
+    pos += 1;
+    while bytes.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        pos += 1;
+    }
+
+    let mut value = Vec::new();
+    match bytes.get(pos) {
+        Some(&quote) if quote == b'"' || quote == b'\'' => {
+            pos += 1;
+            while let Some(&b) = bytes.get(pos) {
+                pos += 1;
+                if b == quote {
+                    break;
+                }
+                value.push(b.to_ascii_lowercase());
+            }
+        }
+        _ => {
+            while let Some(&b) = bytes.get(pos) {
+                if b.is_ascii_whitespace() || b == b'>' {
+                    break;
+                }
+                value.push(b.to_ascii_lowercase());
+                pos += 1;
+            }
+        }
+    }
+    Some((name, value, pos))
+}
+
+/// The encoding named by the `charset=` in the `content` attribute of a
+/// `<meta http-equiv="Content-Type">`
+///
+/// See <https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element>
+fn charset_from_meta_content(content: &[u8]) -> Option<&'static Encoding> {
+    let start = find(content, b"charset")? + 7;
+    let rest = content[start..].trim_ascii_start();
+    let rest = rest.strip_prefix(b"=")?.trim_ascii_start();
+    let value = match rest.first() {
+        Some(&quote) if quote == b'"' || quote == b'\'' => {
+            let end = rest[1..].iter().position(|&b| b == quote)?;
+            &rest[1..end + 1]
+        }
+        _ => {
+            let end = rest
+                .iter()
+                .position(|&b| b.is_ascii_whitespace() || b == b';')
+                .unwrap_or(rest.len());
+            &rest[..end]
+        }
+    };
+    declared_encoding(value)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[test]
+fn detects_document_encodings() {
+    use encoding_rs::{EUC_KR, GBK, SHIFT_JIS, UTF_16LE};
+
+    // "日本" in Shift_JIS
+    let html = b"<!DOCTYPE html><!-- <meta charset=gbk> --><html><head>\
+        <meta http-equiv=\"Content-Type\" content=\"text/html; charset=shift_jis\">\
+        </head><body>\x93\xfa\x96\x7b</body></html>";
+    assert_eq!(detect_html_encoding(html, None), SHIFT_JIS);
+    assert!(decode_html(html, None).contains("<body>日本</body>"));
+
+    // The Content-Type takes precedence over the document, and a BOM over both
+    assert_eq!(
+        detect_html_encoding(html, Some("text/html; charset=\"GBK\"")),
+        GBK
+    );
+    let bom_html = [&[0xff, 0xfe][..], b"<\0p\0>\0"].concat();
+    assert_eq!(detect_html_encoding(&bom_html, Some("text/html")), UTF_16LE);
+    assert_eq!(decode_html(&bom_html, None), "<p>");
+
+    assert_eq!(
+        detect_html_encoding(b"<?xml version='1.0' encoding='EUC-KR'?><html/>", None),
+        EUC_KR
+    );
+    assert_eq!(
+        detect_html_encoding(
+            b"<div title='<meta charset=gbk>'><meta charset='utf-16'>",
+            None
+        ),
+        UTF_8
+    );
+
+    // Undeclared documents are UTF-8 if they can be
+    assert_eq!(detect_html_encoding("<p>café</p>".as_bytes(), None), UTF_8);
+    assert_eq!(detect_html_encoding(b"<p>caf\xe9</p>", None), WINDOWS_1252);
+
+    // Stylesheets declare their encoding with @charset
+    let css = b"@charset \"windows-1252\"; p::before { content: \"\xe9\" }";
+    assert!(decode_css(css, None).contains("content: \"é\""));
+    assert!(decode_css(css, Some("text/css; charset=utf-8")).contains('\u{fffd}'));
+}
@@ -1,5 +1,9 @@
 pub mod net;
 
+pub mod encoding;
+
+pub mod media;
+
 pub mod navigation;
 
 mod events;
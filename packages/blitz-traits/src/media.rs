@@ -0,0 +1,75 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+pub type SharedMediaProvider<F> = Arc<dyn MediaProvider<Frame = F>>;
+pub type BoxedMediaSource<F> = Box<dyn MediaSource<Frame = F>>;
+
+/// Whether a media resource is being played by a `<video>` or an `<audio>` element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Audio,
+    Video,
+}
+
+/// A type that opens media resources (video and audio) for a Document.
+///
+/// The Document owns the playback state of its media elements (whether they are playing, their
+/// current position, looping, etc) and asks the opened `MediaSource` for the frame to display at
+/// the current position.
+pub trait MediaProvider: Send + Sync + 'static {
+    type Frame;
+    /// Start loading the media resource at `url`. Returns `None` if it can't be played.
+    fn open(
+        &self,
+        doc_id: usize,
+        url: Url,
+        kind: MediaKind,
+    ) -> Option<BoxedMediaSource<Self::Frame>>;
+}
+
+/// A media resource which has been opened by a `MediaProvider`.
+///
+/// Loading may happen in the background: the Document polls `metadata` until it is available.
+pub trait MediaSource: Send + 'static {
+    type Frame;
+
+    /// The metadata of the resource, or `None` while it is still loading
+    fn metadata(&mut self) -> Option<MediaMetadata>;
+
+    /// Decode the video frame which should be displayed at `time`. Returns `None` if the resource
+    /// has no video, or if the frame is the same as the one returned by the previous call.
+    fn video_frame(&mut self, time: Duration) -> Option<Self::Frame>;
+
+    /// Called when playback starts or stops at `time`. Audio should be output while playing.
+    fn set_playing(&mut self, _playing: bool, _time: Duration) {}
+
+    /// Called when the element is muted or unmuted
+    fn set_muted(&mut self, _muted: bool) {}
+}
+
+/// The metadata of a media resource
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediaMetadata {
+    /// The duration of the resource (`None` for unbounded streams)
+    pub duration: Option<Duration>,
+    /// The natural width and height of the video (`None` for audio)
+    pub video_size: Option<(u32, u32)>,
+    /// The number of video frames per second, which redraws are scheduled at
+    pub frame_rate: Option<f32>,
+}
+
+/// A default MediaProvider which can't play anything
+pub struct DummyMediaProvider<F>(PhantomData<F>);
+impl<F> Default for DummyMediaProvider<F> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+impl<F: Send + Sync + 'static> MediaProvider for DummyMediaProvider<F> {
+    type Frame = F;
+    fn open(&self, _doc_id: usize, _url: Url, _kind: MediaKind) -> Option<BoxedMediaSource<F>> {
+        None
+    }
+}
@@ -21,6 +21,18 @@ pub trait NetProvider: Send + Sync + 'static {
 pub trait NetHandler: Send + Sync + 'static {
     type Data;
     fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Self::Data>);
+
+    /// Handle a response along with its metadata. Providers which know the `Content-Type` of a
+    /// response should call this rather than `bytes`, as handlers of text resources use it to
+    /// determine their character encoding. By default the metadata is ignored.
+    fn response(
+        self: Box<Self>,
+        doc_id: usize,
+        response: Response,
+        callback: SharedCallback<Self::Data>,
+    ) {
+        self.bytes(doc_id, response.bytes, callback)
+    }
 }
 
 /// A type which accepts the parsed result of a network request and sends it back to the Document
@@ -51,6 +63,23 @@ impl Request {
     }
 }
 
+#[non_exhaustive]
+#[derive(Debug)]
+/// A response type loosely representing https://fetch.spec.whatwg.org/#responses
+pub struct Response {
+    pub bytes: Bytes,
+    /// The value of the `Content-Type` header (or the media type of a `data:` url)
+    pub content_type: Option<String>,
+}
+impl Response {
+    pub fn new(bytes: Bytes, content_type: Option<String>) -> Self {
+        Self {
+            bytes,
+            content_type,
+        }
+    }
+}
+
 /// A default noop NetProvider
 pub struct DummyNetProvider<D>(PhantomData<D>);
 impl<D> Default for DummyNetProvider<D> {
@@ -11,7 +11,7 @@
 
 use std::sync::Arc;
 
-use blitz_html::HtmlDocument;
+use blitz_html::{DocumentFormat, HtmlDocument};
 use blitz_renderer_vello::BlitzVelloRenderer;
 use blitz_shell::{
     BlitzApplication, BlitzShellEvent, BlitzShellNetCallback, Config, WindowConfig,
@@ -33,10 +33,14 @@ pub fn launch_url(url: &str) {
         .unwrap();
     let _guard = rt.enter();
 
-    let html = rt.block_on(blitz_net::get_text(&url));
+    let (html, content_type) = rt.block_on(blitz_net::get_document(&url));
+
+    // XHTML documents are detected by their Content-Type (or url)
+    let format = DocumentFormat::detect(&html, Some(&url), content_type.as_deref());
 
     launch_internal(
         &html,
+        format,
         Config {
             stylesheets: Vec::new(),
             base_url: Some(url),
@@ -58,10 +62,11 @@ pub fn launch_static_html_cfg(html: &str, cfg: Config) {
     #[cfg(feature = "net")]
     let _guard = rt.enter();
 
-    launch_internal(html, cfg)
+    let format = DocumentFormat::detect(html, cfg.base_url.as_deref(), None);
+    launch_internal(html, format, cfg)
 }
 
-fn launch_internal(html: &str, cfg: Config) {
+fn launch_internal(html: &str, format: DocumentFormat, cfg: Config) {
     let event_loop = create_default_event_loop::<BlitzShellEvent>();
 
     #[cfg(feature = "net")]
@@ -78,8 +83,9 @@ fn launch_internal(html: &str, cfg: Config) {
 
     let navigation_provider = Arc::new(DummyNavigationProvider);
 
-    let doc = HtmlDocument::from_html(
+    let doc = HtmlDocument::from_source(
         html,
+        format,
         cfg.base_url,
         cfg.stylesheets,
         net_provider,
@@ -12,7 +12,10 @@ use dioxus_core::{ElementId, Event, VirtualDom};
 use dioxus_html::{FormValue, PlatformEventData, set_event_converter};
 use futures_util::{FutureExt, pin_mut};
 
-use super::event_handler::{NativeClickData, NativeConverter, NativeFormData};
+use super::event_handler::{
+    NativeClickData, NativeConverter, NativeElement, NativeFormData, NativeMediaData,
+    NativeToggleData,
+};
 use crate::NodeId;
 use crate::keyboard_event::BlitzKeyboardData;
 use crate::mutation_writer::{DioxusState, MutationWriter};
@@ -52,18 +55,26 @@ impl Document for DioxusDocument {
     type Doc = BaseDocument;
 
     fn poll(&mut self, mut cx: std::task::Context) -> bool {
+        let animated = self.inner.poll_image_animations(&cx);
+        let media_changed = self.inner.poll_media(&cx);
+        self.dispatch_media_events();
+        let canvas_changed = self.inner.poll_canvases(&cx);
+        let changed = animated || media_changed || canvas_changed;
+
         {
             let fut = self.vdom.wait_for_work();
             pin_mut!(fut);
 
             match fut.poll_unpin(&mut cx) {
                 std::task::Poll::Ready(_) => {}
-                std::task::Poll::Pending => return false,
+                std::task::Poll::Pending => return changed,
             }
         }
 
         let mut writer = MutationWriter::new(&mut self.inner, &mut self.vdom_state);
         self.vdom.render_immediate(&mut writer);
+        drop(writer);
+        self.dispatch_mounted_events();
 
         true
     }
@@ -82,6 +93,12 @@ impl Document for DioxusDocument {
         let mut prevent_default = false;
         let mut stop_propagation = false;
 
+        // Activating a summary may toggle its details element, which fires a toggle event
+        let details_state = chain
+            .iter()
+            .find_map(|&node_id| self.inner.summary_details(node_id))
+            .map(|details_id| (details_id, self.inner.is_details_open(details_id)));
+
         match &event.data {
             DomEventData::MouseMove { .. }
             | DomEventData::MouseDown { .. }
@@ -274,6 +291,16 @@ impl Document for DioxusDocument {
         if !event.cancelable || !prevent_default {
             self.inner.as_mut().handle_event(event);
         }
+
+        if let Some((details_id, was_open)) = details_state {
+            let open = self.inner.is_details_open(details_id);
+            if open != was_open {
+                self.dispatch_toggle_event(details_id, open);
+            }
+        }
+
+        // Clicking the controls of media elements plays, pauses or seeks them
+        self.dispatch_media_events();
     }
 }
 
@@ -282,6 +309,59 @@ fn wrap_event_data<T: Any>(value: T) -> Rc<dyn Any> {
 }
 
 impl DioxusDocument {
+    /// Fire a (non-bubbling) toggle event at a `<details>` element which has been opened or closed
+    fn dispatch_toggle_event(&mut self, details_id: NodeId, open: bool) {
+        let dioxus_id = self.inner.tree()[details_id]
+            .element_data()
+            .and_then(DioxusDocument::dioxus_id);
+        if let Some(id) = dioxus_id {
+            let toggle_event = Event::new(wrap_event_data(NativeToggleData { open }), false);
+            self.vdom.runtime().handle_event("toggle", toggle_event, id);
+        }
+    }
+
+    /// Fire the (non-bubbling) media events which have been queued by the document at their media
+    /// elements
+    fn dispatch_media_events(&mut self) {
+        for (node_id, media_event) in self.inner.take_media_events() {
+            // Elements whose resource has been removed (which fire `emptied`) have no state
+            let state = self.inner.media_state(node_id).unwrap_or_default();
+            let dioxus_id = self
+                .inner
+                .get_node(node_id)
+                .and_then(|node| node.element_data())
+                .and_then(DioxusDocument::dioxus_id);
+            if let Some(id) = dioxus_id {
+                let event = Event::new(wrap_event_data(NativeMediaData { state }), false);
+                self.vdom
+                    .runtime()
+                    .handle_event(media_event.name(), event, id);
+            }
+        }
+    }
+
+    /// Fire (non-bubbling) mounted events at the elements with `onmounted` listeners which have
+    /// been created since the last render
+    fn dispatch_mounted_events(&mut self) {
+        set_event_converter(Box::new(NativeConverter {}));
+        for node_id in std::mem::take(&mut self.vdom_state.mounted_nodes) {
+            let dioxus_id = self
+                .inner
+                .get_node(node_id)
+                .and_then(|node| node.element_data())
+                .and_then(DioxusDocument::dioxus_id);
+            let Some(id) = dioxus_id else {
+                continue;
+            };
+            let element = NativeElement {
+                node_id,
+                canvas: self.inner.canvas_context(node_id),
+            };
+            let event = Event::new(wrap_event_data(element), false);
+            self.vdom.runtime().handle_event("mounted", event, id);
+        }
+    }
+
     /// Generate the FormData from an input event
     /// Currently only cares about input checkboxes
     pub fn input_event_form_data(
@@ -401,6 +481,8 @@ impl DioxusDocument {
     pub fn initial_build(&mut self) {
         let mut writer = MutationWriter::new(&mut self.inner, &mut self.vdom_state);
         self.vdom.rebuild(&mut writer);
+        drop(writer);
+        self.dispatch_mounted_events();
         // dbg!(self.vdom.rebuild_to_vec());
         // std::process::exit(0);
         // dbg!(writer.state);
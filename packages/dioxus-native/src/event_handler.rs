@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 
+use blitz_dom::{CanvasContext2d, MediaState};
 use dioxus_html::{
     AnimationData, ClipboardData, CompositionData, DragData, FocusData, FormData, FormValue,
-    HasFileData, HasFormData, HasMouseData, HtmlEventConverter, ImageData, KeyboardData, MediaData,
-    MountedData, MouseData, PlatformEventData, PointerData, ResizeData, ScrollData, SelectionData,
-    ToggleData, TouchData, TransitionData, VisibleData, WheelData,
+    HasFileData, HasFormData, HasMediaData, HasMouseData, HasToggleData, HtmlEventConverter,
+    ImageData, KeyboardData, MediaData, MountedData, MouseData, PlatformEventData, PointerData,
+    RenderedElementBacking, ResizeData, ScrollData, SelectionData, ToggleData, TouchData,
+    TransitionData, VisibleData, WheelData,
     geometry::{ClientPoint, ElementPoint, PagePoint, ScreenPoint},
     input_data::{MouseButton, MouseButtonSet},
     point_interaction::{
@@ -94,12 +96,14 @@ impl HtmlEventConverter for NativeConverter {
         KeyboardData::from(data)
     }
 
-    fn convert_media_data(&self, _event: &PlatformEventData) -> MediaData {
-        todo!()
+    fn convert_media_data(&self, event: &PlatformEventData) -> MediaData {
+        let o = event.downcast::<NativeMediaData>().unwrap().clone();
+        MediaData::from(o)
     }
 
-    fn convert_mounted_data(&self, _event: &PlatformEventData) -> MountedData {
-        todo!()
+    fn convert_mounted_data(&self, event: &PlatformEventData) -> MountedData {
+        let o = event.downcast::<NativeElement>().unwrap().clone();
+        MountedData::from(o)
     }
 
     fn convert_mouse_data(&self, event: &PlatformEventData) -> MouseData {
@@ -119,8 +123,9 @@ impl HtmlEventConverter for NativeConverter {
         todo!()
     }
 
-    fn convert_toggle_data(&self, _event: &PlatformEventData) -> ToggleData {
-        todo!()
+    fn convert_toggle_data(&self, event: &PlatformEventData) -> ToggleData {
+        let o = event.downcast::<NativeToggleData>().unwrap().clone();
+        ToggleData::from(o)
     }
 
     fn convert_touch_data(&self, _event: &PlatformEventData) -> TouchData {
@@ -165,3 +170,43 @@ impl HasFormData for NativeFormData {
 }
 
 impl HasFileData for NativeFormData {}
+
+/// The data of `toggle` events (retrieve it with `ToggleData::downcast`)
+#[derive(Clone, Debug)]
+pub struct NativeToggleData {
+    /// Whether the `<details>` element is open after being toggled
+    pub open: bool,
+}
+
+impl HasToggleData for NativeToggleData {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NativeMediaData {
+    /// The playback state of the media element when the event was fired
+    pub state: MediaState,
+}
+
+impl HasMediaData for NativeMediaData {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
+
+/// The element passed to `onmounted` handlers (retrieve it with `MountedData::downcast`)
+#[derive(Clone, Debug)]
+pub struct NativeElement {
+    /// The id of the element's node in the document
+    pub node_id: usize,
+    /// A 2D drawing context for `<canvas>` elements
+    pub canvas: Option<CanvasContext2d>,
+}
+
+impl RenderedElementBacking for NativeElement {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+}
@@ -19,6 +19,7 @@ mod mutation_writer;
 pub use dioxus_application::DioxusNativeApplication;
 pub use dioxus_document::DioxusDocument;
 pub use event::DioxusNativeEvent;
+pub use event_handler::{NativeElement, NativeMediaData, NativeToggleData};
 
 use blitz_shell::{BlitzShellEvent, Config, WindowConfig, create_default_event_loop};
 use dioxus_core::{ComponentFunction, Element, VirtualDom};
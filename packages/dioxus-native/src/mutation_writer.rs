@@ -1,12 +1,10 @@
 use crate::{NodeId, dioxus_document::qual_name};
 use blitz_dom::{
-    BaseDocument, ElementNodeData, NodeData, QualName, RestyleHint, local_name, namespace_url,
-    net::ImageHandler,
+    BaseDocument, ElementNodeData, Node, NodeData, QualName, RestyleHint, local_name,
+    namespace_url,
     node::{Attribute, NodeSpecificData},
     ns,
-    util::ImageType,
 };
-use blitz_traits::net::{Request, Url};
 use dioxus_core::{
     AttributeValue, ElementId, Template, TemplateAttribute, TemplateNode, WriteMutations,
 };
@@ -22,6 +20,8 @@ pub struct DioxusState {
     stack: Vec<NodeId>,
     /// Mapping from vdom ElementId -> rdom NodeId
     node_id_mapping: Vec<Option<NodeId>>,
+    /// Elements with `onmounted` listeners which haven't been sent their mounted event yet
+    pub(crate) mounted_nodes: Vec<NodeId>,
 }
 
 /// A writer for mutations that can be used with the RealDom.
@@ -100,6 +100,7 @@ impl DioxusState {
             templates: FxHashMap::default(),
             stack: vec![root_id],
             node_id_mapping: vec![Some(root_id)],
+            mounted_nodes: Vec::new(),
         }
     }
 
@@ -158,6 +159,7 @@ impl WriteMutations for MutationWriter<'_> {
             self.doc.get_node_mut(parent).unwrap().children.push(child);
             self.doc.get_node_mut(child).unwrap().parent = Some(parent);
         }
+        self.doc.mark_slots_dirty(parent);
 
         self.maybe_push_style_node(parent);
     }
@@ -283,7 +285,6 @@ impl WriteMutations for MutationWriter<'_> {
         id: ElementId,
     ) {
         let node_id = self.state.element_to_node_id(id);
-        let doc_id = self.doc.id();
 
         #[cfg(feature = "tracing")]
         tracing::info!(
@@ -295,11 +296,12 @@ impl WriteMutations for MutationWriter<'_> {
         );
 
         self.doc.snapshot_node(node_id);
+        self.doc.mark_form_state_dirty(node_id);
+        self.doc.mark_slots_dirty(node_id);
 
         let node = &mut self.doc.nodes[node_id];
 
-        let stylo_element_data = &mut *node.stylo_element_data.borrow_mut();
-        if let Some(data) = stylo_element_data {
+        if let Some(data) = &mut *node.stylo_element_data.borrow_mut() {
             data.hint |= RestyleHint::restyle_subtree();
         }
 
@@ -342,17 +344,6 @@ impl WriteMutations for MutationWriter<'_> {
                 if name == "style" {
                     element.flush_style_attribute(&self.doc.guard);
                 }
-
-                if element.name.local == local_name!("img") && name == "src" {
-                    element.node_specific_data = NodeSpecificData::None;
-                    if let Ok(url) = Url::parse(val) {
-                        self.doc.net_provider.fetch(
-                            doc_id,
-                            Request::get(url),
-                            Box::new(ImageHandler::new(node_id, ImageType::Image)),
-                        );
-                    }
-                }
             }
 
             if let AttributeValue::None = value {
@@ -367,6 +358,69 @@ impl WriteMutations for MutationWriter<'_> {
                 element.attrs.retain(|attr| attr.name.local != *name);
             }
         }
+
+        if name == "placeholder" {
+            self.doc.update_placeholder(node_id);
+        }
+
+        // Reload the nested document of iframes when their content changes
+        let is_iframe = self.doc.nodes[node_id]
+            .data
+            .is_element_with_tag_name(&local_name!("iframe"));
+        if is_iframe && matches!(name, "src" | "srcdoc") {
+            self.doc.load_iframe(node_id);
+        }
+
+        // Canvases are cleared when they are resized
+        let is_canvas = self.doc.nodes[node_id]
+            .data
+            .is_element_with_tag_name(&local_name!("canvas"));
+        if is_canvas && matches!(name, "width" | "height") {
+            self.doc.reset_canvas(node_id);
+        }
+
+        // Reload media elements when their sources or posters change
+        let node = &self.doc.nodes[node_id];
+        let is_media = |node: &Node| {
+            node.data.is_element_with_tag_name(&local_name!("video"))
+                || node.data.is_element_with_tag_name(&local_name!("audio"))
+        };
+        if is_media(node) && matches!(name, "src" | "poster") {
+            self.doc.load_media(node_id);
+        } else if is_media(node) && name == "muted" {
+            self.doc.update_media_muted(node_id);
+        } else if node.data.is_element_with_tag_name(&local_name!("source")) && name == "src" {
+            let media_id = node
+                .parent
+                .filter(|&parent_id| is_media(&self.doc.nodes[parent_id]));
+            if let Some(media_id) = media_id {
+                self.doc.load_media(media_id);
+            }
+        }
+
+        // Reselect the source of images when their sources change
+        let node = &self.doc.nodes[node_id];
+        if node.data.is_element_with_tag_name(&local_name!("img"))
+            && matches!(name, "src" | "srcset" | "sizes")
+        {
+            self.doc.load_image(node_id);
+        } else if node.data.is_element_with_tag_name(&local_name!("source"))
+            && matches!(name, "srcset" | "sizes" | "media" | "type")
+        {
+            let picture_images: Vec<usize> = node
+                .parent
+                .map(|parent_id| &self.doc.nodes[parent_id])
+                .filter(|parent| {
+                    parent
+                        .data
+                        .is_element_with_tag_name(&local_name!("picture"))
+                })
+                .map(|parent| parent.children.clone())
+                .unwrap_or_default();
+            for img_id in picture_images {
+                self.doc.load_image(img_id);
+            }
+        }
     }
 
     fn set_node_text(&mut self, value: &str, id: ElementId) {
@@ -388,6 +442,10 @@ impl WriteMutations for MutationWriter<'_> {
             text.content.push_str(value);
             let parent = node.parent;
             self.maybe_push_style_node(parent);
+            // The text of a `<textarea>` or `<option>` affects the validity of its form
+            if let Some(parent) = parent {
+                self.doc.mark_form_state_dirty(parent);
+            }
         }
     }
 
@@ -409,6 +467,12 @@ impl WriteMutations for MutationWriter<'_> {
             _id,
         );
 
+        // Mounted events are sent once the mutations have been applied
+        if _name == "mounted" {
+            let node_id = self.state.element_to_node_id(_id);
+            self.state.mounted_nodes.push(node_id);
+        }
+
         // let node_id = self.state.element_to_node_id(id);
         // let mut node = self.rdom.get_mut(node_id).unwrap();
         // node.add_event_listener(name);
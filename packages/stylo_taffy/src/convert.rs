@@ -67,7 +67,8 @@ pub fn dimension(val: &stylo::Size) -> taffy::Dimension {
         stylo::Size::LengthPercentage(val) => length_percentage(&val.0).into(),
         stylo::Size::Auto => taffy::Dimension::AUTO,
 
-        // TODO: implement other values in Taffy
+        // Taffy can't represent intrinsic sizing keywords. They are treated as `auto` here and
+        // resolved to lengths by the embedder (see `intrinsic_size`).
         stylo::Size::MaxContent => taffy::Dimension::AUTO,
         stylo::Size::MinContent => taffy::Dimension::AUTO,
         stylo::Size::FitContent => taffy::Dimension::AUTO,
@@ -79,13 +80,38 @@ pub fn dimension(val: &stylo::Size) -> taffy::Dimension {
     }
 }
 
+/// An intrinsic sizing keyword which can't be represented as a [`taffy::Dimension`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IntrinsicSize {
+    MinContent,
+    MaxContent,
+    /// `fit-content` (`None`) or `fit-content(<length-percentage>)`
+    FitContent(Option<taffy::LengthPercentage>),
+    Stretch,
+}
+
+#[inline]
+pub fn intrinsic_size(val: &stylo::Size) -> Option<IntrinsicSize> {
+    match val {
+        stylo::Size::MinContent => Some(IntrinsicSize::MinContent),
+        stylo::Size::MaxContent => Some(IntrinsicSize::MaxContent),
+        stylo::Size::FitContent => Some(IntrinsicSize::FitContent(None)),
+        stylo::Size::FitContentFunction(val) => {
+            Some(IntrinsicSize::FitContent(Some(length_percentage(&val.0))))
+        }
+        stylo::Size::Stretch => Some(IntrinsicSize::Stretch),
+        _ => None,
+    }
+}
+
 #[inline]
 pub fn max_size_dimension(val: &stylo::MaxSize) -> taffy::Dimension {
     match val {
         stylo::MaxSize::LengthPercentage(val) => length_percentage(&val.0).into(),
         stylo::MaxSize::None => taffy::Dimension::AUTO,
 
-        // TODO: implement other values in Taffy
+        // Taffy can't represent intrinsic sizing keywords. They are treated as `auto` here and
+        // resolved to lengths by the embedder (see `intrinsic_max_size`).
         stylo::MaxSize::MaxContent => taffy::Dimension::AUTO,
         stylo::MaxSize::MinContent => taffy::Dimension::AUTO,
         stylo::MaxSize::FitContent => taffy::Dimension::AUTO,
@@ -97,6 +123,20 @@ pub fn max_size_dimension(val: &stylo::MaxSize) -> taffy::Dimension {
     }
 }
 
+#[inline]
+pub fn intrinsic_max_size(val: &stylo::MaxSize) -> Option<IntrinsicSize> {
+    match val {
+        stylo::MaxSize::MinContent => Some(IntrinsicSize::MinContent),
+        stylo::MaxSize::MaxContent => Some(IntrinsicSize::MaxContent),
+        stylo::MaxSize::FitContent => Some(IntrinsicSize::FitContent(None)),
+        stylo::MaxSize::FitContentFunction(val) => {
+            Some(IntrinsicSize::FitContent(Some(length_percentage(&val.0))))
+        }
+        stylo::MaxSize::Stretch => Some(IntrinsicSize::Stretch),
+        _ => None,
+    }
+}
+
 #[inline]
 pub fn margin(val: &stylo::MarginVal) -> taffy::LengthPercentageAuto {
     match val {
@@ -290,13 +330,20 @@ pub(crate) fn text_align(input: stylo::TextAlign) -> taffy::TextAlign {
 #[inline]
 #[cfg(feature = "flexbox")]
 pub fn flex_basis(input: &stylo::FlexBasis) -> taffy::Dimension {
-    // TODO: Support flex-basis: content in Taffy
+    // Taffy can't represent `flex-basis: content`. It is treated as `auto` here and resolved to
+    // a length by the embedder (see `is_content_flex_basis`).
     match input {
         stylo::FlexBasis::Content => taffy::Dimension::AUTO,
         stylo::FlexBasis::Size(size) => dimension(size),
     }
 }
 
+#[inline]
+#[cfg(feature = "flexbox")]
+pub fn is_content_flex_basis(input: &stylo::FlexBasis) -> bool {
+    matches!(input, stylo::FlexBasis::Content)
+}
+
 #[inline]
 #[cfg(feature = "flexbox")]
 pub fn flex_direction(input: stylo::FlexDirection) -> taffy::FlexDirection {